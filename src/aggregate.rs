@@ -0,0 +1,135 @@
+use calendar::Freeze;
+use config_file::{AggregateConfig, PatternScheme};
+use connectivity::Connectivity;
+use controls::Controls;
+use led_controller::LedController;
+use pin::{PatternTimings, RgbLedLight};
+use remote_status::{JobStatus, RemoteStatus, StatusSummary};
+use scheduler::{Tick, TickScheduler};
+use status_board::{IntegrationSnapshot, StatusBoard};
+use std::sync::Arc;
+use std::time::Duration;
+
+const REFRESH_MILLIS: u64 = 1000;
+
+// Drives one extra RGB LED that shows the worst state across all integrations:
+// red if any of them is failing, building if any is building, and otherwise
+// passing once at least one of them has passed.
+pub fn run_aggregate(
+    config: AggregateConfig,
+    board: StatusBoard,
+    patterns: PatternScheme,
+    timings: PatternTimings,
+    controls: Arc<Controls>,
+    connectivity: Connectivity,
+    freeze: Freeze,
+    scheduler: TickScheduler,
+) {
+    let mut led = RgbLedLight::new(config.led_pins[0], config.led_pins[1], config.led_pins[2]);
+    led.set_output_type(config.output_type);
+    let mut led_controller = LedController::new(led, config.colors, patterns, timings, controls);
+    led_controller.set_connectivity(connectivity);
+    led_controller.set_freeze(freeze);
+
+    let mut shown: Option<(RemoteStatus, bool)> = None;
+    let mut ticker = scheduler.ticker();
+    while ticker.is_running() {
+        let snapshots = board.snapshots();
+        if !snapshots.is_empty() {
+            let (status, summary) = combine(&snapshots);
+            // Only re-show on changes, so running animations aren't restarted
+            let offline = led_controller.is_offline();
+            if shown.map(|(shown_status, _)| shown_status) != Some(status) {
+                info!("--Aggregate--: Overall status is now {:?}.", status);
+            }
+            if shown != Some((status, offline)) {
+                led_controller.show_status(status, &summary);
+                shown = Some((status, offline));
+            }
+        }
+        if ticker.wait(Duration::from_millis(REFRESH_MILLIS)) == Tick::Shutdown {
+            break;
+        }
+    }
+    led_controller.shut_down();
+}
+
+fn combine(snapshots: &[IntegrationSnapshot]) -> (RemoteStatus, StatusSummary) {
+    let any = |status: RemoteStatus| snapshots.iter().any(|s| s.status == status);
+    let status = if any(RemoteStatus::Failing) {
+        RemoteStatus::Failing
+    } else if any(RemoteStatus::InProgress) {
+        RemoteStatus::InProgress
+    } else if any(RemoteStatus::Passing) {
+        RemoteStatus::Passing
+    } else {
+        RemoteStatus::Unknown
+    };
+
+    // Job names are prefixed with their integration, since they can clash
+    let mut summary = StatusSummary::default();
+    summary.disconnected = snapshots.iter().all(|s| s.summary.disconnected);
+    summary.non_critical_failures_only = snapshots
+        .iter()
+        .filter(|s| s.status == RemoteStatus::Failing)
+        .all(|s| s.summary.non_critical_failures_only);
+    summary.non_mainline_failures_only = snapshots
+        .iter()
+        .filter(|s| s.status == RemoteStatus::Failing)
+        .all(|s| s.summary.non_mainline_failures_only);
+    for snapshot in snapshots {
+        summary.passing += snapshot.summary.passing;
+        summary.failing += snapshot.summary.failing;
+        summary.in_progress += snapshot.summary.in_progress;
+        summary.aborted += snapshot.summary.aborted;
+        summary.not_built += snapshot.summary.not_built;
+        summary.indeterminate += snapshot.summary.indeterminate;
+        for job in &snapshot.summary.failing_jobs {
+            summary
+                .failing_jobs
+                .push(format!("{}: {}", snapshot.name, job));
+        }
+        for job in &snapshot.summary.flaky_jobs {
+            summary
+                .flaky_jobs
+                .push(format!("{}: {}", snapshot.name, job));
+        }
+        for job in &snapshot.summary.overdue_jobs {
+            summary
+                .overdue_jobs
+                .push(format!("{}: {}", snapshot.name, job));
+        }
+        for build in &snapshot.summary.failed_builds {
+            summary
+                .failed_builds
+                .push(format!("{}: {}", snapshot.name, build));
+        }
+        for job in &snapshot.summary.claimed_jobs {
+            summary
+                .claimed_jobs
+                .push(format!("{}: {}", snapshot.name, job));
+        }
+        for job in &snapshot.summary.warning_jobs {
+            summary
+                .warning_jobs
+                .push(format!("{}: {}", snapshot.name, job));
+        }
+        for job in &snapshot.summary.jobs {
+            summary.jobs.push(JobStatus {
+                name: format!("{}: {}", snapshot.name, job.name),
+                status: job.status,
+            });
+        }
+        for stage in &snapshot.summary.failed_stages {
+            summary
+                .failed_stages
+                .push(format!("{}: {}", snapshot.name, stage));
+        }
+        for culprit in &snapshot.summary.culprits {
+            if !summary.culprits.contains(culprit) {
+                summary.culprits.push(culprit.clone());
+            }
+        }
+    }
+    (status, summary)
+}
@@ -0,0 +1,1806 @@
+use aggregate;
+use backoff::Backoff;
+use beacon;
+use budget::PollBudget;
+use calendar::{self, Freeze};
+use config_file::*;
+use connectivity::{self, Connectivity};
+use controls::{self, Controls};
+use credentials::{self, CredentialReloader, Credentials};
+use ctrlc;
+use displays;
+use durations::BuildDurations;
+use errors::RunError;
+use escalation;
+use events;
+use failures::FailureLimits;
+use faults::FaultInjector;
+use gpio;
+use health::{self, Health};
+use history::JobHistory;
+use integrations::aggregation::AggregationKind;
+use integrations::bitbucket_integration::BitbucketIntegration;
+use integrations::demo_integration::DemoIntegration;
+use integrations::exec_integration::ExecIntegration;
+use integrations::github_integration::GitHubIntegration;
+use integrations::grafana_integration::GrafanaIntegration;
+use integrations::icinga_integration::IcingaIntegration;
+use integrations::jenkins_integration::{JenkinsIntegration, JobSelection};
+use integrations::jira_integration::JiraIntegration;
+use integrations::k8s_integration::{self, K8sIntegration};
+use integrations::plugin_integration::{Plugin, PluginIntegration};
+use integrations::pull_request_integration::PullRequestIntegration;
+use integrations::relayed_integration::RelayedIntegration;
+use integrations::remote_integration::RemoteIntegration;
+use integrations::sentry_integration::SentryIntegration;
+use integrations::sonarqube_integration::SonarQubeIntegration;
+use integrations::team_city_integration::TeamCityIntegration;
+use integrations::unity_cloud_integration::UnityCloudIntegration;
+use job_groups::{self, JobPattern};
+use led_controller::LedController;
+use log4rs;
+use metrics;
+use network::{self, BaseUrls, HttpClient};
+use notifiers::slack::SlackNotifier;
+use notifiers::speech::SpeechNotifier;
+use notifiers::teams::TeamsNotifier;
+use notifiers::webhook::WebhookNotifier;
+use notifiers::{self, Notifier};
+use pin::{OutputType, PatternTimings, RgbLedLight};
+use preflight::Preflight;
+use redact;
+use relay::{self, RelayFeed};
+use report;
+use schedule::Schedule;
+use scheduler::{Tick, TickScheduler};
+use selector;
+use serde_json;
+use signals;
+use status_board::StatusBoard;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::prelude::*;
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use supervisor::{panic_message, Supervisor};
+use systemd::{self, Readiness};
+use toml;
+use unity_webhook;
+use websocket;
+
+const SLEEP_DURATION: u64 = 10000;
+// Unity Cloud's rate limit is strict, and the headers it sends to budget with
+// haven't always been right, so it's polled less often unless configured.
+const UNITY_POLL_INTERVAL: u64 = 1000 * 60;
+const MAX_BACKOFF_DURATION: u64 = 1000 * 60 * 5;
+
+// Reads config.toml and log4rs.yml from next to the executable, then polls
+// and displays everything they configure until Ctrl-C. Errors if it couldn't
+// start, or if a thread stopped for good before that.
+// With demo set, made-up builds are shown instead of polling any servers,
+// and nothing is sent or stored anywhere else.
+pub fn run(demo: bool) -> Result<(), RunError> {
+    let scheduler = TickScheduler::new();
+    let ctrlc_scheduler = scheduler.clone();
+    ctrlc::set_handler(move || {
+        info!("Ctrl-C received, signaling child threads to stop...");
+        systemd::notify("STOPPING=1");
+        ctrlc_scheduler.shutdown(); // wakes up any thread that's waiting
+    })
+    .map_err(|err| RunError::StartupFailed {
+        reason: format!("Error setting Ctrl-C handler: {}", err),
+    })?;
+
+    let status_board = StatusBoard::new();
+    let controls = Arc::new(Controls::new());
+    let health = Health::new();
+    let connectivity = Connectivity::new();
+    let freeze = Freeze::new();
+    let exe_dir = exe_dir()?;
+    redact::install_panic_hook();
+
+    // Init logging
+    let mut log_config_file_path = exe_dir.clone();
+    log_config_file_path.push("log4rs.yml");
+    println!("Looking for log config file at: {:?}", log_config_file_path);
+    // Every encoder leaves passwords and tokens out of what it writes
+    log4rs::init_file(log_config_file_path, redact::log_deserializers()).map_err(|err| {
+        RunError::StartupFailed {
+            reason: format!("Failed to init logging: {}", err),
+        }
+    })?;
+
+    // Init config file
+    let mut config_values = read_config(&exe_dir)?;
+    config_values
+        .resolve_pins()
+        .map_err(|err| RunError::ConfigInvalid {
+            reason: format!("Invalid pins: {}", err),
+        })?;
+    gpio::check_access().map_err(|err| RunError::GpioInitFailed {
+        reason: err.to_string(),
+    })?;
+    if demo {
+        info!("Demo mode: showing made-up builds. Notifications, metrics and relaying are off.");
+        config_values.slack = None;
+        config_values.webhooks.clear();
+        config_values.email = None;
+        config_values.speech = None;
+        config_values.daily_report = None;
+        config_values.metrics = None;
+        config_values.relay_server = None;
+        config_values.unity_webhook = None;
+        config_values.relay_client = None;
+        // Being offline would hide the demo
+        config_values.connectivity = None;
+        config_values.vault = None;
+    }
+    let credential_provider = credentials::provider_from_config(&config_values).map_err(|err| {
+        RunError::ConfigInvalid {
+            reason: err.to_string(),
+        }
+    })?;
+    if let Some(ref provider) = credential_provider {
+        provider
+            .fetch()
+            .map_err(|err| RunError::StartupFailed {
+                reason: format!(
+                    "Fetching credentials from {} failed: {}",
+                    provider.name(),
+                    err
+                ),
+            })?
+            .apply_to(&mut config_values);
+        info!("Fetched credentials from {}.", provider.name());
+    }
+
+    let credentials = Credentials::from_config(&config_values);
+    let mut instances = read_instances(&config_values, demo)?;
+    let jenkins_critical_jobs = config_values.jenkins_critical_jobs();
+    let jenkins_pr_jobs = config_values.jenkins_pr_jobs();
+    let jenkins_username = config_values.jenkins_username;
+    let jenkins_uses_api_token = config_values.jenkins_api_token.is_some();
+    let jenkins_password = config_values
+        .jenkins_api_token
+        .unwrap_or(config_values.jenkins_password);
+    let jenkins_base_url = config_values.jenkins_base_url;
+    let jenkins_base_urls = BaseUrls::new(&jenkins_base_url, &config_values.jenkins_fallback_urls);
+    let jenkins_scheduler = scheduler.clone();
+    let (jenkins_r, jenkins_g, jenkins_b) = (
+        config_values.jenkins_led_pins[0],
+        config_values.jenkins_led_pins[1],
+        config_values.jenkins_led_pins[2],
+    );
+    let jenkins_output_type = config_values.jenkins_output_type;
+    let jenkins_colors = config_values.jenkins_colors;
+    let jenkins_polling = config_values.jenkins_polling;
+    let mut jenkins_client = build_http_client(
+        "jenkins_http",
+        &config_values.jenkins_http,
+        &jenkins_base_url,
+    )?;
+    let jenkins_flakiness = config_values.flakiness;
+    let jenkins_durations = config_values.build_durations;
+    let jenkins_aggregation = config_values.jenkins_aggregation;
+    let jenkins_last_completed_only = config_values.jenkins_last_completed_only;
+    let jenkins_aborted_is_failure = config_values.jenkins_aborted_is_failure;
+    let jenkins_mainline = match config_values.jenkins_mainline_regex {
+        Some(ref pattern) => {
+            Some(
+                JobPattern::parse(pattern).map_err(|err| RunError::ConfigInvalid {
+                    reason: format!("Invalid jenkins_mainline_regex \"{}\": {}", pattern, err),
+                })?,
+            )
+        }
+        None => None,
+    };
+    let jenkins_queue_threshold = config_values.jenkins_queue_threshold;
+    // Polls that find the job list unchanged are cheap, so they can come often
+    let jenkins_watch = config_values.jenkins_watch_seconds.is_some();
+    let jenkins_poll_interval = config_values
+        .jenkins_watch_seconds
+        .map_or(SLEEP_DURATION, |seconds| seconds * 1000);
+    let jenkins_pr_pins = match config_values.jenkins_pr_led_pins {
+        Some(_) if jenkins_pr_jobs.is_empty() => {
+            warn!("jenkins_pr_led_pins is set, but no job in [jenkins_jobs] has pull_request = true. Leaving the PR LED off.");
+            None
+        }
+        Some(pins) => Some((pins[0], pins[1], pins[2])),
+        None => None,
+    };
+    // Pull request jobs are left off the main LED, and only fetched for the PR LED
+    let jenkins_jobs = match (jenkins_pr_jobs.is_empty(), jenkins_pr_pins) {
+        (true, _) => JobSelection::All,
+        (false, Some(_)) => JobSelection::MainlineAndPullRequests(jenkins_pr_jobs.clone()),
+        (false, None) => JobSelection::Mainline(jenkins_pr_jobs.clone()),
+    };
+    let mut thread_names = vec!["Jenkins", "Unity Cloud"];
+    if jenkins_pr_pins.is_some() {
+        thread_names.push("Jenkins PRs");
+    }
+    thread_names.extend(instances.iter().map(|instance| instance.name.as_str()));
+    let readiness = Readiness::new(&thread_names);
+
+    let unity_api_token = config_values.unity_cloud_api_token;
+    let unity_base_url = config_values.unity_base_url;
+    let unity_scheduler = scheduler.clone();
+    let (unity_r, unity_g, unity_b) = (
+        config_values.unity_led_pins[0],
+        config_values.unity_led_pins[1],
+        config_values.unity_led_pins[2],
+    );
+    let unity_output_type = config_values.unity_output_type;
+    let unity_colors = config_values.unity_colors;
+    let unity_aggregation = config_values.unity_aggregation;
+    let unity_polling = config_values.unity_polling;
+    // Made-up builds cost nothing to poll, and change faster than Unity builds do
+    let unity_poll_interval = if demo {
+        SLEEP_DURATION
+    } else {
+        UNITY_POLL_INTERVAL
+    };
+    let mut unity_client =
+        build_http_client("unity_http", &config_values.unity_http, &unity_base_url)?;
+    if let Some(ref recording) = config_values.recording {
+        info!("Recording every response in {}.", recording.directory);
+        let directory = Path::new(&recording.directory);
+        jenkins_client
+            .0
+            .record_to(directory.join("Jenkins"), recording.max_polls);
+        unity_client
+            .0
+            .record_to(directory.join("Unity Cloud"), recording.max_polls);
+        for instance in &mut instances {
+            let instance_directory = directory.join(&instance.name);
+            instance
+                .client
+                .record_to(instance_directory, recording.max_polls);
+        }
+    }
+    let faults =
+        FaultInjector::from_config(config_values.fault_injection.as_ref()).map_err(|err| {
+            RunError::ConfigInvalid {
+                reason: format!("Invalid [fault_injection]: {}", err),
+            }
+        })?;
+    if let Some(faults) = faults {
+        warn!("Fault injection is on, requests to Jenkins and Unity Cloud will fail on purpose.");
+        jenkins_client.0.inject_faults(faults.clone());
+        for instance in &mut instances {
+            instance.client.inject_faults(faults.clone());
+        }
+        unity_client.0.inject_faults(faults);
+    }
+
+    let mut failure_limits = FailureLimits::new(
+        config_values.allowed_failures,
+        config_values.allowed_failures_per_integration.clone(),
+        config_values.allowed_total_failures,
+    );
+    failure_limits.set_cooldown(
+        config_values
+            .failure_cooldown_minutes
+            .map(|minutes| Duration::from_secs(minutes * 60)),
+    );
+    let mut supervisor = Supervisor::new(
+        failure_limits,
+        config_values.restart_limit.clone(),
+        health.clone(),
+        scheduler.clone(),
+    );
+    let schedule = match config_values.schedule {
+        Some(schedule_config) => Some(Schedule::from_config(&schedule_config).map_err(|err| {
+            RunError::ConfigInvalid {
+                reason: format!("Invalid [schedule]: {}", err),
+            }
+        })?),
+        None => None,
+    };
+    let (jenkins_schedule, unity_schedule) = (schedule.clone(), schedule.clone());
+    let instance_schedule = schedule;
+    let escalation = config_values.escalation;
+    // Failures blinking slower or faster would hide the color blind patterns
+    let led_escalation = if config_values.color_blind_mode {
+        None
+    } else {
+        escalation.clone()
+    };
+    let (jenkins_escalation, unity_escalation) = (led_escalation.clone(), led_escalation.clone());
+    let instance_escalation = led_escalation;
+    let led_patterns = if config_values.color_blind_mode {
+        info!("Color blind mode enabled, ignoring configured LED patterns.");
+        PatternScheme::color_blind()
+    } else {
+        config_values.led_patterns
+    };
+    let led_timings = config_values.led_timings;
+    let power_on_test = config_values.power_on_test;
+    let (jenkins_power_on_test, unity_power_on_test, instance_power_on_test) = (
+        power_on_test.clone(),
+        power_on_test.clone(),
+        power_on_test.clone(),
+    );
+    let (jenkins_patterns, jenkins_timings) = (led_patterns.clone(), led_timings.clone());
+    let (unity_patterns, unity_timings) = (led_patterns.clone(), led_timings.clone());
+    let (instance_patterns, instance_timings) = (led_patterns.clone(), led_timings.clone());
+
+    // Init optional controls and displays
+    let aggregate_handle = config_values.aggregate.map(|aggregate_config| {
+        let aggregate_board = status_board.clone();
+        let aggregate_patterns = led_patterns.clone();
+        let aggregate_timings = led_timings.clone();
+        let aggregate_controls = Arc::clone(&controls);
+        let aggregate_connectivity = connectivity.clone();
+        let aggregate_freeze = freeze.clone();
+        let aggregate_scheduler = scheduler.clone();
+        thread::spawn(move || {
+            aggregate::run_aggregate(
+                aggregate_config,
+                aggregate_board,
+                aggregate_patterns,
+                aggregate_timings,
+                aggregate_controls,
+                aggregate_connectivity,
+                aggregate_freeze,
+                aggregate_scheduler,
+            )
+        })
+    });
+    let job_group_handles: Vec<_> = config_values
+        .jenkins_job_groups
+        .into_iter()
+        .map(|group_config| {
+            let group_board = status_board.clone();
+            let group_patterns = led_patterns.clone();
+            let group_timings = led_timings.clone();
+            let group_controls = Arc::clone(&controls);
+            let group_connectivity = connectivity.clone();
+            let group_freeze = freeze.clone();
+            let group_scheduler = scheduler.clone();
+            thread::spawn(move || {
+                job_groups::run_job_group(
+                    group_config,
+                    group_board,
+                    group_patterns,
+                    group_timings,
+                    group_controls,
+                    group_connectivity,
+                    group_freeze,
+                    group_scheduler,
+                )
+            })
+        })
+        .collect();
+    let selector_handle = config_values.selector.map(|selector_config| {
+        let selector_board = status_board.clone();
+        let selector_controls = Arc::clone(&controls);
+        let selector_connectivity = connectivity.clone();
+        let selector_freeze = freeze.clone();
+        let selector_scheduler = scheduler.clone();
+        thread::spawn(move || {
+            selector::run_selector(
+                selector_config,
+                selector_board,
+                led_patterns,
+                led_timings,
+                selector_controls,
+                selector_connectivity,
+                selector_freeze,
+                selector_scheduler,
+            )
+        })
+    });
+    let beacon_handle = config_values.beacon.map(|beacon_config| {
+        let beacon_board = status_board.clone();
+        let beacon_controls = Arc::clone(&controls);
+        let beacon_scheduler = scheduler.clone();
+        thread::spawn(move || {
+            beacon::run_beacon(
+                beacon_config,
+                beacon_board,
+                beacon_controls,
+                beacon_scheduler,
+            )
+        })
+    });
+    let button_handle = config_values.button.map(|button_config| {
+        let button_controls = Arc::clone(&controls);
+        let button_scheduler = scheduler.clone();
+        thread::spawn(move || {
+            controls::run_button(button_config, button_controls, button_scheduler)
+        })
+    });
+    if let Some(ref oled_config) = config_values.oled {
+        displays::start_oled(oled_config, &status_board);
+    }
+    if let Some(ref seven_segment_config) = config_values.seven_segment {
+        displays::start_seven_segment(seven_segment_config, &status_board);
+    }
+    let report_slack = config_values.slack.clone();
+    let report_handle = config_values.daily_report.map(|report_config| {
+        let report_board = status_board.clone();
+        let report_scheduler = scheduler.clone();
+        thread::spawn(move || {
+            report::run_daily_report(report_config, report_slack, report_board, report_scheduler)
+        })
+    });
+    let mut notifiers: Vec<Box<Notifier>> = Vec::new();
+    if let Some(slack_config) = config_values.slack {
+        notifiers.push(Box::new(SlackNotifier::new(slack_config)));
+    }
+    for webhook_config in config_values.webhooks {
+        notifiers.push(match webhook_config.format {
+            WebhookFormat::Generic => Box::new(WebhookNotifier::new(webhook_config)),
+            WebhookFormat::Teams => Box::new(TeamsNotifier::new(webhook_config)),
+        });
+    }
+    if let Some(speech_config) = config_values.speech {
+        match SpeechNotifier::new(speech_config) {
+            Ok(speech) => notifiers.push(Box::new(speech)),
+            Err(e) => error!(
+                "Invalid [speech] in config file. Announcements disabled. Error: {}",
+                e
+            ),
+        }
+    }
+    if !notifiers.is_empty() {
+        let remind_after = escalation.as_ref().map_or(Vec::new(), |escalation| {
+            escalation
+                .remind_after_minutes
+                .iter()
+                .map(|minutes| Duration::from_secs(minutes * 60))
+                .collect()
+        });
+        notifiers::start_notifiers(notifiers, &status_board, remind_after);
+    }
+    if let Some(ref escalation_config) = escalation {
+        escalation::start_buzzer(escalation_config, &status_board);
+    }
+    if let Some(email_config) = config_values.email {
+        notifiers::email::start_email_notifier(email_config, &status_board);
+    }
+    systemd::start_status_updates(&status_board);
+    if let Some(ref metrics_config) = config_values.metrics {
+        metrics::start_metrics(metrics_config, &status_board);
+    }
+    if let Some(ref websocket_config) = config_values.websocket {
+        websocket::start_websocket(websocket_config, &status_board);
+    }
+    if let Some(ref relay_server_config) = config_values.relay_server {
+        relay::start_relay_server(relay_server_config, &status_board);
+    }
+    // Display-only: show what the relay server sends instead of polling
+    let relay_feed = config_values.relay_client.map(|relay_client_config| {
+        relay::start_relay_client(relay_client_config, scheduler.clone())
+    });
+    let instance_feed = relay_feed.clone();
+    let (jenkins_feed, unity_feed) = (relay_feed.clone(), relay_feed);
+    let health_handle = config_values.health.map(|health_config| {
+        let health = health.clone();
+        let health_scheduler = scheduler.clone();
+        thread::spawn(move || health::run_health(health_config, health, health_scheduler))
+    });
+    let mut reloader = CredentialReloader::new(
+        exe_dir.clone(),
+        credentials.clone(),
+        jenkins_client.0.clone(),
+        unity_client.0.clone(),
+    );
+    reloader.set_provider(credential_provider.clone());
+    reloader.set_instances(
+        instances
+            .iter()
+            .map(|instance| instance.credentials.clone())
+            .collect(),
+    );
+    let refresh_handle = credential_provider
+        .as_ref()
+        .and_then(|provider| provider.refresh_interval())
+        .map(|interval| {
+            let refresh_reloader = reloader.clone();
+            let refresh_scheduler = scheduler.clone();
+            thread::spawn(move || {
+                credentials::run_refresh(refresh_reloader, interval, refresh_scheduler)
+            })
+        });
+    let signals_handle = {
+        let signals_board = status_board.clone();
+        let signals_health = health.clone();
+        let signals_scheduler = scheduler.clone();
+        thread::spawn(move || {
+            signals::run_signals(signals_board, signals_health, reloader, signals_scheduler)
+        })
+    };
+    let connectivity_handle = config_values.connectivity.map(|connectivity_config| {
+        let connectivity = connectivity.clone();
+        let connectivity_scheduler = scheduler.clone();
+        thread::spawn(move || {
+            connectivity::run_connectivity(
+                connectivity_config,
+                connectivity,
+                connectivity_scheduler,
+            )
+        })
+    });
+    let unity_webhook_handle = config_values.unity_webhook.map(|webhook_config| {
+        // Every Unity Cloud integration polls when a build event comes in
+        let mut unity_names = vec!["Unity Cloud".to_string()];
+        unity_names.extend(
+            instances
+                .iter()
+                .filter_map(|instance| match instance.server {
+                    InstanceServer::Unity { .. } => Some(instance.name.clone()),
+                    _ => None,
+                }),
+        );
+        let webhook_scheduler = scheduler.clone();
+        thread::spawn(move || {
+            unity_webhook::run_unity_webhook(webhook_config, unity_names, webhook_scheduler)
+        })
+    });
+    let calendar_handle = config_values.calendar.map(|calendar_config| {
+        let calendar_freeze = freeze.clone();
+        let calendar_scheduler = scheduler.clone();
+        thread::spawn(move || {
+            calendar::run_calendar(calendar_config, calendar_freeze, calendar_scheduler)
+        })
+    });
+    let led_strip_handle = config_values.led_strip.map(|led_strip_config| {
+        let led_strip_board = status_board.clone();
+        let led_strip_scheduler = scheduler.clone();
+        thread::spawn(move || {
+            displays::run_led_strip(led_strip_config, led_strip_board, led_strip_scheduler)
+        })
+    });
+    let lcd_handle = config_values.lcd.map(|lcd_config| {
+        let lcd_board = status_board.clone();
+        let lcd_scheduler = scheduler.clone();
+        thread::spawn(move || displays::run_lcd(lcd_config, lcd_board, lcd_scheduler))
+    });
+
+    // Init main threads
+    if let Some((pr_r, pr_g, pr_b)) = jenkins_pr_pins {
+        let pr_board = status_board.clone();
+        let pr_controls = Arc::clone(&controls);
+        let pr_connectivity = connectivity.clone();
+        let pr_freeze = freeze.clone();
+        let pr_health = health.clone();
+        let pr_readiness = readiness.clone();
+        let pr_scheduler = scheduler.clone();
+        let (pr_colors, pr_patterns, pr_timings, pr_schedule) = (
+            jenkins_colors.clone(),
+            jenkins_patterns.clone(),
+            jenkins_timings.clone(),
+            jenkins_schedule.clone(),
+        );
+        let pr_power_on_test = jenkins_power_on_test.clone();
+        let pr_escalation = jenkins_escalation.clone();
+        let pr_polling = jenkins_polling.clone();
+        supervisor.spawn("Jenkins PRs", move || {
+            // Taken from the main Jenkins poll, even with a relay feed
+            let pr_integration: Box<RemoteIntegration> = if demo {
+                Box::new(DemoIntegration::new(
+                    "Jenkins PRs",
+                    pr_r,
+                    pr_g,
+                    pr_b,
+                    jenkins_aggregation.strategy(),
+                    1,
+                ))
+            } else {
+                Box::new(PullRequestIntegration::new(
+                    pr_r,
+                    pr_g,
+                    pr_b,
+                    pr_board.clone(),
+                ))
+            };
+            start_thread(
+                pr_integration,
+                jenkins_output_type,
+                pr_colors.clone(),
+                pr_patterns.clone(),
+                pr_timings.clone(),
+                pr_power_on_test.clone(),
+                pr_schedule.clone(),
+                pr_escalation.clone(),
+                PollBudget::new(&pr_polling, Duration::from_millis(jenkins_poll_interval)),
+                Arc::clone(&pr_controls),
+                pr_connectivity.clone(),
+                pr_freeze.clone(),
+                pr_board.clone(),
+                pr_health.clone(),
+                pr_readiness.clone(),
+                pr_scheduler.clone(),
+            )
+        });
+    }
+    let jenkins_board = status_board.clone();
+    let jenkins_controls = Arc::clone(&controls);
+    let jenkins_connectivity = connectivity.clone();
+    let jenkins_freeze = freeze.clone();
+    let jenkins_health = health.clone();
+    let jenkins_readiness = readiness.clone();
+    let jenkins_credentials = credentials.clone();
+    supervisor.spawn("Jenkins", move || {
+        let jenkins_integration: Box<RemoteIntegration> = match jenkins_feed {
+            _ if demo => Box::new(DemoIntegration::new(
+                "Jenkins",
+                jenkins_r,
+                jenkins_g,
+                jenkins_b,
+                jenkins_aggregation.strategy(),
+                0,
+            )),
+            Some(ref feed) => Box::new(RelayedIntegration::new(
+                "Jenkins",
+                jenkins_r,
+                jenkins_g,
+                jenkins_b,
+                feed.clone(),
+            )),
+            None => {
+                let mut jenkins_integration = JenkinsIntegration::new(
+                    jenkins_r,
+                    jenkins_g,
+                    jenkins_b,
+                    &jenkins_username,
+                    &jenkins_password,
+                    jenkins_uses_api_token,
+                    jenkins_base_urls.clone(),
+                    jenkins_client.0.clone(),
+                    jenkins_flakiness.as_ref().map(JobHistory::new),
+                    jenkins_critical_jobs.clone(),
+                    jenkins_aggregation.strategy(),
+                    jenkins_last_completed_only,
+                    jenkins_queue_threshold,
+                    jenkins_durations.as_ref().map(BuildDurations::new),
+                    jenkins_jobs.clone(),
+                );
+                jenkins_integration.set_credentials(jenkins_credentials.clone());
+                jenkins_integration.set_aborted_is_failure(jenkins_aborted_is_failure);
+                jenkins_integration.set_mainline(jenkins_mainline.clone());
+                jenkins_integration.set_watch(jenkins_watch);
+                Box::new(jenkins_integration)
+            }
+        };
+        start_thread(
+            jenkins_integration,
+            jenkins_output_type,
+            jenkins_colors.clone(),
+            jenkins_patterns.clone(),
+            jenkins_timings.clone(),
+            jenkins_power_on_test.clone(),
+            jenkins_schedule.clone(),
+            jenkins_escalation.clone(),
+            PollBudget::new(
+                &jenkins_polling,
+                Duration::from_millis(jenkins_poll_interval),
+            ),
+            Arc::clone(&jenkins_controls),
+            jenkins_connectivity.clone(),
+            jenkins_freeze.clone(),
+            jenkins_board.clone(),
+            jenkins_health.clone(),
+            jenkins_readiness.clone(),
+            jenkins_scheduler.clone(),
+        )
+    });
+
+    let unity_cloud_board = status_board.clone();
+    let unity_cloud_controls = Arc::clone(&controls);
+    let unity_cloud_connectivity = connectivity.clone();
+    let unity_cloud_freeze = freeze.clone();
+    let unity_cloud_health = health.clone();
+    let unity_cloud_readiness = readiness.clone();
+    let unity_credentials = credentials.clone();
+    supervisor.spawn("Unity Cloud", move || {
+        let unity_cloud_integration: Box<RemoteIntegration> = match unity_feed {
+            _ if demo => Box::new(DemoIntegration::new(
+                "Unity Cloud",
+                unity_r,
+                unity_g,
+                unity_b,
+                unity_aggregation.strategy(),
+                2,
+            )),
+            Some(ref feed) => Box::new(RelayedIntegration::new(
+                "Unity Cloud",
+                unity_r,
+                unity_g,
+                unity_b,
+                feed.clone(),
+            )),
+            None => {
+                let mut unity_cloud_integration = UnityCloudIntegration::new(
+                    unity_r,
+                    unity_g,
+                    unity_b,
+                    &unity_api_token,
+                    &unity_base_url,
+                    unity_client.0.clone(),
+                    unity_aggregation.strategy(),
+                );
+                unity_cloud_integration.set_credentials(unity_credentials.clone());
+                Box::new(unity_cloud_integration)
+            }
+        };
+        start_thread(
+            unity_cloud_integration,
+            unity_output_type,
+            unity_colors.clone(),
+            unity_patterns.clone(),
+            unity_timings.clone(),
+            unity_power_on_test.clone(),
+            unity_schedule.clone(),
+            unity_escalation.clone(),
+            PollBudget::new(&unity_polling, Duration::from_millis(unity_poll_interval)),
+            Arc::clone(&unity_cloud_controls),
+            unity_cloud_connectivity.clone(),
+            unity_cloud_freeze.clone(),
+            unity_cloud_board.clone(),
+            unity_cloud_health.clone(),
+            unity_cloud_readiness.clone(),
+            unity_scheduler.clone(),
+        )
+    });
+
+    for (i, instance) in instances.into_iter().enumerate() {
+        let instance_board = status_board.clone();
+        let instance_controls = Arc::clone(&controls);
+        let instance_connectivity = connectivity.clone();
+        let instance_freeze = freeze.clone();
+        let instance_health = health.clone();
+        let instance_readiness = readiness.clone();
+        let instance_scheduler = scheduler.clone();
+        let instance_feed = instance_feed.clone();
+        let (instance_patterns, instance_timings) =
+            (instance_patterns.clone(), instance_timings.clone());
+        let instance_power_on_test = instance_power_on_test.clone();
+        let (instance_schedule, instance_escalation) =
+            (instance_schedule.clone(), instance_escalation.clone());
+        let name = instance.name.clone();
+        let instance = panic::AssertUnwindSafe(instance);
+        supervisor.spawn(&name, move || {
+            start_thread(
+                // The demo's scenes carry on from the main integrations'
+                instance.integration(
+                    demo,
+                    instance_feed.as_ref(),
+                    3 + i,
+                    jenkins_aborted_is_failure,
+                ),
+                instance.output_type,
+                instance.colors.clone(),
+                instance_patterns.clone(),
+                instance_timings.clone(),
+                instance_power_on_test.clone(),
+                instance_schedule.clone(),
+                instance_escalation.clone(),
+                PollBudget::new(
+                    &instance.polling,
+                    Duration::from_millis(instance.poll_interval),
+                ),
+                Arc::clone(&instance_controls),
+                instance_connectivity.clone(),
+                instance_freeze.clone(),
+                instance_board.clone(),
+                instance_health.clone(),
+                instance_readiness.clone(),
+                instance_scheduler.clone(),
+            )
+        });
+    }
+
+    // Wait for all threads to finish, keeping the first reason one stopped early.
+    let mut results = supervisor.join_all();
+    results.extend(vec![
+        selector_handle.map_or(Ok(()), |handle| join("selector", handle)),
+        aggregate_handle.map_or(Ok(()), |handle| join("aggregate LED", handle)),
+        beacon_handle.map_or(Ok(()), |handle| join("beacon", handle)),
+        button_handle.map_or(Ok(()), |handle| join("button", handle)),
+        lcd_handle.map_or(Ok(()), |handle| join("LCD", handle)),
+        led_strip_handle.map_or(Ok(()), |handle| join("LED strip", handle)),
+        health_handle.map_or(Ok(()), |handle| join("health check", handle)),
+        join("signal handler", signals_handle),
+        refresh_handle.map_or(Ok(()), |handle| join("credential refresh", handle)),
+        connectivity_handle.map_or(Ok(()), |handle| join("connectivity check", handle)),
+        calendar_handle.map_or(Ok(()), |handle| join("calendar", handle)),
+        unity_webhook_handle.map_or(Ok(()), |handle| join("Unity Cloud webhook", handle)),
+        report_handle.map_or(Ok(()), |handle| join("daily report", handle)),
+    ]);
+    for handle in job_group_handles {
+        results.push(join("job group LED", handle));
+    }
+    let result = results.into_iter().collect::<Result<Vec<()>, RunError>>();
+
+    info!("All threads terminated. Terminating program...");
+    result.map(|_| ())
+}
+
+// Where the executable is, which is where log4rs.yml and config.toml go.
+pub fn exe_dir() -> Result<PathBuf, RunError> {
+    std::env::current_exe()
+        .ok()
+        .and_then(|path| path.parent().map(|dir| dir.to_path_buf()))
+        .ok_or_else(|| RunError::StartupFailed {
+            reason: "Failed to obtain current executable directory.".to_string(),
+        })
+}
+
+pub fn read_config(exe_dir: &Path) -> Result<Config, RunError> {
+    let config_file_path = exe_dir.join("config.toml");
+    info!("Looking for config file at: {:?}", config_file_path);
+    let mut config_file = File::open(&config_file_path).map_err(|err| RunError::ConfigMissing {
+        path: config_file_path.display().to_string(),
+        reason: err.to_string(),
+    })?;
+    let mut config_text = String::new();
+    config_file
+        .read_to_string(&mut config_text)
+        .map_err(|err| RunError::ConfigInvalid {
+            reason: format!("Failed to read config file: {}", err),
+        })?;
+    let mut config: Config =
+        toml::from_str(config_text.as_str()).map_err(|err| RunError::ConfigInvalid {
+            reason: format!("Failed to deserialize config file: {}", err),
+        })?;
+    config
+        .decrypt_secrets()
+        .map_err(|err| RunError::ConfigInvalid {
+            reason: format!("Failed to decrypt config file: {}", err),
+        })?;
+    config
+        .apply_netrc()
+        .map_err(|err| RunError::ConfigInvalid {
+            reason: format!("Invalid [netrc]: {}", err),
+        })?;
+    redact::register_secrets(&config.secrets());
+    Ok(config)
+}
+
+// Waits for a thread, turning a panic that escaped it into an error.
+fn join<R>(name: &str, handle: thread::JoinHandle<R>) -> Result<R, RunError> {
+    handle.join().map_err(|payload| RunError::ThreadFailed {
+        thread: name.to_string(),
+        reason: panic_message(&payload),
+    })
+}
+
+// Polls remote until the scheduler shuts down, showing its status on the LED
+// and publishing it to the board.
+pub fn start_thread<T: RemoteIntegration>(
+    mut remote: T,
+    output_type: OutputType,
+    colors: ColorScheme,
+    patterns: PatternScheme,
+    timings: PatternTimings,
+    power_on_test: PowerOnTestConfig,
+    schedule: Option<Schedule>,
+    escalation: Option<EscalationConfig>,
+    mut budget: PollBudget,
+    controls: Arc<Controls>,
+    connectivity: Connectivity,
+    freeze: Freeze,
+    board: StatusBoard,
+    health: Health,
+    readiness: Readiness,
+    scheduler: TickScheduler,
+) {
+    let mut led = RgbLedLight::new(
+        remote.get_red_id(),
+        remote.get_green_id(),
+        remote.get_blue_id(),
+    );
+    led.set_output_type(output_type);
+    events::set_integration(remote.get_name());
+    health.heartbeat(remote.get_name());
+    let mut led_controller = LedController::new(led, colors, patterns, timings, controls);
+    led_controller.run_power_on_test(&power_on_test);
+    readiness.mark_ready(remote.get_name());
+    led_controller.set_escalation(escalation);
+    led_controller.set_connectivity(connectivity);
+    led_controller.set_freeze(freeze);
+    let mut last_result = None;
+    let mut poll_backoff = Backoff::new(
+        Duration::from_millis(SLEEP_DURATION),
+        Duration::from_millis(MAX_BACKOFF_DURATION),
+    );
+    // The LED is refreshed at least this often, even when polls are further apart
+    let refresh = Duration::from_millis(SLEEP_DURATION);
+    let mut sleep_duration = refresh;
+    let mut poll_now = false;
+    let mut preflight_done = false;
+    let mut ticker = scheduler.named_ticker(remote.get_name());
+    while ticker.is_running() {
+        health.heartbeat(remote.get_name());
+        let active = schedule.as_ref().map_or(true, |s| s.is_active_now());
+        let until_due = if poll_now {
+            None
+        } else {
+            budget.until_due(Instant::now())
+        };
+        poll_now = false;
+        if let Some(until_due) = until_due {
+            sleep_duration = until_due.min(refresh);
+        } else if active
+            || schedule
+                .as_ref()
+                .map_or(true, |s| s.poll_outside_active_hours)
+        {
+            let poll_started = Instant::now();
+            budget.poll_started(poll_started, remote.get_request_budget());
+            let status = remote.get_status();
+            let mut summary = remote.get_summary();
+            // Shown on the status page and in events, so no credentials
+            summary.error = summary.error.map(|error| redact::redact(&error));
+            events::log_poll(status, &summary, poll_started.elapsed());
+            board.update(remote.get_name(), status, summary.clone());
+            match summary.error {
+                Some(ref error) => health.poll_failed(remote.get_name(), error),
+                None if !summary.disconnected => health.poll_succeeded(remote.get_name()),
+                None => (),
+            }
+            // The first poll doubles as a check of the URL and credentials
+            if !preflight_done {
+                preflight_done = true;
+                let preflight = Preflight::from_summary(&summary);
+                preflight.log(remote.get_name());
+                led_controller.show_preflight(&preflight);
+            }
+
+            // Back off while the server can't be reached at all
+            sleep_duration = if summary.disconnected && remote.should_back_off() {
+                let delay = poll_backoff.next_delay();
+                warn!(
+                    "{} unreachable {} times in a row. Next poll in {} seconds.",
+                    remote.get_name(),
+                    poll_backoff.consecutive_failures(),
+                    delay.as_secs()
+                );
+                delay
+            } else {
+                poll_backoff.reset();
+                budget
+                    .next_interval(remote.get_request_budget())
+                    .min(refresh)
+            };
+            if let Some(delay) = remote.get_rate_limit_delay() {
+                if delay > sleep_duration {
+                    info!(
+                        "{} is rate limiting us. Next poll in {} seconds.",
+                        remote.get_name(),
+                        delay.as_secs()
+                    );
+                    sleep_duration = delay;
+                }
+            }
+            last_result = Some((status, summary));
+        }
+
+        let brightness = match schedule {
+            Some(ref schedule) if !active && schedule.outside_hours == OutsideHoursMode::Dim => {
+                Some(schedule.dim_percent)
+            }
+            Some(_) if !active => None,
+            _ => Some(100),
+        };
+        match (brightness, &last_result) {
+            (Some(brightness), &Some((status, ref summary))) => {
+                led_controller.set_brightness(brightness);
+                // The first failure shows as disconnected; only after that are we really backing off.
+                if poll_backoff.consecutive_failures() > 1 {
+                    led_controller.show_degraded();
+                } else {
+                    led_controller.show_status(status, summary);
+                }
+            }
+            _ => led_controller.turn_off(),
+        }
+        health.led_shown(remote.get_name(), led_controller.shown());
+
+        // Wakes up early to stop, or to poll right away after a reload
+        match ticker.wait(sleep_duration) {
+            Tick::Shutdown => break,
+            Tick::Reload => poll_now = true,
+            Tick::Elapsed => (),
+        }
+    }
+    led_controller.shut_down();
+}
+
+// The client is shared with restarted threads. It isn't marked unwind safe,
+// but a poll panicking halfway through can't leave it in a broken state.
+fn build_http_client(
+    section: &str,
+    http_config: &HttpConfig,
+    base_url: &str,
+) -> Result<panic::AssertUnwindSafe<HttpClient>, RunError> {
+    let client =
+        network::build_client(http_config, base_url).map_err(|err| RunError::ConfigInvalid {
+            reason: format!("Invalid [{}]: {}", section, err),
+        })?;
+    Ok(panic::AssertUnwindSafe(client))
+}
+
+// Another Jenkins server or Unity Cloud org, see jenkins_instances and
+// unity_instances, Team City, a GitHub or Bitbucket repository, SonarQube,
+// Grafana, Icinga, Kubernetes, Sentry, JIRA, a plugin or a command.
+struct Instance {
+    name: String,
+    pins: (u16, u16, u16),
+    output_type: OutputType,
+    colors: ColorScheme,
+    aggregation: AggregationKind,
+    polling: PollingConfig,
+    poll_interval: u64,
+    client: HttpClient,
+    // Reloaded along with the main ones, see CredentialReloader
+    credentials: Credentials,
+    server: InstanceServer,
+}
+
+enum InstanceServer {
+    Jenkins {
+        username: String,
+        password: String,
+        uses_api_token: bool,
+        base_urls: BaseUrls,
+    },
+    Unity {
+        api_token: String,
+        base_url: String,
+    },
+    TeamCity {
+        base_url: String,
+        username: String,
+        password: String,
+        project: Option<String>,
+        watch: bool,
+    },
+    GitHub {
+        api_url: String,
+        repository: String,
+        branch: String,
+        token: Option<String>,
+    },
+    Bitbucket {
+        api_url: String,
+        workspace: String,
+        repository: String,
+        branches: Vec<String>,
+        username: String,
+        app_password: String,
+    },
+    SonarQube {
+        base_url: String,
+        project_keys: Vec<String>,
+        token: Option<String>,
+    },
+    Grafana {
+        base_url: String,
+        labels: BTreeMap<String, String>,
+        token: Option<String>,
+    },
+    Icinga {
+        base_url: String,
+        username: String,
+        password: String,
+        services: Vec<String>,
+        host_groups: Vec<String>,
+    },
+    Kubernetes {
+        api_url: String,
+        namespaces: Vec<String>,
+        label_selector: Option<String>,
+        token: Option<String>,
+        token_file: Option<String>,
+    },
+    Sentry {
+        base_url: String,
+        organization: String,
+        project: String,
+        token: String,
+        window_minutes: u64,
+        warning_threshold: u64,
+        failing_threshold: u64,
+    },
+    Jira {
+        base_url: String,
+        jql: String,
+        username: String,
+        password: String,
+        warning_threshold: u64,
+        failing_threshold: u64,
+    },
+    Plugin {
+        plugin: Arc<Plugin>,
+        settings_json: String,
+    },
+    Exec {
+        command: String,
+        timeout_seconds: u64,
+    },
+}
+
+impl Instance {
+    fn integration(
+        &self,
+        demo: bool,
+        feed: Option<&RelayFeed>,
+        first_scene: usize,
+        aborted_is_failure: bool,
+    ) -> Box<RemoteIntegration> {
+        let (r, g, b) = self.pins;
+        if demo {
+            return Box::new(DemoIntegration::new(
+                &self.name,
+                r,
+                g,
+                b,
+                self.aggregation.strategy(),
+                first_scene,
+            ));
+        }
+        if let Some(feed) = feed {
+            return Box::new(RelayedIntegration::new(&self.name, r, g, b, feed.clone()));
+        }
+        match self.server {
+            InstanceServer::Jenkins {
+                ref username,
+                ref password,
+                uses_api_token,
+                ref base_urls,
+            } => {
+                let mut integration = JenkinsIntegration::new(
+                    r,
+                    g,
+                    b,
+                    username,
+                    password,
+                    uses_api_token,
+                    base_urls.clone(),
+                    self.client.clone(),
+                    None,
+                    Vec::new(),
+                    self.aggregation.strategy(),
+                    false,
+                    None,
+                    None,
+                    JobSelection::All,
+                );
+                integration.set_name(&self.name);
+                integration.set_aborted_is_failure(aborted_is_failure);
+                integration.set_credentials(self.credentials.clone());
+                Box::new(integration)
+            }
+            InstanceServer::Unity {
+                ref api_token,
+                ref base_url,
+            } => {
+                let mut integration = UnityCloudIntegration::new(
+                    r,
+                    g,
+                    b,
+                    api_token,
+                    base_url,
+                    self.client.clone(),
+                    self.aggregation.strategy(),
+                );
+                integration.set_name(&self.name);
+                integration.set_credentials(self.credentials.clone());
+                Box::new(integration)
+            }
+            InstanceServer::TeamCity {
+                ref base_url,
+                ref username,
+                ref password,
+                ref project,
+                watch,
+            } => {
+                let mut integration = TeamCityIntegration::new(
+                    r,
+                    g,
+                    b,
+                    &self.name,
+                    base_url,
+                    username,
+                    password,
+                    project.clone(),
+                    self.client.clone(),
+                    self.aggregation.strategy(),
+                );
+                integration.set_watch(watch);
+                integration.set_credentials(self.credentials.clone());
+                Box::new(integration)
+            }
+            InstanceServer::GitHub {
+                ref api_url,
+                ref repository,
+                ref branch,
+                ref token,
+            } => {
+                let mut integration = GitHubIntegration::new(
+                    r,
+                    g,
+                    b,
+                    &self.name,
+                    api_url,
+                    repository,
+                    branch,
+                    token.clone(),
+                    self.client.clone(),
+                    self.aggregation.strategy(),
+                );
+                integration.set_credentials(self.credentials.clone());
+                Box::new(integration)
+            }
+            InstanceServer::Bitbucket {
+                ref api_url,
+                ref workspace,
+                ref repository,
+                ref branches,
+                ref username,
+                ref app_password,
+            } => {
+                let mut integration = BitbucketIntegration::new(
+                    r,
+                    g,
+                    b,
+                    &self.name,
+                    api_url,
+                    workspace,
+                    repository,
+                    branches.clone(),
+                    username,
+                    app_password,
+                    self.client.clone(),
+                    self.aggregation.strategy(),
+                );
+                integration.set_credentials(self.credentials.clone());
+                Box::new(integration)
+            }
+            InstanceServer::SonarQube {
+                ref base_url,
+                ref project_keys,
+                ref token,
+            } => {
+                let mut integration = SonarQubeIntegration::new(
+                    r,
+                    g,
+                    b,
+                    &self.name,
+                    base_url,
+                    project_keys.clone(),
+                    token.clone(),
+                    self.client.clone(),
+                    self.aggregation.strategy(),
+                );
+                integration.set_credentials(self.credentials.clone());
+                Box::new(integration)
+            }
+            InstanceServer::Grafana {
+                ref base_url,
+                ref labels,
+                ref token,
+            } => {
+                let mut integration = GrafanaIntegration::new(
+                    r,
+                    g,
+                    b,
+                    &self.name,
+                    base_url,
+                    labels.clone(),
+                    token.clone(),
+                    self.client.clone(),
+                );
+                integration.set_credentials(self.credentials.clone());
+                Box::new(integration)
+            }
+            InstanceServer::Icinga {
+                ref base_url,
+                ref username,
+                ref password,
+                ref services,
+                ref host_groups,
+            } => {
+                let mut integration = IcingaIntegration::new(
+                    r,
+                    g,
+                    b,
+                    &self.name,
+                    base_url,
+                    username,
+                    password,
+                    services.clone(),
+                    host_groups.clone(),
+                    self.client.clone(),
+                    self.aggregation.strategy(),
+                );
+                integration.set_credentials(self.credentials.clone());
+                Box::new(integration)
+            }
+            InstanceServer::Kubernetes {
+                ref api_url,
+                ref namespaces,
+                ref label_selector,
+                ref token,
+                ref token_file,
+            } => {
+                let mut integration = K8sIntegration::new(
+                    r,
+                    g,
+                    b,
+                    &self.name,
+                    api_url,
+                    namespaces.clone(),
+                    label_selector.clone(),
+                    token.clone(),
+                    token_file.clone(),
+                    self.client.clone(),
+                    self.aggregation.strategy(),
+                );
+                integration.set_credentials(self.credentials.clone());
+                Box::new(integration)
+            }
+            InstanceServer::Sentry {
+                ref base_url,
+                ref organization,
+                ref project,
+                ref token,
+                window_minutes,
+                warning_threshold,
+                failing_threshold,
+            } => {
+                let mut integration = SentryIntegration::new(
+                    r,
+                    g,
+                    b,
+                    &self.name,
+                    base_url,
+                    organization,
+                    project,
+                    token,
+                    window_minutes,
+                    warning_threshold,
+                    failing_threshold,
+                    self.client.clone(),
+                );
+                integration.set_credentials(self.credentials.clone());
+                Box::new(integration)
+            }
+            InstanceServer::Jira {
+                ref base_url,
+                ref jql,
+                ref username,
+                ref password,
+                warning_threshold,
+                failing_threshold,
+            } => {
+                let mut integration = JiraIntegration::new(
+                    r,
+                    g,
+                    b,
+                    &self.name,
+                    base_url,
+                    jql,
+                    username,
+                    password,
+                    warning_threshold,
+                    failing_threshold,
+                    self.client.clone(),
+                );
+                integration.set_credentials(self.credentials.clone());
+                Box::new(integration)
+            }
+            InstanceServer::Plugin {
+                ref plugin,
+                ref settings_json,
+            } => Box::new(PluginIntegration::new(
+                r,
+                g,
+                b,
+                &self.name,
+                Arc::clone(plugin),
+                settings_json,
+            )),
+            InstanceServer::Exec {
+                ref command,
+                timeout_seconds,
+            } => Box::new(ExecIntegration::new(
+                r,
+                g,
+                b,
+                &self.name,
+                command,
+                Duration::from_secs(timeout_seconds),
+            )),
+        }
+    }
+}
+
+// The instances in config, which each need a name no other integration has.
+fn read_instances(config: &Config, demo: bool) -> Result<Vec<Instance>, RunError> {
+    let mut names = vec![
+        "Jenkins".to_string(),
+        "Jenkins PRs".to_string(),
+        "Unity Cloud".to_string(),
+    ];
+    let mut claim = |name: String| {
+        if names.contains(&name) {
+            return Err(RunError::ConfigInvalid {
+                reason: format!(
+                    "There's more than one \"{}\", give each instance a name of its own",
+                    name
+                ),
+            });
+        }
+        names.push(name.clone());
+        Ok(name)
+    };
+    let mut instances = Vec::new();
+    for jenkins in &config.jenkins_instances {
+        let name = claim(jenkins.display_name())?;
+        let client =
+            build_http_client(&format!("{} http", name), &jenkins.http, &jenkins.base_url)?;
+        instances.push(Instance {
+            pins: (
+                jenkins.led_pins[0],
+                jenkins.led_pins[1],
+                jenkins.led_pins[2],
+            ),
+            output_type: jenkins.output_type,
+            colors: jenkins.colors.clone(),
+            aggregation: jenkins.aggregation,
+            polling: jenkins.polling.clone(),
+            poll_interval: SLEEP_DURATION,
+            client: client.0,
+            server: InstanceServer::Jenkins {
+                username: jenkins.username.clone(),
+                password: jenkins
+                    .api_token
+                    .clone()
+                    .unwrap_or_else(|| jenkins.password.clone()),
+                uses_api_token: jenkins.api_token.is_some(),
+                base_urls: BaseUrls::new(&jenkins.base_url, &jenkins.fallback_urls),
+            },
+            credentials: Credentials::for_instance(config, &name),
+            name: name,
+        });
+    }
+    for unity in &config.unity_instances {
+        let name = claim(unity.display_name())?;
+        let client = build_http_client(&format!("{} http", name), &unity.http, &unity.base_url)?;
+        instances.push(Instance {
+            pins: (unity.led_pins[0], unity.led_pins[1], unity.led_pins[2]),
+            output_type: unity.output_type,
+            colors: unity.colors.clone(),
+            aggregation: unity.aggregation,
+            polling: unity.polling.clone(),
+            poll_interval: if demo {
+                SLEEP_DURATION
+            } else {
+                UNITY_POLL_INTERVAL
+            },
+            client: client.0,
+            server: InstanceServer::Unity {
+                api_token: unity.api_token.clone(),
+                base_url: unity.base_url.clone(),
+            },
+            credentials: Credentials::for_instance(config, &name),
+            name: name,
+        });
+    }
+    if config.team_city_enabled() {
+        let name = claim("Team City".to_string())?;
+        let client = build_http_client(
+            "team_city_http",
+            &config.team_city_http,
+            &config.team_city_base_url,
+        )?;
+        instances.push(Instance {
+            pins: (
+                config.team_city_led_pins[0],
+                config.team_city_led_pins[1],
+                config.team_city_led_pins[2],
+            ),
+            output_type: config.team_city_output_type,
+            colors: config.team_city_colors.clone(),
+            aggregation: config.team_city_aggregation,
+            polling: config.team_city_polling.clone(),
+            poll_interval: config
+                .team_city_watch_seconds
+                .map_or(SLEEP_DURATION, |seconds| seconds * 1000),
+            client: client.0,
+            server: InstanceServer::TeamCity {
+                base_url: config.team_city_base_url.clone(),
+                username: config.team_city_username.clone(),
+                password: config.team_city_password.clone(),
+                project: config.team_city_project.clone(),
+                watch: config.team_city_watch_seconds.is_some(),
+            },
+            credentials: Credentials::for_instance(config, &name),
+            name: name,
+        });
+    }
+    for github in &config.github {
+        let name = claim(github.display_name())?;
+        let client = build_http_client(&format!("{} http", name), &github.http, &github.api_url)?;
+        instances.push(Instance {
+            pins: (github.led_pins[0], github.led_pins[1], github.led_pins[2]),
+            output_type: github.output_type,
+            colors: github.colors.clone(),
+            aggregation: github.aggregation,
+            polling: github.polling.clone(),
+            poll_interval: SLEEP_DURATION,
+            client: client.0,
+            server: InstanceServer::GitHub {
+                api_url: github.api_url.clone(),
+                repository: github.repository.clone(),
+                branch: github.branch.clone(),
+                token: github.token.clone(),
+            },
+            credentials: Credentials::for_instance(config, &name),
+            name: name,
+        });
+    }
+    for bitbucket in &config.bitbucket {
+        let name = claim(bitbucket.display_name())?;
+        let client = build_http_client(
+            &format!("{} http", name),
+            &bitbucket.http,
+            &bitbucket.api_url,
+        )?;
+        instances.push(Instance {
+            pins: (
+                bitbucket.led_pins[0],
+                bitbucket.led_pins[1],
+                bitbucket.led_pins[2],
+            ),
+            output_type: bitbucket.output_type,
+            colors: bitbucket.colors.clone(),
+            aggregation: bitbucket.aggregation,
+            polling: bitbucket.polling.clone(),
+            poll_interval: SLEEP_DURATION,
+            client: client.0,
+            server: InstanceServer::Bitbucket {
+                api_url: bitbucket.api_url.clone(),
+                workspace: bitbucket.workspace.clone(),
+                repository: bitbucket.repository.clone(),
+                branches: bitbucket.branches.clone(),
+                username: bitbucket.username.clone(),
+                app_password: bitbucket.app_password.clone(),
+            },
+            credentials: Credentials::for_instance(config, &name),
+            name: name,
+        });
+    }
+    for sonarqube in &config.sonarqube {
+        let name = claim(sonarqube.display_name())?;
+        let client = build_http_client(
+            &format!("{} http", name),
+            &sonarqube.http,
+            &sonarqube.base_url,
+        )?;
+        instances.push(Instance {
+            pins: (
+                sonarqube.led_pins[0],
+                sonarqube.led_pins[1],
+                sonarqube.led_pins[2],
+            ),
+            output_type: sonarqube.output_type,
+            colors: sonarqube.colors.clone(),
+            aggregation: sonarqube.aggregation,
+            polling: sonarqube.polling.clone(),
+            poll_interval: SLEEP_DURATION,
+            client: client.0,
+            server: InstanceServer::SonarQube {
+                base_url: sonarqube.base_url.clone(),
+                project_keys: sonarqube.project_keys.clone(),
+                token: sonarqube.token.clone(),
+            },
+            credentials: Credentials::for_instance(config, &name),
+            name: name,
+        });
+    }
+    for grafana in &config.grafana {
+        let name = claim(grafana.display_name())?;
+        let client =
+            build_http_client(&format!("{} http", name), &grafana.http, &grafana.base_url)?;
+        instances.push(Instance {
+            pins: (
+                grafana.led_pins[0],
+                grafana.led_pins[1],
+                grafana.led_pins[2],
+            ),
+            output_type: grafana.output_type,
+            colors: grafana.colors.clone(),
+            // Only used in demo mode, alerts aren't aggregated
+            aggregation: AggregationKind::default(),
+            polling: grafana.polling.clone(),
+            poll_interval: SLEEP_DURATION,
+            client: client.0,
+            server: InstanceServer::Grafana {
+                base_url: grafana.base_url.clone(),
+                labels: grafana.labels.clone(),
+                token: grafana.token.clone(),
+            },
+            credentials: Credentials::for_instance(config, &name),
+            name: name,
+        });
+    }
+    for icinga in &config.icinga {
+        let name = claim(icinga.display_name())?;
+        let client = build_http_client(&format!("{} http", name), &icinga.http, &icinga.base_url)?;
+        instances.push(Instance {
+            pins: (icinga.led_pins[0], icinga.led_pins[1], icinga.led_pins[2]),
+            output_type: icinga.output_type,
+            colors: icinga.colors.clone(),
+            aggregation: icinga.aggregation,
+            polling: icinga.polling.clone(),
+            poll_interval: SLEEP_DURATION,
+            client: client.0,
+            server: InstanceServer::Icinga {
+                base_url: icinga.base_url.clone(),
+                username: icinga.username.clone(),
+                password: icinga.password.clone(),
+                services: icinga.services.clone(),
+                host_groups: icinga.host_groups.clone(),
+            },
+            credentials: Credentials::for_instance(config, &name),
+            name: name,
+        });
+    }
+    for kubernetes in &config.kubernetes {
+        let name = claim(kubernetes.display_name())?;
+        let client = build_http_client(
+            &format!("{} http", name),
+            &kubernetes.http,
+            &kubernetes.api_url,
+        )?;
+        // Inside a pod, its own service account
+        let in_cluster = Path::new(k8s_integration::IN_CLUSTER_TOKEN_FILE).exists();
+        let token_file = match (&kubernetes.token, &kubernetes.token_file) {
+            (&None, &None) if in_cluster => {
+                Some(k8s_integration::IN_CLUSTER_TOKEN_FILE.to_string())
+            }
+            (_, token_file) => token_file.clone(),
+        };
+        instances.push(Instance {
+            pins: (
+                kubernetes.led_pins[0],
+                kubernetes.led_pins[1],
+                kubernetes.led_pins[2],
+            ),
+            output_type: kubernetes.output_type,
+            colors: kubernetes.colors.clone(),
+            aggregation: kubernetes.aggregation,
+            polling: kubernetes.polling.clone(),
+            poll_interval: SLEEP_DURATION,
+            client: client.0,
+            server: InstanceServer::Kubernetes {
+                api_url: kubernetes.api_url.clone(),
+                namespaces: kubernetes.namespaces.clone(),
+                label_selector: kubernetes.label_selector.clone(),
+                token: kubernetes.token.clone(),
+                token_file: token_file,
+            },
+            credentials: Credentials::for_instance(config, &name),
+            name: name,
+        });
+    }
+    for sentry in &config.sentry {
+        let name = claim(sentry.display_name())?;
+        let client = build_http_client(&format!("{} http", name), &sentry.http, &sentry.base_url)?;
+        instances.push(Instance {
+            pins: (sentry.led_pins[0], sentry.led_pins[1], sentry.led_pins[2]),
+            output_type: sentry.output_type,
+            colors: sentry.colors.clone(),
+            // Only used in demo mode, the issue count isn't aggregated
+            aggregation: AggregationKind::default(),
+            polling: sentry.polling.clone(),
+            poll_interval: SLEEP_DURATION,
+            client: client.0,
+            server: InstanceServer::Sentry {
+                base_url: sentry.base_url.clone(),
+                organization: sentry.organization.clone(),
+                project: sentry.project.clone(),
+                token: sentry.token.clone(),
+                window_minutes: sentry.window_minutes,
+                warning_threshold: sentry.warning_threshold,
+                failing_threshold: sentry.failing_threshold,
+            },
+            credentials: Credentials::for_instance(config, &name),
+            name: name,
+        });
+    }
+    for jira in &config.jira {
+        let name = claim(jira.display_name())?;
+        let client = build_http_client(&format!("{} http", name), &jira.http, &jira.base_url)?;
+        instances.push(Instance {
+            pins: (jira.led_pins[0], jira.led_pins[1], jira.led_pins[2]),
+            output_type: jira.output_type,
+            colors: jira.colors.clone(),
+            // Only used in demo mode, the issue count isn't aggregated
+            aggregation: AggregationKind::default(),
+            polling: jira.polling.clone(),
+            poll_interval: SLEEP_DURATION,
+            client: client.0,
+            server: InstanceServer::Jira {
+                base_url: jira.base_url.clone(),
+                jql: jira.jql.clone(),
+                username: jira.username.clone(),
+                password: jira.password.clone(),
+                warning_threshold: jira.warning_threshold,
+                failing_threshold: jira.failing_threshold,
+            },
+            credentials: Credentials::for_instance(config, &name),
+            name: name,
+        });
+    }
+    for plugin_config in &config.plugins {
+        let name = claim(plugin_config.name.clone())?;
+        let invalid = |reason: String| RunError::ConfigInvalid {
+            reason: format!("Invalid [[plugins]] \"{}\": {}", name, reason),
+        };
+        let path = exe_dir()?.join("plugins").join(&plugin_config.library);
+        let plugin = Plugin::load(&path).map_err(|err| invalid(err.to_string()))?;
+        let settings_json = serde_json::to_string(&plugin_config.settings)
+            .map_err(|err| invalid(err.to_string()))?;
+        // Plugins make their own requests, this client is never used
+        let client = build_http_client(
+            &format!("{} http", name),
+            &HttpConfig::default(),
+            "http://localhost",
+        )?;
+        instances.push(Instance {
+            pins: (
+                plugin_config.led_pins[0],
+                plugin_config.led_pins[1],
+                plugin_config.led_pins[2],
+            ),
+            output_type: plugin_config.output_type,
+            colors: plugin_config.colors.clone(),
+            // Only used in demo mode, plugins summarize their own jobs
+            aggregation: AggregationKind::default(),
+            polling: plugin_config.polling.clone(),
+            poll_interval: SLEEP_DURATION,
+            client: client.0,
+            server: InstanceServer::Plugin {
+                plugin: Arc::new(plugin),
+                settings_json: settings_json,
+            },
+            credentials: Credentials::for_instance(config, &name),
+            name: name,
+        });
+    }
+    for exec in &config.exec {
+        let name = claim(exec.name.clone())?;
+        // Commands make their own requests, if any, this client is never used
+        let client = build_http_client(
+            &format!("{} http", name),
+            &HttpConfig::default(),
+            "http://localhost",
+        )?;
+        instances.push(Instance {
+            pins: (exec.led_pins[0], exec.led_pins[1], exec.led_pins[2]),
+            output_type: exec.output_type,
+            colors: exec.colors.clone(),
+            // Only used in demo mode, commands summarize their own jobs
+            aggregation: AggregationKind::default(),
+            polling: exec.polling.clone(),
+            poll_interval: SLEEP_DURATION,
+            client: client.0,
+            server: InstanceServer::Exec {
+                command: exec.command.clone(),
+                timeout_seconds: exec.timeout_seconds,
+            },
+            credentials: Credentials::for_instance(config, &name),
+            name: name,
+        });
+    }
+    Ok(instances)
+}
@@ -0,0 +1,132 @@
+use config_file::{HttpConfig, OAuth2Config};
+use failure::Error;
+use reqwest::{Client, StatusCode};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// Fetch a new token this long before the old one expires, so a request never
+// goes out with a token that expires on the way.
+const EXPIRY_MARGIN_SECONDS: u64 = 60;
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: Option<u64>,
+}
+
+pub struct AccessToken {
+    token: String,
+    // Tokens without an expiry are used until the server rejects them
+    expires_at: Option<Instant>,
+}
+
+// Where the bearer token sent along with every request comes from: either a
+// fixed token from the config, or an OAuth2 client credentials flow that gets
+// a new token whenever the previous one is about to expire.
+#[derive(Clone)]
+pub enum BearerAuth {
+    Token(String),
+    OAuth2 {
+        config: OAuth2Config,
+        current: Arc<Mutex<Option<AccessToken>>>,
+    },
+}
+
+impl BearerAuth {
+    pub fn from_config(config: &HttpConfig) -> Result<Option<BearerAuth>, Error> {
+        match (&config.bearer_token, &config.oauth2) {
+            (&Some(_), &Some(_)) => {
+                Err(format_err!("Use either bearer_token or oauth2, not both."))
+            }
+            (&Some(ref token), &None) => Ok(Some(BearerAuth::Token(token.clone()))),
+            (&None, &Some(ref oauth2)) => Ok(Some(BearerAuth::OAuth2 {
+                config: oauth2.clone(),
+                current: Arc::new(Mutex::new(None)),
+            })),
+            (&None, &None) => Ok(None),
+        }
+    }
+
+    // The token to send, fetching a new one first if needed.
+    pub fn token(&self, client: &Client) -> Result<String, Error> {
+        match *self {
+            BearerAuth::Token(ref token) => Ok(token.clone()),
+            BearerAuth::OAuth2 {
+                ref config,
+                ref current,
+            } => {
+                let mut current = current
+                    .lock()
+                    .map_err(|_| format_err!("Failed to acquire a lock on the OAuth2 token."))?;
+                let still_valid = match *current {
+                    Some(AccessToken {
+                        expires_at: Some(expires_at),
+                        ..
+                    }) => expires_at > Instant::now() + Duration::from_secs(EXPIRY_MARGIN_SECONDS),
+                    Some(AccessToken {
+                        expires_at: None, ..
+                    }) => true,
+                    None => false,
+                };
+                if !still_valid {
+                    *current = Some(request_token(client, config)?);
+                }
+                Ok(current
+                    .as_ref()
+                    .map(|access| access.token.clone())
+                    .unwrap_or_default())
+            }
+        }
+    }
+
+    // Forgets the current token after the server rejected it, so the next
+    // request gets a new one. Returns whether there's any point in retrying.
+    pub fn invalidate(&self) -> bool {
+        match *self {
+            BearerAuth::Token(_) => false,
+            BearerAuth::OAuth2 { ref current, .. } => match current.lock() {
+                Ok(mut current) => {
+                    *current = None;
+                    true
+                }
+                Err(_) => false,
+            },
+        }
+    }
+}
+
+fn request_token(client: &Client, config: &OAuth2Config) -> Result<AccessToken, Error> {
+    let mut form = vec![
+        ("grant_type", "client_credentials"),
+        ("client_id", config.client_id.as_str()),
+        ("client_secret", config.client_secret.as_str()),
+    ];
+    if let Some(ref scope) = config.scope {
+        form.push(("scope", scope.as_str()));
+    }
+    let mut response = client.post(config.token_url.as_str()).form(&form).send()?;
+    if response.status() != StatusCode::Ok {
+        return Err(format_err!(
+            "Requesting an OAuth2 token from {} failed with code: {}",
+            config.token_url,
+            response.status()
+        ));
+    }
+    let token: TokenResponse = response.json()?;
+    info!(
+        "Got a new OAuth2 token from {}, expires in {}.",
+        config.token_url,
+        token
+            .expires_in
+            .map_or("an unknown time".to_string(), |seconds| format!(
+                "{} seconds",
+                seconds
+            ))
+    );
+    Ok(AccessToken {
+        token: token.access_token,
+        expires_at: token
+            .expires_in
+            .map(|seconds| Instant::now() + Duration::from_secs(seconds)),
+    })
+}
@@ -0,0 +1,45 @@
+use rand::{self, Rng};
+use std::time::Duration;
+
+// Exponential backoff with jitter: each consecutive failure doubles the delay,
+// up to `max`, and the result is randomly spread by +/- 25% so that several
+// threads failing at once don't all retry in lockstep.
+pub struct Backoff {
+    base: Duration,
+    max: Duration,
+    consecutive_failures: u32,
+}
+
+impl Backoff {
+    pub fn new(base: Duration, max: Duration) -> Backoff {
+        Backoff {
+            base: base,
+            max: max,
+            consecutive_failures: 0,
+        }
+    }
+
+    pub fn consecutive_failures(&self) -> u32 {
+        self.consecutive_failures
+    }
+
+    pub fn reset(&mut self) {
+        self.consecutive_failures = 0;
+    }
+
+    // Records a failure and returns how long to wait before trying again.
+    pub fn next_delay(&mut self) -> Duration {
+        let exponent = self.consecutive_failures.min(16);
+        self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+
+        let base_millis = duration_millis(self.base);
+        let max_millis = duration_millis(self.max);
+        let delay_millis = base_millis.saturating_mul(1 << exponent).min(max_millis);
+        let jitter = rand::thread_rng().gen_range(0.75, 1.25);
+        Duration::from_millis((delay_millis as f64 * jitter) as u64)
+    }
+}
+
+fn duration_millis(duration: Duration) -> u64 {
+    duration.as_secs() * 1000 + (duration.subsec_nanos() / 1_000_000) as u64
+}
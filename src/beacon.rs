@@ -0,0 +1,127 @@
+use config_file::BeaconConfig;
+use controls::Controls;
+use gpio;
+use remote_status::RemoteStatus;
+use scheduler::{Tick, TickScheduler};
+use status_board::StatusBoard;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const REFRESH_MILLIS: u64 = 1000;
+
+// Switches a relay, e.g. for a mains powered rotating beacon, on while any
+// integration is failing. Acknowledging the failure or muting turns it off.
+pub fn run_beacon(
+    config: BeaconConfig,
+    board: StatusBoard,
+    controls: Arc<Controls>,
+    scheduler: TickScheduler,
+) {
+    let gpio = gpio::wiring_pi();
+    let switch = |on: bool| gpio.digital_write(config.pin, on != config.active_low);
+    let mut guard = OnTimeGuard::new(
+        Duration::from_secs(config.max_on_minutes * 60),
+        Duration::from_secs(config.cooldown_minutes * 60),
+    );
+    switch(false);
+
+    let mut failing_since: Option<Instant> = None;
+    let mut is_on = false;
+    let mut ticker = scheduler.ticker();
+    while ticker.is_running() {
+        let failing = board
+            .snapshots()
+            .iter()
+            .any(|snapshot| snapshot.status == RemoteStatus::Failing);
+        failing_since = if failing {
+            failing_since.or_else(|| Some(Instant::now()))
+        } else {
+            None
+        };
+        let wanted = failing_since.map_or(false, |since| {
+            !controls.is_muted() && !controls.is_acknowledged(since)
+        });
+
+        let on = guard.update(wanted, Instant::now());
+        if on != is_on {
+            match (on, wanted) {
+                (true, _) => info!("--Beacon--: On."),
+                (false, true) => warn!(
+                    "--Beacon--: On for {} minutes, switching it off for {} minutes to rest.",
+                    config.max_on_minutes, config.cooldown_minutes
+                ),
+                (false, false) => info!("--Beacon--: Off."),
+            }
+            switch(on);
+            is_on = on;
+        }
+        if ticker.wait(Duration::from_millis(REFRESH_MILLIS)) == Tick::Shutdown {
+            break;
+        }
+    }
+    switch(false);
+}
+
+// Keeps something from staying on longer than max_on at a time, then keeps
+// it off for cooldown before it may come back on.
+struct OnTimeGuard {
+    max_on: Duration,
+    cooldown: Duration,
+    on_since: Option<Instant>,
+    cooling_until: Option<Instant>,
+}
+
+impl OnTimeGuard {
+    fn new(max_on: Duration, cooldown: Duration) -> OnTimeGuard {
+        OnTimeGuard {
+            max_on: max_on,
+            cooldown: cooldown,
+            on_since: None,
+            cooling_until: None,
+        }
+    }
+
+    // Whether it should be on now, given whether it's wanted on.
+    fn update(&mut self, wanted: bool, now: Instant) -> bool {
+        if self.cooling_until.map_or(false, |until| now < until) {
+            return false;
+        }
+        self.cooling_until = None;
+        if !wanted {
+            self.on_since = None;
+            return false;
+        }
+        let on_since = *self.on_since.get_or_insert(now);
+        if now.duration_since(on_since) >= self.max_on {
+            self.on_since = None;
+            self.cooling_until = Some(now + self.cooldown);
+            return false;
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rests_after_the_maximum_on_time() {
+        let minutes = |minutes: u64| Duration::from_secs(minutes * 60);
+        let mut guard = OnTimeGuard::new(minutes(30), minutes(10));
+        let start = Instant::now();
+
+        assert!(guard.update(true, start));
+        assert!(guard.update(true, start + minutes(29)));
+        assert!(!guard.update(true, start + minutes(30)));
+        assert!(!guard.update(true, start + minutes(39)));
+        // Still failing after the rest, so back on
+        assert!(guard.update(true, start + minutes(40)));
+        assert!(guard.update(true, start + minutes(69)));
+
+        // A failure that's fixed starts the clock over
+        assert!(!guard.update(false, start + minutes(69)));
+        assert!(guard.update(true, start + minutes(70)));
+        assert!(guard.update(true, start + minutes(99)));
+    }
+}
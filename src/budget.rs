@@ -0,0 +1,142 @@
+use config_file::PollingConfig;
+use std::time::{Duration, Instant};
+
+// Without a configured maximum, never wait longer than this between polls.
+const DEFAULT_MAX_INTERVAL_SECONDS: u64 = 10 * 60;
+
+// What a server's X-RateLimit-* headers last said, and how many requests
+// we've sent it in total.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RequestBudget {
+    pub remaining: u32,
+    pub resets_in: Duration,
+    pub requests_sent: usize,
+}
+
+// Spreads the requests left evenly over the time until the window resets.
+pub fn spread_interval(remaining: u32, resets_in: Duration, requests_per_poll: u32) -> Duration {
+    match remaining / requests_per_poll.max(1) {
+        0 => resets_in,
+        polls_left => resets_in / polls_left,
+    }
+}
+
+// Decides when an integration polls next: every min_interval, unless the
+// server's rate limit only leaves enough requests for polling less often,
+// but never less often than every max_interval.
+pub struct PollBudget {
+    min_interval: Duration,
+    max_interval: Duration,
+    interval: Duration,
+    last_poll: Option<Instant>,
+    requests_sent_before_poll: Option<usize>,
+}
+
+impl PollBudget {
+    pub fn new(config: &PollingConfig, default_interval: Duration) -> PollBudget {
+        let min_interval = config
+            .min_interval_seconds
+            .map_or(default_interval, Duration::from_secs);
+        let max_interval = config
+            .max_interval_seconds
+            .map_or(
+                Duration::from_secs(DEFAULT_MAX_INTERVAL_SECONDS),
+                Duration::from_secs,
+            )
+            .max(min_interval);
+        PollBudget {
+            min_interval: min_interval,
+            max_interval: max_interval,
+            interval: min_interval,
+            last_poll: None,
+            requests_sent_before_poll: None,
+        }
+    }
+
+    // How long until the next poll, or None if it's due now.
+    pub fn until_due(&self, now: Instant) -> Option<Duration> {
+        self.last_poll
+            .map(|last_poll| last_poll + self.interval)
+            .and_then(|due| if due > now { Some(due - now) } else { None })
+    }
+
+    pub fn poll_started(&mut self, now: Instant, budget: Option<RequestBudget>) {
+        self.last_poll = Some(now);
+        self.requests_sent_before_poll = budget.map(|budget| budget.requests_sent);
+    }
+
+    // The interval until the poll after the one that just finished.
+    pub fn next_interval(&mut self, budget: Option<RequestBudget>) -> Duration {
+        let spread = budget.map(|budget| {
+            let requests_per_poll = self
+                .requests_sent_before_poll
+                .map_or(1, |before| budget.requests_sent.saturating_sub(before));
+            spread_interval(budget.remaining, budget.resets_in, requests_per_poll as u32)
+        });
+        self.interval = spread
+            .unwrap_or(self.min_interval)
+            .max(self.min_interval)
+            .min(self.max_interval);
+        self.interval
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn budget(remaining: u32, resets_in_seconds: u64, requests_sent: usize) -> RequestBudget {
+        RequestBudget {
+            remaining: remaining,
+            resets_in: Duration::from_secs(resets_in_seconds),
+            requests_sent: requests_sent,
+        }
+    }
+
+    #[test]
+    fn requests_are_spread_over_the_window() {
+        let hour = Duration::from_secs(60 * 60);
+        assert_eq!(spread_interval(60, hour, 2), Duration::from_secs(120));
+        assert_eq!(spread_interval(1, hour, 2), hour);
+        assert_eq!(spread_interval(60, hour, 0), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn interval_is_clamped_to_the_config() {
+        let config = PollingConfig {
+            min_interval_seconds: Some(30),
+            max_interval_seconds: Some(300),
+        };
+        let mut polls = PollBudget::new(&config, Duration::from_secs(10));
+        let start = Instant::now();
+        assert_eq!(polls.until_due(start), None);
+
+        // Plenty left: the minimum
+        polls.poll_started(start, Some(budget(1000, 3600, 0)));
+        assert_eq!(
+            polls.next_interval(Some(budget(998, 3600, 2))),
+            Duration::from_secs(30)
+        );
+        assert_eq!(
+            polls.until_due(start + Duration::from_secs(10)),
+            Some(Duration::from_secs(20))
+        );
+
+        // 2 requests a poll with 20 left over 10 minutes: once a minute
+        polls.poll_started(start, Some(budget(22, 600, 2)));
+        assert_eq!(
+            polls.next_interval(Some(budget(20, 600, 4))),
+            Duration::from_secs(60)
+        );
+
+        // Nearly out: no longer than the maximum
+        polls.poll_started(start, Some(budget(2, 3600, 4)));
+        assert_eq!(
+            polls.next_interval(Some(budget(0, 3600, 6))),
+            Duration::from_secs(300)
+        );
+
+        // The server stopped saying
+        assert_eq!(polls.next_interval(None), Duration::from_secs(30));
+    }
+}
@@ -0,0 +1,324 @@
+use chrono::{Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use config_file::CalendarConfig;
+use failure::Error;
+use headers::{Headers, AUTHORIZATION};
+use network::{self, get_basic_credentials, HttpClient};
+use scheduler::{Tick, TickScheduler};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// How often the events already fetched are checked against the clock, so a
+// freeze starts on time even if the feed is only fetched every few minutes.
+const CHECK_SECONDS: u64 = 30;
+const DAY_SECONDS: i64 = 24 * 60 * 60;
+
+// The release freeze or deployment window going on right now, if any, shared
+// with every LED controller. Stays None without [calendar].
+#[derive(Clone)]
+pub struct Freeze {
+    current: Arc<Mutex<Option<String>>>,
+}
+
+impl Freeze {
+    pub fn new() -> Freeze {
+        Freeze {
+            current: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    // The event's name while one is on.
+    pub fn current(&self) -> Option<String> {
+        self.current.lock().ok().and_then(|current| current.clone())
+    }
+
+    fn set(&self, event: Option<String>) {
+        if let Ok(mut current) = self.current.lock() {
+            *current = event;
+        }
+    }
+}
+
+// Daily or weekly repeats. Rules with anything else, e.g. BYDAY, only count
+// at their first occurrence.
+#[derive(Debug, PartialEq)]
+struct Repeat {
+    period: i64,
+    until: Option<i64>,
+    count: Option<i64>,
+}
+
+// One VEVENT, with times in seconds since the epoch.
+#[derive(Debug, PartialEq)]
+struct Event {
+    summary: String,
+    start: i64,
+    end: i64,
+    repeat: Option<Repeat>,
+}
+
+impl Event {
+    fn is_on(&self, now: i64) -> bool {
+        let start = match self.repeat {
+            None => self.start,
+            Some(ref repeat) => {
+                if now < self.start {
+                    return false;
+                }
+                let mut occurrence = (now - self.start) / repeat.period;
+                if let Some(count) = repeat.count {
+                    occurrence = occurrence.min(count - 1);
+                }
+                let start = self.start + occurrence * repeat.period;
+                if repeat.until.map_or(false, |until| start > until) {
+                    return false;
+                }
+                start
+            }
+        };
+        start <= now && now < start + (self.end - self.start)
+    }
+}
+
+// Just enough of RFC 5545 for calendar exports: unfolded lines, VEVENTs with
+// SUMMARY, DTSTART, DTEND and a simple RRULE. Times with a TZID are taken to be
+// in the build light's own time zone. Cancelled events are left out.
+fn parse_events(ical: &str) -> Vec<Event> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in ical.lines() {
+        let line = line.trim_end_matches('\r');
+        if line.starts_with(' ') || line.starts_with('\t') {
+            if let Some(last) = lines.last_mut() {
+                last.push_str(&line[1..]);
+                continue;
+            }
+        }
+        lines.push(line.to_string());
+    }
+
+    let mut events = Vec::new();
+    let mut properties: Option<Vec<(String, String)>> = None;
+    for line in lines {
+        match line.as_str() {
+            "BEGIN:VEVENT" => properties = Some(Vec::new()),
+            "END:VEVENT" => {
+                if let Some(event) = properties.take().and_then(|p| event_from(&p)) {
+                    events.push(event);
+                }
+            }
+            _ => {
+                if let Some(ref mut properties) = properties {
+                    if let Some(colon) = line.find(':') {
+                        let (name, value) = line.split_at(colon);
+                        properties.push((name.to_string(), value[1..].to_string()));
+                    }
+                }
+            }
+        }
+    }
+    events
+}
+
+fn event_from(properties: &[(String, String)]) -> Option<Event> {
+    // Parameters like ;TZID=... or ;VALUE=DATE come after the name
+    let property = |wanted: &str| {
+        properties
+            .iter()
+            .find(|&&(ref name, _)| name.split(';').next() == Some(wanted))
+            .map(|&(_, ref value)| value.as_str())
+    };
+    if property("STATUS") == Some("CANCELLED") {
+        return None;
+    }
+    let start_value = property("DTSTART")?;
+    let start = parse_time(start_value)?;
+    let end = match property("DTEND").and_then(parse_time) {
+        Some(end) => end,
+        // All day events without an end last the day
+        None if start_value.len() == 8 => start + DAY_SECONDS,
+        None => start,
+    };
+    Some(Event {
+        summary: unescape(property("SUMMARY").unwrap_or("")),
+        start: start,
+        end: end,
+        repeat: property("RRULE").and_then(parse_repeat),
+    })
+}
+
+fn parse_time(value: &str) -> Option<i64> {
+    if value.len() == 8 {
+        let date = NaiveDate::parse_from_str(value, "%Y%m%d").ok()?;
+        return Local
+            .from_local_datetime(&date.and_hms(0, 0, 0))
+            .single()
+            .map(|time| time.timestamp());
+    }
+    if value.ends_with('Z') {
+        let time = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%SZ").ok()?;
+        return Some(Utc.from_utc_datetime(&time).timestamp());
+    }
+    let time = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()?;
+    Local
+        .from_local_datetime(&time)
+        .single()
+        .map(|time| time.timestamp())
+}
+
+fn parse_repeat(rule: &str) -> Option<Repeat> {
+    let parts: Vec<(&str, &str)> = rule
+        .split(';')
+        .filter_map(|part| {
+            let mut pair = part.splitn(2, '=');
+            Some((pair.next()?, pair.next()?))
+        })
+        .collect();
+    let part = |wanted: &str| {
+        parts
+            .iter()
+            .find(|&&(name, _)| name == wanted)
+            .map(|&(_, value)| value)
+    };
+    let unsupported = ["BYDAY", "BYMONTHDAY", "BYMONTH", "BYSETPOS", "BYHOUR"];
+    if unsupported.iter().any(|name| part(name).is_some()) {
+        return None;
+    }
+    let frequency = match part("FREQ")? {
+        "DAILY" => DAY_SECONDS,
+        "WEEKLY" => 7 * DAY_SECONDS,
+        _ => return None,
+    };
+    let interval: i64 = part("INTERVAL").and_then(|i| i.parse().ok()).unwrap_or(1);
+    Some(Repeat {
+        period: frequency * interval.max(1),
+        until: part("UNTIL").and_then(parse_time),
+        count: part("COUNT").and_then(|count| count.parse().ok()),
+    })
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("\\n", " ")
+        .replace("\\,", ",")
+        .replace("\\;", ";")
+        .replace("\\\\", "\\")
+}
+
+// The first event on right now whose name contains one of names, ignoring case.
+fn current_event(events: &[Event], names: &[String], now: i64) -> Option<String> {
+    events
+        .iter()
+        .filter(|event| event.is_on(now))
+        .find(|event| {
+            let summary = event.summary.to_lowercase();
+            names
+                .iter()
+                .any(|name| summary.contains(&name.to_lowercase()))
+        })
+        .map(|event| event.summary.clone())
+}
+
+fn fetch_events(client: &HttpClient, config: &CalendarConfig) -> Result<Vec<Event>, Error> {
+    let mut headers = Headers::new();
+    if let Some(ref username) = config.username {
+        headers.set(
+            AUTHORIZATION,
+            get_basic_credentials(username, config.password.clone()),
+        );
+    }
+    let ical = network::get_url_text(client, &config.url, headers)?;
+    Ok(parse_events(&ical))
+}
+
+// Fetches config.url every interval_seconds until shutdown, and checks
+// whether one of its events is on every half a minute in between. A feed
+// that can't be fetched keeps the events it had.
+pub fn run_calendar(config: CalendarConfig, freeze: Freeze, scheduler: TickScheduler) {
+    let client = match network::build_client(&config.http, &config.url) {
+        Ok(client) => client,
+        Err(err) => {
+            error!(
+                "--Calendar--: Invalid [calendar.http], calendar disabled. Error: {}",
+                err
+            );
+            return;
+        }
+    };
+    let interval = Duration::from_secs(config.interval_seconds);
+    let mut events = Vec::new();
+    let mut fetched_at: Option<Instant> = None;
+    let mut ticker = scheduler.ticker();
+    loop {
+        if fetched_at.map_or(true, |at| at.elapsed() >= interval) {
+            match fetch_events(&client, &config) {
+                Ok(fetched) => events = fetched,
+                Err(err) => warn!(
+                    "--Calendar--: Failed to fetch {}. Error: {}",
+                    config.url, err
+                ),
+            }
+            fetched_at = Some(Instant::now());
+        }
+        let current = current_event(&events, &config.event_names, Local::now().timestamp());
+        if current != freeze.current() {
+            match current {
+                Some(ref event) => info!("--Calendar--: \"{}\" has started.", event),
+                None => info!("--Calendar--: The freeze is over."),
+            }
+            freeze.set(current);
+            // So the LEDs change right away
+            scheduler.reload();
+        }
+
+        match ticker.wait(Duration::from_secs(CHECK_SECONDS)) {
+            Tick::Shutdown => break,
+            Tick::Reload | Tick::Elapsed => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn freezes_are_found_by_name_and_time() {
+        let ical = "BEGIN:VCALENDAR\r\n\
+BEGIN:VEVENT\r\n\
+SUMMARY:Release freeze\\, v1.4\r\n\
+DTSTART:20180312T080000Z\r\n\
+DTEND:20180314T160000Z\r\n\
+END:VEVENT\r\n\
+BEGIN:VEVENT\r\n\
+SUMMARY:Weekly deployment\r\n  window\r\n\
+DTSTART:20180301T130000Z\r\n\
+DTEND:20180301T150000Z\r\n\
+RRULE:FREQ=WEEKLY;COUNT=10\r\n\
+END:VEVENT\r\n\
+BEGIN:VEVENT\r\n\
+SUMMARY:Release freeze\r\n\
+STATUS:CANCELLED\r\n\
+DTSTART:20180320T080000Z\r\n\
+DTEND:20180321T080000Z\r\n\
+END:VEVENT\r\n\
+END:VCALENDAR\r\n";
+        let events = parse_events(ical);
+        assert_eq!(events.len(), 2);
+        let names = vec![
+            "release freeze".to_string(),
+            "deployment window".to_string(),
+        ];
+        let at = |time: &str| parse_time(time).unwrap();
+
+        assert_eq!(
+            current_event(&events, &names, at("20180313T120000Z")),
+            Some("Release freeze, v1.4".to_string())
+        );
+        assert_eq!(current_event(&events, &names, at("20180314T160000Z")), None);
+        // The third Thursday
+        assert_eq!(
+            current_event(&events, &names, at("20180315T140000Z")),
+            Some("Weekly deployment window".to_string())
+        );
+        assert_eq!(current_event(&events, &names, at("20180315T150000Z")), None);
+        assert_eq!(current_event(&events, &names, at("20180320T120000Z")), None);
+    }
+}
@@ -0,0 +1,70 @@
+use headers::{self, Headers};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+// Further off from a server's clock than this, ours is probably the one that's wrong.
+const MAX_SKEW_SECONDS: i64 = 60;
+
+// When the server sent the response, going by its Date header.
+pub fn server_time(headers: &Headers) -> Option<SystemTime> {
+    headers.get("Date").and_then(headers::parse_http_date)
+}
+
+// How many seconds our clock is ahead of the server's, or behind if negative.
+pub fn skew_seconds(server: SystemTime, local: SystemTime) -> i64 {
+    match local.duration_since(server) {
+        Ok(ahead) => ahead.as_secs() as i64,
+        Err(behind) => -(behind.duration().as_secs() as i64),
+    }
+}
+
+// Compares the Pi's clock with the Date header of every response, starting
+// with the first poll. The Pi has no battery backed clock, so after a power
+// cut it can be far off until NTP catches up.
+#[derive(Clone)]
+pub struct ClockCheck {
+    skewed: Arc<Mutex<bool>>,
+}
+
+impl ClockCheck {
+    pub fn new() -> ClockCheck {
+        ClockCheck {
+            skewed: Arc::new(Mutex::new(false)),
+        }
+    }
+
+    // Warns when the clock goes badly off, and once more when it's right again.
+    pub fn observe(&self, url: &str, headers: &Headers) {
+        let skew = match server_time(headers) {
+            Some(server) => skew_seconds(server, SystemTime::now()),
+            None => return,
+        };
+        let skewed = skew.abs() > MAX_SKEW_SECONDS;
+        if let Ok(mut was_skewed) = self.skewed.lock() {
+            if skewed && !*was_skewed {
+                warn!(
+                    "The clock is {} seconds {} {}. Is NTP running? Rate limits are worked out with the server's clock until it's right.",
+                    skew.abs(),
+                    if skew > 0 { "ahead of" } else { "behind" },
+                    url
+                );
+            } else if !skewed && *was_skewed {
+                info!("The clock agrees with {} again.", url);
+            }
+            *was_skewed = skewed;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn skew_is_signed() {
+        let server = SystemTime::now();
+        assert_eq!(skew_seconds(server, server + Duration::from_secs(90)), 90);
+        assert_eq!(skew_seconds(server, server - Duration::from_secs(90)), -90);
+    }
+}
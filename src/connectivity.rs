@@ -0,0 +1,160 @@
+use config_file::ConnectivityConfig;
+use network;
+use reqwest::Client;
+use scheduler::{Tick, TickScheduler};
+use std::io::ErrorKind;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+// Whether the build light's own network is up, shared with every LED
+// controller. Starts out online, and stays that way without [connectivity].
+#[derive(Clone)]
+pub struct Connectivity {
+    offline: Arc<AtomicBool>,
+}
+
+impl Connectivity {
+    pub fn new() -> Connectivity {
+        Connectivity {
+            offline: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub fn is_offline(&self) -> bool {
+        self.offline.load(Ordering::SeqCst)
+    }
+
+    fn set_offline(&self, offline: bool) {
+        self.offline.store(offline, Ordering::SeqCst);
+    }
+}
+
+// Counts failed checks, so a single dropped packet doesn't count as offline.
+struct CheckHistory {
+    consecutive_failures: u32,
+    failures_before_offline: u32,
+}
+
+impl CheckHistory {
+    // Whether we're offline after this check.
+    fn record(&mut self, reachable: bool) -> bool {
+        if reachable {
+            self.consecutive_failures = 0;
+        } else {
+            self.consecutive_failures += 1;
+        }
+        self.consecutive_failures >= self.failures_before_offline.max(1)
+    }
+}
+
+// Checks config.check_url and config.gateway every interval_seconds until
+// shutdown. The network counts as down once neither of them has answered
+// failures_before_offline times in a row, and as up again as soon as one does.
+pub fn run_connectivity(
+    config: ConnectivityConfig,
+    connectivity: Connectivity,
+    scheduler: TickScheduler,
+) {
+    if config.check_url.is_none() && config.gateway.is_none() {
+        error!("--Connectivity--: Needs a check_url, a gateway, or both. Network checks disabled.");
+        return;
+    }
+    let timeout = Duration::from_secs(config.timeout_seconds);
+    let client = network::build_client_with_timeout(timeout);
+    let mut history = CheckHistory {
+        consecutive_failures: 0,
+        failures_before_offline: config.failures_before_offline,
+    };
+
+    let mut ticker = scheduler.ticker();
+    loop {
+        let reachable = config
+            .check_url
+            .as_ref()
+            .map_or(false, |url| url_answers(&client, url))
+            || config
+                .gateway
+                .as_ref()
+                .map_or(false, |gateway| gateway_answers(gateway, timeout));
+        let offline = history.record(reachable);
+        if offline != connectivity.is_offline() {
+            if offline {
+                warn!("--Connectivity--: The network is down.");
+            } else {
+                info!("--Connectivity--: The network is back up.");
+            }
+            connectivity.set_offline(offline);
+            // So the LEDs change right away, and polling doesn't wait out its backoff
+            scheduler.reload();
+        }
+
+        match ticker.wait(Duration::from_secs(config.interval_seconds)) {
+            Tick::Shutdown => break,
+            Tick::Reload | Tick::Elapsed => (),
+        }
+    }
+}
+
+// Any response at all will do, even an error status.
+fn url_answers(client: &Client, url: &str) -> bool {
+    match client.head(url).send() {
+        Ok(_) => true,
+        Err(e) => {
+            debug!("--Connectivity--: No answer from {}. Error: {}", url, e);
+            false
+        }
+    }
+}
+
+// A refused connection still means the gateway is there to refuse it.
+fn gateway_answers(gateway: &str, timeout: Duration) -> bool {
+    let addresses = match gateway.to_socket_addrs() {
+        Ok(addresses) => addresses,
+        Err(e) => {
+            debug!(
+                "--Connectivity--: Couldn't resolve {}. Error: {}",
+                gateway, e
+            );
+            return false;
+        }
+    };
+    addresses.into_iter().any(
+        |address| match TcpStream::connect_timeout(&address, timeout) {
+            Ok(_) => true,
+            Err(ref e) if e.kind() == ErrorKind::ConnectionRefused => true,
+            Err(e) => {
+                debug!("--Connectivity--: No answer from {}. Error: {}", gateway, e);
+                false
+            }
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn offline_only_after_enough_failed_checks() {
+        let mut history = CheckHistory {
+            consecutive_failures: 0,
+            failures_before_offline: 2,
+        };
+        assert!(!history.record(false));
+        assert!(history.record(false));
+        assert!(history.record(false));
+        assert!(!history.record(true));
+        assert!(!history.record(false));
+    }
+
+    #[test]
+    fn listening_gateway_answers() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let gateway = listener.local_addr().unwrap().to_string();
+        assert!(gateway_answers(&gateway, Duration::from_secs(1)));
+        assert!(!gateway_answers("not a gateway", Duration::from_secs(1)));
+    }
+}
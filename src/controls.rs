@@ -0,0 +1,87 @@
+use config_file::ButtonConfig;
+use pin::{Button, ButtonPress};
+use scheduler::TickScheduler;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// User overrides set from physical controls, shared with every LED controller.
+pub struct Controls {
+    acknowledged_at: Mutex<Option<Instant>>,
+    muted_until: Mutex<Option<Instant>>,
+}
+
+impl Controls {
+    pub fn new() -> Controls {
+        Controls {
+            acknowledged_at: Mutex::new(None),
+            muted_until: Mutex::new(None),
+        }
+    }
+
+    pub fn acknowledge(&self) {
+        if let Ok(mut acknowledged_at) = self.acknowledged_at.lock() {
+            *acknowledged_at = Some(Instant::now());
+        }
+    }
+
+    // A failure only counts as acknowledged if the button was pressed after
+    // it started. A new failure needs a new press.
+    pub fn is_acknowledged(&self, failing_since: Instant) -> bool {
+        match self.acknowledged_at.lock() {
+            Ok(acknowledged_at) => acknowledged_at.map_or(false, |at| at >= failing_since),
+            Err(_) => false,
+        }
+    }
+
+    // Mutes for the given duration, or unmutes if already muted. Returns whether we're now muted.
+    pub fn toggle_mute(&self, duration: Duration) -> bool {
+        if let Ok(mut muted_until) = self.muted_until.lock() {
+            if self.is_muted_until(&muted_until) {
+                *muted_until = None;
+                false
+            } else {
+                *muted_until = Some(Instant::now() + duration);
+                true
+            }
+        } else {
+            false
+        }
+    }
+
+    pub fn is_muted(&self) -> bool {
+        match self.muted_until.lock() {
+            Ok(muted_until) => self.is_muted_until(&muted_until),
+            Err(_) => false,
+        }
+    }
+
+    fn is_muted_until(&self, muted_until: &Option<Instant>) -> bool {
+        muted_until.map_or(false, |until| Instant::now() < until)
+    }
+}
+
+// Watches the acknowledge/mute button until shutdown.
+pub fn run_button(config: ButtonConfig, controls: Arc<Controls>, scheduler: TickScheduler) {
+    let mut button = Button::new(config.pin, config.long_press_millis);
+    let mute_duration = Duration::from_secs(config.mute_minutes * 60);
+    let mut ticker = scheduler.ticker();
+    while ticker.is_running() {
+        match button.wait_for_press(Duration::from_millis(500)) {
+            Some(ButtonPress::Short) => {
+                info!("--Button--: Current failures acknowledged.");
+                controls.acknowledge();
+            }
+            Some(ButtonPress::Long) => {
+                if controls.toggle_mute(mute_duration) {
+                    info!(
+                        "--Button--: LEDs muted for {} minutes.",
+                        config.mute_minutes
+                    );
+                } else {
+                    info!("--Button--: LEDs unmuted.");
+                }
+            }
+            None => {}
+        }
+    }
+}
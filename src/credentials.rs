@@ -0,0 +1,438 @@
+use app;
+use config_file::Config;
+use failure::Error;
+use network::HttpClient;
+use redact;
+use scheduler::{Tick, TickScheduler};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use vault::VaultProvider;
+
+// Credentials from somewhere other than config.toml. The ones that are set
+// replace the config's, the rest are left as they are.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ProvidedCredentials {
+    pub jenkins_username: Option<String>,
+    pub jenkins_password: Option<String>,
+    pub jenkins_api_token: Option<String>,
+    pub unity_cloud_api_token: Option<String>,
+}
+
+impl ProvidedCredentials {
+    pub fn apply_to(&self, config: &mut Config) {
+        if let Some(ref username) = self.jenkins_username {
+            config.jenkins_username = username.clone();
+        }
+        if let Some(ref password) = self.jenkins_password {
+            config.jenkins_password = password.clone();
+        }
+        if self.jenkins_api_token.is_some() {
+            config.jenkins_api_token = self.jenkins_api_token.clone();
+        }
+        if let Some(ref token) = self.unity_cloud_api_token {
+            config.unity_cloud_api_token = token.clone();
+        }
+        redact::register_secrets(&config.secrets());
+    }
+}
+
+// Somewhere to fetch credentials from at startup, and again every
+// refresh_interval, instead of keeping them on the SD card.
+pub trait CredentialProvider: Send + Sync {
+    fn name(&self) -> &str;
+    fn fetch(&self) -> Result<ProvidedCredentials, Error>;
+    // None to only fetch them at startup, and on SIGHUP
+    fn refresh_interval(&self) -> Option<Duration>;
+}
+
+// The provider the config sets up, if any.
+pub fn provider_from_config(config: &Config) -> Result<Option<Arc<CredentialProvider>>, Error> {
+    match config.vault {
+        Some(ref vault) => Ok(Some(Arc::new(
+            VaultProvider::new(vault.clone())
+                .map_err(|err| format_err!("Invalid [vault]: {}", err))?,
+        ))),
+        None => Ok(None),
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct JenkinsCredentials {
+    pub username: String,
+    // The API token, when there is one
+    pub password: String,
+    pub password_is_api_token: bool,
+}
+
+impl JenkinsCredentials {
+    fn from_config(config: &Config) -> JenkinsCredentials {
+        JenkinsCredentials {
+            username: config.jenkins_username.clone(),
+            password: config
+                .jenkins_api_token
+                .clone()
+                .unwrap_or_else(|| config.jenkins_password.clone()),
+            password_is_api_token: config.jenkins_api_token.is_some(),
+        }
+    }
+}
+
+// What the integrations other than Jenkins and Unity Cloud log in with. Some
+// only need a token, and leave the username empty.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Login {
+    pub username: String,
+    // A password or a token
+    pub secret: Option<String>,
+}
+
+enum InstanceLogin {
+    Jenkins(JenkinsCredentials),
+    Unity(String),
+    Other(Login),
+}
+
+// Each instance's login in config, by its name.
+fn instance_logins(config: &Config) -> Vec<(String, InstanceLogin)> {
+    let mut logins = Vec::new();
+    for jenkins in &config.jenkins_instances {
+        let credentials = JenkinsCredentials {
+            username: jenkins.username.clone(),
+            password: jenkins
+                .api_token
+                .clone()
+                .unwrap_or_else(|| jenkins.password.clone()),
+            password_is_api_token: jenkins.api_token.is_some(),
+        };
+        logins.push((jenkins.display_name(), InstanceLogin::Jenkins(credentials)));
+    }
+    for unity in &config.unity_instances {
+        logins.push((
+            unity.display_name(),
+            InstanceLogin::Unity(unity.api_token.clone()),
+        ));
+    }
+    let token = |token: &Option<String>| {
+        InstanceLogin::Other(Login {
+            username: String::new(),
+            secret: token.clone(),
+        })
+    };
+    let password = |username: &str, password: &str| {
+        InstanceLogin::Other(Login {
+            username: username.to_string(),
+            secret: Some(password.to_string()),
+        })
+    };
+    if config.team_city_enabled() {
+        logins.push((
+            "Team City".to_string(),
+            password(&config.team_city_username, &config.team_city_password),
+        ));
+    }
+    for github in &config.github {
+        logins.push((github.display_name(), token(&github.token)));
+    }
+    for bitbucket in &config.bitbucket {
+        logins.push((
+            bitbucket.display_name(),
+            password(&bitbucket.username, &bitbucket.app_password),
+        ));
+    }
+    for sonarqube in &config.sonarqube {
+        logins.push((sonarqube.display_name(), token(&sonarqube.token)));
+    }
+    for grafana in &config.grafana {
+        logins.push((grafana.display_name(), token(&grafana.token)));
+    }
+    for icinga in &config.icinga {
+        logins.push((
+            icinga.display_name(),
+            password(&icinga.username, &icinga.password),
+        ));
+    }
+    for kubernetes in &config.kubernetes {
+        logins.push((kubernetes.display_name(), token(&kubernetes.token)));
+    }
+    for sentry in &config.sentry {
+        logins.push((sentry.display_name(), password("", &sentry.token)));
+    }
+    for jira in &config.jira {
+        logins.push((
+            jira.display_name(),
+            password(&jira.username, &jira.password),
+        ));
+    }
+    logins
+}
+
+// Swaps in value, telling whether it was any different.
+fn replace<T: PartialEq>(current: &Mutex<T>, value: T) -> bool {
+    let mut current = match current.lock() {
+        Ok(current) => current,
+        Err(_) => return false,
+    };
+    if *current == value {
+        return false;
+    }
+    *current = value;
+    true
+}
+
+// The credentials integrations log in with, shared so they can be swapped
+// for new ones while the integrations keep running.
+#[derive(Clone)]
+pub struct Credentials {
+    // The instance these belong to, see for_instance
+    instance: Option<String>,
+    jenkins: Arc<Mutex<JenkinsCredentials>>,
+    unity_api_token: Arc<Mutex<String>>,
+    login: Arc<Mutex<Login>>,
+}
+
+impl Credentials {
+    pub fn from_config(config: &Config) -> Credentials {
+        Credentials {
+            instance: None,
+            jenkins: Arc::new(Mutex::new(JenkinsCredentials::from_config(config))),
+            unity_api_token: Arc::new(Mutex::new(config.unity_cloud_api_token.clone())),
+            login: Arc::new(Mutex::new(Login::default())),
+        }
+    }
+
+    // The credentials of the instance called name, e.g. "Jenkins mobile",
+    // which only hand out its own login. See CredentialReloader::set_instances.
+    pub fn for_instance(config: &Config, name: &str) -> Credentials {
+        let credentials = Credentials {
+            instance: Some(name.to_string()),
+            jenkins: Arc::new(Mutex::new(JenkinsCredentials::default())),
+            unity_api_token: Arc::new(Mutex::new(String::new())),
+            login: Arc::new(Mutex::new(Login::default())),
+        };
+        credentials.update(config);
+        credentials
+    }
+
+    pub fn jenkins(&self) -> Option<JenkinsCredentials> {
+        self.jenkins.lock().ok().map(|jenkins| jenkins.clone())
+    }
+
+    pub fn unity_api_token(&self) -> Option<String> {
+        self.unity_api_token.lock().ok().map(|token| token.clone())
+    }
+
+    pub fn login(&self) -> Option<Login> {
+        self.login.lock().ok().map(|login| login.clone())
+    }
+
+    // Takes on the credentials in config, returning the settings that changed.
+    fn update(&self, config: &Config) -> Vec<String> {
+        let name = match self.instance {
+            Some(ref name) => name,
+            None => {
+                let mut changed = Vec::new();
+                if replace(&self.jenkins, JenkinsCredentials::from_config(config)) {
+                    changed.push("Jenkins credentials".to_string());
+                }
+                if replace(&self.unity_api_token, config.unity_cloud_api_token.clone()) {
+                    changed.push("unity_cloud_api_token".to_string());
+                }
+                return changed;
+            }
+        };
+        let login = instance_logins(config)
+            .into_iter()
+            .find(|&(ref instance, _)| instance == name)
+            .map(|(_, login)| login);
+        let changed = match login {
+            Some(InstanceLogin::Jenkins(jenkins)) => replace(&self.jenkins, jenkins),
+            Some(InstanceLogin::Unity(token)) => replace(&self.unity_api_token, token),
+            Some(InstanceLogin::Other(login)) => replace(&self.login, login),
+            // Instances added or removed since startup take a restart
+            None => false,
+        };
+        if changed {
+            vec![format!("{} credentials", name)]
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+// Re-reads config.toml on SIGHUP, or fetches from the credential provider,
+// and hands the credentials to the running integrations, so a rotated token
+// doesn't need a restart. Nothing else in the file is looked at.
+#[derive(Clone)]
+pub struct CredentialReloader {
+    exe_dir: PathBuf,
+    credentials: Credentials,
+    // Those of jenkins_instances, unity_instances and the other integrations
+    instances: Vec<Credentials>,
+    jenkins_client: HttpClient,
+    unity_client: HttpClient,
+    provider: Option<Arc<CredentialProvider>>,
+}
+
+impl CredentialReloader {
+    pub fn new(
+        exe_dir: PathBuf,
+        credentials: Credentials,
+        jenkins_client: HttpClient,
+        unity_client: HttpClient,
+    ) -> CredentialReloader {
+        CredentialReloader {
+            exe_dir: exe_dir,
+            credentials: credentials,
+            instances: Vec::new(),
+            jenkins_client: jenkins_client,
+            unity_client: unity_client,
+            provider: None,
+        }
+    }
+
+    pub fn set_provider(&mut self, provider: Option<Arc<CredentialProvider>>) {
+        self.provider = provider;
+    }
+
+    pub fn set_instances(&mut self, instances: Vec<Credentials>) {
+        self.instances = instances;
+    }
+
+    // Keeps the old credentials if config.toml can't be read, or the
+    // provider can't be reached.
+    pub fn reload(&self) -> Result<Vec<String>, Error> {
+        let mut config = app::read_config(&self.exe_dir)?;
+        if let Some(ref provider) = self.provider {
+            provider
+                .fetch()
+                .map_err(|err| {
+                    format_err!("Fetching them from {} failed: {}", provider.name(), err)
+                })?
+                .apply_to(&mut config);
+        }
+        self.jenkins_client
+            .reload_bearer_auth(&config.jenkins_http)
+            .map_err(|err| format_err!("Invalid [jenkins_http]: {}", err))?;
+        self.unity_client
+            .reload_bearer_auth(&config.unity_http)
+            .map_err(|err| format_err!("Invalid [unity_http]: {}", err))?;
+        let mut changed = self.credentials.update(&config);
+        for instance in &self.instances {
+            changed.extend(instance.update(&config));
+        }
+        Ok(changed)
+    }
+
+    // Reloads, logging what came of it. New credentials are tried out right
+    // away, so bad ones show up now rather than on the next poll.
+    pub fn reload_logged(&self, scheduler: &TickScheduler) {
+        match self.reload() {
+            Ok(ref changed) if changed.is_empty() => {
+                info!("--Credentials--: Reloaded, none of them changed.")
+            }
+            Ok(changed) => {
+                info!("--Credentials--: Reloaded, new {}.", changed.join(", "));
+                scheduler.reload();
+            }
+            Err(e) => error!(
+                "--Credentials--: Couldn't reload them, keeping the old ones. Error: {}",
+                e
+            ),
+        }
+    }
+}
+
+// Fetches the credentials from the provider again every refresh_interval.
+pub fn run_refresh(reloader: CredentialReloader, interval: Duration, scheduler: TickScheduler) {
+    let mut ticker = scheduler.ticker();
+    let mut next_refresh = Instant::now() + interval;
+    while ticker.is_running() {
+        // Reloads wake this up too, without counting as a refresh
+        let now = Instant::now();
+        if now < next_refresh {
+            if ticker.wait(next_refresh - now) == Tick::Shutdown {
+                break;
+            }
+            continue;
+        }
+        info!("--Credentials--: Refreshing them.");
+        reloader.reload_logged(&scheduler);
+        next_refresh = Instant::now() + interval;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use toml;
+
+    fn config(api_token: &str) -> Config {
+        toml::from_str(&format!(
+            "jenkins_username = \"light\"
+             jenkins_api_token = \"{}\"
+             jenkins_base_url = \"https://ci.example.com\"
+             jenkins_led_pins = [17, 27, 22]
+             unity_cloud_api_token = \"unity\"
+             unity_base_url = \"https://build-api.cloud.unity3d.com\"
+             unity_led_pins = [5, 6, 13]",
+            api_token
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn only_changed_credentials_are_reported() {
+        let credentials = Credentials::from_config(&config("old"));
+        let shared = credentials.clone();
+        assert!(credentials.update(&config("old")).is_empty());
+
+        assert_eq!(
+            credentials.update(&config("new")),
+            vec!["Jenkins credentials"]
+        );
+        let jenkins = shared.jenkins().unwrap();
+        assert_eq!(jenkins.password, "new");
+        assert!(jenkins.password_is_api_token);
+        assert_eq!(shared.unity_api_token(), Some("unity".to_string()));
+    }
+
+    #[test]
+    fn instances_only_take_on_their_own_login() {
+        let config = |github_token: &str| -> Config {
+            toml::from_str(&format!(
+                "jenkins_username = \"light\"
+                 jenkins_password = \"main\"
+                 jenkins_base_url = \"https://ci.example.com\"
+                 jenkins_led_pins = [17, 27, 22]
+                 unity_cloud_api_token = \"unity\"
+                 unity_base_url = \"https://build-api.cloud.unity3d.com\"
+                 unity_led_pins = [5, 6, 13]
+                 [[github]]
+                 repository = \"futurice/app\"
+                 token = \"{}\"
+                 led_pins = [1, 2, 3]
+                 [[jenkins_instances]]
+                 name = \"mobile\"
+                 base_url = \"https://mobile.example.com\"
+                 api_token = \"mobile-token\"
+                 led_pins = [4, 7, 8]",
+                github_token
+            ))
+            .unwrap()
+        };
+        let github = Credentials::for_instance(&config("old"), "GitHub futurice/app");
+        let mobile = Credentials::for_instance(&config("old"), "Jenkins mobile");
+        assert_eq!(github.login().unwrap().secret, Some("old".to_string()));
+        assert_eq!(mobile.jenkins().unwrap().password, "mobile-token");
+
+        assert_eq!(
+            github.update(&config("new")),
+            vec!["GitHub futurice/app credentials"]
+        );
+        assert!(mobile.update(&config("new")).is_empty());
+        assert_eq!(github.login().unwrap().secret, Some("new".to_string()));
+        // Its own API token, not the main server's password
+        assert!(mobile.jenkins().unwrap().password_is_api_token);
+    }
+}
@@ -0,0 +1,49 @@
+use gpio::{self, GpioBackend};
+use std::sync::Arc;
+
+// An APA102 (DotStar) or SK9822 addressable LED strip. They take a clock
+// line, so unlike WS2812s they can be bit-banged over GPIO without tight
+// timing.
+pub struct LedStrip {
+    gpio: Arc<GpioBackend>,
+    clock_pin: u16,
+    data_pin: u16,
+    // The strip's own global brightness, 0 to 31
+    brightness: u8,
+}
+
+impl LedStrip {
+    pub fn new(clock: u16, data: u16, brightness: u8) -> LedStrip {
+        LedStrip {
+            gpio: gpio::wiring_pi(),
+            clock_pin: clock,
+            data_pin: data,
+            brightness: if brightness > 31 { 31 } else { brightness },
+        }
+    }
+
+    // Sets the strip's LEDs, first to last, as 0-255 R, G, B.
+    pub fn show(&mut self, pixels: &[(u8, u8, u8)]) {
+        self.write_bytes(&[0, 0, 0, 0]);
+        let brightness = 0xE0 | self.brightness;
+        for &(r, g, b) in pixels {
+            self.write_bytes(&[brightness, b, g, r]);
+        }
+        // Each LED delays the data by half a clock, so the last ones need
+        // another half clock per LED to latch
+        let end_bytes = (pixels.len() + 15) / 16;
+        self.write_bytes(&vec![0xFF; end_bytes.max(4)]);
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        // Most significant bit first, read on the clock's rising edge
+        for byte in bytes {
+            for bit in (0..8).rev() {
+                self.gpio
+                    .digital_write(self.data_pin, byte & (1 << bit) != 0);
+                self.gpio.digital_write(self.clock_pin, true);
+                self.gpio.digital_write(self.clock_pin, false);
+            }
+        }
+    }
+}
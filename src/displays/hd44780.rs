@@ -0,0 +1,122 @@
+use gpio::{self, GpioBackend};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+// DDRAM start address of each row. Rows 3 and 4 only exist on 20x4 panels.
+const ROW_OFFSETS: [u8; 4] = [0x00, 0x40, 0x14, 0x54];
+
+const CMD_CLEAR: u8 = 0x01;
+const CMD_ENTRY_MODE_INCREMENT: u8 = 0x06;
+const CMD_DISPLAY_ON: u8 = 0x0C;
+const CMD_FUNCTION_SET_4BIT_2LINE: u8 = 0x28;
+const CMD_SET_DDRAM_ADDRESS: u8 = 0x80;
+
+// Drives an HD44780-compatible character LCD in 4-bit mode. The R/W line is
+// expected to be tied to ground, so we only ever write to the panel.
+pub struct CharacterLcd {
+    gpio: Arc<GpioBackend>,
+    rs_pin: u16,
+    enable_pin: u16,
+    data_pins: Vec<u16>,
+    columns: usize,
+    rows: usize,
+}
+
+impl CharacterLcd {
+    pub fn new(rs: u16, enable: u16, data: &[u16], columns: usize, rows: usize) -> CharacterLcd {
+        let mut lcd = CharacterLcd {
+            gpio: gpio::wiring_pi(),
+            rs_pin: rs,
+            enable_pin: enable,
+            data_pins: data.to_vec(),
+            columns: columns,
+            rows: if rows > ROW_OFFSETS.len() {
+                ROW_OFFSETS.len()
+            } else {
+                rows
+            },
+        };
+        lcd.initialize();
+        lcd
+    }
+
+    pub fn columns(&self) -> usize {
+        self.columns
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn clear(&mut self) {
+        self.command(CMD_CLEAR);
+        thread::sleep(Duration::from_millis(2)); // Clear is the slowest command the panel has
+    }
+
+    // Writes text to the given row, truncating or space-padding it to the panel width.
+    pub fn write_line(&mut self, row: usize, text: &str) {
+        if row >= self.rows {
+            return;
+        }
+        self.command(CMD_SET_DDRAM_ADDRESS | ROW_OFFSETS[row]);
+        let mut chars: Vec<u8> = text
+            .chars()
+            .map(|c| if c.is_ascii() { c as u8 } else { b'?' })
+            .take(self.columns)
+            .collect();
+        while chars.len() < self.columns {
+            chars.push(b' ');
+        }
+        for c in chars {
+            self.write_data(c);
+        }
+    }
+
+    fn initialize(&mut self) {
+        // Power-on reset dance from the datasheet: force 8-bit mode three
+        // times, then drop into 4-bit mode.
+        thread::sleep(Duration::from_millis(50));
+        self.gpio.digital_write(self.rs_pin, false);
+        self.write_nibble(0x03);
+        thread::sleep(Duration::from_millis(5));
+        self.write_nibble(0x03);
+        thread::sleep(Duration::from_millis(1));
+        self.write_nibble(0x03);
+        self.write_nibble(0x02);
+
+        self.command(CMD_FUNCTION_SET_4BIT_2LINE);
+        self.command(CMD_DISPLAY_ON);
+        self.command(CMD_ENTRY_MODE_INCREMENT);
+        self.clear();
+    }
+
+    fn command(&mut self, value: u8) {
+        self.gpio.digital_write(self.rs_pin, false);
+        self.write_byte(value);
+    }
+
+    fn write_data(&mut self, value: u8) {
+        self.gpio.digital_write(self.rs_pin, true);
+        self.write_byte(value);
+    }
+
+    fn write_byte(&mut self, value: u8) {
+        self.write_nibble(value >> 4);
+        self.write_nibble(value & 0x0F);
+    }
+
+    fn write_nibble(&mut self, nibble: u8) {
+        for (bit, pin) in self.data_pins.iter().enumerate() {
+            if nibble & (1 << bit) != 0 {
+                self.gpio.digital_write(*pin, true);
+            } else {
+                self.gpio.digital_write(*pin, false);
+            }
+        }
+        self.gpio.digital_write(self.enable_pin, true);
+        thread::sleep(Duration::new(0, 1000));
+        self.gpio.digital_write(self.enable_pin, false);
+        thread::sleep(Duration::new(0, 50000)); // Commands need > 37us to settle
+    }
+}
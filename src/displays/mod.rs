@@ -0,0 +1,327 @@
+pub mod apa102;
+mod font;
+pub mod hd44780;
+pub mod ssd1306;
+pub mod tm1637;
+
+use chrono::Local;
+use config_file::{LcdConfig, LedStripConfig, OledConfig, SevenSegmentConfig};
+use displays::apa102::LedStrip;
+use displays::hd44780::CharacterLcd;
+use displays::ssd1306::Oled;
+use displays::tm1637::SevenSegmentDisplay;
+use remote_status::{BuildProgress, RemoteStatus};
+use scheduler::{Tick, TickScheduler};
+use status_board::{IntegrationSnapshot, StatusBoard};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// Shows one integration per page on a character LCD, flipping to the next
+// integration every refresh, until shutdown.
+pub fn run_lcd(config: LcdConfig, board: StatusBoard, scheduler: TickScheduler) {
+    if config.data_pins.len() != 4 {
+        error!(
+            "--LCD--: Expected 4 data pins (D4-D7), but {} were configured. LCD disabled.",
+            config.data_pins.len()
+        );
+        return;
+    }
+    let mut lcd = CharacterLcd::new(
+        config.rs_pin,
+        config.enable_pin,
+        &config.data_pins,
+        config.columns,
+        config.rows,
+    );
+    lcd.write_line(0, "Build light");
+    lcd.write_line(1, "Waiting...");
+
+    let mut page = 0;
+    let mut ticker = scheduler.ticker();
+    loop {
+        let snapshots = board.snapshots();
+        if !snapshots.is_empty() {
+            let snapshot = &snapshots[page % snapshots.len()];
+            let lines = render_lcd_page(snapshot, lcd.columns(), lcd.rows());
+            for (row, line) in lines.iter().enumerate() {
+                lcd.write_line(row, line);
+            }
+            page = page.wrapping_add(1);
+        }
+        if ticker.wait(Duration::from_secs(config.refresh_seconds)) == Tick::Shutdown {
+            break;
+        }
+    }
+    lcd.clear();
+}
+
+// How often the LED strip is redrawn, and how fast the chase moves.
+const STRIP_FRAME_MILLIS: u64 = 100;
+// The chasing dot's length, fading out behind it.
+const CHASE_LEDS: usize = 3;
+
+// Shows how far along the running build of config.integration is on a stretch
+// of an LED strip: a bar that fills up as the build goes, or a dot chasing
+// along when nothing says how long it'll take. Dark while nothing's building.
+pub fn run_led_strip(config: LedStripConfig, board: StatusBoard, scheduler: TickScheduler) {
+    let mut strip = LedStrip::new(config.clock_pin, config.data_pin, config.brightness);
+    let dark = vec![(0, 0, 0); config.first_led + config.leds];
+    strip.show(&dark);
+
+    let mut frame: usize = 0;
+    let mut shown = dark.clone();
+    let mut ticker = scheduler.ticker();
+    loop {
+        let snapshots = board.snapshots();
+        // The build that started first, if several are running
+        let build = snapshots
+            .iter()
+            .find(|snapshot| snapshot.name == config.integration)
+            .and_then(|snapshot| {
+                snapshot
+                    .summary
+                    .running_builds
+                    .iter()
+                    .min_by_key(|build| build.started_at)
+                    .cloned()
+            });
+        let now_millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|since_epoch| since_epoch.as_secs() as i64 * 1000)
+            .unwrap_or(0);
+        let mut pixels = vec![(0, 0, 0); config.first_led];
+        pixels.extend(render_progress(
+            build.as_ref(),
+            now_millis,
+            config.leds,
+            frame,
+            config.color,
+            config.overdue_color,
+        ));
+        if pixels != shown {
+            strip.show(&pixels);
+            shown = pixels;
+        }
+        frame = frame.wrapping_add(1);
+        if ticker.wait(Duration::from_millis(STRIP_FRAME_MILLIS)) == Tick::Shutdown {
+            break;
+        }
+    }
+    strip.show(&dark);
+}
+
+// The segment's LEDs as 0-255 R, G, B. The bar's last LED is dimmed by how
+// much of it is filled, so it grows smoothly on long builds.
+fn render_progress(
+    build: Option<&BuildProgress>,
+    now_millis: i64,
+    leds: usize,
+    frame: usize,
+    color: (i32, i32, i32),
+    overdue_color: (i32, i32, i32),
+) -> Vec<(u8, u8, u8)> {
+    let mut pixels = vec![(0, 0, 0); leds];
+    let build = match build {
+        Some(build) if leds > 0 => build,
+        _ => return pixels,
+    };
+    match build.fraction(now_millis) {
+        Some(fraction) if fraction >= 1.0 => {
+            pixels = vec![to_pixel(overdue_color, 100); leds];
+        }
+        Some(fraction) => {
+            let filled = fraction * leds as f64;
+            for (i, pixel) in pixels.iter_mut().enumerate() {
+                let percent = ((filled - i as f64).max(0.0).min(1.0) * 100.0) as i32;
+                *pixel = to_pixel(color, percent);
+            }
+        }
+        None => {
+            let head = frame % leds;
+            for trail in 0..CHASE_LEDS.min(leds) {
+                let percent = (100 - trail * 100 / CHASE_LEDS) as i32;
+                pixels[(head + leds - trail) % leds] = to_pixel(color, percent);
+            }
+        }
+    }
+    pixels
+}
+
+// From the 0-100 colors used everywhere else.
+fn to_pixel(color: (i32, i32, i32), percent: i32) -> (u8, u8, u8) {
+    let channel = |value: i32| (value.max(0).min(100) * percent * 255 / 10000) as u8;
+    (channel(color.0), channel(color.1), channel(color.2))
+}
+
+fn render_lcd_page(snapshot: &IntegrationSnapshot, columns: usize, rows: usize) -> Vec<String> {
+    let poll_time = snapshot.last_poll.format("%H:%M").to_string();
+    let status = snapshot.status.label();
+    // Name gets whatever room is left after the status and poll time.
+    let name_width = columns.saturating_sub(status.len() + poll_time.len() + 2);
+    let name: String = snapshot.name.chars().take(name_width).collect();
+    let mut lines = vec![format!(
+        "{:<name_width$} {} {}",
+        name,
+        status,
+        poll_time,
+        name_width = name_width
+    )];
+
+    let summary = &snapshot.summary;
+    let counts = format!(
+        "P{} F{} B{} ?{}",
+        summary.passing, summary.failing, summary.in_progress, summary.indeterminate
+    );
+    let failing_jobs = summary.failing_jobs.join(",");
+    if rows <= 2 {
+        // Not enough room for both, so the failing jobs win.
+        if failing_jobs.is_empty() {
+            lines.push(counts);
+        } else {
+            lines.push(failing_jobs);
+        }
+    } else {
+        lines.push(counts);
+        // Who broke it takes the last row, if there's one left after a failing job
+        let show_culprits = !summary.culprits.is_empty() && rows > 3;
+        let job_rows = if show_culprits { rows - 3 } else { rows - 2 };
+        for job in summary.failing_jobs.iter().take(job_rows) {
+            lines.push(job.clone());
+        }
+        if show_culprits {
+            lines.push(format!("by {}", summary.culprits.join(",")));
+        }
+    }
+    lines
+}
+
+// Status glyphs for the OLED, in the same column format as the font.
+const GLYPH_PASSING: [u8; 5] = [0x10, 0x20, 0x10, 0x08, 0x04];
+const GLYPH_FAILING: [u8; 5] = [0x63, 0x77, 0x3E, 0x77, 0x63];
+const GLYPH_IN_PROGRESS: [u8; 5] = [0x63, 0x55, 0x49, 0x55, 0x63];
+const GLYPH_UNKNOWN: [u8; 5] = [0x02, 0x01, 0x51, 0x09, 0x06];
+
+// Subscribes an SSD1306 OLED to the status board, so it is redrawn by the worker
+// threads whenever one of them finishes a poll.
+pub fn start_oled(config: &OledConfig, board: &StatusBoard) {
+    let oled = match Oled::new(&config.i2c_bus, config.address) {
+        Ok(oled) => Arc::new(Mutex::new(oled)),
+        Err(e) => {
+            error!(
+                "--OLED--: Failed to initialize OLED. OLED disabled. Error: {}",
+                e
+            );
+            return;
+        }
+    };
+    board.subscribe(Box::new(move |snapshots| {
+        if let Ok(mut oled) = oled.lock() {
+            draw_oled_dashboard(&mut oled, snapshots);
+            if let Err(e) = oled.flush() {
+                warn!("--OLED--: Failed to update OLED. Error: {}", e);
+            }
+        }
+    }));
+}
+
+// Subscribes a TM1637 7-segment display to the status board, showing the
+// number of failing builds across all integrations after every poll.
+pub fn start_seven_segment(config: &SevenSegmentConfig, board: &StatusBoard) {
+    let mut display =
+        SevenSegmentDisplay::new(config.clock_pin, config.data_pin, config.brightness);
+    display.show_dashes();
+    let display = Mutex::new(display);
+    board.subscribe(Box::new(move |snapshots| {
+        let failing_builds = snapshots
+            .iter()
+            .map(|snapshot| snapshot.summary.failing)
+            .sum();
+        if let Ok(mut display) = display.lock() {
+            display.show_number(failing_builds);
+        }
+    }));
+}
+
+fn draw_oled_dashboard(oled: &mut Oled, snapshots: &[IntegrationSnapshot]) {
+    let now = Local::now();
+    oled.clear();
+    oled.draw_text(0, 0, &format!("BUILDS {:>14}", now.format("%H:%M")));
+    // One row per integration, below the header row.
+    for (i, snapshot) in snapshots.iter().take(ssd1306::PAGES - 1).enumerate() {
+        let page = i + 1;
+        let glyph = match snapshot.status {
+            RemoteStatus::Passing => GLYPH_PASSING,
+            RemoteStatus::Failing => GLYPH_FAILING,
+            RemoteStatus::InProgress => GLYPH_IN_PROGRESS,
+            RemoteStatus::Unknown => GLYPH_UNKNOWN,
+        };
+        let column = oled.draw_columns(page, 0, &glyph);
+        let name: String = snapshot.name.chars().take(7).collect();
+        let age = format_age(now.signed_duration_since(snapshot.last_poll).num_seconds());
+        oled.draw_text(
+            page,
+            column + font::GLYPH_WIDTH,
+            &format!(
+                "{:<7} {:>2}/{:<2} {:>3}",
+                name, snapshot.summary.passing, snapshot.summary.failing, age
+            ),
+        );
+    }
+    // Who broke the builds goes on the bottom row, if no integration needs it
+    let culprits: Vec<&str> = snapshots
+        .iter()
+        .filter(|snapshot| snapshot.status == RemoteStatus::Failing)
+        .flat_map(|snapshot| snapshot.summary.culprits.iter().map(|name| name.as_str()))
+        .collect();
+    if !culprits.is_empty() && snapshots.len() < ssd1306::PAGES - 1 {
+        // Anything past the edge of the screen is cut off
+        oled.draw_text(ssd1306::PAGES - 1, 0, &format!("BY {}", culprits.join(",")));
+    }
+}
+
+fn format_age(seconds: i64) -> String {
+    if seconds < 60 {
+        format!("{}S", seconds.max(0))
+    } else if seconds < 60 * 60 {
+        format!("{}M", seconds / 60)
+    } else {
+        format!("{}H", seconds / (60 * 60))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn running_builds_fill_the_strip() {
+        let green = (0, 100, 0);
+        let teal = (0, 100, 100);
+        let build = BuildProgress {
+            job: "api".to_string(),
+            started_at: 1_000_000,
+            estimated_millis: Some(100_000),
+        };
+        let render = |build: Option<&BuildProgress>, now: i64| {
+            render_progress(build, now, 4, 0, green, teal)
+        };
+
+        assert_eq!(render(None, 1_050_000), vec![(0, 0, 0); 4]);
+        // Halfway, two LEDs full
+        assert_eq!(
+            render(Some(&build), 1_050_000),
+            vec![(0, 255, 0), (0, 255, 0), (0, 0, 0), (0, 0, 0)]
+        );
+        // The third LED a quarter of the way up to its full brightness
+        assert_eq!(render(Some(&build), 1_056_250)[2], (0, 63, 0));
+        assert_eq!(render(Some(&build), 1_200_000), vec![(0, 255, 255); 4]);
+
+        let unestimated = BuildProgress {
+            estimated_millis: None,
+            ..build
+        };
+        let chase = render_progress(Some(&unestimated), 1_050_000, 4, 5, green, teal);
+        assert_eq!(chase[1], (0, 255, 0));
+        assert_eq!(chase.iter().filter(|pixel| **pixel != (0, 0, 0)).count(), 3);
+    }
+}
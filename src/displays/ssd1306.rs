@@ -0,0 +1,103 @@
+use displays::font;
+use failure::Error;
+use libc;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::os::unix::io::AsRawFd;
+
+pub const WIDTH: usize = 128;
+pub const HEIGHT: usize = 64;
+pub const PAGES: usize = HEIGHT / 8;
+
+// From linux/i2c-dev.h
+const I2C_SLAVE: libc::c_ulong = 0x0703;
+
+const CONTROL_COMMAND: u8 = 0x00;
+const CONTROL_DATA: u8 = 0x40;
+
+// Display off, clock, 1/64 multiplex, no offset, start line 0, charge pump on,
+// horizontal addressing, flipped to the usual orientation, COM config for 128x64,
+// contrast, precharge, VCOMH, resume from RAM, non-inverted, display on.
+const INIT_SEQUENCE: [u8; 25] = [
+    0xAE, 0xD5, 0x80, 0xA8, 0x3F, 0xD3, 0x00, 0x40, 0x8D, 0x14, 0x20, 0x00, 0xA1, 0xC8, 0xDA, 0x12,
+    0x81, 0xCF, 0xD9, 0xF1, 0xDB, 0x40, 0xA4, 0xA6, 0xAF,
+];
+
+// A 128x64 SSD1306 OLED on a Linux I2C bus. Drawing happens in an in-memory
+// frame buffer, which is pushed to the panel in one go by `flush`.
+pub struct Oled {
+    device: File,
+    buffer: [u8; WIDTH * PAGES],
+}
+
+impl Oled {
+    pub fn new(i2c_bus: &str, address: u16) -> Result<Oled, Error> {
+        let device = OpenOptions::new().read(true).write(true).open(i2c_bus)?;
+        let result =
+            unsafe { libc::ioctl(device.as_raw_fd(), I2C_SLAVE, address as libc::c_ulong) };
+        if result < 0 {
+            return Err(format_err!(
+                "Unable to select I2C address {:#x} on {}",
+                address,
+                i2c_bus
+            ));
+        }
+
+        let mut oled = Oled {
+            device: device,
+            buffer: [0; WIDTH * PAGES],
+        };
+        oled.commands(&INIT_SEQUENCE)?;
+        oled.flush()?;
+        Ok(oled)
+    }
+
+    pub fn clear(&mut self) {
+        self.buffer = [0; WIDTH * PAGES];
+    }
+
+    // Draws text on one of the eight 8-pixel-high pages, starting at the given pixel column.
+    // Returns the column just past the last character drawn.
+    pub fn draw_text(&mut self, page: usize, column: usize, text: &str) -> usize {
+        let mut column = column;
+        for c in text.chars() {
+            column = self.draw_columns(page, column, &font::glyph(c));
+            column += 1; // Spacing between characters
+        }
+        column
+    }
+
+    pub fn draw_columns(&mut self, page: usize, column: usize, columns: &[u8]) -> usize {
+        if page >= PAGES {
+            return column;
+        }
+        let mut column = column;
+        for bits in columns {
+            if column >= WIDTH {
+                break;
+            }
+            self.buffer[page * WIDTH + column] = *bits;
+            column += 1;
+        }
+        column
+    }
+
+    pub fn flush(&mut self) -> Result<(), Error> {
+        // Full-screen window, so the write below fills the whole panel.
+        self.commands(&[0x21, 0x00, (WIDTH - 1) as u8, 0x22, 0x00, (PAGES - 1) as u8])?;
+        let buffer = self.buffer;
+        for chunk in buffer.chunks(16) {
+            let mut message = vec![CONTROL_DATA];
+            message.extend_from_slice(chunk);
+            self.device.write_all(&message)?;
+        }
+        Ok(())
+    }
+
+    fn commands(&mut self, commands: &[u8]) -> Result<(), Error> {
+        for command in commands {
+            self.device.write_all(&[CONTROL_COMMAND, *command])?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,117 @@
+use gpio::{self, GpioBackend};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+const CMD_DATA_AUTO_INCREMENT: u8 = 0x40;
+const CMD_ADDRESS_FIRST_DIGIT: u8 = 0xC0;
+const CMD_DISPLAY_ON: u8 = 0x88;
+
+// Segments for 0-9, with bit 0 being segment A.
+const DIGITS: [u8; 10] = [0x3F, 0x06, 0x5B, 0x4F, 0x66, 0x6D, 0x7D, 0x07, 0x7F, 0x6F];
+const SEGMENT_DASH: u8 = 0x40;
+const SEGMENT_BLANK: u8 = 0x00;
+
+// A TM1637-driven 4-digit 7-segment display. The chip's two-wire protocol is
+// bit-banged over GPIO. We never read its acknowledge bit, since there's
+// nothing useful to be done about a missing one.
+pub struct SevenSegmentDisplay {
+    gpio: Arc<GpioBackend>,
+    clock_pin: u16,
+    data_pin: u16,
+    brightness: u8,
+}
+
+impl SevenSegmentDisplay {
+    pub fn new(clock: u16, data: u16, brightness: u8) -> SevenSegmentDisplay {
+        SevenSegmentDisplay {
+            gpio: gpio::wiring_pi(),
+            clock_pin: clock,
+            data_pin: data,
+            brightness: if brightness > 7 { 7 } else { brightness },
+        }
+    }
+
+    // Shows a right-aligned number, or 9999 if it doesn't fit.
+    pub fn show_number(&mut self, number: usize) {
+        let number = if number > 9999 { 9999 } else { number };
+        let mut segments = [SEGMENT_BLANK; 4];
+        let mut remaining = number;
+        for position in (0..4).rev() {
+            segments[position] = DIGITS[remaining % 10];
+            remaining /= 10;
+            if remaining == 0 {
+                break;
+            }
+        }
+        self.show_segments(&segments);
+    }
+
+    pub fn show_dashes(&mut self) {
+        self.show_segments(&[SEGMENT_DASH; 4]);
+    }
+
+    fn show_segments(&mut self, segments: &[u8; 4]) {
+        self.start();
+        self.write_byte(CMD_DATA_AUTO_INCREMENT);
+        self.stop();
+
+        self.start();
+        self.write_byte(CMD_ADDRESS_FIRST_DIGIT);
+        for segment in segments {
+            self.write_byte(*segment);
+        }
+        self.stop();
+
+        self.start();
+        let brightness = self.brightness;
+        self.write_byte(CMD_DISPLAY_ON | brightness);
+        self.stop();
+    }
+
+    fn start(&mut self) {
+        self.gpio.digital_write(self.data_pin, true);
+        self.gpio.digital_write(self.clock_pin, true);
+        bit_delay();
+        self.gpio.digital_write(self.data_pin, false);
+        bit_delay();
+    }
+
+    fn stop(&mut self) {
+        self.gpio.digital_write(self.clock_pin, false);
+        bit_delay();
+        self.gpio.digital_write(self.data_pin, false);
+        bit_delay();
+        self.gpio.digital_write(self.clock_pin, true);
+        bit_delay();
+        self.gpio.digital_write(self.data_pin, true);
+        bit_delay();
+    }
+
+    fn write_byte(&mut self, value: u8) {
+        // Least significant bit first
+        for bit in 0..8 {
+            self.gpio.digital_write(self.clock_pin, false);
+            if value & (1 << bit) != 0 {
+                self.gpio.digital_write(self.data_pin, true);
+            } else {
+                self.gpio.digital_write(self.data_pin, false);
+            }
+            bit_delay();
+            self.gpio.digital_write(self.clock_pin, true);
+            bit_delay();
+        }
+
+        // Clock out the acknowledge bit
+        self.gpio.digital_write(self.clock_pin, false);
+        self.gpio.digital_write(self.data_pin, true);
+        bit_delay();
+        self.gpio.digital_write(self.clock_pin, true);
+        bit_delay();
+        self.gpio.digital_write(self.clock_pin, false);
+    }
+}
+
+fn bit_delay() {
+    thread::sleep(Duration::new(0, 5000));
+}
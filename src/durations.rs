@@ -0,0 +1,160 @@
+use config_file::DurationsConfig;
+use serde_json;
+use std::collections::{BTreeMap, VecDeque};
+use std::fs::File;
+use std::path::PathBuf;
+
+// One finished build of a job.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+struct BuildDuration {
+    number: u32,
+    millis: u64,
+}
+
+// Rolling window of how long each job's last few builds took, used to spot a
+// running build that's taking much longer than usual, and may well be hung.
+// Optionally kept on disk, so restarting the build light doesn't forget them.
+pub struct BuildDurations {
+    window: usize,
+    min_builds: usize,
+    factor: f64,
+    path: Option<PathBuf>,
+    // Oldest first
+    jobs: BTreeMap<String, VecDeque<BuildDuration>>,
+}
+
+impl BuildDurations {
+    pub fn new(config: &DurationsConfig) -> BuildDurations {
+        let path = config.history_file.as_ref().map(PathBuf::from);
+        let jobs = path
+            .as_ref()
+            .and_then(|path| match File::open(path) {
+                Ok(file) => serde_json::from_reader(file)
+                    .map_err(|err| {
+                        warn!(
+                            "Couldn't read build durations from {:?}. Starting over. Error: {}",
+                            path, err
+                        )
+                    })
+                    .ok(),
+                // Nothing saved yet
+                Err(_) => None,
+            })
+            .unwrap_or_default();
+        BuildDurations {
+            window: config.window.max(1),
+            min_builds: config.min_builds.max(1),
+            factor: config.factor,
+            path: path,
+            jobs: jobs,
+        }
+    }
+
+    // Records how long the latest finished build of each job took, as (job,
+    // build number, milliseconds). Builds that were already recorded are
+    // skipped, since we see the same build on every poll until the next one.
+    pub fn record(&mut self, builds: &[(String, u32, u64)]) {
+        let mut changed = false;
+        for &(ref job, number, millis) in builds {
+            let durations = self.jobs.entry(job.clone()).or_insert_with(VecDeque::new);
+            if durations.back().map_or(false, |last| last.number == number) {
+                continue;
+            }
+            durations.push_back(BuildDuration {
+                number: number,
+                millis: millis,
+            });
+            while durations.len() > self.window {
+                durations.pop_front();
+            }
+            changed = true;
+        }
+        if changed {
+            self.save();
+        }
+    }
+
+    // How long the job's builds usually take, once enough of them were recorded.
+    pub fn average_millis(&self, job: &str) -> Option<u64> {
+        match self.jobs.get(job) {
+            Some(durations) if durations.len() >= self.min_builds => Some(
+                durations.iter().map(|build| build.millis).sum::<u64>() / durations.len() as u64,
+            ),
+            _ => None,
+        }
+    }
+
+    // Of the running builds, given as (job, milliseconds so far), the jobs
+    // whose builds have run for longer than factor times their average.
+    pub fn overdue(&self, running: &[(String, u64)]) -> Vec<String> {
+        running
+            .iter()
+            .filter(|&&(ref job, elapsed)| {
+                self.average_millis(job).map_or(false, |average| {
+                    elapsed as f64 > average as f64 * self.factor
+                })
+            })
+            .map(|&(ref job, _)| job.clone())
+            .collect()
+    }
+
+    fn save(&self) {
+        if let Some(ref path) = self.path {
+            let result = File::create(path)
+                .map_err(|err| err.to_string())
+                .and_then(|file| {
+                    serde_json::to_writer(file, &self.jobs).map_err(|err| err.to_string())
+                });
+            if let Err(err) = result {
+                warn!(
+                    "Couldn't save build durations to {:?}. Error: {}",
+                    path, err
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn durations() -> BuildDurations {
+        BuildDurations::new(&DurationsConfig {
+            factor: 1.5,
+            window: 3,
+            min_builds: 2,
+            history_file: None,
+        })
+    }
+
+    #[test]
+    fn averages_the_most_recent_builds() {
+        let mut durations = durations();
+        durations.record(&[("app".to_string(), 1, 1000)]);
+        assert_eq!(durations.average_millis("app"), None);
+
+        durations.record(&[("app".to_string(), 2, 3000)]);
+        // Seen again on the next poll, not a new build
+        durations.record(&[("app".to_string(), 2, 3000)]);
+        assert_eq!(durations.average_millis("app"), Some(2000));
+
+        durations.record(&[("app".to_string(), 3, 5000)]);
+        durations.record(&[("app".to_string(), 4, 7000)]);
+        assert_eq!(durations.average_millis("app"), Some(5000));
+    }
+
+    #[test]
+    fn builds_past_the_factor_are_overdue() {
+        let mut durations = durations();
+        durations.record(&[("app".to_string(), 1, 1000), ("api".to_string(), 1, 1000)]);
+        durations.record(&[("app".to_string(), 2, 1000), ("api".to_string(), 2, 1000)]);
+
+        let running = vec![
+            ("app".to_string(), 1400),
+            ("api".to_string(), 1600),
+            ("new".to_string(), 100_000),
+        ];
+        assert_eq!(durations.overdue(&running), vec!["api".to_string()]);
+    }
+}
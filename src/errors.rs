@@ -1,3 +1,25 @@
+use redact;
+use reqwest::StatusCode;
+use std::fmt;
+
+#[derive(Debug, Fail)]
+pub struct HttpStatusError {
+    pub url: String,
+    pub status: StatusCode,
+}
+
+// The URL may have credentials in it
+impl fmt::Display for HttpStatusError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "HTTP call to {} failed with code: {}",
+            redact::redact(&self.url),
+            self.status
+        )
+    }
+}
+
 #[derive(Debug, Fail)]
 pub enum UnityRetrievalError {
     #[fail(
@@ -8,3 +30,39 @@ pub enum UnityRetrievalError {
     #[fail(display = "Unity Cloud Build returned an HTTP error: {}", http_error_message)]
     HttpError { http_error_message: String },
 }
+
+// Why the build light couldn't start, or stopped early. Each kind exits with
+// its own code, so e.g. systemd can be told not to keep restarting after a
+// config error that a restart won't fix.
+#[derive(Debug, Fail)]
+pub enum RunError {
+    #[fail(display = "No config file at {}: {}", path, reason)]
+    ConfigMissing { path: String, reason: String },
+
+    #[fail(display = "Invalid config file. {}", reason)]
+    ConfigInvalid { reason: String },
+
+    #[fail(display = "Couldn't access the GPIO pins: {}", reason)]
+    GpioInitFailed { reason: String },
+
+    #[fail(display = "{}", reason)]
+    StartupFailed { reason: String },
+
+    #[fail(display = "The {} thread stopped: {}", thread, reason)]
+    ThreadFailed { thread: String, reason: String },
+
+    #[fail(display = "{}", reason)]
+    InvalidArguments { reason: String },
+}
+
+impl RunError {
+    pub fn exit_code(&self) -> i32 {
+        match *self {
+            RunError::ConfigMissing { .. } => 2,
+            RunError::ConfigInvalid { .. } => 3,
+            RunError::GpioInitFailed { .. } => 4,
+            RunError::InvalidArguments { .. } => 5,
+            RunError::StartupFailed { .. } | RunError::ThreadFailed { .. } => 1,
+        }
+    }
+}
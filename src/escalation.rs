@@ -0,0 +1,129 @@
+use config_file::EscalationConfig;
+use gpio::{self, GpioBackend};
+use pin::Pattern;
+use remote_status::RemoteStatus;
+use status_board::StatusBoard;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const BEEPS: u32 = 3;
+const BEEP_MILLIS: u64 = 200;
+
+struct Failure {
+    since: Instant,
+    buzzed: bool,
+}
+
+// The pattern a failure that has lasted failing_for blinks in: slowly while
+// it's new, in its usual pattern after calm_minutes, and fast once it's been
+// going on for urgent_minutes.
+pub fn escalated_pattern(
+    config: &EscalationConfig,
+    failing_for: Duration,
+    pattern: Pattern,
+) -> Pattern {
+    if failing_for >= Duration::from_secs(config.urgent_minutes * 60) {
+        Pattern::FastBlink
+    } else if failing_for < Duration::from_secs(config.calm_minutes * 60) {
+        Pattern::SlowBlink
+    } else {
+        pattern
+    }
+}
+
+// Sounds the buzzer, if there is one, once for every failure that lasts
+// buzzer_after_minutes.
+pub fn start_buzzer(config: &EscalationConfig, board: &StatusBoard) {
+    if let Some(pin) = config.buzzer_pin {
+        watch(config, board, gpio::wiring_pi(), pin);
+    }
+}
+
+fn watch(config: &EscalationConfig, board: &StatusBoard, gpio: Arc<GpioBackend>, pin: u16) {
+    let buzz_after = Duration::from_secs(config.buzzer_after_minutes * 60);
+    let failing: Mutex<HashMap<String, Failure>> = Mutex::new(HashMap::new());
+    board.subscribe(Box::new(move |snapshots| {
+        if let Ok(mut failing) = failing.lock() {
+            for snapshot in snapshots {
+                match snapshot.status {
+                    RemoteStatus::Passing => {
+                        failing.remove(&snapshot.name);
+                    }
+                    RemoteStatus::Failing => {
+                        let failure = failing
+                            .entry(snapshot.name.clone())
+                            .or_insert_with(|| Failure {
+                                since: Instant::now(),
+                                buzzed: false,
+                            });
+                        if !failure.buzzed && failure.since.elapsed() >= buzz_after {
+                            failure.buzzed = true;
+                            warn!(
+                                "--Escalation--: {} has been failing for {} minutes, sounding the buzzer.",
+                                snapshot.name,
+                                failure.since.elapsed().as_secs() / 60
+                            );
+                            // Off the poll thread, so the LEDs don't wait for it
+                            let gpio = Arc::clone(&gpio);
+                            thread::spawn(move || beep(&*gpio, pin));
+                        }
+                    }
+                    // Building or unknown doesn't end a failure
+                    _ => (),
+                }
+            }
+        }
+    }));
+}
+
+fn beep(gpio: &GpioBackend, pin: u16) {
+    for _ in 0..BEEPS {
+        gpio.digital_write(pin, true);
+        thread::sleep(Duration::from_millis(BEEP_MILLIS));
+        gpio.digital_write(pin, false);
+        thread::sleep(Duration::from_millis(BEEP_MILLIS));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gpio::FakeGpio;
+    use remote_status::StatusSummary;
+
+    fn config(buzzer_after_minutes: u64) -> EscalationConfig {
+        EscalationConfig {
+            calm_minutes: 15,
+            urgent_minutes: 60,
+            buzzer_pin: Some(4),
+            buzzer_after_minutes: buzzer_after_minutes,
+            remind_after_minutes: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn failures_blink_faster_the_longer_they_last() {
+        let minutes = |minutes: u64| Duration::from_secs(minutes * 60);
+        let escalated = |failing_for| escalated_pattern(&config(60), failing_for, Pattern::Blink);
+        assert_eq!(escalated(minutes(0)), Pattern::SlowBlink);
+        assert_eq!(escalated(minutes(15)), Pattern::Blink);
+        assert_eq!(escalated(minutes(59)), Pattern::Blink);
+        assert_eq!(escalated(minutes(60)), Pattern::FastBlink);
+    }
+
+    #[test]
+    fn buzzer_sounds_once_per_failure() {
+        let gpio = FakeGpio::new();
+        let board = StatusBoard::new();
+        watch(&config(0), &board, gpio.clone(), 4);
+        board.update("Jenkins", RemoteStatus::Failing, StatusSummary::default());
+        board.update("Jenkins", RemoteStatus::Failing, StatusSummary::default());
+        thread::sleep(Duration::from_millis(BEEP_MILLIS * 2 * BEEPS as u64 + 500));
+        assert_eq!(
+            gpio.digital_writes.lock().unwrap().len(),
+            BEEPS as usize * 2
+        );
+    }
+}
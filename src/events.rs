@@ -0,0 +1,61 @@
+use log_mdc;
+use remote_status::{RemoteStatus, StatusSummary};
+use std::time::Duration;
+
+// Structured fields for log lines, kept in log4rs's MDC. With the json encoder
+// (see config/log4rs-json.yml) every line comes out as one JSON object with
+// these fields under "mdc", ready to ship to Loki or Elasticsearch. The plain
+// text encoder just ignores them.
+
+// Tags everything logged from the current thread with the integration's name.
+pub fn set_integration(name: &str) {
+    log_mdc::insert("integration", name);
+}
+
+pub fn log_poll(status: RemoteStatus, summary: &StatusSummary, latency: Duration) {
+    let latency_ms = duration_millis(latency);
+    let mut fields = vec![
+        ("event", "poll".to_string()),
+        ("status", format!("{:?}", status)),
+        ("passing", summary.passing.to_string()),
+        ("failing", summary.failing.to_string()),
+        ("in_progress", summary.in_progress.to_string()),
+        ("aborted", summary.aborted.to_string()),
+        ("not_built", summary.not_built.to_string()),
+        ("indeterminate", summary.indeterminate.to_string()),
+        ("latency_ms", latency_ms.to_string()),
+    ];
+    if let Some(ref error) = summary.error {
+        fields.push(("error", error.clone()));
+    }
+    let _fields = log_mdc::extend_scoped(fields);
+    match summary.error {
+        Some(ref error) => warn!(
+            "Poll finished in {} ms with an error: {}",
+            latency_ms, error
+        ),
+        None => info!(
+            "Poll finished in {} ms: {:?}, {} passing, {} failing.",
+            latency_ms, status, summary.passing, summary.failing
+        ),
+    }
+}
+
+pub fn log_restart(thread_name: &str, error: &str, delay: Duration) {
+    let _fields = log_mdc::extend_scoped(vec![
+        ("event", "thread_restart".to_string()),
+        ("thread", thread_name.to_string()),
+        ("error", error.to_string()),
+        ("delay_ms", duration_millis(delay).to_string()),
+    ]);
+    error!(
+        "Thread {} terminated abnormally. Details: {}. Restarting in {} seconds...",
+        thread_name,
+        error,
+        delay.as_secs()
+    );
+}
+
+fn duration_millis(duration: Duration) -> u64 {
+    duration.as_secs() * 1000 + u64::from(duration.subsec_nanos() / 1_000_000)
+}
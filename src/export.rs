@@ -0,0 +1,246 @@
+use app;
+use chrono::{DateTime, Duration as ChronoDuration, Local, NaiveDate, TimeZone, Utc};
+use errors::RunError;
+use failure::Error;
+use metrics::{MetricsStore, PollRecord};
+use serde_json::Value;
+use std::io::{self, Write};
+
+pub const USAGE: &str = "Usage: rusty_build_light export [--from DATE] [--to DATE] [--format csv|json] [--database FILE]
+
+Writes the build history kept by [metrics] to standard output. Dates are
+YYYY-MM-DD in local time, or RFC 3339 timestamps. --to includes the whole day.
+The database defaults to the one in config.toml.";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ExportOptions {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub format: ExportFormat,
+    pub database: Option<String>,
+}
+
+// Parses what comes after "export" on the command line.
+pub fn parse_args(args: &[String]) -> Result<ExportOptions, Error> {
+    let mut options = ExportOptions {
+        from: None,
+        to: None,
+        format: ExportFormat::Csv,
+        database: None,
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let value = args
+            .next()
+            .ok_or_else(|| format_err!("{} needs a value", arg))?;
+        match arg.as_str() {
+            "--from" => options.from = Some(parse_date(value, false)?),
+            "--to" => options.to = Some(parse_date(value, true)?),
+            "--format" => {
+                options.format = match value.as_str() {
+                    "csv" => ExportFormat::Csv,
+                    "json" => ExportFormat::Json,
+                    _ => return Err(format_err!("Unknown format \"{}\"", value)),
+                }
+            }
+            "--database" => options.database = Some(value.clone()),
+            _ => return Err(format_err!("Unknown option \"{}\"", arg)),
+        }
+    }
+    Ok(options)
+}
+
+// A date on its own is the start of that day, or the end of it for --to.
+fn parse_date(value: &str, end_of_day: bool) -> Result<DateTime<Utc>, Error> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d").map_err(|_| {
+        format_err!(
+            "\"{}\" isn't a YYYY-MM-DD date or an RFC 3339 timestamp",
+            value
+        )
+    })?;
+    let date = if end_of_day { date.succ() } else { date };
+    Local
+        .from_local_datetime(&date.and_hms(0, 0, 0))
+        .earliest()
+        .map(|time| time.with_timezone(&Utc))
+        .ok_or_else(|| format_err!("{} doesn't exist in local time", value))
+}
+
+pub fn run_export(args: &[String]) -> Result<(), RunError> {
+    let invalid = |reason: String| RunError::InvalidArguments {
+        reason: format!("{}\n\n{}", reason, USAGE),
+    };
+    let options = parse_args(args).map_err(|err| invalid(err.to_string()))?;
+    let database = match options.database {
+        Some(ref database) => database.clone(),
+        None => app::read_config(&app::exe_dir()?)?
+            .metrics
+            .map(|metrics| metrics.database)
+            .ok_or_else(|| {
+                invalid("No [metrics] in config.toml, and no --database given.".to_string())
+            })?,
+    };
+    let failed = |err: Error| RunError::StartupFailed {
+        reason: format!("Export failed: {}", err),
+    };
+    // Retention doesn't matter, nothing gets pruned
+    let store = MetricsStore::open(&database, 0).map_err(&failed)?;
+    let from = options
+        .from
+        .unwrap_or_else(|| Utc.ymd(1970, 1, 1).and_hms(0, 0, 0));
+    // A little past now, in case a poll was just stored
+    let to = options
+        .to
+        .unwrap_or_else(|| Utc::now() + ChronoDuration::minutes(1));
+    let polls = store.polls_between(from, to).map_err(&failed)?;
+
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    match options.format {
+        ExportFormat::Csv => write_csv(&polls, &mut out),
+        ExportFormat::Json => write_json(&polls, &mut out),
+    }
+    .map_err(failed)
+}
+
+// One row per job of every poll, with the poll's own columns repeated. Polls
+// without any jobs get a single row with the job columns left empty.
+pub fn write_csv<W: Write>(polls: &[PollRecord], out: &mut W) -> Result<(), Error> {
+    writeln!(
+        out,
+        "polled_at,integration,status,passing,failing,in_progress,indeterminate,job,job_status"
+    )?;
+    for poll in polls {
+        let poll_columns = format!(
+            "{},{},{:?},{},{},{},{}",
+            poll.polled_at.to_rfc3339(),
+            csv_field(&poll.integration),
+            poll.status,
+            poll.passing,
+            poll.failing,
+            poll.in_progress,
+            poll.indeterminate
+        );
+        if poll.jobs.is_empty() {
+            writeln!(out, "{},,", poll_columns)?;
+        }
+        for job in &poll.jobs {
+            writeln!(
+                out,
+                "{},{},{:?}",
+                poll_columns,
+                csv_field(&job.name),
+                job.status
+            )?;
+        }
+    }
+    Ok(())
+}
+
+// Quoted only when it has to be, with quotes inside doubled.
+fn csv_field(field: &str) -> String {
+    if field.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+pub fn write_json<W: Write>(polls: &[PollRecord], out: &mut W) -> Result<(), Error> {
+    let polls: Vec<Value> = polls
+        .iter()
+        .map(|poll| {
+            json!({
+                "polled_at": poll.polled_at.to_rfc3339(),
+                "integration": poll.integration,
+                "status": poll.status,
+                "passing": poll.passing,
+                "failing": poll.failing,
+                "in_progress": poll.in_progress,
+                "indeterminate": poll.indeterminate,
+                "jobs": poll.jobs,
+            })
+        })
+        .collect();
+    ::serde_json::to_writer_pretty(&mut *out, &polls)?;
+    writeln!(out)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use remote_status::{JobStatus, RemoteStatus};
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn options_are_parsed() {
+        let options = parse_args(&args(&[
+            "--from",
+            "2018-03-01T00:00:00Z",
+            "--to",
+            "2018-03-31T00:00:00+02:00",
+            "--format",
+            "json",
+        ]))
+        .unwrap();
+        assert_eq!(options.from, Some(Utc.ymd(2018, 3, 1).and_hms(0, 0, 0)));
+        assert_eq!(options.to, Some(Utc.ymd(2018, 3, 30).and_hms(22, 0, 0)));
+        assert_eq!(options.format, ExportFormat::Json);
+        assert!(parse_args(&args(&["--format", "xml"])).is_err());
+        assert!(parse_args(&args(&["--from"])).is_err());
+
+        // A plain --to date includes that whole day
+        let options = parse_args(&args(&["--from", "2018-03-01", "--to", "2018-03-01"])).unwrap();
+        assert_eq!(
+            options
+                .to
+                .unwrap()
+                .signed_duration_since(options.from.unwrap()),
+            ChronoDuration::days(1)
+        );
+    }
+
+    #[test]
+    fn csv_has_a_row_per_job() {
+        let poll = PollRecord {
+            polled_at: Utc.ymd(2018, 3, 1).and_hms(12, 0, 0),
+            integration: "Jenkins".to_string(),
+            status: RemoteStatus::Failing,
+            passing: 1,
+            failing: 1,
+            in_progress: 0,
+            indeterminate: 0,
+            jobs: vec![
+                JobStatus {
+                    name: "api".to_string(),
+                    status: RemoteStatus::Failing,
+                },
+                JobStatus {
+                    name: "app, \"web\"".to_string(),
+                    status: RemoteStatus::Passing,
+                },
+            ],
+        };
+        let mut out = Vec::new();
+        write_csv(&[poll], &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "polled_at,integration,status,passing,failing,in_progress,indeterminate,job,job_status\n\
+             2018-03-01T12:00:00+00:00,Jenkins,Failing,1,1,0,0,api,Failing\n\
+             2018-03-01T12:00:00+00:00,Jenkins,Failing,1,1,0,0,\"app, \"\"web\"\"\",Passing\n"
+        );
+    }
+}
@@ -0,0 +1,139 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// Which limit an integration thread's crashes have gone over.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Exceeded {
+    // Only this thread gives up
+    Thread(u32),
+    // Everything stops, see allowed_total_failures
+    Total(u32),
+}
+
+// Counts how often each integration thread has crashed. Each one has a limit
+// of its own, so a flaky Unity connection can't use up Jenkins' restarts, and
+// there can be one for all of them together too.
+#[derive(Clone)]
+pub struct FailureLimits {
+    per_thread: Option<u32>,
+    overrides: BTreeMap<String, u32>,
+    total: Option<u32>,
+    // How long to wait before trying again, rather than giving up for good
+    cooldown: Option<Duration>,
+    counts: Arc<Mutex<HashMap<String, u32>>>,
+}
+
+impl FailureLimits {
+    pub fn new(
+        per_thread: Option<u32>,
+        overrides: BTreeMap<String, u32>,
+        total: Option<u32>,
+    ) -> FailureLimits {
+        FailureLimits {
+            per_thread: per_thread,
+            overrides: overrides,
+            total: total,
+            cooldown: None,
+            counts: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub fn set_cooldown(&mut self, cooldown: Option<Duration>) {
+        self.cooldown = cooldown;
+    }
+
+    pub fn cooldown(&self) -> Option<Duration> {
+        self.cooldown
+    }
+
+    pub fn record_failure(&self, thread_name: &str) {
+        match self.counts.lock() {
+            Ok(mut counts) => *counts.entry(thread_name.to_string()).or_insert(0) += 1,
+            Err(_) => error!(
+                "Attempted to increment failure count for thread {}, but failed to acquire a lock on the counter.",
+                thread_name
+            ),
+        }
+    }
+
+    // The limit that thread_name's crashes, or everyone's, have gone over.
+    pub fn exceeded(&self, thread_name: &str) -> Option<Exceeded> {
+        let counts = match self.counts.lock() {
+            Ok(counts) => counts,
+            Err(_) => return None,
+        };
+        let total: u32 = counts.values().sum();
+        match self.total {
+            Some(limit) if total > limit => return Some(Exceeded::Total(limit)),
+            _ => (),
+        }
+        let count = counts.get(thread_name).cloned().unwrap_or(0);
+        match self.limit_for(thread_name) {
+            Some(limit) if count > limit => Some(Exceeded::Thread(limit)),
+            _ => None,
+        }
+    }
+
+    // Starts counting over after a cool-down: thread_name's own crashes for
+    // Exceeded::Thread, everyone's for Exceeded::Total.
+    pub fn reset(&self, thread_name: &str, exceeded: Exceeded) {
+        if let Ok(mut counts) = self.counts.lock() {
+            match exceeded {
+                Exceeded::Thread(_) => {
+                    counts.remove(thread_name);
+                }
+                Exceeded::Total(_) => counts.clear(),
+            }
+        }
+    }
+
+    fn limit_for(&self, thread_name: &str) -> Option<u32> {
+        self.overrides.get(thread_name).cloned().or(self.per_thread)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn each_integration_uses_up_only_its_own_restarts() {
+        let mut overrides = BTreeMap::new();
+        overrides.insert("Unity Cloud".to_string(), 2);
+        let limits = FailureLimits::new(Some(0), overrides, Some(4));
+
+        limits.record_failure("Unity Cloud");
+        limits.record_failure("Unity Cloud");
+        assert_eq!(limits.exceeded("Unity Cloud"), None);
+        limits.record_failure("Unity Cloud");
+        assert_eq!(limits.exceeded("Unity Cloud"), Some(Exceeded::Thread(2)));
+        assert_eq!(limits.exceeded("Jenkins"), None);
+
+        limits.record_failure("Jenkins");
+        assert_eq!(limits.exceeded("Jenkins"), Some(Exceeded::Thread(0)));
+        // Five crashes between them is over the combined limit
+        limits.record_failure("Jenkins PRs");
+        assert_eq!(limits.exceeded("Jenkins PRs"), Some(Exceeded::Total(4)));
+    }
+
+    #[test]
+    fn counts_start_over_after_a_cool_down() {
+        let limits = FailureLimits::new(Some(1), BTreeMap::new(), Some(3));
+        limits.record_failure("Jenkins");
+        limits.record_failure("Jenkins");
+        limits.record_failure("Unity Cloud");
+        assert_eq!(limits.exceeded("Jenkins"), Some(Exceeded::Thread(1)));
+
+        limits.reset("Jenkins", Exceeded::Thread(1));
+        assert_eq!(limits.exceeded("Jenkins"), None);
+        // Unity Cloud's crash still counts
+        limits.record_failure("Jenkins");
+        limits.record_failure("Unity Cloud");
+        limits.record_failure("Unity Cloud");
+        assert_eq!(limits.exceeded("Jenkins"), Some(Exceeded::Total(3)));
+
+        limits.reset("Jenkins", Exceeded::Total(3));
+        assert_eq!(limits.exceeded("Unity Cloud"), None);
+    }
+}
@@ -0,0 +1,136 @@
+use config_file::FaultInjectionConfig;
+use failure::Error;
+use rand::{self, Rng};
+use std::env;
+
+// Overrides [fault_injection], with one percentage used for every kind of
+// fault, e.g. FAULT_INJECTION_PERCENT=5 for a soak test without editing config.toml.
+const ENV_VAR: &str = "FAULT_INJECTION_PERCENT";
+
+// Ends part way through, so it doesn't parse as any response.
+pub const TRUNCATED_JSON: &str = "{\"jobs\": [{\"name\": \"";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Fault {
+    // Waits out the client's timeout, then fails the way an unreachable server does
+    Timeout,
+    // 500 Internal Server Error
+    ServerError,
+    // A 200 whose body isn't valid JSON
+    MalformedJson,
+}
+
+#[derive(Debug, Fail)]
+#[fail(display = "Injected timeout calling {}", url)]
+pub struct InjectedTimeout {
+    pub url: String,
+}
+
+// Makes a share of an integration's requests fail on purpose, instead of
+// sending them, to exercise the retries, backoff and LED fallbacks in long
+// soak tests. Never meant to be on in a real build light.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FaultInjector {
+    timeout_percent: u32,
+    server_error_percent: u32,
+    malformed_json_percent: u32,
+}
+
+impl FaultInjector {
+    // None unless [fault_injection] or FAULT_INJECTION_PERCENT turns it on.
+    pub fn from_config(
+        config: Option<&FaultInjectionConfig>,
+    ) -> Result<Option<FaultInjector>, Error> {
+        let injector = match env::var(ENV_VAR) {
+            Ok(percent) => {
+                let percent = percent
+                    .trim()
+                    .parse::<u32>()
+                    .map_err(|_| format_err!("{} isn't a percentage: \"{}\"", ENV_VAR, percent))?;
+                FaultInjector {
+                    timeout_percent: percent,
+                    server_error_percent: percent,
+                    malformed_json_percent: percent,
+                }
+            }
+            Err(_) => match config {
+                Some(config) => FaultInjector {
+                    timeout_percent: config.timeout_percent,
+                    server_error_percent: config.server_error_percent,
+                    malformed_json_percent: config.malformed_json_percent,
+                },
+                None => return Ok(None),
+            },
+        };
+        if injector.total_percent() > 100 {
+            return Err(format_err!(
+                "The fault percentages add up to {}, more than 100",
+                injector.total_percent()
+            ));
+        }
+        if injector.total_percent() == 0 {
+            return Ok(None);
+        }
+        Ok(Some(injector))
+    }
+
+    fn total_percent(&self) -> u32 {
+        self.timeout_percent + self.server_error_percent + self.malformed_json_percent
+    }
+
+    // Which fault, if any, the next request gets.
+    pub fn next_fault(&self) -> Option<Fault> {
+        self.fault_for(rand::thread_rng().gen_range(0, 100))
+    }
+
+    // roll is from 0 to 99, each fault taking up its percentage of them.
+    fn fault_for(&self, roll: u32) -> Option<Fault> {
+        let faults = [
+            (Fault::Timeout, self.timeout_percent),
+            (Fault::ServerError, self.server_error_percent),
+            (Fault::MalformedJson, self.malformed_json_percent),
+        ];
+        let mut upto = 0;
+        for &(fault, percent) in faults.iter() {
+            upto += percent;
+            if roll < upto {
+                return Some(fault);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn each_fault_gets_its_share_of_requests() {
+        let config = FaultInjectionConfig {
+            timeout_percent: 10,
+            server_error_percent: 5,
+            malformed_json_percent: 1,
+        };
+        let injector = FaultInjector::from_config(Some(&config)).unwrap().unwrap();
+        let faults: Vec<Option<Fault>> = (0..100).map(|roll| injector.fault_for(roll)).collect();
+        let count = |fault: Option<Fault>| faults.iter().filter(|&&f| f == fault).count();
+        assert_eq!(count(Some(Fault::Timeout)), 10);
+        assert_eq!(count(Some(Fault::ServerError)), 5);
+        assert_eq!(count(Some(Fault::MalformedJson)), 1);
+        assert_eq!(count(None), 84);
+
+        let off = FaultInjectionConfig {
+            timeout_percent: 0,
+            server_error_percent: 0,
+            malformed_json_percent: 0,
+        };
+        assert_eq!(FaultInjector::from_config(Some(&off)).unwrap(), None);
+        let too_many = FaultInjectionConfig {
+            timeout_percent: 60,
+            server_error_percent: 60,
+            malformed_json_percent: 0,
+        };
+        assert!(FaultInjector::from_config(Some(&too_many)).is_err());
+    }
+}
@@ -0,0 +1,281 @@
+use failure::Error;
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io;
+use std::sync::{Arc, Mutex};
+use wiringpi;
+use wiringpi::pin::{Gpio, InputPin, OutputPin, Pull, SoftPwmPin, Value};
+
+lazy_static! {
+    static ref WIRING_PI: Arc<GpioBackend> = Arc::new(WiringPiBackend::new());
+}
+
+// Everything the build light does with the Pi's GPIO pins, so the LED and
+// display logic can run against something other than real hardware.
+// Pins use the Broadcom numbering.
+pub trait GpioBackend: Send + Sync {
+    // Software PWM duty cycle, from 0 to 100
+    fn pwm_write(&self, pin: u16, value: i32);
+    fn digital_write(&self, pin: u16, high: bool);
+    // Reads an input pin with the internal pull-up on
+    fn digital_read(&self, pin: u16) -> bool;
+}
+
+// The real pins, through WiringPi.
+pub fn wiring_pi() -> Arc<GpioBackend> {
+    Arc::clone(&WIRING_PI)
+}
+
+// How pin numbers in config.toml are meant. The pins are driven by their
+// Broadcom numbers, so the others are translated when the config is read.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PinNumbering {
+    // Broadcom's GPIO numbers, as in "GPIO17" or "BCM17"
+    Bcm,
+    // WiringPi's own numbers, as printed by `gpio readall`
+    WiringPi,
+    // Positions on the 40-pin header, 1 to 40
+    Physical,
+}
+
+impl Default for PinNumbering {
+    fn default() -> PinNumbering {
+        PinNumbering::Bcm
+    }
+}
+
+// Header position to Broadcom number, for the header positions that are GPIOs.
+#[rustfmt::skip]
+const PHYSICAL_TO_BCM: [(u16, u16); 28] = [
+    (3, 2), (5, 3), (7, 4), (8, 14), (10, 15), (11, 17), (12, 18), (13, 27),
+    (15, 22), (16, 23), (18, 24), (19, 10), (21, 9), (22, 25), (23, 11), (24, 8),
+    (26, 7), (27, 0), (28, 1), (29, 5), (31, 6), (32, 12), (33, 13), (35, 19),
+    (36, 16), (37, 26), (38, 20), (40, 21),
+];
+
+// WiringPi number to Broadcom number, for the pins on the 40-pin header.
+#[rustfmt::skip]
+const WIRING_PI_TO_BCM: [(u16, u16); 28] = [
+    (0, 17), (1, 18), (2, 27), (3, 22), (4, 23), (5, 24), (6, 25), (7, 4),
+    (8, 2), (9, 3), (10, 8), (11, 7), (12, 10), (13, 9), (14, 11), (15, 14),
+    (16, 15), (21, 5), (22, 6), (23, 13), (24, 19), (25, 26), (26, 12), (27, 16),
+    (28, 20), (29, 21), (30, 0), (31, 1),
+];
+
+impl PinNumbering {
+    // The Broadcom number of the pin, or None if it isn't a GPIO pin.
+    pub fn to_bcm(self, pin: u16) -> Option<u16> {
+        let lookup = |table: &[(u16, u16)]| {
+            table
+                .iter()
+                .find(|&&(from, _)| from == pin)
+                .map(|&(_, bcm)| bcm)
+        };
+        match self {
+            PinNumbering::Bcm if pin <= 27 => Some(pin),
+            PinNumbering::Bcm => None,
+            PinNumbering::WiringPi => lookup(&WIRING_PI_TO_BCM),
+            PinNumbering::Physical => lookup(&PHYSICAL_TO_BCM),
+        }
+    }
+}
+
+// Translates every pin the config uses to its Broadcom number in place, and
+// fails if any of them isn't a GPIO pin, or if two things claim the same one.
+// Each pin comes with a description of what it's for, for the error message.
+// Reserved pins are already Broadcom numbers, e.g. the I2C bus's.
+pub fn resolve_pins(
+    numbering: PinNumbering,
+    reserved: Vec<(String, u16)>,
+    pins: Vec<(String, &mut u16)>,
+) -> Result<(), Error> {
+    let mut claimed: HashMap<u16, String> = reserved
+        .into_iter()
+        .map(|(owner, bcm)| (bcm, owner))
+        .collect();
+    for (owner, pin) in pins {
+        let bcm = numbering.to_bcm(*pin).ok_or_else(|| {
+            format_err!(
+                "{} is pin {}, which isn't a GPIO pin in {:?} numbering",
+                owner,
+                *pin,
+                numbering
+            )
+        })?;
+        if let Some(other_owner) = claimed.get(&bcm) {
+            return Err(format_err!(
+                "{} and {} are both set to pin {} (GPIO {})",
+                other_owner,
+                owner,
+                *pin,
+                bcm
+            ));
+        }
+        claimed.insert(bcm, owner);
+        *pin = bcm;
+    }
+    Ok(())
+}
+
+// WiringPi exits the whole program if it can't get at the pins, so this checks
+// up front that it will be able to. Only on the Pi itself; anywhere else the
+// pins are stubbed out by wiringpi's development feature.
+pub fn check_access() -> Result<(), io::Error> {
+    if cfg!(any(target_arch = "arm", target_arch = "aarch64")) {
+        let open = |path| OpenOptions::new().read(true).write(true).open(path);
+        // /dev/mem needs root, /dev/gpiomem only the gpio group
+        open("/dev/gpiomem").or_else(|_| open("/dev/mem"))?;
+    }
+    Ok(())
+}
+
+// WiringPi sets a pin's mode (and starts a PWM thread for it) when the pin is
+// created, so each pin is only created once, the first time it's used.
+struct WiringPiBackend {
+    pi: wiringpi::WiringPi<Gpio>,
+    pwm_pins: Mutex<HashMap<u16, SoftPwmPin<Gpio>>>,
+    output_pins: Mutex<HashMap<u16, OutputPin<Gpio>>>,
+    input_pins: Mutex<HashMap<u16, InputPin<Gpio>>>,
+}
+
+impl WiringPiBackend {
+    fn new() -> WiringPiBackend {
+        WiringPiBackend {
+            pi: wiringpi::setup_gpio(),
+            pwm_pins: Mutex::new(HashMap::new()),
+            output_pins: Mutex::new(HashMap::new()),
+            input_pins: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl GpioBackend for WiringPiBackend {
+    fn pwm_write(&self, pin: u16, value: i32) {
+        if let Ok(mut pins) = self.pwm_pins.lock() {
+            let pi = &self.pi;
+            pins.entry(pin)
+                .or_insert_with(|| pi.soft_pwm_pin(pin))
+                .pwm_write(value);
+        }
+    }
+
+    fn digital_write(&self, pin: u16, high: bool) {
+        if let Ok(mut pins) = self.output_pins.lock() {
+            let pi = &self.pi;
+            pins.entry(pin)
+                .or_insert_with(|| pi.output_pin(pin))
+                .digital_write(if high { Value::High } else { Value::Low });
+        }
+    }
+
+    fn digital_read(&self, pin: u16) -> bool {
+        match self.input_pins.lock() {
+            Ok(mut pins) => {
+                let pi = &self.pi;
+                let input = pins.entry(pin).or_insert_with(|| {
+                    let input = pi.input_pin(pin);
+                    input.pull_up_dn_control(Pull::Up);
+                    input
+                });
+                input.digital_read() == Value::High
+            }
+            // Pulled up, so that's what an untouched pin reads
+            Err(_) => true,
+        }
+    }
+}
+
+// Records every write instead of touching any pins, and reads back whatever
+// the test set the inputs to.
+#[cfg(test)]
+pub struct FakeGpio {
+    pub pwm_writes: Mutex<Vec<(u16, i32)>>,
+    pub digital_writes: Mutex<Vec<(u16, bool)>>,
+    pub inputs: Mutex<HashMap<u16, bool>>,
+}
+
+#[cfg(test)]
+impl FakeGpio {
+    pub fn new() -> Arc<FakeGpio> {
+        Arc::new(FakeGpio {
+            pwm_writes: Mutex::new(Vec::new()),
+            digital_writes: Mutex::new(Vec::new()),
+            inputs: Mutex::new(HashMap::new()),
+        })
+    }
+
+    // The latest value written to each of the given pins.
+    pub fn pwm_values(&self, pins: &[u16]) -> Vec<Option<i32>> {
+        let writes = self.pwm_writes.lock().unwrap();
+        pins.iter()
+            .map(|pin| {
+                writes
+                    .iter()
+                    .rev()
+                    .find(|&&(written, _)| written == *pin)
+                    .map(|&(_, value)| value)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+impl GpioBackend for FakeGpio {
+    fn pwm_write(&self, pin: u16, value: i32) {
+        self.pwm_writes.lock().unwrap().push((pin, value));
+    }
+
+    fn digital_write(&self, pin: u16, high: bool) {
+        self.digital_writes.lock().unwrap().push((pin, high));
+    }
+
+    fn digital_read(&self, pin: u16) -> bool {
+        *self.inputs.lock().unwrap().get(&pin).unwrap_or(&true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pins_are_translated_to_broadcom_numbers() {
+        assert_eq!(PinNumbering::Bcm.to_bcm(17), Some(17));
+        assert_eq!(PinNumbering::Bcm.to_bcm(40), None);
+        assert_eq!(PinNumbering::WiringPi.to_bcm(0), Some(17));
+        assert_eq!(PinNumbering::Physical.to_bcm(11), Some(17));
+        // Ground
+        assert_eq!(PinNumbering::Physical.to_bcm(6), None);
+    }
+
+    #[test]
+    fn shared_pins_are_refused() {
+        let (mut led, mut button) = (11, 12);
+        resolve_pins(
+            PinNumbering::Physical,
+            Vec::new(),
+            vec![
+                ("led".to_string(), &mut led),
+                ("button".to_string(), &mut button),
+            ],
+        )
+        .unwrap();
+        assert_eq!((led, button), (17, 18));
+
+        let (mut led, mut buzzer) = (17, 2);
+        let error = resolve_pins(
+            PinNumbering::Bcm,
+            vec![("[oled] (I2C SDA)".to_string(), 2)],
+            vec![
+                ("led".to_string(), &mut led),
+                ("buzzer".to_string(), &mut buzzer),
+            ],
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "[oled] (I2C SDA) and buzzer are both set to pin 2 (GPIO 2)"
+        );
+    }
+}
@@ -1,5 +1,128 @@
+use base64;
+use chrono::{DateTime, TimeZone, Utc};
+use std::slice;
+use std::str::FromStr;
+use std::time::SystemTime;
+
+pub const AUTHORIZATION: &str = "Authorization";
+
 // requests left in window
-header! {(XRateLimitRemaining, "X-RateLimit-Remaining") => [u32]}
+pub const X_RATE_LIMIT_REMAINING: &str = "X-RateLimit-Remaining";
 
 // when window will reset in epoch seconds
-header! {(XRateLimitReset, "X-RateLimit-Reset") => [u64]}
+pub const X_RATE_LIMIT_RESET: &str = "X-RateLimit-Reset";
+
+// how many results a search matched, beyond the ones on this page (Sentry)
+pub const X_HITS: &str = "X-Hits";
+
+// Request or response headers as plain strings, in the order they came.
+// Names are matched case-insensitively. Only network.rs turns these into the
+// HTTP client's own header types, and back.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Headers {
+    fields: Vec<(String, String)>,
+}
+
+impl Headers {
+    pub fn new() -> Headers {
+        Headers { fields: Vec::new() }
+    }
+
+    // Replaces whatever values name had.
+    pub fn set<N: Into<String>, V: Into<String>>(&mut self, name: N, value: V) {
+        let name = name.into();
+        self.remove(&name);
+        self.fields.push((name, value.into()));
+    }
+
+    // Adds another value for name, e.g. a second Set-Cookie.
+    pub fn append<N: Into<String>, V: Into<String>>(&mut self, name: N, value: V) {
+        self.fields.push((name.into(), value.into()));
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        self.fields
+            .retain(|&(ref field, _)| !field.eq_ignore_ascii_case(name));
+    }
+
+    // The first value of name.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|&&(ref field, _)| field.eq_ignore_ascii_case(name))
+            .map(|&(_, ref value)| value.as_str())
+    }
+
+    pub fn get_parsed<T: FromStr>(&self, name: &str) -> Option<T> {
+        self.get(name).and_then(|value| value.trim().parse().ok())
+    }
+
+    pub fn iter(&self) -> slice::Iter<(String, String)> {
+        self.fields.iter()
+    }
+}
+
+// An Authorization value for a username and password, which may be left out.
+pub fn basic_auth(username: &str, password: Option<&str>) -> String {
+    let credentials = format!("{}:{}", username, password.unwrap_or(""));
+    format!("Basic {}", base64::encode(credentials.as_bytes()))
+}
+
+pub fn bearer_auth(token: &str) -> String {
+    format!("Bearer {}", token)
+}
+
+// A date as servers send it in Date, Last-Modified or Retry-After: the usual
+// "Sun, 06 Nov 1994 08:49:37 GMT", or one of the two obsolete formats.
+pub fn parse_http_date(value: &str) -> Option<SystemTime> {
+    let value = value.trim();
+    [
+        "%a, %d %b %Y %H:%M:%S GMT",
+        "%A, %d-%b-%y %H:%M:%S GMT",
+        "%a %b %e %H:%M:%S %Y",
+    ]
+    .iter()
+    .filter_map(|format| Utc.datetime_from_str(value, format).ok())
+    .next()
+    .map(SystemTime::from)
+}
+
+pub fn format_http_date(time: SystemTime) -> String {
+    let time: DateTime<Utc> = time.into();
+    time.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn headers_are_plain_strings_matched_in_any_case() {
+        let mut headers = Headers::new();
+        headers.set("X-RateLimit-Remaining", " 42 ");
+        headers.append("Set-Cookie", "a=1");
+        headers.append("set-cookie", "b=2");
+        headers.set("x-ratelimit-remaining", "41");
+
+        assert_eq!(headers.get_parsed::<u32>(X_RATE_LIMIT_REMAINING), Some(41));
+        assert_eq!(headers.get("SET-COOKIE"), Some("a=1"));
+        assert_eq!(headers.iter().count(), 3);
+        assert_eq!(headers.get_parsed::<u64>(X_HITS), None);
+        assert_eq!(
+            basic_auth("Aladdin", Some("open sesame")),
+            "Basic QWxhZGRpbjpvcGVuIHNlc2FtZQ=="
+        );
+
+        let date = UNIX_EPOCH + Duration::from_secs(784_111_777);
+        assert_eq!(format_http_date(date), "Sun, 06 Nov 1994 08:49:37 GMT");
+        for value in &[
+            "Sun, 06 Nov 1994 08:49:37 GMT",
+            "Sunday, 06-Nov-94 08:49:37 GMT",
+            "Sun Nov  6 08:49:37 1994",
+        ] {
+            assert_eq!(parse_http_date(value), Some(date), "{}", value);
+        }
+        assert_eq!(parse_http_date("yesterday"), None);
+    }
+}
@@ -0,0 +1,240 @@
+use chrono::{DateTime, Local};
+use config_file::HealthConfig;
+use scheduler::{Tick, TickScheduler};
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use systemd;
+
+const ACCEPT_POLL_MILLIS: u64 = 200;
+// How many of a thread's most recent poll errors are kept
+const ERROR_BACKLOG: usize = 10;
+
+struct ThreadHealth {
+    last_heartbeat: Instant,
+    last_successful_poll: Option<DateTime<Local>>,
+    // What its LED is showing, for threads that have one
+    led: Option<String>,
+    recent_errors: VecDeque<(DateTime<Local>, String)>,
+    // How often the supervisor has restarted it, and when it last did
+    restarts: u32,
+    last_restart: Option<DateTime<Local>>,
+}
+
+// A copy of what's known about one thread, for the status dump.
+#[derive(Clone, Debug)]
+pub struct ThreadState {
+    pub name: String,
+    pub last_successful_poll: Option<DateTime<Local>>,
+    pub led: Option<String>,
+    pub recent_errors: Vec<(DateTime<Local>, String)>,
+    pub restarts: u32,
+    pub last_restart: Option<DateTime<Local>>,
+}
+
+// Liveness of every worker thread. Threads check in once per loop, so one
+// that hangs (e.g. on a request that never returns) stops checking in and
+// shows up as stale.
+#[derive(Clone)]
+pub struct Health {
+    threads: Arc<Mutex<BTreeMap<String, ThreadHealth>>>,
+}
+
+impl Health {
+    pub fn new() -> Health {
+        Health {
+            threads: Arc::new(Mutex::new(BTreeMap::new())),
+        }
+    }
+
+    pub fn heartbeat(&self, thread_name: &str) {
+        self.update(thread_name, |thread| thread.last_heartbeat = Instant::now());
+    }
+
+    pub fn poll_succeeded(&self, thread_name: &str) {
+        self.update(thread_name, |thread| {
+            thread.last_heartbeat = Instant::now();
+            thread.last_successful_poll = Some(Local::now());
+        });
+    }
+
+    pub fn poll_failed(&self, thread_name: &str, error: &str) {
+        self.update(thread_name, |thread| {
+            thread.last_heartbeat = Instant::now();
+            if thread.recent_errors.len() == ERROR_BACKLOG {
+                thread.recent_errors.pop_front();
+            }
+            thread
+                .recent_errors
+                .push_back((Local::now(), error.to_string()));
+        });
+    }
+
+    pub fn led_shown(&self, thread_name: &str, led: &str) {
+        self.update(thread_name, |thread| thread.led = Some(led.to_string()));
+    }
+
+    pub fn restarted(&self, thread_name: &str) {
+        self.update(thread_name, |thread| {
+            thread.restarts += 1;
+            thread.last_restart = Some(Local::now());
+        });
+    }
+
+    pub fn thread_states(&self) -> Vec<ThreadState> {
+        match self.threads.lock() {
+            Ok(threads) => threads
+                .iter()
+                .map(|(name, thread)| ThreadState {
+                    name: name.clone(),
+                    last_successful_poll: thread.last_successful_poll,
+                    led: thread.led.clone(),
+                    recent_errors: thread.recent_errors.iter().cloned().collect(),
+                    restarts: thread.restarts,
+                    last_restart: thread.last_restart,
+                })
+                .collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    fn update<F: FnOnce(&mut ThreadHealth)>(&self, thread_name: &str, change: F) {
+        if let Ok(mut threads) = self.threads.lock() {
+            change(
+                threads
+                    .entry(thread_name.to_string())
+                    .or_insert_with(|| ThreadHealth {
+                        last_heartbeat: Instant::now(),
+                        last_successful_poll: None,
+                        led: None,
+                        recent_errors: VecDeque::new(),
+                        restarts: 0,
+                        last_restart: None,
+                    }),
+            );
+        }
+    }
+
+    fn is_healthy(&self, stale_after: Duration) -> bool {
+        match self.threads.lock() {
+            Ok(threads) => threads
+                .values()
+                .all(|thread| thread.last_heartbeat.elapsed() < stale_after),
+            Err(_) => false,
+        }
+    }
+
+    fn to_json(&self, stale_after: Duration) -> String {
+        let mut report = json!({});
+        if let Ok(threads) = self.threads.lock() {
+            for (name, thread) in threads.iter() {
+                report[name] = json!({
+                    "alive": thread.last_heartbeat.elapsed() < stale_after,
+                    "seconds_since_heartbeat": thread.last_heartbeat.elapsed().as_secs(),
+                    "last_successful_poll": thread.last_successful_poll.map(|time| time.to_rfc3339()),
+                    "restarts": thread.restarts,
+                    "last_restart": thread.last_restart.map(|time| time.to_rfc3339()),
+                });
+            }
+        }
+        json!({
+            "healthy": self.is_healthy(stale_after),
+            "threads": report,
+        })
+        .to_string()
+    }
+}
+
+// Serves /healthz if a listen address is configured, and keeps petting
+// systemd's watchdog for as long as every thread is healthy.
+pub fn run_health(config: HealthConfig, health: Health, scheduler: TickScheduler) {
+    let stale_after = Duration::from_secs(config.stale_after_seconds);
+    let listener = match config.listen {
+        Some(ref address) => match TcpListener::bind(address.as_str())
+            .and_then(|listener| listener.set_nonblocking(true).map(|_| listener))
+        {
+            Ok(listener) => {
+                info!("--Health--: Serving /healthz on {}", address);
+                Some(listener)
+            }
+            Err(e) => {
+                error!(
+                    "--Health--: Failed to listen on {}. /healthz disabled. Error: {}",
+                    address, e
+                );
+                None
+            }
+        },
+        None => None,
+    };
+    // systemd recommends pinging at half the watchdog interval
+    let watchdog_interval = if config.systemd_watchdog {
+        systemd::watchdog_interval_micros().map(|usec| Duration::from_millis(usec / 2000))
+    } else {
+        None
+    };
+    if listener.is_none() && watchdog_interval.is_none() {
+        return;
+    }
+
+    let mut last_watchdog_ping: Option<Instant> = None;
+    let mut ticker = scheduler.ticker();
+    loop {
+        if let Some(interval) = watchdog_interval {
+            if last_watchdog_ping.map_or(true, |ping| ping.elapsed() >= interval) {
+                if health.is_healthy(stale_after) {
+                    systemd::notify("WATCHDOG=1");
+                    last_watchdog_ping = Some(Instant::now());
+                } else {
+                    warn!("--Health--: A thread is stale, letting systemd's watchdog run out.");
+                }
+            }
+        }
+        if let Some(ref listener) = listener {
+            loop {
+                match listener.accept() {
+                    Ok((stream, _)) => respond(stream, &health, stale_after),
+                    Err(ref e) if e.kind() == ErrorKind::WouldBlock => break,
+                    Err(e) => {
+                        warn!("--Health--: Failed to accept a connection. Error: {}", e);
+                        break;
+                    }
+                }
+            }
+        }
+        if ticker.wait(Duration::from_millis(ACCEPT_POLL_MILLIS)) == Tick::Shutdown {
+            break;
+        }
+    }
+}
+
+fn respond(mut stream: TcpStream, health: &Health, stale_after: Duration) {
+    // Only the request line matters, so one read is enough
+    let mut request = [0u8; 1024];
+    let _ = stream.set_nonblocking(false);
+    let _ = stream.set_read_timeout(Some(Duration::from_secs(2)));
+    let read = stream.read(&mut request).unwrap_or(0);
+    let request = String::from_utf8_lossy(&request[..read]);
+    let path = request.split_whitespace().nth(1).unwrap_or("");
+
+    let (status_line, body) = if path == "/healthz" {
+        if health.is_healthy(stale_after) {
+            ("200 OK", health.to_json(stale_after))
+        } else {
+            ("503 Service Unavailable", health.to_json(stale_after))
+        }
+    } else {
+        ("404 Not Found", json!({ "error": "not found" }).to_string())
+    };
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status_line,
+        body.len(),
+        body
+    );
+    if let Err(e) = stream.write_all(response.as_bytes()) {
+        warn!("--Health--: Failed to respond to a request. Error: {}", e);
+    }
+}
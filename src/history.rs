@@ -0,0 +1,138 @@
+use config_file::FlakinessConfig;
+use serde_json;
+use std::collections::{BTreeMap, VecDeque};
+use std::fs::File;
+use std::path::PathBuf;
+
+// One finished build of a job.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+struct BuildResult {
+    number: u32,
+    passed: bool,
+}
+
+// Rolling window of the last few results of every job, used to spot flaky jobs:
+// ones that keep flipping between passing and failing. Optionally kept on disk,
+// so restarting the build light doesn't forget them.
+pub struct JobHistory {
+    window: usize,
+    min_flips: usize,
+    path: Option<PathBuf>,
+    // Oldest first
+    jobs: BTreeMap<String, VecDeque<BuildResult>>,
+    flaky_jobs: Vec<String>,
+}
+
+impl JobHistory {
+    pub fn new(config: &FlakinessConfig) -> JobHistory {
+        let path = config.history_file.as_ref().map(PathBuf::from);
+        let jobs = path
+            .as_ref()
+            .and_then(|path| match File::open(path) {
+                Ok(file) => serde_json::from_reader(file)
+                    .map_err(|err| {
+                        warn!(
+                            "Couldn't read job history from {:?}. Starting over. Error: {}",
+                            path, err
+                        )
+                    })
+                    .ok(),
+                // Nothing saved yet
+                Err(_) => None,
+            })
+            .unwrap_or_default();
+        let mut history = JobHistory {
+            window: config.window.max(2),
+            min_flips: config.min_flips.max(1),
+            path: path,
+            jobs: jobs,
+            flaky_jobs: Vec::new(),
+        };
+        history.flaky_jobs = history.find_flaky_jobs();
+        history
+    }
+
+    // Records the latest finished build of each job, and returns the jobs that
+    // are currently flaky. Builds that were already recorded are skipped, since
+    // we see the same build on every poll until the next one finishes.
+    pub fn record(&mut self, builds: &[(String, u32, bool)]) -> Vec<String> {
+        let mut changed = false;
+        for &(ref job, number, passed) in builds {
+            let results = self.jobs.entry(job.clone()).or_insert_with(VecDeque::new);
+            if results.back().map_or(false, |last| last.number == number) {
+                continue;
+            }
+            results.push_back(BuildResult {
+                number: number,
+                passed: passed,
+            });
+            while results.len() > self.window {
+                results.pop_front();
+            }
+            changed = true;
+        }
+        if !changed {
+            return self.flaky_jobs.clone();
+        }
+
+        let flaky_jobs = self.find_flaky_jobs();
+        if flaky_jobs != self.flaky_jobs {
+            self.log_report(&flaky_jobs);
+        }
+        self.flaky_jobs = flaky_jobs;
+        self.save();
+        self.flaky_jobs.clone()
+    }
+
+    fn find_flaky_jobs(&self) -> Vec<String> {
+        self.jobs
+            .iter()
+            .filter(|&(_, results)| flips(results) >= self.min_flips)
+            .map(|(job, _)| job.clone())
+            .collect()
+    }
+
+    fn log_report(&self, flaky_jobs: &[String]) {
+        if flaky_jobs.is_empty() {
+            info!("Flakiness report: no flaky jobs.");
+            return;
+        }
+        warn!("Flakiness report: {} flaky job(s).", flaky_jobs.len());
+        for job in flaky_jobs {
+            let results = &self.jobs[job];
+            let timeline: String = results
+                .iter()
+                .map(|result| if result.passed { '+' } else { '-' })
+                .collect();
+            warn!(
+                "  {}: flipped {} times in the last {} builds ({}, oldest first)",
+                job,
+                flips(results),
+                results.len(),
+                timeline
+            );
+        }
+    }
+
+    fn save(&self) {
+        if let Some(ref path) = self.path {
+            let result = File::create(path)
+                .map_err(|err| err.to_string())
+                .and_then(|file| {
+                    serde_json::to_writer(file, &self.jobs).map_err(|err| err.to_string())
+                });
+            if let Err(err) = result {
+                warn!("Couldn't save job history to {:?}. Error: {}", path, err);
+            }
+        }
+    }
+}
+
+// How many times a job went from passing to failing or back.
+fn flips(results: &VecDeque<BuildResult>) -> usize {
+    results
+        .iter()
+        .zip(results.iter().skip(1))
+        .filter(|&(a, b)| a.passed != b.passed)
+        .count()
+}
@@ -0,0 +1,120 @@
+use remote_status::RemoteStatus;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum JobState {
+    Passing,
+    Failing,
+    InProgress,
+    // Aborted, not built, couldn't be retrieved...
+    Indeterminate,
+}
+
+impl JobState {
+    // What a job in this state shows on its own.
+    pub fn status(&self) -> RemoteStatus {
+        match *self {
+            JobState::Passing => RemoteStatus::Passing,
+            JobState::Failing => RemoteStatus::Failing,
+            JobState::InProgress => RemoteStatus::InProgress,
+            JobState::Indeterminate => RemoteStatus::Unknown,
+        }
+    }
+}
+
+// The latest build of one job or build target.
+#[derive(Copy, Clone, Debug)]
+pub struct JobOutcome {
+    pub state: JobState,
+    // Milliseconds since the epoch, if the server told us
+    pub started_at: Option<i64>,
+}
+
+// Decides what an integration's LED shows, given the latest build of each of its jobs.
+pub trait AggregationStrategy: Send {
+    fn aggregate(&self, jobs: &[JobOutcome]) -> RemoteStatus;
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum AggregationKind {
+    AnyFailureIsRed,
+    Majority,
+    LatestOnly,
+}
+
+impl Default for AggregationKind {
+    fn default() -> AggregationKind {
+        AggregationKind::AnyFailureIsRed
+    }
+}
+
+impl AggregationKind {
+    pub fn strategy(&self) -> Box<AggregationStrategy> {
+        match *self {
+            AggregationKind::AnyFailureIsRed => Box::new(AnyFailureIsRed),
+            AggregationKind::Majority => Box::new(Majority),
+            AggregationKind::LatestOnly => Box::new(LatestOnly),
+        }
+    }
+}
+
+fn count(jobs: &[JobOutcome], state: JobState) -> usize {
+    jobs.iter().filter(|job| job.state == state).count()
+}
+
+// Any failing job turns the LED red. Otherwise anything building shows as
+// building, and a single passing job is enough to show passing.
+pub struct AnyFailureIsRed;
+
+impl AggregationStrategy for AnyFailureIsRed {
+    fn aggregate(&self, jobs: &[JobOutcome]) -> RemoteStatus {
+        if count(jobs, JobState::Failing) > 0 {
+            RemoteStatus::Failing
+        } else if count(jobs, JobState::InProgress) > 0 {
+            RemoteStatus::InProgress
+        } else if count(jobs, JobState::Passing) > 0 {
+            RemoteStatus::Passing
+        } else {
+            RemoteStatus::Unknown
+        }
+    }
+}
+
+// Failing only when more jobs fail than pass, and passing only when passing
+// jobs outnumber both failing and indeterminate ones.
+pub struct Majority;
+
+impl AggregationStrategy for Majority {
+    fn aggregate(&self, jobs: &[JobOutcome]) -> RemoteStatus {
+        let passing = count(jobs, JobState::Passing);
+        let failing = count(jobs, JobState::Failing);
+        let indeterminate = count(jobs, JobState::Indeterminate);
+        if failing > passing {
+            RemoteStatus::Failing
+        } else if failing == 0 && count(jobs, JobState::InProgress) > 0 {
+            RemoteStatus::InProgress
+        } else if passing > failing && passing > indeterminate {
+            RemoteStatus::Passing
+        } else {
+            RemoteStatus::Unknown
+        }
+    }
+}
+
+// Only the most recently started build counts, whichever job it belongs to.
+pub struct LatestOnly;
+
+impl AggregationStrategy for LatestOnly {
+    fn aggregate(&self, jobs: &[JobOutcome]) -> RemoteStatus {
+        let latest = jobs
+            .iter()
+            .filter(|job| job.started_at.is_some())
+            .max_by_key(|job| job.started_at);
+        match latest.map(|job| job.state) {
+            Some(JobState::Passing) => RemoteStatus::Passing,
+            Some(JobState::Failing) => RemoteStatus::Failing,
+            Some(JobState::InProgress) => RemoteStatus::InProgress,
+            Some(JobState::Indeterminate) | None => RemoteStatus::Unknown,
+        }
+    }
+}
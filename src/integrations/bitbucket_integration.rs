@@ -0,0 +1,334 @@
+use budget::RequestBudget;
+use chrono::DateTime;
+use credentials::Credentials;
+use failure::Error;
+use headers::{Headers, AUTHORIZATION};
+use integrations::aggregation::{AggregationStrategy, JobOutcome, JobState};
+use integrations::bitbucket_response::*;
+use integrations::remote_integration::RemoteIntegration;
+use network::{self, get_basic_credentials, get_url_response, HttpClient};
+use remote_status::{JobStatus, RemoteStatus, StatusSummary};
+use reqwest::Url;
+use std::time::Duration;
+
+// The most Bitbucket gives on one page. Branches that haven't had a pipeline
+// among the latest 100 are left out.
+const PIPELINES_PER_PAGE: usize = 100;
+
+// The latest pipeline of a branch.
+struct BranchPipeline {
+    branch: String,
+    build_number: u64,
+    state: JobState,
+    stopped: bool,
+    // Milliseconds since the epoch, if Bitbucket said
+    created_at: Option<i64>,
+}
+
+// Bitbucket Cloud Pipelines of one repository. Each branch's latest pipeline
+// counts as a job, like a Jenkins job's last build.
+pub struct BitbucketIntegration {
+    r: u16,
+    g: u16,
+    b: u16,
+    name: String,
+    api_url: String,
+    workspace: String,
+    repository: String,
+    // Empty for every branch
+    branches: Vec<String>,
+    username: String,
+    app_password: String,
+    client: HttpClient,
+    last_summary: StatusSummary,
+    aggregation: Box<AggregationStrategy>,
+    // Checked for a reloaded login before every poll, if set
+    credentials: Option<Credentials>,
+}
+
+impl BitbucketIntegration {
+    pub fn new(
+        r: u16,
+        g: u16,
+        b: u16,
+        name: &str,
+        api_url: &str,
+        workspace: &str,
+        repository: &str,
+        branches: Vec<String>,
+        username: &str,
+        app_password: &str,
+        client: HttpClient,
+        aggregation: Box<AggregationStrategy>,
+    ) -> BitbucketIntegration {
+        BitbucketIntegration {
+            r: r,
+            g: g,
+            b: b,
+            name: name.to_string(),
+            api_url: api_url.trim_end_matches('/').to_string(),
+            workspace: workspace.to_string(),
+            repository: repository.to_string(),
+            branches: branches,
+            username: username.to_string(),
+            app_password: app_password.to_string(),
+            client: client,
+            last_summary: StatusSummary::default(),
+            aggregation: aggregation,
+            credentials: None,
+        }
+    }
+
+    // Picks up a login reloaded into credentials, see CredentialReloader.
+    pub fn set_credentials(&mut self, credentials: Credentials) {
+        self.credentials = Some(credentials);
+    }
+
+    fn headers(&self) -> Headers {
+        let mut headers = Headers::new();
+        headers.set(
+            AUTHORIZATION,
+            get_basic_credentials(&self.username, Some(self.app_password.clone())),
+        );
+        headers
+    }
+
+    // Newest first. With just one branch Bitbucket can do the filtering, so
+    // a busy repository's other branches don't push it off the page.
+    fn pipelines_url(&self) -> Result<String, Error> {
+        let mut url = Url::parse(&format!(
+            "{}/repositories/{}/{}/pipelines/",
+            self.api_url, self.workspace, self.repository
+        ))?;
+        url.query_pairs_mut()
+            .append_pair("sort", "-created_on")
+            .append_pair("pagelen", &PIPELINES_PER_PAGE.to_string());
+        if self.branches.len() == 1 {
+            url.query_pairs_mut()
+                .append_pair("target.branch", &self.branches[0]);
+        }
+        Ok(url.to_string())
+    }
+
+    fn get_branch_pipelines(&self) -> Result<Vec<BranchPipeline>, Error> {
+        let (pipelines, _): (BitbucketPipelines, Headers) =
+            get_url_response(&self.client, &self.pipelines_url()?, self.headers())?;
+        let mut latest: Vec<BranchPipeline> = Vec::new();
+        for pipeline in pipelines.values {
+            let branch = match (pipeline.target.ref_type, pipeline.target.ref_name) {
+                (Some(ref ref_type), Some(ref_name)) if ref_type == "branch" => ref_name,
+                _ => continue,
+            };
+            let wanted = self.branches.is_empty() || self.branches.contains(&branch);
+            if !wanted || latest.iter().any(|seen| seen.branch == branch) {
+                continue;
+            }
+            let (state, stopped) = pipeline_state(
+                &pipeline.state.name,
+                pipeline.state.result.as_ref().map(|r| r.name.as_str()),
+            );
+            latest.push(BranchPipeline {
+                branch: branch,
+                build_number: pipeline.build_number,
+                state: state,
+                stopped: stopped,
+                created_at: pipeline
+                    .created_on
+                    .as_ref()
+                    .and_then(|time| DateTime::parse_from_rfc3339(time).ok())
+                    .map(|time| time.timestamp() * 1000),
+            });
+        }
+        for branch in &self.branches {
+            if !latest.iter().any(|seen| &seen.branch == branch) {
+                warn!(
+                    "--Bitbucket--: No recent pipelines on {} in {}/{}.",
+                    branch, self.workspace, self.repository
+                );
+            }
+        }
+        Ok(latest)
+    }
+}
+
+// Anything that hasn't completed is in progress, including pipelines paused
+// at a manual step. Stopped pipelines are counted apart, like aborted Jenkins
+// builds.
+fn pipeline_state(state: &str, result: Option<&str>) -> (JobState, bool) {
+    match (state, result) {
+        ("COMPLETED", Some("SUCCESSFUL")) => (JobState::Passing, false),
+        ("COMPLETED", Some("FAILED")) | ("COMPLETED", Some("ERROR")) => (JobState::Failing, false),
+        ("COMPLETED", Some("STOPPED")) => (JobState::Indeterminate, true),
+        ("COMPLETED", _) => (JobState::Indeterminate, false),
+        _ => (JobState::InProgress, false),
+    }
+}
+
+impl RemoteIntegration for BitbucketIntegration {
+    fn get_red_id(&self) -> u16 {
+        self.r
+    }
+    fn get_green_id(&self) -> u16 {
+        self.g
+    }
+    fn get_blue_id(&self) -> u16 {
+        self.b
+    }
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+    fn get_summary(&self) -> StatusSummary {
+        self.last_summary.clone()
+    }
+    fn get_rate_limit_delay(&self) -> Option<Duration> {
+        self.client.rate_limited_for()
+    }
+    fn get_request_budget(&self) -> Option<RequestBudget> {
+        self.client.request_budget()
+    }
+
+    fn get_status(&mut self) -> RemoteStatus {
+        if let Some(login) = self.credentials.as_ref().and_then(|c| c.login()) {
+            let app_password = login.secret.unwrap_or_default();
+            if login.username != self.username || app_password != self.app_password {
+                info!("--Bitbucket--: Switching to the reloaded credentials.");
+                self.username = login.username;
+                self.app_password = app_password;
+            }
+        }
+        self.client.start_poll();
+        let pipelines = match self.get_branch_pipelines() {
+            Ok(pipelines) => pipelines,
+            Err(err) => {
+                warn!(
+                    "--Bitbucket--: Failed to get the pipelines of {}/{}. Error: {}",
+                    self.workspace, self.repository, err
+                );
+                self.last_summary = if network::is_unreachable(&err) {
+                    StatusSummary::disconnected(err.to_string())
+                } else {
+                    StatusSummary {
+                        error: Some(err.to_string()),
+                        ..StatusSummary::default()
+                    }
+                };
+                return RemoteStatus::Unknown;
+            }
+        };
+        let outcomes: Vec<JobOutcome> = pipelines
+            .iter()
+            .map(|pipeline| JobOutcome {
+                state: pipeline.state,
+                started_at: pipeline.created_at,
+            })
+            .collect();
+        let status = self.aggregation.aggregate(&outcomes);
+
+        let count = |state: JobState| {
+            pipelines
+                .iter()
+                .filter(|pipeline| pipeline.state == state && !pipeline.stopped)
+                .count()
+        };
+        let failing: Vec<&BranchPipeline> = pipelines
+            .iter()
+            .filter(|pipeline| pipeline.state == JobState::Failing)
+            .collect();
+        let failed_builds: Vec<String> = failing
+            .iter()
+            .map(|pipeline| format!("{} #{}", pipeline.branch, pipeline.build_number))
+            .collect();
+        for build in &failed_builds {
+            warn!("--Bitbucket--: {} failed.", build);
+        }
+        self.last_summary = StatusSummary {
+            passing: count(JobState::Passing),
+            failing: failing.len(),
+            in_progress: count(JobState::InProgress),
+            aborted: pipelines.iter().filter(|pipeline| pipeline.stopped).count(),
+            indeterminate: count(JobState::Indeterminate),
+            failing_jobs: failing
+                .iter()
+                .map(|pipeline| pipeline.branch.clone())
+                .collect(),
+            failed_builds: failed_builds,
+            jobs: pipelines
+                .iter()
+                .map(|pipeline| JobStatus {
+                    name: pipeline.branch.clone(),
+                    status: pipeline.state.status(),
+                })
+                .collect(),
+            ..StatusSummary::default()
+        };
+        info!(
+            "--Bitbucket--: {}/{}: {}.",
+            self.workspace,
+            self.repository,
+            self.last_summary.describe()
+        );
+        status
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use integrations::aggregation::AggregationKind;
+    use test_support::{fixture, MockServer};
+
+    fn bitbucket(server: &MockServer, branches: &[&str]) -> BitbucketIntegration {
+        BitbucketIntegration::new(
+            0,
+            0,
+            0,
+            "Bitbucket futurice/build-light",
+            &format!("{}/2.0/", server.base_url),
+            "futurice",
+            "build-light",
+            branches.iter().map(|branch| branch.to_string()).collect(),
+            "builder",
+            "app-password",
+            server.client(),
+            AggregationKind::AnyFailureIsRed.strategy(),
+        )
+    }
+
+    #[test]
+    fn each_branch_counts_its_latest_pipeline() {
+        let server = MockServer::start();
+        server.on(
+            "/2.0/repositories/futurice/build-light/pipelines/",
+            200,
+            &fixture("bitbucket_pipelines.json"),
+        );
+
+        // Tags don't count, and master's older success doesn't hide its failure
+        let mut all = bitbucket(&server, &[]);
+        assert_eq!(all.get_status(), RemoteStatus::Failing);
+        let summary = all.get_summary();
+        assert_eq!(
+            (
+                summary.passing,
+                summary.failing,
+                summary.in_progress,
+                summary.aborted
+            ),
+            (0, 1, 1, 1)
+        );
+        assert_eq!(summary.failed_builds, vec!["master #44".to_string()]);
+        assert!(server.requests()[0].contains("sort=-created_on"));
+        assert!(server.requests()[0].contains("Authorization: Basic"));
+
+        let mut filtered = bitbucket(&server, &["develop", "feature/leds"]);
+        filtered.get_status();
+        let summary = filtered.get_summary();
+        assert_eq!(
+            (summary.failing, summary.in_progress, summary.aborted),
+            (0, 1, 1)
+        );
+
+        bitbucket(&server, &["master"]).get_status();
+        assert!(server.requests()[2].contains("target.branch=master"));
+    }
+}
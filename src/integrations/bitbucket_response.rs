@@ -0,0 +1,36 @@
+// From 2.0/repositories/{workspace}/{repository}/pipelines/
+#[derive(Deserialize)]
+pub struct BitbucketPipelines {
+    pub values: Vec<BitbucketPipeline>,
+}
+
+#[derive(Deserialize)]
+pub struct BitbucketPipeline {
+    pub build_number: u64,
+    pub created_on: Option<String>,
+    pub state: BitbucketPipelineState,
+    pub target: BitbucketPipelineTarget,
+}
+
+// Names are left as strings, since Bitbucket adds new ones now and then, see
+// pipeline_state
+#[derive(Deserialize)]
+pub struct BitbucketPipelineState {
+    // "PENDING", "IN_PROGRESS" or "COMPLETED"
+    pub name: String,
+    // Only set once the pipeline has completed, e.g. "SUCCESSFUL" or "STOPPED"
+    pub result: Option<BitbucketPipelineResult>,
+}
+
+#[derive(Deserialize)]
+pub struct BitbucketPipelineResult {
+    pub name: String,
+}
+
+// Pipelines run for branches, tags, bookmarks and custom triggers. Only
+// branches have a ref_name worth showing
+#[derive(Deserialize)]
+pub struct BitbucketPipelineTarget {
+    pub ref_type: Option<String>,
+    pub ref_name: Option<String>,
+}
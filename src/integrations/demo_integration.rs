@@ -0,0 +1,176 @@
+use integrations::aggregation::{AggregationStrategy, JobOutcome, JobState};
+use integrations::remote_integration::RemoteIntegration;
+use remote_status::{JobStatus, RemoteStatus, StatusSummary};
+use std::time::{Duration, Instant};
+
+// How long each made-up state is shown before moving on to the next.
+const SCENE_SECONDS: u64 = 20;
+
+const JOB_NAMES: [&str; 3] = ["demo-api", "demo-app", "demo-web"];
+
+// Each scene's jobs, in the order of JOB_NAMES: all passing, some failing,
+// all failing, building, and unknown.
+const SCENES: [[JobState; 3]; 5] = [
+    [JobState::Passing, JobState::Passing, JobState::Passing],
+    [JobState::Passing, JobState::Failing, JobState::Passing],
+    [JobState::Failing, JobState::Failing, JobState::Failing],
+    [JobState::InProgress, JobState::Passing, JobState::Passing],
+    [
+        JobState::Indeterminate,
+        JobState::Indeterminate,
+        JobState::Indeterminate,
+    ],
+];
+
+// Stands in for a real integration in --demo mode, cycling through made-up
+// builds on a timer instead of polling a server. The builds still go through
+// the configured aggregation, so the LED shows what it would for real ones.
+pub struct DemoIntegration {
+    name: String,
+    r: u16,
+    g: u16,
+    b: u16,
+    strategy: Box<AggregationStrategy>,
+    started: Instant,
+    // Which scene to start with, so integrations don't all show the same thing
+    first_scene: usize,
+    last_summary: StatusSummary,
+}
+
+impl DemoIntegration {
+    pub fn new(
+        name: &str,
+        r: u16,
+        g: u16,
+        b: u16,
+        strategy: Box<AggregationStrategy>,
+        first_scene: usize,
+    ) -> DemoIntegration {
+        DemoIntegration {
+            name: name.to_string(),
+            r: r,
+            g: g,
+            b: b,
+            strategy: strategy,
+            started: Instant::now(),
+            first_scene: first_scene,
+            last_summary: StatusSummary::default(),
+        }
+    }
+
+    fn scene_at(&self, elapsed: Duration) -> usize {
+        (self.first_scene + (elapsed.as_secs() / SCENE_SECONDS) as usize) % SCENES.len()
+    }
+
+    fn show_scene(&mut self, scene: usize) -> RemoteStatus {
+        let states = &SCENES[scene];
+        let outcomes: Vec<JobOutcome> = states
+            .iter()
+            .map(|state| JobOutcome {
+                state: *state,
+                started_at: None,
+            })
+            .collect();
+        let count = |wanted: JobState| states.iter().filter(|&&state| state == wanted).count();
+        let jobs: Vec<JobStatus> = JOB_NAMES
+            .iter()
+            .zip(states.iter())
+            .map(|(name, state)| JobStatus {
+                name: name.to_string(),
+                status: state.status(),
+            })
+            .collect();
+        self.last_summary = StatusSummary {
+            passing: count(JobState::Passing),
+            failing: count(JobState::Failing),
+            in_progress: count(JobState::InProgress),
+            indeterminate: count(JobState::Indeterminate),
+            failing_jobs: jobs
+                .iter()
+                .filter(|job| job.status == RemoteStatus::Failing)
+                .map(|job| job.name.clone())
+                .collect(),
+            jobs: jobs,
+            ..StatusSummary::default()
+        };
+        self.strategy.aggregate(&outcomes)
+    }
+}
+
+impl RemoteIntegration for DemoIntegration {
+    fn get_red_id(&self) -> u16 {
+        self.r
+    }
+    fn get_green_id(&self) -> u16 {
+        self.g
+    }
+    fn get_blue_id(&self) -> u16 {
+        self.b
+    }
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+    fn get_summary(&self) -> StatusSummary {
+        self.last_summary.clone()
+    }
+    fn get_rate_limit_delay(&self) -> Option<Duration> {
+        None
+    }
+    fn should_back_off(&self) -> bool {
+        false
+    }
+
+    fn get_status(&mut self) -> RemoteStatus {
+        let scene = self.scene_at(self.started.elapsed());
+        self.show_scene(scene)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use integrations::aggregation::AggregationKind;
+
+    #[test]
+    fn scenes_cycle_through_every_status() {
+        let mut demo = DemoIntegration::new(
+            "Jenkins",
+            1,
+            2,
+            3,
+            AggregationKind::AnyFailureIsRed.strategy(),
+            0,
+        );
+        let statuses: Vec<RemoteStatus> = (0..SCENES.len())
+            .map(|scene| demo.show_scene(scene))
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                RemoteStatus::Passing,
+                RemoteStatus::Failing,
+                RemoteStatus::Failing,
+                RemoteStatus::InProgress,
+                RemoteStatus::Unknown,
+            ]
+        );
+        // Some failing, so the LED shows some_failing rather than all_failing
+        demo.show_scene(1);
+        assert_eq!(
+            demo.get_summary().failing_jobs,
+            vec!["demo-app".to_string()]
+        );
+        assert!(demo.get_summary().passing > 0);
+
+        let later = DemoIntegration::new(
+            "Unity Cloud",
+            4,
+            5,
+            6,
+            AggregationKind::AnyFailureIsRed.strategy(),
+            2,
+        );
+        assert_eq!(later.scene_at(Duration::from_secs(0)), 2);
+        assert_eq!(later.scene_at(Duration::from_secs(SCENE_SECONDS * 3)), 0);
+    }
+}
@@ -0,0 +1,234 @@
+use budget::RequestBudget;
+use failure::Error;
+use integrations::plugin_integration;
+use integrations::remote_integration::RemoteIntegration;
+use remote_status::{JobStatus, RemoteStatus, StatusSummary};
+use std::io::Read;
+use std::process::{Command, ExitStatus, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const WAIT_STEP_MILLIS: u64 = 100;
+
+// Runs a shell command every poll and shows what it prints: either a JSON
+// reply like a plugin's, or a line starting with a keyword like SUCCESS,
+// FAILURE or BUILDING. The rest of the line is kept as details. Printing
+// nothing at all goes by the exit status instead.
+pub struct ExecIntegration {
+    r: u16,
+    g: u16,
+    b: u16,
+    name: String,
+    command: String,
+    timeout: Duration,
+    last_summary: StatusSummary,
+}
+
+impl ExecIntegration {
+    pub fn new(
+        r: u16,
+        g: u16,
+        b: u16,
+        name: &str,
+        command: &str,
+        timeout: Duration,
+    ) -> ExecIntegration {
+        ExecIntegration {
+            r: r,
+            g: g,
+            b: b,
+            name: name.to_string(),
+            command: command.to_string(),
+            timeout: timeout,
+            last_summary: StatusSummary::default(),
+        }
+    }
+
+    // The command's output and exit status, killing it if it takes longer
+    // than timeout. Its stderr goes to the build light's.
+    fn run(&self) -> Result<(String, ExitStatus), Error> {
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(&self.command)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        // Read on the side, so a chatty command can't fill the pipe and block
+        let mut stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| format_err!("The command's output couldn't be read"))?;
+        let reader = thread::spawn(move || {
+            let mut output = String::new();
+            stdout.read_to_string(&mut output).map(|_| output)
+        });
+        let started = Instant::now();
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if started.elapsed() >= self.timeout {
+                child.kill()?;
+                child.wait()?;
+                return Err(format_err!(
+                    "Timed out after {} seconds",
+                    self.timeout.as_secs()
+                ));
+            }
+            thread::sleep(Duration::from_millis(WAIT_STEP_MILLIS));
+        };
+        let output = reader
+            .join()
+            .map_err(|_| format_err!("Reading the command's output failed"))??;
+        Ok((output, status))
+    }
+
+    fn read_output(
+        &self,
+        output: &str,
+        exit_status: ExitStatus,
+    ) -> Result<(RemoteStatus, StatusSummary), Error> {
+        let output = output.trim();
+        if output.starts_with('{') {
+            return plugin_integration::read_reply(output);
+        }
+        let line = output.lines().next().unwrap_or("");
+        let mut words = line.splitn(2, char::is_whitespace);
+        let keyword = words.next().unwrap_or("").to_uppercase();
+        let details = words.next().map_or("", |rest| rest.trim());
+        let mut warning = false;
+        let status = match keyword.as_str() {
+            "" if exit_status.success() => RemoteStatus::Passing,
+            "" => RemoteStatus::Failing,
+            "SUCCESS" | "PASSING" | "PASSED" | "OK" => RemoteStatus::Passing,
+            "UNSTABLE" | "WARNING" => {
+                warning = true;
+                RemoteStatus::Passing
+            }
+            "FAILURE" | "FAILING" | "FAILED" => RemoteStatus::Failing,
+            "BUILDING" | "RUNNING" | "IN_PROGRESS" => RemoteStatus::InProgress,
+            "UNKNOWN" => RemoteStatus::Unknown,
+            _ => return Err(format_err!("Unrecognized output \"{}\"", line)),
+        };
+
+        // The command is the one job
+        let failing = status == RemoteStatus::Failing;
+        let name = vec![self.name.clone()];
+        let summary = StatusSummary {
+            passing: if status == RemoteStatus::Passing {
+                1
+            } else {
+                0
+            },
+            failing: if failing { 1 } else { 0 },
+            in_progress: if status == RemoteStatus::InProgress {
+                1
+            } else {
+                0
+            },
+            indeterminate: if status == RemoteStatus::Unknown {
+                1
+            } else {
+                0
+            },
+            failing_jobs: if failing { name.clone() } else { Vec::new() },
+            warning_jobs: if warning { name } else { Vec::new() },
+            failed_builds: if failing && !details.is_empty() {
+                vec![details.to_string()]
+            } else {
+                Vec::new()
+            },
+            jobs: vec![JobStatus {
+                name: self.name.clone(),
+                status: status,
+            }],
+            ..StatusSummary::default()
+        };
+        Ok((status, summary))
+    }
+}
+
+impl RemoteIntegration for ExecIntegration {
+    fn get_red_id(&self) -> u16 {
+        self.r
+    }
+    fn get_green_id(&self) -> u16 {
+        self.g
+    }
+    fn get_blue_id(&self) -> u16 {
+        self.b
+    }
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+    fn get_summary(&self) -> StatusSummary {
+        self.last_summary.clone()
+    }
+    fn get_rate_limit_delay(&self) -> Option<Duration> {
+        None
+    }
+    fn get_request_budget(&self) -> Option<RequestBudget> {
+        None
+    }
+
+    fn get_status(&mut self) -> RemoteStatus {
+        let result = self
+            .run()
+            .and_then(|(output, exit_status)| self.read_output(&output, exit_status));
+        match result {
+            Ok((status, summary)) => {
+                self.last_summary = summary;
+                info!("--{}--: {}.", self.name, self.last_summary.describe());
+                status
+            }
+            Err(err) => {
+                warn!(
+                    "--{}--: Failed to run \"{}\". Error: {}",
+                    self.name, self.command, err
+                );
+                self.last_summary = StatusSummary {
+                    error: Some(err.to_string()),
+                    ..StatusSummary::default()
+                };
+                RemoteStatus::Unknown
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn command_output_becomes_a_status() {
+        let exec = |command: &str| {
+            let mut exec =
+                ExecIntegration::new(0, 0, 0, "Nightly", command, Duration::from_secs(5));
+            let status = exec.get_status();
+            (status, exec.get_summary())
+        };
+
+        let (status, summary) = exec("echo 'FAILURE nightly #12 https://ci.example.com/12'");
+        assert_eq!(status, RemoteStatus::Failing);
+        assert_eq!(summary.failing_jobs, vec!["Nightly".to_string()]);
+        assert_eq!(
+            summary.failed_builds,
+            vec!["nightly #12 https://ci.example.com/12".to_string()]
+        );
+        assert_eq!(exec("echo building").0, RemoteStatus::InProgress);
+        assert_eq!(exec("echo UNSTABLE").1.warning_jobs.len(), 1);
+        assert_eq!(
+            exec(r#"echo '{"jobs": [{"name": "lint", "status": "passing"}]}'"#).0,
+            RemoteStatus::Passing
+        );
+        // Nothing printed goes by the exit status
+        assert_eq!(exec("true").0, RemoteStatus::Passing);
+        assert_eq!(exec("exit 3").0, RemoteStatus::Failing);
+        assert!(exec("echo maybe").1.error.is_some());
+
+        let mut slow = ExecIntegration::new(0, 0, 0, "Slow", "sleep 5", Duration::from_secs(1));
+        assert_eq!(slow.get_status(), RemoteStatus::Unknown);
+        assert!(slow.get_summary().error.unwrap().contains("Timed out"));
+    }
+}
@@ -0,0 +1,326 @@
+use budget::RequestBudget;
+use chrono::DateTime;
+use credentials::Credentials;
+use failure::Error;
+use headers::{Headers, AUTHORIZATION};
+use integrations::aggregation::{AggregationStrategy, JobOutcome, JobState};
+use integrations::github_response::*;
+use integrations::remote_integration::RemoteIntegration;
+use network::{self, get_url_response, HttpClient};
+use pagination::get_all_pages;
+use remote_status::{JobStatus, RemoteStatus, StatusSummary};
+use std::time::Duration;
+
+// 500 check runs on one commit ought to be enough for anyone
+const MAX_CHECK_RUN_PAGES: usize = 5;
+
+// A commit status context or a check run, on the branch's latest commit.
+struct Check {
+    name: String,
+    state: JobState,
+    cancelled: bool,
+    url: Option<String>,
+    // Milliseconds since the epoch, if GitHub said
+    started_at: Option<i64>,
+}
+
+// Whatever reports to GitHub about a branch's latest commit: the commit
+// statuses external CI systems set, and the check runs of the Checks API,
+// which GitHub Actions and most CI apps use.
+pub struct GitHubIntegration {
+    r: u16,
+    g: u16,
+    b: u16,
+    name: String,
+    api_url: String,
+    // As "owner/repo"
+    repository: String,
+    branch: String,
+    token: Option<String>,
+    client: HttpClient,
+    last_summary: StatusSummary,
+    aggregation: Box<AggregationStrategy>,
+    // Checked for a reloaded login before every poll, if set
+    credentials: Option<Credentials>,
+}
+
+impl GitHubIntegration {
+    pub fn new(
+        r: u16,
+        g: u16,
+        b: u16,
+        name: &str,
+        api_url: &str,
+        repository: &str,
+        branch: &str,
+        token: Option<String>,
+        client: HttpClient,
+        aggregation: Box<AggregationStrategy>,
+    ) -> GitHubIntegration {
+        GitHubIntegration {
+            r: r,
+            g: g,
+            b: b,
+            name: name.to_string(),
+            api_url: api_url.trim_end_matches('/').to_string(),
+            repository: repository.to_string(),
+            branch: branch.to_string(),
+            token: token,
+            client: client,
+            last_summary: StatusSummary::default(),
+            aggregation: aggregation,
+            credentials: None,
+        }
+    }
+
+    // Picks up a token reloaded into credentials, see CredentialReloader.
+    pub fn set_credentials(&mut self, credentials: Credentials) {
+        self.credentials = Some(credentials);
+    }
+
+    fn headers(&self) -> Headers {
+        let mut headers = Headers::new();
+        headers.set("Accept", "application/vnd.github.v3+json");
+        if let Some(ref token) = self.token {
+            headers.set(AUTHORIZATION, format!("token {}", token));
+        }
+        headers
+    }
+
+    fn commit_url(&self, commit: &str, what: &str) -> String {
+        format!(
+            "{base}/repos/{repository}/commits/{commit}/{what}",
+            base = self.api_url,
+            repository = self.repository,
+            commit = commit,
+            what = what
+        )
+    }
+
+    // The branch's latest commit, and every check on it. The check runs are
+    // asked for by commit, in case the branch moves on in between.
+    fn get_checks(&self) -> Result<(String, Vec<Check>), Error> {
+        let (combined, _): (GitHubCombinedStatus, Headers) = get_url_response(
+            &self.client,
+            &self.commit_url(&self.branch, "status"),
+            self.headers(),
+        )?;
+        let mut checks: Vec<Check> = combined.statuses.into_iter().map(status_check).collect();
+        let runs = get_all_pages::<GitHubCheckRuns>(
+            &self.client,
+            &format!(
+                "{}?per_page=100",
+                self.commit_url(&combined.sha, "check-runs")
+            ),
+            self.headers(),
+            MAX_CHECK_RUN_PAGES,
+        )?;
+        checks.extend(runs.into_iter().map(run_check));
+        Ok((combined.sha, checks))
+    }
+}
+
+fn timestamp(time: &Option<String>) -> Option<i64> {
+    time.as_ref()
+        .and_then(|time| DateTime::parse_from_rfc3339(time).ok())
+        .map(|time| time.timestamp() * 1000)
+}
+
+fn status_check(status: GitHubStatus) -> Check {
+    Check {
+        state: match status.state {
+            GitHubStatusState::Success => JobState::Passing,
+            GitHubStatusState::Failure | GitHubStatusState::Error => JobState::Failing,
+            GitHubStatusState::Pending => JobState::InProgress,
+        },
+        cancelled: false,
+        started_at: timestamp(&status.updated_at),
+        url: status.target_url,
+        name: status.context,
+    }
+}
+
+fn run_check(run: GitHubCheckRun) -> Check {
+    let (state, cancelled) = check_state(&run.status, run.conclusion.as_ref().map(|c| c.as_str()));
+    Check {
+        state: state,
+        cancelled: cancelled,
+        started_at: timestamp(&run.started_at),
+        url: run.html_url,
+        name: run.name,
+    }
+}
+
+// Anything that hasn't completed is in progress, whatever it's waiting for.
+// Cancelled runs are counted apart, like aborted Jenkins builds.
+fn check_state(status: &str, conclusion: Option<&str>) -> (JobState, bool) {
+    match (status, conclusion) {
+        ("completed", Some("success"))
+        | ("completed", Some("neutral"))
+        | ("completed", Some("skipped")) => (JobState::Passing, false),
+        ("completed", Some("failure"))
+        | ("completed", Some("timed_out"))
+        | ("completed", Some("action_required"))
+        | ("completed", Some("startup_failure")) => (JobState::Failing, false),
+        ("completed", Some("cancelled")) => (JobState::Indeterminate, true),
+        ("completed", _) => (JobState::Indeterminate, false),
+        _ => (JobState::InProgress, false),
+    }
+}
+
+impl RemoteIntegration for GitHubIntegration {
+    fn get_red_id(&self) -> u16 {
+        self.r
+    }
+    fn get_green_id(&self) -> u16 {
+        self.g
+    }
+    fn get_blue_id(&self) -> u16 {
+        self.b
+    }
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+    fn get_summary(&self) -> StatusSummary {
+        self.last_summary.clone()
+    }
+    fn get_rate_limit_delay(&self) -> Option<Duration> {
+        self.client.rate_limited_for()
+    }
+    fn get_request_budget(&self) -> Option<RequestBudget> {
+        self.client.request_budget()
+    }
+
+    fn get_status(&mut self) -> RemoteStatus {
+        if let Some(login) = self.credentials.as_ref().and_then(|c| c.login()) {
+            if login.secret != self.token {
+                info!("--GitHub--: Switching to the reloaded token.");
+                self.token = login.secret;
+            }
+        }
+        self.client.start_poll();
+        let (sha, checks) = match self.get_checks() {
+            Ok(checks) => checks,
+            Err(err) => {
+                warn!(
+                    "--GitHub--: Failed to get the checks of {} {}. Error: {}",
+                    self.repository, self.branch, err
+                );
+                self.last_summary = if network::is_unreachable(&err) {
+                    StatusSummary::disconnected(err.to_string())
+                } else {
+                    StatusSummary {
+                        error: Some(err.to_string()),
+                        ..StatusSummary::default()
+                    }
+                };
+                return RemoteStatus::Unknown;
+            }
+        };
+        let outcomes: Vec<JobOutcome> = checks
+            .iter()
+            .map(|check| JobOutcome {
+                state: check.state,
+                started_at: check.started_at,
+            })
+            .collect();
+        let status = self.aggregation.aggregate(&outcomes);
+
+        let count = |state: JobState| {
+            checks
+                .iter()
+                .filter(|check| check.state == state && !check.cancelled)
+                .count()
+        };
+        let failing: Vec<&Check> = checks
+            .iter()
+            .filter(|check| check.state == JobState::Failing)
+            .collect();
+        let failed_builds: Vec<String> = failing
+            .iter()
+            .map(|check| match check.url {
+                Some(ref url) => format!("{} ({})", check.name, url),
+                None => check.name.clone(),
+            })
+            .collect();
+        for build in &failed_builds {
+            warn!("--GitHub--: {} failed on {}.", build, self.branch);
+        }
+        self.last_summary = StatusSummary {
+            passing: count(JobState::Passing),
+            failing: failing.len(),
+            in_progress: count(JobState::InProgress),
+            aborted: checks.iter().filter(|check| check.cancelled).count(),
+            indeterminate: count(JobState::Indeterminate),
+            failing_jobs: failing.iter().map(|check| check.name.clone()).collect(),
+            failed_builds: failed_builds,
+            jobs: checks
+                .iter()
+                .map(|check| JobStatus {
+                    name: check.name.clone(),
+                    status: check.state.status(),
+                })
+                .collect(),
+            ..StatusSummary::default()
+        };
+        info!(
+            "--GitHub--: {} at {}: {}.",
+            self.branch,
+            &sha[..sha.len().min(7)],
+            self.last_summary.describe()
+        );
+        status
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use integrations::aggregation::AggregationKind;
+    use test_support::{fixture, MockServer};
+
+    #[test]
+    fn statuses_and_check_runs_both_count() {
+        let server = MockServer::start();
+        server
+            .on(
+                "/repos/futurice/build-light/commits/master/status",
+                200,
+                &fixture("github_status.json"),
+            )
+            .on(
+                "/repos/futurice/build-light/commits/6dcb09b5b57875f334f61aebed695e2e4193db5e/check-runs",
+                200,
+                &fixture("github_check_runs.json"),
+            );
+        let mut github = GitHubIntegration::new(
+            0,
+            0,
+            0,
+            "GitHub futurice/build-light",
+            &format!("{}/", server.base_url),
+            "futurice/build-light",
+            "master",
+            Some("github-token".to_string()),
+            server.client(),
+            AggregationKind::AnyFailureIsRed.strategy(),
+        );
+
+        assert_eq!(github.get_status(), RemoteStatus::Failing);
+        let summary = github.get_summary();
+        assert_eq!(
+            (
+                summary.passing,
+                summary.failing,
+                summary.in_progress,
+                summary.aborted
+            ),
+            (1, 1, 1, 1)
+        );
+        assert_eq!(
+            summary.failed_builds,
+            vec!["test (https://github.com/futurice/build-light/runs/4)".to_string()]
+        );
+        assert!(server.requests()[0].contains("Authorization: token github-token"));
+    }
+}
@@ -0,0 +1,53 @@
+use pagination::Page;
+
+// From repos/{repository}/commits/{ref}/status, which combines the latest
+// status of each context
+#[derive(Deserialize)]
+pub struct GitHubCombinedStatus {
+    pub sha: String,
+    pub statuses: Vec<GitHubStatus>,
+}
+
+#[derive(Deserialize)]
+pub struct GitHubStatus {
+    pub context: String,
+    pub state: GitHubStatusState,
+    pub target_url: Option<String>,
+    pub updated_at: Option<String>,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum GitHubStatusState {
+    Success,
+    Failure,
+    Error,
+    Pending,
+}
+
+// From repos/{repository}/commits/{ref}/check-runs
+#[derive(Deserialize)]
+pub struct GitHubCheckRuns {
+    pub check_runs: Vec<GitHubCheckRun>,
+}
+
+impl Page for GitHubCheckRuns {
+    type Item = GitHubCheckRun;
+
+    fn into_items(self) -> Vec<GitHubCheckRun> {
+        self.check_runs
+    }
+}
+
+// Status and conclusion are left as strings, since GitHub adds new ones now
+// and then, see check_state
+#[derive(Deserialize)]
+pub struct GitHubCheckRun {
+    pub name: String,
+    // "queued", "in_progress" or "completed"
+    pub status: String,
+    // Only set once the run has completed, e.g. "success" or "timed_out"
+    pub conclusion: Option<String>,
+    pub html_url: Option<String>,
+    pub started_at: Option<String>,
+}
@@ -0,0 +1,255 @@
+use budget::RequestBudget;
+use chrono::DateTime;
+use credentials::Credentials;
+use failure::Error;
+use headers::{self, Headers, AUTHORIZATION};
+use integrations::grafana_response::*;
+use integrations::remote_integration::RemoteIntegration;
+use network::{self, get_url_response, HttpClient};
+use remote_status::{JobStatus, RemoteStatus, StatusSummary};
+use reqwest::Url;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+// Firing Grafana alerts with the configured labels, e.g. an SLO breach
+// indicator. Each firing alert counts as a failing job; with none the LED is
+// green. Silenced and inhibited alerts are left out.
+pub struct GrafanaIntegration {
+    r: u16,
+    g: u16,
+    b: u16,
+    name: String,
+    base_url: String,
+    // Every one has to match. Empty for all alerts.
+    labels: BTreeMap<String, String>,
+    token: Option<String>,
+    client: HttpClient,
+    last_summary: StatusSummary,
+    // Checked for a reloaded login before every poll, if set
+    credentials: Option<Credentials>,
+}
+
+impl GrafanaIntegration {
+    pub fn new(
+        r: u16,
+        g: u16,
+        b: u16,
+        name: &str,
+        base_url: &str,
+        labels: BTreeMap<String, String>,
+        token: Option<String>,
+        client: HttpClient,
+    ) -> GrafanaIntegration {
+        GrafanaIntegration {
+            r: r,
+            g: g,
+            b: b,
+            name: name.to_string(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            labels: labels,
+            token: token,
+            client: client,
+            last_summary: StatusSummary::default(),
+            credentials: None,
+        }
+    }
+
+    // Picks up a token reloaded into credentials, see CredentialReloader.
+    pub fn set_credentials(&mut self, credentials: Credentials) {
+        self.credentials = Some(credentials);
+    }
+
+    fn headers(&self) -> Headers {
+        let mut headers = Headers::new();
+        if let Some(ref token) = self.token {
+            headers.set(AUTHORIZATION, headers::bearer_auth(token));
+        }
+        headers
+    }
+
+    // Grafana's built-in Alertmanager, which knows which alerts are firing
+    // and which of them are silenced.
+    fn alerts_url(&self) -> Result<String, Error> {
+        let mut url = Url::parse(&format!(
+            "{}/api/alertmanager/grafana/api/v2/alerts",
+            self.base_url
+        ))?;
+        url.query_pairs_mut()
+            .append_pair("active", "true")
+            .append_pair("silenced", "false")
+            .append_pair("inhibited", "false");
+        for (label, value) in &self.labels {
+            url.query_pairs_mut()
+                .append_pair("filter", &format!("{}=\"{}\"", label, value));
+        }
+        Ok(url.to_string())
+    }
+
+    // Filtered here too, in case Grafana ignores any of the parameters.
+    fn get_firing_alerts(&self) -> Result<Vec<GrafanaAlert>, Error> {
+        let (alerts, _): (Vec<GrafanaAlert>, Headers) =
+            get_url_response(&self.client, &self.alerts_url()?, self.headers())?;
+        Ok(alerts
+            .into_iter()
+            .filter(|alert| alert.status.state == "active")
+            .filter(|alert| {
+                self.labels
+                    .iter()
+                    .all(|(label, value)| alert.labels.get(label) == Some(value))
+            })
+            .collect())
+    }
+}
+
+fn alert_name(alert: &GrafanaAlert) -> String {
+    alert
+        .labels
+        .get("alertname")
+        .cloned()
+        .unwrap_or_else(|| "Unnamed alert".to_string())
+}
+
+impl RemoteIntegration for GrafanaIntegration {
+    fn get_red_id(&self) -> u16 {
+        self.r
+    }
+    fn get_green_id(&self) -> u16 {
+        self.g
+    }
+    fn get_blue_id(&self) -> u16 {
+        self.b
+    }
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+    fn get_summary(&self) -> StatusSummary {
+        self.last_summary.clone()
+    }
+    fn get_rate_limit_delay(&self) -> Option<Duration> {
+        self.client.rate_limited_for()
+    }
+    fn get_request_budget(&self) -> Option<RequestBudget> {
+        self.client.request_budget()
+    }
+
+    fn get_status(&mut self) -> RemoteStatus {
+        if let Some(login) = self.credentials.as_ref().and_then(|c| c.login()) {
+            if login.secret != self.token {
+                info!("--Grafana--: Switching to the reloaded token.");
+                self.token = login.secret;
+            }
+        }
+        self.client.start_poll();
+        let alerts = match self.get_firing_alerts() {
+            Ok(alerts) => alerts,
+            Err(err) => {
+                warn!(
+                    "--Grafana--: Failed to get the firing alerts. Error: {}",
+                    err
+                );
+                self.last_summary = if network::is_unreachable(&err) {
+                    StatusSummary::disconnected(err.to_string())
+                } else {
+                    StatusSummary {
+                        error: Some(err.to_string()),
+                        ..StatusSummary::default()
+                    }
+                };
+                return RemoteStatus::Unknown;
+            }
+        };
+
+        let mut failing_jobs: Vec<String> = alerts.iter().map(alert_name).collect();
+        // Alerts with more than one series fire once per series
+        failing_jobs.sort();
+        failing_jobs.dedup();
+        let failed_builds: Vec<String> = alerts
+            .iter()
+            .map(|alert| {
+                let mut build = alert_name(alert);
+                if let Some(summary) = alert.annotations.get("summary") {
+                    build.push_str(&format!(": {}", summary));
+                }
+                if let Some(since) = alert
+                    .starts_at
+                    .as_ref()
+                    .and_then(|time| DateTime::parse_from_rfc3339(time).ok())
+                {
+                    build.push_str(&format!(", since {}", since.format("%H:%M")));
+                }
+                if let Some(ref url) = alert.generator_url {
+                    build.push_str(&format!(" ({})", url));
+                }
+                build
+            })
+            .collect();
+        for build in &failed_builds {
+            warn!("--Grafana--: Firing: {}", build);
+        }
+        self.last_summary = StatusSummary {
+            failing: alerts.len(),
+            failing_jobs: failing_jobs,
+            failed_builds: failed_builds,
+            jobs: alerts
+                .iter()
+                .map(|alert| JobStatus {
+                    name: alert_name(alert),
+                    status: RemoteStatus::Failing,
+                })
+                .collect(),
+            ..StatusSummary::default()
+        };
+        info!("--Grafana--: {}.", self.last_summary.describe());
+        if alerts.is_empty() {
+            RemoteStatus::Passing
+        } else {
+            RemoteStatus::Failing
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_support::{fixture, MockServer};
+
+    fn grafana(server: &MockServer, labels: &[(&str, &str)]) -> GrafanaIntegration {
+        GrafanaIntegration::new(
+            0,
+            0,
+            0,
+            "Grafana",
+            &server.base_url,
+            labels
+                .iter()
+                .map(|&(label, value)| (label.to_string(), value.to_string()))
+                .collect(),
+            Some("grafana-token".to_string()),
+            server.client(),
+        )
+    }
+
+    #[test]
+    fn only_firing_alerts_with_the_labels_count() {
+        let server = MockServer::start();
+        server.on(
+            "/api/alertmanager/grafana/api/v2/alerts",
+            200,
+            &fixture("grafana_alerts.json"),
+        );
+
+        // The disk space alert is silenced
+        let mut backend = grafana(&server, &[("team", "backend"), ("severity", "critical")]);
+        assert_eq!(backend.get_status(), RemoteStatus::Failing);
+        let summary = backend.get_summary();
+        assert_eq!(summary.failing_jobs, vec!["API latency SLO".to_string()]);
+        assert!(summary.failed_builds[0].contains("above 800 ms"));
+        let request = &server.requests()[0];
+        assert!(request.contains("filter=team%3D%22backend%22"));
+        assert!(request.contains("Authorization: Bearer grafana-token"));
+
+        let mut ops = grafana(&server, &[("team", "ops")]);
+        assert_eq!(ops.get_status(), RemoteStatus::Passing);
+        assert_eq!(ops.get_summary().failing, 0);
+    }
+}
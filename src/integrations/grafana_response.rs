@@ -0,0 +1,20 @@
+use std::collections::BTreeMap;
+
+// One alert from api/alertmanager/grafana/api/v2/alerts
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GrafanaAlert {
+    pub labels: BTreeMap<String, String>,
+    #[serde(default)]
+    pub annotations: BTreeMap<String, String>,
+    pub status: GrafanaAlertStatus,
+    pub starts_at: Option<String>,
+    #[serde(rename = "generatorURL")]
+    pub generator_url: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct GrafanaAlertStatus {
+    // "active", or "suppressed" while silenced or inhibited
+    pub state: String,
+}
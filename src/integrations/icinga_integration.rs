@@ -0,0 +1,325 @@
+use budget::RequestBudget;
+use credentials::Credentials;
+use failure::Error;
+use headers::{Headers, AUTHORIZATION};
+use integrations::aggregation::{AggregationStrategy, JobOutcome, JobState};
+use integrations::icinga_response::*;
+use integrations::remote_integration::RemoteIntegration;
+use network::{self, get_basic_credentials, get_url_response, HttpClient};
+use remote_status::{JobStatus, RemoteStatus, StatusSummary};
+use reqwest::Url;
+use std::time::Duration;
+
+// Only what's shown or decided on is asked for
+const ATTRS: &[&str] = &[
+    "display_name",
+    "host_name",
+    "state",
+    "acknowledgement",
+    "downtime_depth",
+    "last_check_result",
+];
+
+// One service's latest check.
+struct Check {
+    name: String,
+    state: JobState,
+    warning: bool,
+    // Acknowledged or in a downtime, so someone's on it
+    handled: bool,
+    output: Option<String>,
+    // Milliseconds since the epoch
+    checked_at: Option<i64>,
+}
+
+// Icinga 2 (or Nagios-style) service checks, so infra teams can reuse the
+// build light. Each service counts as a job: OK is passing, WARNING passing
+// with a warning and CRITICAL failing.
+pub struct IcingaIntegration {
+    r: u16,
+    g: u16,
+    b: u16,
+    name: String,
+    base_url: String,
+    username: String,
+    password: String,
+    // As "host!service"
+    services: Vec<String>,
+    // Every service on the hosts in these groups
+    host_groups: Vec<String>,
+    client: HttpClient,
+    last_summary: StatusSummary,
+    aggregation: Box<AggregationStrategy>,
+    // Checked for a reloaded login before every poll, if set
+    credentials: Option<Credentials>,
+}
+
+impl IcingaIntegration {
+    pub fn new(
+        r: u16,
+        g: u16,
+        b: u16,
+        name: &str,
+        base_url: &str,
+        username: &str,
+        password: &str,
+        services: Vec<String>,
+        host_groups: Vec<String>,
+        client: HttpClient,
+        aggregation: Box<AggregationStrategy>,
+    ) -> IcingaIntegration {
+        IcingaIntegration {
+            r: r,
+            g: g,
+            b: b,
+            name: name.to_string(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            username: username.to_string(),
+            password: password.to_string(),
+            services: services,
+            host_groups: host_groups,
+            client: client,
+            last_summary: StatusSummary::default(),
+            aggregation: aggregation,
+            credentials: None,
+        }
+    }
+
+    // Picks up a login reloaded into credentials, see CredentialReloader.
+    pub fn set_credentials(&mut self, credentials: Credentials) {
+        self.credentials = Some(credentials);
+    }
+
+    fn headers(&self) -> Headers {
+        let mut headers = Headers::new();
+        headers.set(
+            AUTHORIZATION,
+            get_basic_credentials(&self.username, Some(self.password.clone())),
+        );
+        headers.set("Accept", "application/json");
+        headers
+    }
+
+    // The named services with one request, and the host groups' services
+    // with a filter in another.
+    fn services_urls(&self) -> Result<Vec<String>, Error> {
+        let base = Url::parse(&format!("{}/v1/objects/services", self.base_url))?;
+        let with_attrs = |url: &mut Url| {
+            for attr in ATTRS {
+                url.query_pairs_mut().append_pair("attrs", attr);
+            }
+        };
+        let mut urls = Vec::new();
+        if !self.services.is_empty() {
+            let mut url = base.clone();
+            for service in &self.services {
+                url.query_pairs_mut().append_pair("services", service);
+            }
+            with_attrs(&mut url);
+            urls.push(url.to_string());
+        }
+        if !self.host_groups.is_empty() {
+            let filter = self
+                .host_groups
+                .iter()
+                .map(|group| format!("\"{}\" in host.groups", group))
+                .collect::<Vec<String>>()
+                .join(" || ");
+            let mut url = base;
+            url.query_pairs_mut().append_pair("filter", &filter);
+            with_attrs(&mut url);
+            urls.push(url.to_string());
+        }
+        Ok(urls)
+    }
+
+    fn get_checks(&self) -> Result<Vec<Check>, Error> {
+        let mut checks: Vec<Check> = Vec::new();
+        let mut seen: Vec<String> = Vec::new();
+        for url in self.services_urls()? {
+            let (services, _): (IcingaServices, Headers) =
+                get_url_response(&self.client, &url, self.headers())?;
+            for service in services.results {
+                // A service can be both named and in a host group
+                if seen.contains(&service.name) {
+                    continue;
+                }
+                seen.push(service.name.clone());
+                checks.push(service_check(service));
+            }
+        }
+        Ok(checks)
+    }
+}
+
+fn service_check(service: IcingaService) -> Check {
+    let attrs = service.attrs;
+    let (state, warning) = match attrs.state as u8 {
+        0 => (JobState::Passing, false),
+        1 => (JobState::Passing, true),
+        2 => (JobState::Failing, false),
+        _ => (JobState::Indeterminate, false),
+    };
+    Check {
+        name: format!("{}: {}", attrs.host_name, attrs.display_name),
+        state: state,
+        warning: warning,
+        handled: attrs.acknowledgement > 0.0 || attrs.downtime_depth > 0.0,
+        output: attrs
+            .last_check_result
+            .as_ref()
+            .map(|result| result.output.clone()),
+        checked_at: attrs
+            .last_check_result
+            .and_then(|result| result.execution_start)
+            .map(|seconds| (seconds * 1000.0) as i64),
+    }
+}
+
+impl RemoteIntegration for IcingaIntegration {
+    fn get_red_id(&self) -> u16 {
+        self.r
+    }
+    fn get_green_id(&self) -> u16 {
+        self.g
+    }
+    fn get_blue_id(&self) -> u16 {
+        self.b
+    }
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+    fn get_summary(&self) -> StatusSummary {
+        self.last_summary.clone()
+    }
+    fn get_rate_limit_delay(&self) -> Option<Duration> {
+        self.client.rate_limited_for()
+    }
+    fn get_request_budget(&self) -> Option<RequestBudget> {
+        self.client.request_budget()
+    }
+
+    fn get_status(&mut self) -> RemoteStatus {
+        if let Some(login) = self.credentials.as_ref().and_then(|c| c.login()) {
+            let password = login.secret.unwrap_or_default();
+            if login.username != self.username || password != self.password {
+                info!("--Icinga--: Switching to the reloaded credentials.");
+                self.username = login.username;
+                self.password = password;
+            }
+        }
+        self.client.start_poll();
+        let checks = match self.get_checks() {
+            Ok(checks) => checks,
+            Err(err) => {
+                warn!(
+                    "--Icinga--: Failed to get the service states. Error: {}",
+                    err
+                );
+                self.last_summary = if network::is_unreachable(&err) {
+                    StatusSummary::disconnected(err.to_string())
+                } else {
+                    StatusSummary {
+                        error: Some(err.to_string()),
+                        ..StatusSummary::default()
+                    }
+                };
+                return RemoteStatus::Unknown;
+            }
+        };
+        let outcomes: Vec<JobOutcome> = checks
+            .iter()
+            .map(|check| JobOutcome {
+                state: check.state,
+                started_at: check.checked_at,
+            })
+            .collect();
+        let status = self.aggregation.aggregate(&outcomes);
+
+        let count = |state: JobState| checks.iter().filter(|check| check.state == state).count();
+        let names = |checks: Vec<&Check>| -> Vec<String> {
+            checks.iter().map(|check| check.name.clone()).collect()
+        };
+        let failing: Vec<&Check> = checks
+            .iter()
+            .filter(|check| check.state == JobState::Failing)
+            .collect();
+        let failed_builds: Vec<String> = failing
+            .iter()
+            .map(|check| match check.output {
+                Some(ref output) => format!("{} ({})", check.name, output),
+                None => check.name.clone(),
+            })
+            .collect();
+        for build in &failed_builds {
+            warn!("--Icinga--: CRITICAL: {}", build);
+        }
+        self.last_summary = StatusSummary {
+            passing: count(JobState::Passing),
+            failing: failing.len(),
+            in_progress: count(JobState::InProgress),
+            indeterminate: count(JobState::Indeterminate),
+            claimed_jobs: names(
+                failing
+                    .iter()
+                    .cloned()
+                    .filter(|check| check.handled)
+                    .collect(),
+            ),
+            failing_jobs: names(failing),
+            warning_jobs: names(checks.iter().filter(|check| check.warning).collect()),
+            failed_builds: failed_builds,
+            jobs: checks
+                .iter()
+                .map(|check| JobStatus {
+                    name: check.name.clone(),
+                    status: check.state.status(),
+                })
+                .collect(),
+            ..StatusSummary::default()
+        };
+        info!("--Icinga--: {}.", self.last_summary.describe());
+        status
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use integrations::aggregation::AggregationKind;
+    use test_support::{fixture, MockServer};
+
+    #[test]
+    fn critical_fails_and_acknowledged_counts_as_claimed() {
+        let server = MockServer::start();
+        server.on(
+            "/v1/objects/services",
+            200,
+            &fixture("icinga_services.json"),
+        );
+        let mut icinga = IcingaIntegration::new(
+            0,
+            0,
+            0,
+            "Icinga",
+            &server.base_url,
+            "build-light",
+            "api-password",
+            vec!["web1!http".to_string()],
+            vec!["databases".to_string()],
+            server.client(),
+            AggregationKind::AnyFailureIsRed.strategy(),
+        );
+
+        assert_eq!(icinga.get_status(), RemoteStatus::Failing);
+        let summary = icinga.get_summary();
+        // Both requests got every service back, but each counts once
+        assert_eq!((summary.passing, summary.failing), (2, 2));
+        assert_eq!(summary.warning_jobs, vec!["web1: disk".to_string()]);
+        assert_eq!(summary.claimed_jobs, vec!["db1: backup".to_string()]);
+        assert!(summary.failed_builds[0].contains("no connection to 'postgres'"));
+        let requests = server.requests();
+        assert!(requests[0].contains("services=web1%21http"));
+        assert!(requests[1].contains("filter=%22databases%22+in+host.groups"));
+    }
+}
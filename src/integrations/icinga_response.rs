@@ -0,0 +1,34 @@
+// From v1/objects/services
+#[derive(Deserialize)]
+pub struct IcingaServices {
+    pub results: Vec<IcingaService>,
+}
+
+#[derive(Deserialize)]
+pub struct IcingaService {
+    // As "host!service"
+    pub name: String,
+    pub attrs: IcingaServiceAttrs,
+}
+
+#[derive(Deserialize)]
+pub struct IcingaServiceAttrs {
+    pub display_name: String,
+    pub host_name: String,
+    // 0 OK, 1 WARNING, 2 CRITICAL, 3 UNKNOWN. Icinga sends it as a float.
+    pub state: f64,
+    // 0 none, 1 normal, 2 sticky
+    #[serde(default)]
+    pub acknowledgement: f64,
+    // How many downtimes the service is in
+    #[serde(default)]
+    pub downtime_depth: f64,
+    pub last_check_result: Option<IcingaCheckResult>,
+}
+
+#[derive(Deserialize)]
+pub struct IcingaCheckResult {
+    pub output: String,
+    // Seconds since the epoch
+    pub execution_start: Option<f64>,
+}
@@ -1,9 +1,83 @@
+use budget::RequestBudget;
+use credentials::Credentials;
+use durations::BuildDurations;
+use errors::HttpStatusError;
 use failure::Error;
+use headers::{Headers, AUTHORIZATION};
+use history::JobHistory;
+use integrations::aggregation::{AggregationStrategy, JobOutcome, JobState};
 use integrations::jenkins_response::*;
-use network::{get_basic_credentials, get_url_response};
-use remote_status::RemoteStatus;
-use reqwest::header::{Authorization, Headers};
-use RemoteIntegration;
+use integrations::remote_integration::RemoteIntegration;
+use job_groups::JobPattern;
+use network::{
+    get_basic_credentials, get_url_body, get_url_response, is_unreachable, BaseUrls, HttpClient,
+};
+use remote_status::{BuildProgress, JobStatus, RemoteStatus, StatusSummary};
+use reqwest::StatusCode;
+use serde_json;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::mem;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// What we look at in a job's latest build.
+#[derive(Clone)]
+struct JobBuild {
+    status: JenkinsBuildStatus,
+    number: u32,
+    started_at: Option<i64>,
+    duration_millis: Option<u64>,
+    estimated_millis: Option<u64>,
+    culprits: Vec<String>,
+    claimed_by: Option<String>,
+}
+
+impl JobBuild {
+    // For jobs without a build to show yet
+    fn not_built() -> JobBuild {
+        JobBuild {
+            status: JenkinsBuildStatus::NotBuilt,
+            number: 0,
+            started_at: None,
+            duration_millis: None,
+            estimated_millis: None,
+            culprits: Vec::new(),
+            claimed_by: None,
+        }
+    }
+}
+
+// Which of the server's jobs an integration looks at. Pull request jobs can be
+// shown on an LED of their own, so a broken PR doesn't turn the main light red.
+#[derive(Clone, Debug, PartialEq)]
+pub enum JobSelection {
+    All,
+    // Every job except these pull request jobs
+    Mainline(Vec<String>),
+    // The same, but the pull request jobs are still fetched, and go in the
+    // summary's pull_request_jobs for the PR LED to show
+    MainlineAndPullRequests(Vec<String>),
+}
+
+impl JobSelection {
+    fn fetches(&self, job: &str) -> bool {
+        match *self {
+            JobSelection::Mainline(_) => !self.is_pull_request(job),
+            _ => true,
+        }
+    }
+
+    fn is_pull_request(&self, job: &str) -> bool {
+        match *self {
+            JobSelection::All => false,
+            JobSelection::Mainline(ref pr_jobs)
+            | JobSelection::MainlineAndPullRequests(ref pr_jobs) => {
+                pr_jobs.iter().any(|pr_job| pr_job == job)
+            }
+        }
+    }
+}
 
 pub struct JenkinsIntegration {
     r: u16,
@@ -11,7 +85,39 @@ pub struct JenkinsIntegration {
     b: u16,
     username: String,
     password: String,
-    base_url: String,
+    password_is_api_token: bool,
+    base_urls: BaseUrls,
+    client: HttpClient,
+    // Header name and value to send along with every request, when Jenkins
+    // has CSRF protection on. None until crumb_checked.
+    crumb: Option<(String, String)>,
+    crumb_checked: bool,
+    last_summary: StatusSummary,
+    history: Option<JobHistory>,
+    critical_jobs: Vec<String>,
+    aggregation: Box<AggregationStrategy>,
+    // Look at each job's last completed build instead of its last build, so a
+    // rebuilding job keeps showing its previous result
+    last_completed_only: bool,
+    // Flag the queue as backed up when more builds than this are waiting
+    queue_threshold: Option<usize>,
+    durations: Option<BuildDurations>,
+    jobs: JobSelection,
+    // Checked for new ones before every poll, if set
+    credentials: Option<Credentials>,
+    // Aborted builds count as failures instead of getting a count of their own
+    aborted_is_failure: bool,
+    // Only failing jobs matching this are worth blinking about, if set
+    mainline: Option<JobPattern>,
+    // For servers other than the main one, see jenkins_instances
+    name: Option<String>,
+    // Only fetch the builds again when the job list has changed, see set_watch
+    watch: bool,
+    // Hash of the last job list, and the builds fetched for it
+    last_listing: Option<(u64, Vec<(String, JobBuild)>)>,
+    // The stage each failing build failed in, by job and build number. A build
+    // is only reported on, and its stages looked up, when it first fails.
+    failed_builds: HashMap<(String, u32), Option<String>>,
 }
 
 impl JenkinsIntegration {
@@ -21,7 +127,16 @@ impl JenkinsIntegration {
         b: u16,
         username: &str,
         password: &str,
-        base_url: &str,
+        password_is_api_token: bool,
+        base_urls: BaseUrls,
+        client: HttpClient,
+        history: Option<JobHistory>,
+        critical_jobs: Vec<String>,
+        aggregation: Box<AggregationStrategy>,
+        last_completed_only: bool,
+        queue_threshold: Option<usize>,
+        durations: Option<BuildDurations>,
+        jobs: JobSelection,
     ) -> JenkinsIntegration {
         JenkinsIntegration {
             r: r,
@@ -29,20 +144,237 @@ impl JenkinsIntegration {
             b: b,
             username: username.to_string(),
             password: password.to_string(),
-            base_url: base_url.to_string(),
+            password_is_api_token: password_is_api_token,
+            base_urls: base_urls,
+            client: client,
+            crumb: None,
+            // Requests made with an API token don't need a crumb
+            crumb_checked: password_is_api_token,
+            last_summary: StatusSummary::default(),
+            history: history,
+            critical_jobs: critical_jobs,
+            aggregation: aggregation,
+            last_completed_only: last_completed_only,
+            queue_threshold: queue_threshold,
+            durations: durations,
+            jobs: jobs,
+            credentials: None,
+            aborted_is_failure: false,
+            mainline: None,
+            name: None,
+            watch: false,
+            last_listing: None,
+            failed_builds: HashMap::new(),
+        }
+    }
+
+    pub fn set_name(&mut self, name: &str) {
+        self.name = Some(name.to_string());
+    }
+
+    pub fn set_aborted_is_failure(&mut self, aborted_is_failure: bool) {
+        self.aborted_is_failure = aborted_is_failure;
+    }
+
+    pub fn set_mainline(&mut self, mainline: Option<JobPattern>) {
+        self.mainline = mainline;
+    }
+
+    // Asks for each job's last build number along with the job list, and
+    // reuses the builds from the last poll while the list hashes the same.
+    // Polling often then mostly costs one small request, plus one for each
+    // failing job to see whether it's been claimed since.
+    pub fn set_watch(&mut self, watch: bool) {
+        self.watch = watch;
+        self.last_listing = None;
+    }
+
+    // Picks up credentials reloaded into credentials, see CredentialReloader.
+    pub fn set_credentials(&mut self, credentials: Credentials) {
+        self.credentials = Some(credentials);
+    }
+
+    fn refresh_credentials(&mut self) {
+        let latest = match self.credentials.as_ref().and_then(|c| c.jenkins()) {
+            Some(latest) => latest,
+            None => return,
+        };
+        if latest.username == self.username
+            && latest.password == self.password
+            && latest.password_is_api_token == self.password_is_api_token
+        {
+            return;
+        }
+        info!("--Jenkins--: Switching to the reloaded credentials.");
+        self.username = latest.username;
+        self.password = latest.password;
+        self.password_is_api_token = latest.password_is_api_token;
+        // The crumb belongs to the old user's session
+        self.crumb = None;
+        self.crumb_checked = self.password_is_api_token;
+    }
+
+    // Without a username the requests go out anonymously, for Jenkins servers
+    // anyone can read. Some of those turn away any Authorization header.
+    fn auth_headers(&self) -> Headers {
+        let mut headers = Headers::new();
+        if !self.username.is_empty() {
+            headers.set(
+                AUTHORIZATION,
+                get_basic_credentials(self.username.as_str(), Some(self.password.clone())),
+            );
+        }
+        if let Some((ref field, ref crumb)) = self.crumb {
+            headers.set(field.as_str(), crumb.as_str());
+        }
+        headers
+    }
+
+    // Asks Jenkins for a CSRF crumb. A 404 means CSRF protection is off, and we
+    // don't need one; any other failure is retried on the next poll.
+    fn check_crumb(&mut self) {
+        let url_string = format!("{base}/crumbIssuer/api/json", base = self.base_urls.current());
+        let crumb_response: Result<(JenkinsCrumb, Headers), Error> =
+            get_url_response(&self.client, &url_string, self.auth_headers());
+        match crumb_response {
+            Ok((crumb, _)) => {
+                info!("--Jenkins--: CSRF protection is on, sending a crumb with every request.");
+                self.crumb = Some((crumb.crumb_request_field, crumb.crumb));
+                self.crumb_checked = true;
+            }
+            Err(ref err) if is_status(err, StatusCode::NotFound) => {
+                self.crumb = None;
+                self.crumb_checked = true;
+            }
+            Err(err) => warn!(
+                "--Jenkins--: Failed to get a CSRF crumb from {}. Error: {}",
+                &url_string, err
+            ),
         }
     }
 
-    fn get_status_internal(&self) -> Result<Vec<Result<JenkinsBuildStatus, Error>>, Error> {
-        let url_string = format!("{base}/api/json", base = self.base_url);
-        let mut auth_headers = Headers::new();
-        auth_headers.set(Authorization(get_basic_credentials(
-            self.username.as_str(),
-            Some(self.password.clone()),
-        )));
+    // How many builds are waiting in the queue.
+    fn get_queue_length(&self) -> Result<usize, Error> {
+        let url_string = format!("{base}/queue/api/json", base = self.base_urls.current());
+        let (queue, _): (JenkinsQueueResponse, Headers) =
+            get_url_response(&self.client, &url_string, self.auth_headers())?;
+        Ok(queue.items.len())
+    }
+
+    // The stage a pipeline build failed in. Other kinds of jobs don't have a
+    // wfapi, and don't have stages either.
+    fn get_failed_stage(&self, job: &str, number: u32) -> Result<Option<String>, Error> {
+        let url_string = format!(
+            "{base}/job/{job}/{number}/wfapi/describe",
+            base = self.base_urls.current(),
+            job = job,
+            number = number
+        );
+        let response: Result<(JenkinsRunDescription, Headers), Error> =
+            get_url_response(&self.client, &url_string, self.auth_headers());
+        match response {
+            Ok((description, _)) => Ok(description.failed_stage().map(|stage| stage.to_string())),
+            Err(ref err) if is_status(err, StatusCode::NotFound) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
 
+    // The last (or last completed) build of a job
+    fn get_build(&self, name: &str, auth_headers: Headers) -> Result<JobBuild, Error> {
+        let job_url_string = format!(
+            "{base}/job/{job}/{build}/api/json",
+            base = self.base_urls.current(),
+            job = name,
+            build = if self.last_completed_only {
+                "lastCompletedBuild"
+            } else {
+                "lastBuild"
+            }
+        );
+        let job_response: Result<(JenkinsBuildResult, Headers), Error> =
+            get_url_response(&self.client, &job_url_string, auth_headers);
+        match job_response {
+            Ok((job_result, _)) => {
+                let status = match (job_result.building, job_result.build_result) {
+                    (true, _) => JenkinsBuildStatus::Building,
+                    (false, Some(result)) => result,
+                    // Finished, but Jenkins hasn't recorded how yet
+                    (false, None) => JenkinsBuildStatus::NotBuilt,
+                };
+                Ok(JobBuild {
+                    status: status,
+                    number: job_result.number,
+                    started_at: job_result.timestamp,
+                    duration_millis: job_result.duration,
+                    estimated_millis: job_result
+                        .estimated_duration
+                        .filter(|&millis| millis > 0)
+                        .map(|millis| millis as u64),
+                    culprits: job_result.culprit_names(),
+                    claimed_by: job_result.claimed_by(),
+                })
+            }
+            // e.g. the first build is still running, and only completed ones were asked for
+            Err(ref job_err) if is_status(job_err, StatusCode::NotFound) => {
+                info!("--Jenkins--: {} has no build to show yet.", name);
+                Ok(JobBuild::not_built())
+            }
+            Err(job_err) => {
+                warn!("--Jenkins--: HTTP failure when attempting to get job result for job: {}. Error: {}", &job_url_string, job_err);
+                Err(job_err)
+            }
+        }
+    }
+
+    fn get_status_internal(&mut self) -> Result<Vec<(String, Result<JobBuild, Error>)>, Error> {
+        if !self.crumb_checked {
+            self.check_crumb();
+        }
+        // Only what's needed of each job, or a big server's listing gets huge.
+        // A new build changes the number even if it was too quick to catch.
+        let url_string = format!(
+            "{base}/api/json?tree=jobs[name,url,color{last_build}]",
+            base = self.base_urls.current(),
+            last_build = if self.watch { ",lastBuild[number]" } else { "" }
+        );
+        let auth_headers = self.auth_headers();
+
+        let listing = get_url_body(&self.client, &url_string, auth_headers.clone());
+        let listing_hash = match listing {
+            Ok((ref body, _)) if self.watch => {
+                let mut hasher = DefaultHasher::new();
+                body.hash(&mut hasher);
+                Some(hasher.finish())
+            }
+            _ => None,
+        };
+        match (listing_hash, &self.last_listing) {
+            (Some(hash), &Some((last_hash, ref builds))) if hash == last_hash => {
+                debug!("--Jenkins--: The job list hasn't changed, reusing the last builds.");
+                // Claiming a build doesn't change the job list, so the failing
+                // ones are fetched again to keep their claims up to date
+                return Ok(builds
+                    .iter()
+                    .map(|&(ref name, ref build)| {
+                        let build = match job_state(build.status, self.aborted_is_failure) {
+                            JobState::Failing => self.get_build(name, auth_headers.clone()),
+                            _ => Ok(build.clone()),
+                        };
+                        (name.clone(), build)
+                    })
+                    .collect());
+            }
+            _ => self.last_listing = None,
+        }
         let all_jobs_response: Result<(JenkinsJobResponse, Headers), Error> =
-            get_url_response(&url_string, auth_headers.clone());
+            listing.and_then(|(body, headers)| Ok((serde_json::from_str(&body)?, headers)));
+        // Crumbs belong to a session, and stop working when it expires
+        if let Err(ref err) = all_jobs_response {
+            if self.crumb.is_some() && is_status(err, StatusCode::Forbidden) {
+                self.crumb = None;
+                self.crumb_checked = false;
+            }
+        }
 
         match all_jobs_response {
             Ok((result, _)) => {
@@ -52,34 +384,29 @@ impl JenkinsIntegration {
                     .filter(|job| {
                         job.color != JenkinsJobColor::Disabled
                             && job.color != JenkinsJobColor::DisabledAnime
+                            && self.jobs.fetches(&job.name)
                     })
                     .map(|job| {
-                        let job_url_string = format!(
-                            "{base}/job/{job}/lastBuild/api/json",
-                            base = self.base_url,
-                            job = job.name
-                        );
-                        let job_response: Result<
-                            (JenkinsBuildResult, Headers),
-                            Error,
-                        > = get_url_response(&job_url_string, auth_headers.clone());
-
-                        match job_response {
-                            Ok((job_result, _)) => {
-                                if job_result.building {
-                                    Ok(JenkinsBuildStatus::Building)
-                                } else {
-                                    let unwrapped_result = job_result.build_result.unwrap();
-                                    Ok(unwrapped_result)
-                                }
-                            }
-                            Err(job_err) => {
-                                warn!("--Jenkins--: HTTP failure when attempting to get job result for job: {}. Error: {}", &job_url_string, job_err);
-                                Err(job_err)
-                            }
+                        // A brand-new job has no last build to ask for
+                        if job.color == JenkinsJobColor::Notbuilt {
+                            info!("--Jenkins--: {} hasn't been built yet.", job.name);
+                            return (job.name.clone(), Ok(JobBuild::not_built()));
                         }
+                        (job.name.clone(), self.get_build(&job.name, auth_headers.clone()))
                     })
-                    .collect();
+                    .collect::<Vec<(String, Result<JobBuild, Error>)>>();
+                // Builds that couldn't be fetched are tried again next time
+                if let Some(hash) = listing_hash {
+                    if results.iter().all(|&(_, ref build)| build.is_ok()) {
+                        let builds = results
+                            .iter()
+                            .filter_map(|&(ref name, ref build)| {
+                                build.as_ref().ok().map(|build| (name.clone(), build.clone()))
+                            })
+                            .collect();
+                        self.last_listing = Some((hash, builds));
+                    }
+                }
                 Ok(results)
             }
             Err(err) => Err(err),
@@ -87,6 +414,49 @@ impl JenkinsIntegration {
     }
 }
 
+// Splits the builds into the durations of finished ones, as (job, build
+// number, milliseconds), and how long the running ones have taken so far.
+fn finished_and_running(
+    job_names: &[String],
+    builds: &[JobBuild],
+) -> (Vec<(String, u32, u64)>, Vec<(String, u64)>) {
+    let now_millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since_epoch| since_epoch.as_secs() as i64 * 1000)
+        .unwrap_or(0);
+    let mut finished = Vec::new();
+    let mut running = Vec::new();
+    for (name, build) in job_names.iter().zip(builds.iter()) {
+        match (build.status, build.duration_millis, build.started_at) {
+            (JenkinsBuildStatus::Building, _, Some(started_at)) => {
+                running.push((name.clone(), (now_millis - started_at).max(0) as u64))
+            }
+            // Aborted builds would drag the average down
+            (JenkinsBuildStatus::Aborted, _, _) => (),
+            (_, Some(millis), _) if millis > 0 => {
+                finished.push((name.clone(), build.number, millis))
+            }
+            _ => (),
+        }
+    }
+    (finished, running)
+}
+
+fn job_state(status: JenkinsBuildStatus, aborted_is_failure: bool) -> JobState {
+    match status {
+        JenkinsBuildStatus::Success => JobState::Passing,
+        JenkinsBuildStatus::Failure | JenkinsBuildStatus::Unstable => JobState::Failing,
+        JenkinsBuildStatus::Aborted if aborted_is_failure => JobState::Failing,
+        JenkinsBuildStatus::Building => JobState::InProgress,
+        JenkinsBuildStatus::NotBuilt | JenkinsBuildStatus::Aborted => JobState::Indeterminate,
+    }
+}
+
+fn is_status(err: &Error, status: StatusCode) -> bool {
+    err.downcast_ref::<HttpStatusError>()
+        .map_or(false, |err| err.status == status)
+}
+
 impl RemoteIntegration for JenkinsIntegration {
     fn get_red_id(&self) -> u16 {
         self.r
@@ -97,31 +467,103 @@ impl RemoteIntegration for JenkinsIntegration {
     fn get_blue_id(&self) -> u16 {
         self.b
     }
+    fn get_name(&self) -> &str {
+        match self.name {
+            Some(ref name) => name,
+            None => "Jenkins",
+        }
+    }
+    fn get_summary(&self) -> StatusSummary {
+        self.last_summary.clone()
+    }
+    fn get_rate_limit_delay(&self) -> Option<Duration> {
+        self.client.rate_limited_for()
+    }
+    fn get_request_budget(&self) -> Option<RequestBudget> {
+        self.client.request_budget()
+    }
 
     fn get_status(&mut self) -> RemoteStatus {
-        match self.get_status_internal() {
+        self.refresh_credentials();
+        self.client.start_poll();
+        let mut result = self.get_status_internal();
+        // Every mirror gets a try before Jenkins counts as unreachable
+        for _ in 1..self.base_urls.count() {
+            match result {
+                Err(ref err) if is_unreachable(err) => (),
+                _ => break,
+            }
+            let unreachable = self.base_urls.current().to_string();
+            self.base_urls.fall_back();
+            warn!(
+                "--Jenkins--: {} can't be reached, switching to {}.",
+                unreachable,
+                self.base_urls.current()
+            );
+            // Crumbs are only good on the server that issued them
+            self.crumb = None;
+            self.crumb_checked = self.password_is_api_token;
+            result = self.get_status_internal();
+        }
+        match result {
             Ok(results) => {
+                let (pull_requests, results): (
+                    Vec<(String, Result<JobBuild, Error>)>,
+                    Vec<(String, Result<JobBuild, Error>)>,
+                ) = results
+                    .into_iter()
+                    .partition(|&(ref name, _)| self.jobs.is_pull_request(name));
+                let aborted_is_failure = self.aborted_is_failure;
+                let pull_request_jobs: Vec<JobStatus> = pull_requests
+                    .iter()
+                    .map(|&(ref name, ref build)| JobStatus {
+                        name: name.clone(),
+                        status: match *build {
+                            Ok(ref build) => job_state(build.status, aborted_is_failure).status(),
+                            Err(_) => RemoteStatus::Unknown,
+                        },
+                    })
+                    .collect();
                 let (retrieved, not_retrieved): (
-                    Vec<Result<JenkinsBuildStatus, Error>>,
-                    Vec<Result<JenkinsBuildStatus, Error>>,
-                ) = results.into_iter().partition(|x| x.is_ok());
+                    Vec<(String, Result<JobBuild, Error>)>,
+                    Vec<(String, Result<JobBuild, Error>)>,
+                ) = results.into_iter().partition(|x| x.1.is_ok());
 
+                let (job_names, builds): (Vec<String>, Vec<JobBuild>) = retrieved
+                    .into_iter()
+                    .map(|(name, status)| (name, status.unwrap()))
+                    .unzip();
                 let retrieved: Vec<JenkinsBuildStatus> =
-                    retrieved.into_iter().map(|x| x.unwrap()).collect();
-                
+                    builds.iter().map(|build| build.status).collect();
+                let build_numbers: Vec<u32> = builds.iter().map(|build| build.number).collect();
+
                 let not_retrieved_count = not_retrieved.len();
+                let is_failure = |x: &JenkinsBuildStatus| {
+                    job_state(*x, aborted_is_failure) == JobState::Failing
+                };
                 let build_failures = *(&retrieved
                     .iter()
-                    .filter(|x| {
-                        **x == JenkinsBuildStatus::Failure || **x == JenkinsBuildStatus::Unstable
-                    })
+                    .filter(|x| is_failure(x))
                     .count());
+                let aborted_count = if aborted_is_failure {
+                    0
+                } else {
+                    retrieved
+                        .iter()
+                        .filter(|x| **x == JenkinsBuildStatus::Aborted)
+                        .count()
+                };
+                let not_built_count = retrieved
+                    .iter()
+                    .filter(|x| **x == JenkinsBuildStatus::NotBuilt)
+                    .count();
                 let indeterminate_count = *(&retrieved
                     .iter()
                     .filter(|x| {
-                        **x != JenkinsBuildStatus::Failure
-                            && **x != JenkinsBuildStatus::Unstable
+                        !is_failure(x)
                             && **x != JenkinsBuildStatus::Success
+                            && **x != JenkinsBuildStatus::Aborted
+                            && **x != JenkinsBuildStatus::NotBuilt
                     })
                     .count()) + not_retrieved_count;
                 let build_successes = *(&retrieved
@@ -134,29 +576,228 @@ impl RemoteIntegration for JenkinsIntegration {
                     .filter(|x| **x == JenkinsBuildStatus::Building)
                     .count());
 
-                info!("--Jenkins--: Retrieved {} jobs, failed to retrieve {} jobs. Of those, {} succeeded, {} failed, and {} were indeterminate.", retrieved.len(), not_retrieved_count, build_successes, build_failures, indeterminate_count);                
-
-                // No successes, or at least one failure
-                if build_successes == 0 || build_failures > 0 {
-                    return RemoteStatus::Failing;
-                }                
-                // If no failures, immediately report any builds-in-progress
-                if  build_failures == 0 && builds_in_progress > 0 {
-                    return RemoteStatus::InProgress;
+                let failing_jobs: Vec<String> = job_names
+                    .iter()
+                    .zip(retrieved.iter())
+                    .filter(|&(_, x)| is_failure(x))
+                    .map(|(name, _)| name.clone())
+                    .collect();
+                // Only finished builds count towards flakiness
+                let flaky_jobs = match self.history {
+                    Some(ref mut history) => {
+                        let finished_builds: Vec<(String, u32, bool)> = job_names
+                            .iter()
+                            .zip(retrieved.iter().zip(build_numbers.iter()))
+                            .filter(|&(_, (status, _))| {
+                                *status == JenkinsBuildStatus::Success
+                                    || *status == JenkinsBuildStatus::Failure
+                                    || *status == JenkinsBuildStatus::Unstable
+                            })
+                            .map(|(name, (status, number))| {
+                                (name.clone(), *number, *status == JenkinsBuildStatus::Success)
+                            })
+                            .collect();
+                        history.record(&finished_builds)
+                    }
+                    None => Vec::new(),
+                };
+                let overdue_jobs = match self.durations {
+                    Some(ref mut durations) => {
+                        let (finished, running) = finished_and_running(&job_names, &builds);
+                        durations.record(&finished);
+                        let overdue_jobs = durations.overdue(&running);
+                        for job in &overdue_jobs {
+                            warn!(
+                                "--Jenkins--: {} is taking longer than usual, its builds average {} minutes.",
+                                job,
+                                durations.average_millis(job).unwrap_or(0) / (60 * 1000)
+                            );
+                        }
+                        overdue_jobs
+                    }
+                    None => Vec::new(),
+                };
+                // Jenkins' own estimate, or else the average from build_durations
+                let running_builds: Vec<BuildProgress> = job_names
+                    .iter()
+                    .zip(builds.iter())
+                    .filter(|&(_, build)| build.status == JenkinsBuildStatus::Building)
+                    .filter_map(|(name, build)| {
+                        build.started_at.map(|started_at| BuildProgress {
+                            job: name.clone(),
+                            started_at: started_at,
+                            estimated_millis: build.estimated_millis.or_else(|| {
+                                self.durations
+                                    .as_ref()
+                                    .and_then(|durations| durations.average_millis(name))
+                            }),
+                        })
+                    })
+                    .collect();
+                // Failing builds seen in the last poll were already reported on
+                let known_failures = mem::replace(&mut self.failed_builds, HashMap::new());
+                let is_new = |name: &String, build: &JobBuild| {
+                    !known_failures.contains_key(&(name.clone(), build.number))
+                };
+                // Culprits of failing builds only; a passing build's committers did nothing wrong
+                let mut culprits: Vec<String> = Vec::new();
+                for (name, build) in job_names.iter().zip(builds.iter()) {
+                    if !failing_jobs.contains(name) || build.culprits.is_empty() {
+                        continue;
+                    }
+                    if is_new(name, build) {
+                        warn!(
+                            "--Jenkins--: {} #{} failed. Changes by {}.",
+                            name,
+                            build.number,
+                            build.culprits.join(", ")
+                        );
+                    }
+                    for culprit in &build.culprits {
+                        if !culprits.contains(culprit) {
+                            culprits.push(culprit.clone());
+                        }
+                    }
+                }
+                let mut claimed_jobs: Vec<String> = Vec::new();
+                for (name, build) in job_names.iter().zip(builds.iter()) {
+                    match build.claimed_by {
+                        Some(ref claimed_by) if failing_jobs.contains(name) => {
+                            if is_new(name, build) {
+                                info!(
+                                    "--Jenkins--: {} #{} is claimed by {}.",
+                                    name, build.number, claimed_by
+                                );
+                            }
+                            claimed_jobs.push(name.clone());
+                        }
+                        _ => (),
+                    }
                 }
-                // No failures, and more successes than indeterminates
-                if build_failures == 0 && build_successes > indeterminate_count {
-                    return RemoteStatus::Passing;
+                let mut failed_stages: Vec<String> = Vec::new();
+                for (name, build) in job_names.iter().zip(builds.iter()) {
+                    if !failing_jobs.contains(name) {
+                        continue;
+                    }
+                    let key = (name.clone(), build.number);
+                    let stage = match known_failures.get(&key) {
+                        Some(stage) => Ok(stage.clone()),
+                        None => self.get_failed_stage(name, build.number),
+                    };
+                    match stage {
+                        Ok(stage) => {
+                            if let Some(ref stage) = stage {
+                                if !known_failures.contains_key(&key) {
+                                    warn!(
+                                        "--Jenkins--: {} #{} failed in stage {}.",
+                                        name, build.number, stage
+                                    );
+                                }
+                                failed_stages.push(format!("{}: {}", name, stage));
+                            }
+                            self.failed_builds.insert(key, stage);
+                        }
+                        Err(e) => warn!(
+                            "--Jenkins--: Failed to get the stages of {} #{}. Error: {}",
+                            name, build.number, e
+                        ),
+                    }
                 }
-                // No failures, but more indeterminates than successes.
-                if build_failures == 0 && indeterminate_count > build_successes {
-                    return RemoteStatus::Failing;
+                let critical_failing = failing_jobs
+                    .iter()
+                    .any(|job| self.critical_jobs.contains(job));
+                if critical_failing {
+                    warn!("--Jenkins--: A critical job is failing.");
+                }
+                let non_mainline_failures_only = match self.mainline {
+                    Some(ref mainline) => {
+                        build_failures > 0 && !failing_jobs.iter().any(|job| mainline.is_match(job))
+                    }
+                    None => false,
+                };
+                let queued = match self.queue_threshold {
+                    Some(_) => match self.get_queue_length() {
+                        Ok(queued) => Some(queued),
+                        Err(e) => {
+                            warn!("--Jenkins--: Failed to get the build queue. Error: {}", e);
+                            None
+                        }
+                    },
+                    None => None,
+                };
+                let queue_backed_up = match (queued, self.queue_threshold) {
+                    (Some(queued), Some(threshold)) => queued > threshold,
+                    _ => false,
+                };
+                if queue_backed_up {
+                    warn!(
+                        "--Jenkins--: {} builds are waiting in the queue.",
+                        queued.unwrap_or(0)
+                    );
                 }
+                self.last_summary = StatusSummary {
+                    passing: build_successes,
+                    failing: build_failures,
+                    in_progress: builds_in_progress,
+                    aborted: aborted_count,
+                    not_built: not_built_count,
+                    indeterminate: indeterminate_count,
+                    failing_jobs: failing_jobs,
+                    flaky_jobs: flaky_jobs,
+                    overdue_jobs: overdue_jobs,
+                    culprits: culprits,
+                    claimed_jobs: claimed_jobs,
+                    warning_jobs: Vec::new(),
+                    running_builds: running_builds,
+                    jobs: job_names
+                        .iter()
+                        .zip(retrieved.iter())
+                        .map(|(name, status)| JobStatus {
+                            name: name.clone(),
+                            status: job_state(*status, aborted_is_failure).status(),
+                        })
+                        .chain(not_retrieved.iter().map(|&(ref name, _)| JobStatus {
+                            name: name.clone(),
+                            status: RemoteStatus::Unknown,
+                        }))
+                        .collect(),
+                    pull_request_jobs: pull_request_jobs,
+                    failed_stages: failed_stages,
+                    failed_builds: Vec::new(),
+                    non_critical_failures_only: !self.critical_jobs.is_empty()
+                        && build_failures > 0
+                        && !critical_failing,
+                    non_mainline_failures_only: non_mainline_failures_only,
+                    queued: queued,
+                    queue_backed_up: queue_backed_up,
+                    disconnected: false,
+                    error: not_retrieved
+                        .first()
+                        .and_then(|&(_, ref result)| result.as_ref().err())
+                        .map(|e| e.to_string()),
+                };
 
-                // None of our other conditions apply
-                return RemoteStatus::Unknown;
+                info!("--Jenkins--: Retrieved {} jobs, failed to retrieve {} jobs. Of those, {} succeeded, {} failed, {} were aborted, {} were not built, and {} were indeterminate.", retrieved.len(), not_retrieved_count, build_successes, build_failures, aborted_count, not_built_count, indeterminate_count);                
+
+                // A failing critical job is red, whatever the strategy says
+                if critical_failing {
+                    return RemoteStatus::Failing;
+                }
+                let outcomes: Vec<JobOutcome> = builds
+                    .iter()
+                    .map(|build| JobOutcome {
+                        state: job_state(build.status, aborted_is_failure),
+                        started_at: build.started_at,
+                    })
+                    .chain(not_retrieved.iter().map(|_| JobOutcome {
+                        state: JobState::Indeterminate,
+                        started_at: None,
+                    }))
+                    .collect();
+                return self.aggregation.aggregate(&outcomes);
             }
             Err(e) => {
+                self.last_summary = StatusSummary::disconnected(e.to_string());
                 warn!(
                     "--Jenkins--: Failed to retrieve any jobs from Jenkins. Details: {}",
                     e
@@ -166,3 +807,500 @@ impl RemoteIntegration for JenkinsIntegration {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use config_file::HttpConfig;
+    use integrations::aggregation::AggregationKind;
+    use test_support::{fixture, MockServer};
+
+    fn integration(server: &MockServer) -> JenkinsIntegration {
+        JenkinsIntegration::new(
+            0,
+            0,
+            0,
+            "user",
+            "password",
+            false,
+            BaseUrls::new(&server.base_url, &[]),
+            server.client(),
+            None,
+            Vec::new(),
+            AggregationKind::AnyFailureIsRed.strategy(),
+            false,
+            None,
+            None,
+            JobSelection::All,
+        )
+    }
+
+    #[test]
+    fn failing_job_makes_jenkins_fail() {
+        let server = MockServer::start();
+        server
+            .on("/api/json", 200, &fixture("jenkins_jobs.json"))
+            .on("/job/app/lastBuild/api/json", 200, &fixture("jenkins_build_success.json"))
+            .on("/job/api/lastBuild/api/json", 200, &fixture("jenkins_build_failure.json"));
+        let mut jenkins = integration(&server);
+
+        assert_eq!(jenkins.get_status(), RemoteStatus::Failing);
+        let summary = jenkins.get_summary();
+        assert_eq!((summary.passing, summary.failing), (1, 1));
+        assert_eq!(summary.failing_jobs, vec!["api".to_string()]);
+        assert_eq!(summary.culprits, vec!["Jane Doe".to_string()]);
+        let job_statuses: Vec<(&str, RemoteStatus)> = summary
+            .jobs
+            .iter()
+            .map(|job| (job.name.as_str(), job.status))
+            .collect();
+        assert!(job_statuses.contains(&("app", RemoteStatus::Passing)));
+        assert!(job_statuses.contains(&("api", RemoteStatus::Failing)));
+        assert!(summary.error.is_none());
+        // Disabled jobs aren't looked at
+        assert!(server
+            .requests()
+            .iter()
+            .all(|request| !request.contains("/job/legacy/")));
+    }
+
+    #[test]
+    fn each_instance_polls_its_own_server() {
+        let main_server = MockServer::start();
+        main_server
+            .on("/api/json", 200, &fixture("jenkins_jobs.json"))
+            .on("/job/app/lastBuild/api/json", 200, &fixture("jenkins_build_success.json"))
+            .on("/job/api/lastBuild/api/json", 200, &fixture("jenkins_build_failure.json"));
+        let other_server = MockServer::start();
+        other_server
+            .on("/api/json", 200, &fixture("jenkins_jobs.json"))
+            .on("/job/app/lastBuild/api/json", 200, &fixture("jenkins_build_success.json"))
+            .on("/job/api/lastBuild/api/json", 200, &fixture("jenkins_build_success.json"));
+        let mut main = integration(&main_server);
+        let mut other = integration(&other_server);
+        other.set_name("Jenkins mobile");
+
+        assert_eq!(main.get_status(), RemoteStatus::Failing);
+        assert_eq!(other.get_status(), RemoteStatus::Passing);
+        assert_eq!(main.get_name(), "Jenkins");
+        assert_eq!(other.get_name(), "Jenkins mobile");
+    }
+
+    #[test]
+    fn public_jenkins_is_read_anonymously() {
+        let server = MockServer::start();
+        server
+            .on("/api/json", 200, &fixture("jenkins_jobs.json"))
+            .on("/job/app/lastBuild/api/json", 200, &fixture("jenkins_build_success.json"))
+            .on("/job/api/lastBuild/api/json", 200, &fixture("jenkins_build_success.json"));
+        let mut jenkins = integration(&server);
+        jenkins.username = String::new();
+        jenkins.password = String::new();
+
+        assert_eq!(jenkins.get_status(), RemoteStatus::Passing);
+        assert!(server
+            .requests()
+            .iter()
+            .all(|request| !request.contains("Authorization")));
+    }
+
+    #[test]
+    fn building_job_shows_as_in_progress() {
+        let server = MockServer::start();
+        server
+            .on("/api/json", 200, &fixture("jenkins_jobs.json"))
+            .on("/job/app/lastBuild/api/json", 200, &fixture("jenkins_build_success.json"))
+            .on("/job/api/lastBuild/api/json", 200, &fixture("jenkins_build_building.json"));
+        let mut jenkins = integration(&server);
+
+        assert_eq!(jenkins.get_status(), RemoteStatus::InProgress);
+        let summary = jenkins.get_summary();
+        assert_eq!(summary.in_progress, 1);
+        assert_eq!(
+            summary.running_builds,
+            vec![BuildProgress {
+                job: "api".to_string(),
+                started_at: 1519920000000,
+                estimated_millis: Some(600000),
+            }]
+        );
+    }
+
+    #[test]
+    fn builds_are_only_fetched_again_when_the_job_list_changes() {
+        let server = MockServer::start();
+        server
+            .on("/api/json", 200, &fixture("jenkins_jobs.json"))
+            .on("/job/app/lastBuild/api/json", 200, &fixture("jenkins_build_success.json"))
+            .on(
+                "/job/api/lastBuild/api/json",
+                200,
+                &fixture("jenkins_build_failure_pipeline.json"),
+            )
+            .on("/job/api/5/wfapi/describe", 200, &fixture("jenkins_wfapi_describe.json"));
+        let mut jenkins = integration(&server);
+        jenkins.set_watch(true);
+
+        assert_eq!(jenkins.get_status(), RemoteStatus::Failing);
+        assert_eq!(jenkins.get_status(), RemoteStatus::Failing);
+        let summary = jenkins.get_summary();
+        assert_eq!(summary.failing_jobs, vec!["api".to_string()]);
+        assert_eq!(summary.failed_stages, vec!["api: Test".to_string()]);
+        let requests = server.requests();
+        let build_requests = |job: &str| {
+            requests
+                .iter()
+                .filter(|request| request.contains(&format!("/job/{}/lastBuild/", job)))
+                .count()
+        };
+        // The failing one is fetched every time, in case it's been claimed
+        assert_eq!((build_requests("app"), build_requests("api")), (1, 2));
+        // The failed build's stages are only looked up once too
+        assert_eq!(
+            requests
+                .iter()
+                .filter(|request| request.contains("/wfapi/"))
+                .count(),
+            1
+        );
+        assert!(requests
+            .iter()
+            .any(|request| request.contains("tree=jobs[name,url,color,lastBuild[number]]")));
+
+        let changed = MockServer::start();
+        changed
+            .on("/api/json", 200, &fixture("jenkins_jobs_new.json"))
+            .on("/job/app/lastBuild/api/json", 200, &fixture("jenkins_build_success.json"));
+        jenkins.base_urls = BaseUrls::new(&changed.base_url, &[]);
+        assert_eq!(jenkins.get_status(), RemoteStatus::Passing);
+        assert!(changed
+            .requests()
+            .iter()
+            .any(|request| request.contains("/job/app/lastBuild/")));
+    }
+
+    #[test]
+    fn claims_are_noticed_while_the_job_list_stays_the_same() {
+        let server = MockServer::start();
+        server
+            .on("/api/json", 200, &fixture("jenkins_jobs.json"))
+            .on("/job/app/lastBuild/api/json", 200, &fixture("jenkins_build_success.json"))
+            .on("/job/api/lastBuild/api/json", 200, &fixture("jenkins_build_failure.json"));
+        let mut jenkins = integration(&server);
+        jenkins.set_watch(true);
+        assert_eq!(jenkins.get_status(), RemoteStatus::Failing);
+        assert!(jenkins.get_summary().claimed_jobs.is_empty());
+
+        let claimed = MockServer::start();
+        claimed
+            .on("/api/json", 200, &fixture("jenkins_jobs.json"))
+            .on(
+                "/job/api/lastBuild/api/json",
+                200,
+                &fixture("jenkins_build_failure_claimed.json"),
+            );
+        jenkins.base_urls = BaseUrls::new(&claimed.base_url, &[]);
+        assert_eq!(jenkins.get_status(), RemoteStatus::Failing);
+        assert_eq!(jenkins.get_summary().claimed_jobs, vec!["api".to_string()]);
+        assert!(!claimed
+            .requests()
+            .iter()
+            .any(|request| request.contains("/job/app/")));
+    }
+
+    #[test]
+    fn jobs_without_builds_are_not_built() {
+        let server = MockServer::start();
+        // "first" has its first build running, so lastBuild isn't there yet either
+        server
+            .on("/api/json", 200, &fixture("jenkins_jobs_new.json"))
+            .on("/job/app/lastBuild/api/json", 200, &fixture("jenkins_build_success.json"));
+        let mut jenkins = integration(&server);
+
+        assert_eq!(jenkins.get_status(), RemoteStatus::Passing);
+        let summary = jenkins.get_summary();
+        assert_eq!(
+            (summary.passing, summary.not_built, summary.indeterminate),
+            (1, 2, 0)
+        );
+        assert_eq!(summary.error, None);
+        let statuses: Vec<RemoteStatus> = summary.jobs.iter().map(|job| job.status).collect();
+        assert_eq!(
+            statuses,
+            vec![RemoteStatus::Passing, RemoteStatus::Unknown, RemoteStatus::Unknown]
+        );
+    }
+
+    #[test]
+    fn finished_build_without_a_result_is_not_built() {
+        let server = MockServer::start();
+        server
+            .on("/api/json", 200, &fixture("jenkins_jobs.json"))
+            .on("/job/app/lastBuild/api/json", 200, &fixture("jenkins_build_success.json"))
+            .on("/job/api/lastBuild/api/json", 200, &fixture("jenkins_build_no_result.json"));
+        let mut jenkins = integration(&server);
+
+        assert_eq!(jenkins.get_status(), RemoteStatus::Passing);
+        let summary = jenkins.get_summary();
+        assert_eq!(
+            (summary.passing, summary.not_built, summary.indeterminate),
+            (1, 1, 0)
+        );
+    }
+
+    #[test]
+    fn aborted_builds_are_counted_apart_unless_they_count_as_failures() {
+        let server = MockServer::start();
+        server
+            .on("/api/json", 200, &fixture("jenkins_jobs.json"))
+            .on("/job/app/lastBuild/api/json", 200, &fixture("jenkins_build_success.json"))
+            .on("/job/api/lastBuild/api/json", 200, &fixture("jenkins_build_aborted.json"));
+        let mut jenkins = integration(&server);
+
+        assert_eq!(jenkins.get_status(), RemoteStatus::Passing);
+        let summary = jenkins.get_summary();
+        assert_eq!(
+            (summary.passing, summary.failing, summary.aborted, summary.indeterminate),
+            (1, 0, 1, 0)
+        );
+
+        jenkins.set_aborted_is_failure(true);
+        assert_eq!(jenkins.get_status(), RemoteStatus::Failing);
+        let summary = jenkins.get_summary();
+        assert_eq!((summary.failing, summary.aborted), (1, 0));
+        assert_eq!(summary.failing_jobs, vec!["api".to_string()]);
+    }
+
+    #[test]
+    fn only_mainline_failures_count_in_full() {
+        let server = MockServer::start();
+        server
+            .on("/api/json", 200, &fixture("jenkins_jobs.json"))
+            .on("/job/app/lastBuild/api/json", 200, &fixture("jenkins_build_success.json"))
+            .on("/job/api/lastBuild/api/json", 200, &fixture("jenkins_build_failure.json"));
+        let mut jenkins = integration(&server);
+
+        jenkins.set_mainline(Some(JobPattern::parse("^app$").unwrap()));
+        assert_eq!(jenkins.get_status(), RemoteStatus::Failing);
+        assert!(jenkins.get_summary().non_mainline_failures_only);
+
+        jenkins.set_mainline(Some(JobPattern::parse("^api$").unwrap()));
+        jenkins.get_status();
+        assert!(!jenkins.get_summary().non_mainline_failures_only);
+    }
+
+    #[test]
+    fn oversized_job_list_is_refused() {
+        let server = MockServer::start();
+        server.on("/api/json", 200, &fixture("jenkins_jobs.json"));
+        let config = HttpConfig {
+            max_response_kilobytes: 0,
+            ..HttpConfig::default()
+        };
+        let mut jenkins = JenkinsIntegration {
+            client: server.client_with(&config),
+            ..integration(&server)
+        };
+
+        assert_eq!(jenkins.get_status(), RemoteStatus::Unknown);
+        let summary = jenkins.get_summary();
+        assert!(summary.disconnected);
+        assert!(summary.error.unwrap().contains("larger than 0 KB"));
+        assert!(server
+            .requests()
+            .iter()
+            .any(|request| request.starts_with("GET /api/json?tree=jobs[name,url,color] ")));
+    }
+
+    #[test]
+    fn malformed_job_list_is_disconnected() {
+        let server = MockServer::start();
+        server.on("/api/json", 200, &fixture("malformed.json"));
+        let mut jenkins = integration(&server);
+
+        assert_eq!(jenkins.get_status(), RemoteStatus::Unknown);
+        let summary = jenkins.get_summary();
+        assert!(summary.disconnected);
+        assert!(summary.error.is_some());
+    }
+
+    #[test]
+    fn malformed_build_counts_as_indeterminate() {
+        let server = MockServer::start();
+        server
+            .on("/api/json", 200, &fixture("jenkins_jobs.json"))
+            .on("/job/app/lastBuild/api/json", 200, &fixture("jenkins_build_success.json"))
+            .on("/job/api/lastBuild/api/json", 200, &fixture("malformed.json"));
+        let mut jenkins = integration(&server);
+
+        assert_eq!(jenkins.get_status(), RemoteStatus::Passing);
+        let summary = jenkins.get_summary();
+        assert_eq!((summary.passing, summary.indeterminate), (1, 1));
+        assert!(!summary.disconnected);
+        assert!(summary.error.is_some());
+    }
+
+    #[test]
+    fn server_error_is_disconnected() {
+        let server = MockServer::start();
+        server.on("/api/json", 500, "");
+        let mut jenkins = integration(&server);
+
+        assert_eq!(jenkins.get_status(), RemoteStatus::Unknown);
+        assert!(jenkins.get_summary().disconnected);
+    }
+
+    #[test]
+    fn crumb_is_sent_when_jenkins_issues_one() {
+        let server = MockServer::start();
+        server
+            .on("/crumbIssuer/api/json", 200, &fixture("jenkins_crumb.json"))
+            .on("/api/json", 200, &fixture("jenkins_jobs.json"))
+            .on("/job/app/lastBuild/api/json", 200, &fixture("jenkins_build_success.json"))
+            .on("/job/api/lastBuild/api/json", 200, &fixture("jenkins_build_success.json"));
+        let mut jenkins = integration(&server);
+
+        assert_eq!(jenkins.get_status(), RemoteStatus::Passing);
+        let requests = server.requests();
+        let job_list_request = requests
+            .iter()
+            .find(|request| request.starts_with("GET /api/json?"))
+            .unwrap();
+        assert!(job_list_request
+            .to_lowercase()
+            .contains("jenkins-crumb: 0123abcd"));
+    }
+
+    #[test]
+    fn long_queue_is_flagged() {
+        let server = MockServer::start();
+        server
+            .on("/api/json", 200, &fixture("jenkins_jobs.json"))
+            .on("/job/app/lastBuild/api/json", 200, &fixture("jenkins_build_success.json"))
+            .on("/job/api/lastBuild/api/json", 200, &fixture("jenkins_build_success.json"))
+            .on("/queue/api/json", 200, &fixture("jenkins_queue.json"));
+        let mut jenkins = integration(&server);
+        jenkins.queue_threshold = Some(2);
+
+        assert_eq!(jenkins.get_status(), RemoteStatus::Passing);
+        let summary = jenkins.get_summary();
+        assert_eq!(summary.queued, Some(3));
+        assert!(summary.queue_backed_up);
+
+        jenkins.queue_threshold = Some(3);
+        jenkins.get_status();
+        assert!(!jenkins.get_summary().queue_backed_up);
+    }
+
+    #[test]
+    fn pipeline_change_authors_are_culprits() {
+        let server = MockServer::start();
+        server
+            .on("/api/json", 200, &fixture("jenkins_jobs.json"))
+            .on("/job/app/lastBuild/api/json", 200, &fixture("jenkins_build_success.json"))
+            .on(
+                "/job/api/lastBuild/api/json",
+                200,
+                &fixture("jenkins_build_failure_pipeline.json"),
+            );
+        let mut jenkins = integration(&server);
+
+        assert_eq!(jenkins.get_status(), RemoteStatus::Failing);
+        assert_eq!(
+            jenkins.get_summary().culprits,
+            vec!["Sam Smith".to_string(), "Alex Lee".to_string()]
+        );
+    }
+
+    #[test]
+    fn pull_request_jobs_are_kept_apart() {
+        let server = MockServer::start();
+        server
+            .on("/api/json", 200, &fixture("jenkins_jobs.json"))
+            .on("/job/app/lastBuild/api/json", 200, &fixture("jenkins_build_success.json"))
+            .on("/job/api/lastBuild/api/json", 200, &fixture("jenkins_build_failure.json"));
+        let pr_jobs = vec!["api".to_string()];
+        let mut mainline = integration(&server);
+        mainline.jobs = JobSelection::Mainline(pr_jobs.clone());
+
+        assert_eq!(mainline.get_status(), RemoteStatus::Passing);
+        assert_eq!(mainline.get_summary().passing, 1);
+        assert!(mainline.get_summary().pull_request_jobs.is_empty());
+        assert!(server
+            .requests()
+            .iter()
+            .all(|request| !request.contains("/job/api/")));
+
+        // With a PR LED, the same poll fetches them for it
+        mainline.jobs = JobSelection::MainlineAndPullRequests(pr_jobs);
+        assert_eq!(mainline.get_status(), RemoteStatus::Passing);
+        let summary = mainline.get_summary();
+        assert_eq!(summary.failing, 0);
+        assert_eq!(
+            summary.pull_request_jobs,
+            vec![JobStatus {
+                name: "api".to_string(),
+                status: RemoteStatus::Failing,
+            }]
+        );
+    }
+
+    #[test]
+    fn claimed_failure_is_noted() {
+        let server = MockServer::start();
+        server
+            .on("/api/json", 200, &fixture("jenkins_jobs.json"))
+            .on("/job/app/lastBuild/api/json", 200, &fixture("jenkins_build_failure.json"))
+            .on(
+                "/job/api/lastBuild/api/json",
+                200,
+                &fixture("jenkins_build_failure_claimed.json"),
+            );
+        let mut jenkins = integration(&server);
+
+        assert_eq!(jenkins.get_status(), RemoteStatus::Failing);
+        let summary = jenkins.get_summary();
+        assert_eq!(summary.failing, 2);
+        assert_eq!(summary.claimed_jobs, vec!["api".to_string()]);
+    }
+
+    #[test]
+    fn failed_pipeline_stage_is_named() {
+        let server = MockServer::start();
+        server
+            .on("/api/json", 200, &fixture("jenkins_jobs.json"))
+            .on("/job/app/lastBuild/api/json", 200, &fixture("jenkins_build_success.json"))
+            .on(
+                "/job/api/lastBuild/api/json",
+                200,
+                &fixture("jenkins_build_failure_pipeline.json"),
+            )
+            .on("/job/api/5/wfapi/describe", 200, &fixture("jenkins_wfapi_describe.json"));
+        let mut jenkins = integration(&server);
+
+        assert_eq!(jenkins.get_status(), RemoteStatus::Failing);
+        let summary = jenkins.get_summary();
+        assert_eq!(summary.failed_stages, vec!["api: Test".to_string()]);
+        // Stages are only looked up for failing jobs
+        assert!(server
+            .requests()
+            .iter()
+            .all(|request| !request.contains("/job/app/") || !request.contains("wfapi")));
+    }
+
+    #[test]
+    fn unreachable_server_falls_back_to_a_mirror() {
+        let server = MockServer::start();
+        server
+            .on("/api/json", 200, &fixture("jenkins_jobs.json"))
+            .on("/job/app/lastBuild/api/json", 200, &fixture("jenkins_build_success.json"))
+            .on("/job/api/lastBuild/api/json", 200, &fixture("jenkins_build_success.json"));
+        let mut jenkins = integration(&server);
+        // Nothing listens on port 1
+        jenkins.base_urls = BaseUrls::new("http://127.0.0.1:1", &[server.base_url.clone()]);
+
+        assert_eq!(jenkins.get_status(), RemoteStatus::Passing);
+        assert!(!jenkins.get_summary().disconnected);
+        assert_eq!(jenkins.base_urls.current(), server.base_url);
+    }
+}
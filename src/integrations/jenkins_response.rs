@@ -10,15 +10,142 @@ pub struct JenkinsJob {
     pub color: JenkinsJobColor,
 }
 
+// From crumbIssuer/api/json, when CSRF protection is on
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JenkinsCrumb {
+    pub crumb: String,
+    pub crumb_request_field: String,
+}
+
+// From queue/api/json
+#[derive(Deserialize)]
+pub struct JenkinsQueueResponse {
+    pub items: Vec<JenkinsQueueItem>,
+}
+
+// Only counted, so nothing about it is needed
+#[derive(Deserialize)]
+pub struct JenkinsQueueItem {}
+
 #[derive(Deserialize)]
 pub struct JenkinsBuildResult {
+    pub number: u32,
     pub building: bool,
+    // When the build started, in milliseconds since the epoch
+    pub timestamp: Option<i64>,
+    // How long the build took in milliseconds, or 0 while it's running
+    pub duration: Option<u64>,
+    // How long Jenkins expects it to take, from earlier builds, or -1 if it can't tell
+    #[serde(rename = "estimatedDuration")]
+    pub estimated_duration: Option<i64>,
 
     #[serde(rename = "result")]
     pub build_result: Option<JenkinsBuildStatus>,
+
+    // Committers since the last successful build
+    #[serde(default)]
+    pub culprits: Vec<JenkinsUser>,
+    // Freestyle jobs have a single change set, pipelines one per checkout
+    #[serde(rename = "changeSet")]
+    pub change_set: Option<JenkinsChangeSet>,
+    #[serde(rename = "changeSets", default)]
+    pub change_sets: Vec<JenkinsChangeSet>,
+
+    // Plugins add their own actions, and most of them don't matter here
+    #[serde(default)]
+    pub actions: Vec<Option<JenkinsAction>>,
 }
 
-#[derive(Deserialize, Debug, PartialEq)]
+impl JenkinsBuildResult {
+    // Who may have broken the build: the culprits if Jenkins worked them out,
+    // otherwise the authors of this build's changes.
+    pub fn culprit_names(&self) -> Vec<String> {
+        let authors = self
+            .change_set
+            .iter()
+            .chain(self.change_sets.iter())
+            .flat_map(|change_set| change_set.items.iter().map(|change| &change.author));
+        let users: Vec<&JenkinsUser> = if self.culprits.is_empty() {
+            authors.collect()
+        } else {
+            self.culprits.iter().collect()
+        };
+        let mut names: Vec<String> = Vec::new();
+        for user in users {
+            if !names.contains(&user.full_name) {
+                names.push(user.full_name.clone());
+            }
+        }
+        names
+    }
+
+    // Whoever claimed the build through the Claim plugin, if anyone did.
+    pub fn claimed_by(&self) -> Option<String> {
+        self.actions
+            .iter()
+            .filter_map(|action| action.as_ref())
+            .find(|action| action.claimed)
+            .map(|action| {
+                action
+                    .claimed_by
+                    .clone()
+                    .unwrap_or_else(|| "someone".to_string())
+            })
+    }
+}
+
+#[derive(Deserialize)]
+pub struct JenkinsAction {
+    // Only the Claim plugin's action has these
+    #[serde(default)]
+    pub claimed: bool,
+    #[serde(rename = "claimedBy")]
+    pub claimed_by: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct JenkinsChangeSet {
+    #[serde(default)]
+    pub items: Vec<JenkinsChange>,
+}
+
+#[derive(Deserialize)]
+pub struct JenkinsChange {
+    pub author: JenkinsUser,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JenkinsUser {
+    pub full_name: String,
+}
+
+// From a pipeline build's wfapi/describe
+#[derive(Deserialize)]
+pub struct JenkinsRunDescription {
+    #[serde(default)]
+    pub stages: Vec<JenkinsStage>,
+}
+
+impl JenkinsRunDescription {
+    // The first stage that broke the build, if it was a stage that did.
+    pub fn failed_stage(&self) -> Option<&str> {
+        self.stages
+            .iter()
+            .find(|stage| stage.status == "FAILED" || stage.status == "UNSTABLE")
+            .map(|stage| stage.name.as_str())
+    }
+}
+
+#[derive(Deserialize)]
+pub struct JenkinsStage {
+    pub name: String,
+    // e.g. SUCCESS, FAILED, UNSTABLE or NOT_EXECUTED
+    pub status: String,
+}
+
+#[derive(Deserialize, Debug, PartialEq, Clone, Copy)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum JenkinsBuildStatus {
     Success,
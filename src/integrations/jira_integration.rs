@@ -0,0 +1,243 @@
+use budget::RequestBudget;
+use credentials::Credentials;
+use failure::Error;
+use headers::{Headers, AUTHORIZATION};
+use integrations::jira_response::*;
+use integrations::remote_integration::RemoteIntegration;
+use network::{self, get_basic_credentials, get_url_response, HttpClient};
+use remote_status::{JobStatus, RemoteStatus, StatusSummary};
+use reqwest::Url;
+use std::time::Duration;
+
+// The issues listed in the logs. The count comes from the search's total.
+const ISSUES_PER_PAGE: usize = 50;
+
+// How many issues a JQL query finds, e.g. the open blockers of the coming
+// release. More than warning_threshold shows as a warning, more than
+// failing_threshold as failing.
+pub struct JiraIntegration {
+    r: u16,
+    g: u16,
+    b: u16,
+    name: String,
+    base_url: String,
+    jql: String,
+    username: String,
+    // An API token on Jira Cloud
+    password: String,
+    warning_threshold: u64,
+    failing_threshold: u64,
+    client: HttpClient,
+    last_summary: StatusSummary,
+    // Checked for a reloaded login before every poll, if set
+    credentials: Option<Credentials>,
+}
+
+impl JiraIntegration {
+    pub fn new(
+        r: u16,
+        g: u16,
+        b: u16,
+        name: &str,
+        base_url: &str,
+        jql: &str,
+        username: &str,
+        password: &str,
+        warning_threshold: u64,
+        failing_threshold: u64,
+        client: HttpClient,
+    ) -> JiraIntegration {
+        JiraIntegration {
+            r: r,
+            g: g,
+            b: b,
+            name: name.to_string(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            jql: jql.to_string(),
+            username: username.to_string(),
+            password: password.to_string(),
+            warning_threshold: warning_threshold,
+            failing_threshold: failing_threshold,
+            client: client,
+            last_summary: StatusSummary::default(),
+            credentials: None,
+        }
+    }
+
+    // Picks up a login reloaded into credentials, see CredentialReloader.
+    pub fn set_credentials(&mut self, credentials: Credentials) {
+        self.credentials = Some(credentials);
+    }
+
+    // Anonymous without a username, for Jira servers that let anyone browse.
+    fn headers(&self) -> Headers {
+        let mut headers = Headers::new();
+        if !self.username.is_empty() {
+            headers.set(
+                AUTHORIZATION,
+                get_basic_credentials(&self.username, Some(self.password.clone())),
+            );
+        }
+        headers
+    }
+
+    fn search_url(&self) -> Result<String, Error> {
+        let mut url = Url::parse(&format!("{}/rest/api/2/search", self.base_url))?;
+        url.query_pairs_mut()
+            .append_pair("jql", &self.jql)
+            .append_pair("fields", "summary")
+            .append_pair("maxResults", &ISSUES_PER_PAGE.to_string());
+        Ok(url.to_string())
+    }
+}
+
+impl RemoteIntegration for JiraIntegration {
+    fn get_red_id(&self) -> u16 {
+        self.r
+    }
+    fn get_green_id(&self) -> u16 {
+        self.g
+    }
+    fn get_blue_id(&self) -> u16 {
+        self.b
+    }
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+    fn get_summary(&self) -> StatusSummary {
+        self.last_summary.clone()
+    }
+    fn get_rate_limit_delay(&self) -> Option<Duration> {
+        self.client.rate_limited_for()
+    }
+    fn get_request_budget(&self) -> Option<RequestBudget> {
+        self.client.request_budget()
+    }
+
+    fn get_status(&mut self) -> RemoteStatus {
+        if let Some(login) = self.credentials.as_ref().and_then(|c| c.login()) {
+            let password = login.secret.unwrap_or_default();
+            if login.username != self.username || password != self.password {
+                info!("--JIRA--: Switching to the reloaded credentials.");
+                self.username = login.username;
+                self.password = password;
+            }
+        }
+        self.client.start_poll();
+        let search: Result<(JiraSearch, Headers), Error> = self
+            .search_url()
+            .and_then(|url| get_url_response(&self.client, &url, self.headers()));
+        let search = match search {
+            Ok((search, _)) => search,
+            Err(err) => {
+                warn!(
+                    "--JIRA--: Failed to search for \"{}\". Error: {}",
+                    self.jql, err
+                );
+                self.last_summary = if network::is_unreachable(&err) {
+                    StatusSummary::disconnected(err.to_string())
+                } else {
+                    StatusSummary {
+                        error: Some(err.to_string()),
+                        ..StatusSummary::default()
+                    }
+                };
+                return RemoteStatus::Unknown;
+            }
+        };
+
+        // The query is the one job
+        let count = search.total;
+        let failing = count > self.failing_threshold;
+        let warning = !failing && count > self.warning_threshold;
+        let issues: Vec<String> = search
+            .issues
+            .iter()
+            .map(|issue| {
+                format!(
+                    "{} {} ({}/browse/{})",
+                    issue.key,
+                    issue.fields.summary.as_ref().map_or("", |s| s.as_str()),
+                    self.base_url,
+                    issue.key
+                )
+            })
+            .collect();
+        if failing || warning {
+            warn!("--JIRA--: {} issues found by \"{}\".", count, self.jql);
+            for issue in &issues {
+                warn!("--JIRA--: {}", issue);
+            }
+        }
+        let status = if failing {
+            RemoteStatus::Failing
+        } else {
+            RemoteStatus::Passing
+        };
+        self.last_summary = StatusSummary {
+            passing: if failing { 0 } else { 1 },
+            failing: if failing { 1 } else { 0 },
+            failing_jobs: if failing {
+                vec![self.jql.clone()]
+            } else {
+                Vec::new()
+            },
+            warning_jobs: if warning {
+                vec![self.jql.clone()]
+            } else {
+                Vec::new()
+            },
+            failed_builds: if failing { issues } else { Vec::new() },
+            jobs: vec![JobStatus {
+                name: self.jql.clone(),
+                status: status,
+            }],
+            ..StatusSummary::default()
+        };
+        info!(
+            "--JIRA--: {} issues. {}.",
+            count,
+            self.last_summary.describe()
+        );
+        status
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_support::{fixture, MockServer};
+
+    #[test]
+    fn open_blockers_turn_the_light_red() {
+        let server = MockServer::start();
+        server.on("/rest/api/2/search", 200, &fixture("jira_search.json"));
+        let mut jira = JiraIntegration::new(
+            0,
+            0,
+            0,
+            "JIRA",
+            &server.base_url,
+            "priority = Blocker AND fixVersion = 1.4 AND resolution = Unresolved",
+            "light@example.com",
+            "jira-api-token",
+            0,
+            0,
+            server.client(),
+        );
+
+        assert_eq!(jira.get_status(), RemoteStatus::Failing);
+        let summary = jira.get_summary();
+        assert_eq!(summary.failed_builds.len(), 2);
+        assert!(summary.failed_builds[0].starts_with("LIGHT-231 Build light stays green"));
+        assert!(summary.failed_builds[0].ends_with("/browse/LIGHT-231)"));
+        let request = &server.requests()[0];
+        assert!(request.contains("jql=priority+%3D+Blocker"));
+        assert!(request.contains("Authorization: Basic"));
+
+        jira.warning_threshold = 1;
+        jira.failing_threshold = 5;
+        assert_eq!(jira.get_status(), RemoteStatus::Passing);
+        assert_eq!(jira.get_summary().warning_jobs.len(), 1);
+    }
+}
@@ -0,0 +1,18 @@
+// From rest/api/2/search
+#[derive(Deserialize)]
+pub struct JiraSearch {
+    // Every match, not just the ones on this page
+    pub total: u64,
+    pub issues: Vec<JiraIssue>,
+}
+
+#[derive(Deserialize)]
+pub struct JiraIssue {
+    pub key: String,
+    pub fields: JiraIssueFields,
+}
+
+#[derive(Deserialize)]
+pub struct JiraIssueFields {
+    pub summary: Option<String>,
+}
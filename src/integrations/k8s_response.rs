@@ -0,0 +1,49 @@
+// From apis/apps/v1/namespaces/{namespace}/deployments
+#[derive(Deserialize)]
+pub struct K8sDeploymentList {
+    pub items: Vec<K8sDeployment>,
+}
+
+#[derive(Deserialize)]
+pub struct K8sDeployment {
+    pub metadata: K8sMetadata,
+    pub spec: K8sDeploymentSpec,
+    #[serde(default)]
+    pub status: K8sDeploymentStatus,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct K8sMetadata {
+    pub name: String,
+    pub namespace: String,
+    pub creation_timestamp: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct K8sDeploymentSpec {
+    // Left out means 1
+    pub replicas: Option<u32>,
+}
+
+// Counts Kubernetes hasn't got round to yet are left out, so they default to 0
+#[derive(Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub struct K8sDeploymentStatus {
+    pub replicas: u32,
+    pub updated_replicas: u32,
+    pub ready_replicas: u32,
+    pub conditions: Vec<K8sCondition>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct K8sCondition {
+    // e.g. "Available" or "Progressing"
+    #[serde(rename = "type")]
+    pub condition_type: String,
+    // "True", "False" or "Unknown"
+    pub status: String,
+    pub reason: Option<String>,
+    pub message: Option<String>,
+}
@@ -1,5 +1,30 @@
+pub mod aggregation;
+pub mod bitbucket_integration;
+mod bitbucket_response;
+pub mod demo_integration;
+pub mod exec_integration;
+pub mod github_integration;
+mod github_response;
+pub mod grafana_integration;
+mod grafana_response;
+pub mod icinga_integration;
+mod icinga_response;
 pub mod jenkins_integration;
 mod jenkins_response;
+pub mod jira_integration;
+mod jira_response;
+pub mod k8s_integration;
+mod k8s_response;
+pub mod plugin_integration;
+mod plugin_response;
+pub mod pull_request_integration;
+pub mod relayed_integration;
 pub mod remote_integration;
+pub mod sentry_integration;
+mod sentry_response;
+pub mod sonarqube_integration;
+mod sonarqube_response;
+pub mod team_city_integration;
+mod team_city_response;
 pub mod unity_cloud_integration;
 mod unity_cloud_response;
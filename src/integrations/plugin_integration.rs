@@ -0,0 +1,311 @@
+use budget::RequestBudget;
+use failure::Error;
+use integrations::plugin_response::*;
+use integrations::remote_integration::RemoteIntegration;
+use libc::{self, c_char, c_void};
+use remote_status::{JobStatus, RemoteStatus, StatusSummary};
+use serde_json;
+use std::ffi::{CStr, CString};
+use std::mem;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+// Bumped whenever the functions below change, so an old plugin is refused
+// instead of crashing the build light.
+pub const ABI_VERSION: u32 = 1;
+
+// A plugin is a shared library exporting, with C linkage:
+//
+//   uint32_t build_light_plugin_abi_version(void);
+//   char *build_light_plugin_poll(const char *settings_json);
+//   void build_light_plugin_free(char *reply);
+//
+// poll gets the plugin's settings as a JSON object and returns a JSON
+// PluginReply, which is handed back to free once read. It's called from the
+// integration's own thread, and from several threads at once if the library
+// is listed more than once.
+type AbiVersionFn = unsafe extern "C" fn() -> u32;
+type PollFn = unsafe extern "C" fn(*const c_char) -> *mut c_char;
+type FreeFn = unsafe extern "C" fn(*mut c_char);
+
+pub struct Plugin {
+    path: String,
+    handle: *mut c_void,
+    poll: PollFn,
+    free: FreeFn,
+}
+
+// The handle and function pointers stay valid until the library is closed in
+// drop, and plugins have to cope with being polled from more than one thread.
+unsafe impl Send for Plugin {}
+unsafe impl Sync for Plugin {}
+
+impl Plugin {
+    pub fn load(path: &Path) -> Result<Plugin, Error> {
+        let path_string = path.to_string_lossy().into_owned();
+        let c_path = CString::new(path_string.clone())?;
+        unsafe {
+            let handle = libc::dlopen(c_path.as_ptr(), libc::RTLD_NOW | libc::RTLD_LOCAL);
+            if handle.is_null() {
+                return Err(format_err!(
+                    "Couldn't load plugin {}: {}",
+                    path_string,
+                    last_dl_error()
+                ));
+            }
+            // Closes the library again if a function is missing
+            let mut plugin = Plugin {
+                path: path_string,
+                handle: handle,
+                poll: missing_poll,
+                free: missing_free,
+            };
+            let abi_version: AbiVersionFn =
+                mem::transmute(plugin.symbol("build_light_plugin_abi_version")?);
+            let version = abi_version();
+            if version != ABI_VERSION {
+                return Err(format_err!(
+                    "Plugin {} was built for plugin ABI version {}, this build light only supports {}",
+                    plugin.path,
+                    version,
+                    ABI_VERSION
+                ));
+            }
+            plugin.poll = mem::transmute(plugin.symbol("build_light_plugin_poll")?);
+            plugin.free = mem::transmute(plugin.symbol("build_light_plugin_free")?);
+            Ok(plugin)
+        }
+    }
+
+    unsafe fn symbol(&self, name: &str) -> Result<*mut c_void, Error> {
+        let c_name = CString::new(name)?;
+        let symbol = libc::dlsym(self.handle, c_name.as_ptr());
+        if symbol.is_null() {
+            return Err(format_err!(
+                "Plugin {} doesn't export {}: {}",
+                self.path,
+                name,
+                last_dl_error()
+            ));
+        }
+        Ok(symbol)
+    }
+
+    pub fn poll(&self, settings_json: &str) -> Result<String, Error> {
+        let settings = CString::new(settings_json)?;
+        unsafe {
+            let reply = (self.poll)(settings.as_ptr());
+            if reply.is_null() {
+                return Err(format_err!("Plugin {} returned nothing", self.path));
+            }
+            let text = CStr::from_ptr(reply).to_string_lossy().into_owned();
+            (self.free)(reply);
+            Ok(text)
+        }
+    }
+}
+
+impl Drop for Plugin {
+    fn drop(&mut self) {
+        unsafe {
+            libc::dlclose(self.handle);
+        }
+    }
+}
+
+// Only there until load has found the real functions.
+unsafe extern "C" fn missing_poll(_: *const c_char) -> *mut c_char {
+    ::std::ptr::null_mut()
+}
+
+unsafe extern "C" fn missing_free(_: *mut c_char) {}
+
+unsafe fn last_dl_error() -> String {
+    let error = libc::dlerror();
+    if error.is_null() {
+        "unknown error".to_string()
+    } else {
+        CStr::from_ptr(error).to_string_lossy().into_owned()
+    }
+}
+
+// A third party integration from a shared library in the plugins directory,
+// see Plugin. The plugin makes its own requests; the build light just polls it
+// and shows what it says.
+pub struct PluginIntegration {
+    r: u16,
+    g: u16,
+    b: u16,
+    name: String,
+    plugin: Arc<Plugin>,
+    settings_json: String,
+    last_summary: StatusSummary,
+}
+
+impl PluginIntegration {
+    pub fn new(
+        r: u16,
+        g: u16,
+        b: u16,
+        name: &str,
+        plugin: Arc<Plugin>,
+        settings_json: &str,
+    ) -> PluginIntegration {
+        PluginIntegration {
+            r: r,
+            g: g,
+            b: b,
+            name: name.to_string(),
+            plugin: plugin,
+            settings_json: settings_json.to_string(),
+            last_summary: StatusSummary::default(),
+        }
+    }
+}
+
+// The status and summary a plugin's reply stands for. Commands run by
+// ExecIntegration can print the same replies.
+pub fn read_reply(reply: &str) -> Result<(RemoteStatus, StatusSummary), Error> {
+    let reply: PluginReply = serde_json::from_str(reply)?;
+    if let Some(error) = reply.error {
+        let summary = if reply.disconnected {
+            StatusSummary::disconnected(error)
+        } else {
+            StatusSummary {
+                error: Some(error),
+                ..StatusSummary::default()
+            }
+        };
+        return Ok((RemoteStatus::Unknown, summary));
+    }
+
+    let mut summary = StatusSummary::default();
+    for job in &reply.jobs {
+        let status = match job.status.as_str() {
+            "warning" => {
+                summary.warning_jobs.push(job.name.clone());
+                RemoteStatus::Passing
+            }
+            other => parse_status(other)?,
+        };
+        match status {
+            RemoteStatus::Passing => summary.passing += 1,
+            RemoteStatus::Failing => {
+                summary.failing += 1;
+                summary.failing_jobs.push(job.name.clone());
+            }
+            RemoteStatus::InProgress => summary.in_progress += 1,
+            RemoteStatus::Unknown => summary.indeterminate += 1,
+        }
+        summary.jobs.push(JobStatus {
+            name: job.name.clone(),
+            status: status,
+        });
+    }
+    summary.failed_builds = reply.details;
+    let status = match reply.status {
+        Some(ref status) => parse_status(status)?,
+        None if summary.failing > 0 => RemoteStatus::Failing,
+        None if summary.in_progress > 0 => RemoteStatus::InProgress,
+        None if summary.passing > 0 => RemoteStatus::Passing,
+        None => RemoteStatus::Unknown,
+    };
+    Ok((status, summary))
+}
+
+fn parse_status(status: &str) -> Result<RemoteStatus, Error> {
+    match status {
+        "passing" => Ok(RemoteStatus::Passing),
+        "failing" => Ok(RemoteStatus::Failing),
+        "in_progress" => Ok(RemoteStatus::InProgress),
+        "unknown" => Ok(RemoteStatus::Unknown),
+        other => Err(format_err!("Unknown status \"{}\"", other)),
+    }
+}
+
+impl RemoteIntegration for PluginIntegration {
+    fn get_red_id(&self) -> u16 {
+        self.r
+    }
+    fn get_green_id(&self) -> u16 {
+        self.g
+    }
+    fn get_blue_id(&self) -> u16 {
+        self.b
+    }
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+    fn get_summary(&self) -> StatusSummary {
+        self.last_summary.clone()
+    }
+    fn get_rate_limit_delay(&self) -> Option<Duration> {
+        None
+    }
+    fn get_request_budget(&self) -> Option<RequestBudget> {
+        None
+    }
+
+    fn get_status(&mut self) -> RemoteStatus {
+        let reply = self
+            .plugin
+            .poll(&self.settings_json)
+            .and_then(|reply| read_reply(&reply));
+        match reply {
+            Ok((status, summary)) => {
+                self.last_summary = summary;
+                if let Some(ref error) = self.last_summary.error {
+                    warn!("--{}--: The plugin reported an error: {}", self.name, error);
+                } else {
+                    info!("--{}--: {}.", self.name, self.last_summary.describe());
+                }
+                status
+            }
+            Err(err) => {
+                warn!(
+                    "--{}--: Failed to poll plugin {}. Error: {}",
+                    self.name, self.plugin.path, err
+                );
+                self.last_summary = StatusSummary {
+                    error: Some(err.to_string()),
+                    ..StatusSummary::default()
+                };
+                RemoteStatus::Unknown
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plugins_are_checked_and_their_replies_read() {
+        assert!(Plugin::load(Path::new("/nonexistent/libplugin.so")).is_err());
+        // Any library without the plugin functions is refused
+        let err = Plugin::load(Path::new("libc.so.6")).err().unwrap();
+        assert!(err.to_string().contains("build_light_plugin_abi_version"));
+
+        let (status, summary) = read_reply(
+            r#"{"jobs": [
+                {"name": "nightly", "status": "failing"},
+                {"name": "lint", "status": "warning"},
+                {"name": "deploy", "status": "in_progress"}
+            ], "details": ["nightly #12 https://ci.example.com/12"]}"#,
+        )
+        .unwrap();
+        assert_eq!(status, RemoteStatus::Failing);
+        assert_eq!(summary.failing_jobs, vec!["nightly".to_string()]);
+        assert_eq!(summary.warning_jobs, vec!["lint".to_string()]);
+        assert_eq!(summary.in_progress, 1);
+        assert_eq!(summary.failed_builds.len(), 1);
+
+        let (status, summary) =
+            read_reply(r#"{"error": "timed out", "disconnected": true}"#).unwrap();
+        assert_eq!(status, RemoteStatus::Unknown);
+        assert!(summary.disconnected);
+        assert!(read_reply(r#"{"status": "green"}"#).is_err());
+    }
+}
@@ -0,0 +1,24 @@
+// What a plugin's poll returns, as JSON.
+#[derive(Deserialize)]
+pub struct PluginReply {
+    // "passing", "failing", "in_progress" or "unknown". Worked out from jobs
+    // when left out.
+    pub status: Option<String>,
+    #[serde(default)]
+    pub jobs: Vec<PluginJob>,
+    // Enough about each failure to find it, e.g. a link
+    #[serde(default)]
+    pub details: Vec<String>,
+    // Set when the poll went wrong
+    pub error: Option<String>,
+    // The server couldn't be reached at all
+    #[serde(default)]
+    pub disconnected: bool,
+}
+
+#[derive(Deserialize)]
+pub struct PluginJob {
+    pub name: String,
+    // Like PluginReply's status, or "warning" for passing with warnings
+    pub status: String,
+}
@@ -0,0 +1,73 @@
+use integrations::remote_integration::RemoteIntegration;
+use job_groups;
+use remote_status::{RemoteStatus, StatusSummary};
+use status_board::StatusBoard;
+use std::time::Duration;
+
+// Shows the pull request jobs out of whatever the main Jenkins integration
+// last put on the status board, like a job group, instead of polling the
+// server a second time.
+pub struct PullRequestIntegration {
+    r: u16,
+    g: u16,
+    b: u16,
+    board: StatusBoard,
+    last_summary: StatusSummary,
+}
+
+impl PullRequestIntegration {
+    pub fn new(r: u16, g: u16, b: u16, board: StatusBoard) -> PullRequestIntegration {
+        PullRequestIntegration {
+            r: r,
+            g: g,
+            b: b,
+            board: board,
+            last_summary: StatusSummary::default(),
+        }
+    }
+}
+
+impl RemoteIntegration for PullRequestIntegration {
+    fn get_red_id(&self) -> u16 {
+        self.r
+    }
+    fn get_green_id(&self) -> u16 {
+        self.g
+    }
+    fn get_blue_id(&self) -> u16 {
+        self.b
+    }
+    fn get_name(&self) -> &str {
+        "Jenkins PRs"
+    }
+    fn get_summary(&self) -> StatusSummary {
+        self.last_summary.clone()
+    }
+    fn get_rate_limit_delay(&self) -> Option<Duration> {
+        None
+    }
+    // Reading the board costs nothing, Jenkins backs off on its own.
+    fn should_back_off(&self) -> bool {
+        false
+    }
+
+    fn get_status(&mut self) -> RemoteStatus {
+        let jenkins = self
+            .board
+            .snapshots()
+            .into_iter()
+            .find(|snapshot| snapshot.name == "Jenkins");
+        match jenkins {
+            Some(jenkins) => {
+                let (status, summary) = job_groups::pull_request_status(&jenkins.summary);
+                self.last_summary = summary;
+                status
+            }
+            // Jenkins hasn't been polled yet
+            None => {
+                self.last_summary = StatusSummary::default();
+                RemoteStatus::Unknown
+            }
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use integrations::remote_integration::RemoteIntegration;
+use relay::RelayFeed;
+use remote_status::{RemoteStatus, StatusSummary};
+use std::time::Duration;
+
+// Shows what the relay server last said about the integration with this name,
+// instead of polling its server.
+pub struct RelayedIntegration {
+    name: String,
+    r: u16,
+    g: u16,
+    b: u16,
+    feed: RelayFeed,
+    last_summary: StatusSummary,
+}
+
+impl RelayedIntegration {
+    pub fn new(name: &str, r: u16, g: u16, b: u16, feed: RelayFeed) -> RelayedIntegration {
+        RelayedIntegration {
+            name: name.to_string(),
+            r: r,
+            g: g,
+            b: b,
+            feed: feed,
+            last_summary: StatusSummary::default(),
+        }
+    }
+}
+
+impl RemoteIntegration for RelayedIntegration {
+    fn get_red_id(&self) -> u16 {
+        self.r
+    }
+    fn get_green_id(&self) -> u16 {
+        self.g
+    }
+    fn get_blue_id(&self) -> u16 {
+        self.b
+    }
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+    fn get_summary(&self) -> StatusSummary {
+        self.last_summary.clone()
+    }
+    fn get_rate_limit_delay(&self) -> Option<Duration> {
+        None
+    }
+    // Reading the feed costs nothing, and the relay client reconnects on its own.
+    fn should_back_off(&self) -> bool {
+        false
+    }
+
+    fn get_status(&mut self) -> RemoteStatus {
+        match self.feed.latest(&self.name) {
+            Ok(Some((status, summary))) => {
+                self.last_summary = summary;
+                status
+            }
+            // The server hasn't polled this integration yet
+            Ok(None) => {
+                self.last_summary = StatusSummary::default();
+                RemoteStatus::Unknown
+            }
+            Err(e) => {
+                self.last_summary = StatusSummary::disconnected(e);
+                RemoteStatus::Unknown
+            }
+        }
+    }
+}
@@ -1,8 +1,54 @@
-use RemoteStatus;
+use budget::RequestBudget;
+use remote_status::{RemoteStatus, StatusSummary};
+use std::time::Duration;
 
 pub trait RemoteIntegration {
     fn get_status(&mut self) -> RemoteStatus;
+    fn get_summary(&self) -> StatusSummary;
+    fn get_name(&self) -> &str;
+    // Set while the server is rate limiting us, to hold off the next poll.
+    fn get_rate_limit_delay(&self) -> Option<Duration>;
+    // What's left of the server's rate limit, if it says, to spread polls over.
+    fn get_request_budget(&self) -> Option<RequestBudget> {
+        None
+    }
     fn get_red_id(&self) -> u16;
     fn get_green_id(&self) -> u16;
     fn get_blue_id(&self) -> u16;
+    // Whether to poll less often while disconnected. Only worth it when a
+    // poll actually goes out to the server.
+    fn should_back_off(&self) -> bool {
+        true
+    }
+}
+
+// So the integration to run can be picked from the config at runtime.
+impl<T: RemoteIntegration + ?Sized> RemoteIntegration for Box<T> {
+    fn get_status(&mut self) -> RemoteStatus {
+        (**self).get_status()
+    }
+    fn get_summary(&self) -> StatusSummary {
+        (**self).get_summary()
+    }
+    fn get_name(&self) -> &str {
+        (**self).get_name()
+    }
+    fn get_rate_limit_delay(&self) -> Option<Duration> {
+        (**self).get_rate_limit_delay()
+    }
+    fn get_request_budget(&self) -> Option<RequestBudget> {
+        (**self).get_request_budget()
+    }
+    fn get_red_id(&self) -> u16 {
+        (**self).get_red_id()
+    }
+    fn get_green_id(&self) -> u16 {
+        (**self).get_green_id()
+    }
+    fn get_blue_id(&self) -> u16 {
+        (**self).get_blue_id()
+    }
+    fn should_back_off(&self) -> bool {
+        (**self).should_back_off()
+    }
 }
@@ -0,0 +1,264 @@
+use budget::RequestBudget;
+use credentials::Credentials;
+use failure::Error;
+use headers::{self, Headers, AUTHORIZATION, X_HITS};
+use integrations::remote_integration::RemoteIntegration;
+use integrations::sentry_response::*;
+use network::{self, get_url_response, HttpClient};
+use remote_status::{JobStatus, RemoteStatus, StatusSummary};
+use reqwest::Url;
+use std::time::Duration;
+
+// Only counted past this, unless Sentry says how many matched in X-Hits
+const ISSUES_PER_PAGE: usize = 100;
+
+// How many new issues a Sentry project has had lately. More than
+// warning_threshold in the window shows as a warning, more than
+// failing_threshold as failing.
+pub struct SentryIntegration {
+    r: u16,
+    g: u16,
+    b: u16,
+    name: String,
+    base_url: String,
+    organization: String,
+    project: String,
+    token: String,
+    window_minutes: u64,
+    warning_threshold: u64,
+    failing_threshold: u64,
+    client: HttpClient,
+    last_summary: StatusSummary,
+    // Checked for a reloaded login before every poll, if set
+    credentials: Option<Credentials>,
+}
+
+impl SentryIntegration {
+    pub fn new(
+        r: u16,
+        g: u16,
+        b: u16,
+        name: &str,
+        base_url: &str,
+        organization: &str,
+        project: &str,
+        token: &str,
+        window_minutes: u64,
+        warning_threshold: u64,
+        failing_threshold: u64,
+        client: HttpClient,
+    ) -> SentryIntegration {
+        SentryIntegration {
+            r: r,
+            g: g,
+            b: b,
+            name: name.to_string(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            organization: organization.to_string(),
+            project: project.to_string(),
+            token: token.to_string(),
+            window_minutes: window_minutes,
+            warning_threshold: warning_threshold,
+            failing_threshold: failing_threshold,
+            client: client,
+            last_summary: StatusSummary::default(),
+            credentials: None,
+        }
+    }
+
+    // Picks up a token reloaded into credentials, see CredentialReloader.
+    pub fn set_credentials(&mut self, credentials: Credentials) {
+        self.credentials = Some(credentials);
+    }
+
+    fn headers(&self) -> Headers {
+        let mut headers = Headers::new();
+        headers.set(AUTHORIZATION, headers::bearer_auth(&self.token));
+        headers
+    }
+
+    // Unresolved issues first seen within the window, newest first.
+    fn issues_url(&self) -> Result<String, Error> {
+        let mut url = Url::parse(&format!(
+            "{}/api/0/projects/{}/{}/issues/",
+            self.base_url, self.organization, self.project
+        ))?;
+        url.query_pairs_mut()
+            .append_pair(
+                "query",
+                &format!("is:unresolved firstSeen:-{}m", self.window_minutes),
+            )
+            .append_pair("sort", "new")
+            .append_pair("limit", &ISSUES_PER_PAGE.to_string());
+        Ok(url.to_string())
+    }
+
+    // The new issues on the first page, and how many there are in all.
+    fn get_new_issues(&self) -> Result<(Vec<SentryIssue>, u64), Error> {
+        let (issues, headers): (Vec<SentryIssue>, Headers) =
+            get_url_response(&self.client, &self.issues_url()?, self.headers())?;
+        let count = headers
+            .get_parsed::<u64>(X_HITS)
+            .unwrap_or(issues.len() as u64);
+        Ok((issues, count))
+    }
+}
+
+impl RemoteIntegration for SentryIntegration {
+    fn get_red_id(&self) -> u16 {
+        self.r
+    }
+    fn get_green_id(&self) -> u16 {
+        self.g
+    }
+    fn get_blue_id(&self) -> u16 {
+        self.b
+    }
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+    fn get_summary(&self) -> StatusSummary {
+        self.last_summary.clone()
+    }
+    fn get_rate_limit_delay(&self) -> Option<Duration> {
+        self.client.rate_limited_for()
+    }
+    fn get_request_budget(&self) -> Option<RequestBudget> {
+        self.client.request_budget()
+    }
+
+    fn get_status(&mut self) -> RemoteStatus {
+        if let Some(token) = self
+            .credentials
+            .as_ref()
+            .and_then(|c| c.login())
+            .and_then(|login| login.secret)
+        {
+            if token != self.token {
+                info!("--Sentry--: Switching to the reloaded token.");
+                self.token = token;
+            }
+        }
+        self.client.start_poll();
+        let (issues, count) = match self.get_new_issues() {
+            Ok(issues) => issues,
+            Err(err) => {
+                warn!(
+                    "--Sentry--: Failed to get the new issues of {}/{}. Error: {}",
+                    self.organization, self.project, err
+                );
+                self.last_summary = if network::is_unreachable(&err) {
+                    StatusSummary::disconnected(err.to_string())
+                } else {
+                    StatusSummary {
+                        error: Some(err.to_string()),
+                        ..StatusSummary::default()
+                    }
+                };
+                return RemoteStatus::Unknown;
+            }
+        };
+
+        // The project is the one job
+        let project = format!("{}/{}", self.organization, self.project);
+        let failing = count > self.failing_threshold;
+        let warning = !failing && count > self.warning_threshold;
+        let new_issues: Vec<String> = issues
+            .iter()
+            .map(|issue| match issue.permalink {
+                Some(ref link) => format!("{} {} ({})", issue.short_id, issue.title, link),
+                None => format!("{} {}", issue.short_id, issue.title),
+            })
+            .collect();
+        if failing || warning {
+            warn!(
+                "--Sentry--: {} new issues in {} in the last {} minutes.",
+                count, project, self.window_minutes
+            );
+            for issue in &new_issues {
+                warn!("--Sentry--: New: {}", issue);
+            }
+        }
+        let status = if failing {
+            RemoteStatus::Failing
+        } else {
+            RemoteStatus::Passing
+        };
+        self.last_summary = StatusSummary {
+            passing: if failing { 0 } else { 1 },
+            failing: if failing { 1 } else { 0 },
+            failing_jobs: if failing {
+                vec![project.clone()]
+            } else {
+                Vec::new()
+            },
+            warning_jobs: if warning {
+                vec![project.clone()]
+            } else {
+                Vec::new()
+            },
+            failed_builds: if failing { new_issues } else { Vec::new() },
+            jobs: vec![JobStatus {
+                name: project,
+                status: status,
+            }],
+            ..StatusSummary::default()
+        };
+        info!(
+            "--Sentry--: {} new issues in the last {} minutes. {}.",
+            count,
+            self.window_minutes,
+            self.last_summary.describe()
+        );
+        status
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_support::{fixture, MockServer};
+
+    fn sentry(server: &MockServer, failing_threshold: u64) -> SentryIntegration {
+        SentryIntegration::new(
+            0,
+            0,
+            0,
+            "Sentry",
+            &server.base_url,
+            "futurice",
+            "build-light",
+            "sentry-token",
+            30,
+            0,
+            failing_threshold,
+            server.client(),
+        )
+    }
+
+    #[test]
+    fn new_issues_past_the_thresholds_warn_then_fail() {
+        let server = MockServer::start();
+        server.on(
+            "/api/0/projects/futurice/build-light/issues/",
+            200,
+            &fixture("sentry_issues.json"),
+        );
+
+        let mut warning = sentry(&server, 5);
+        assert_eq!(warning.get_status(), RemoteStatus::Passing);
+        let summary = warning.get_summary();
+        assert_eq!(
+            summary.warning_jobs,
+            vec!["futurice/build-light".to_string()]
+        );
+        assert!(server.requests()[0].contains("firstSeen%3A-30m"));
+
+        let mut failing = sentry(&server, 1);
+        assert_eq!(failing.get_status(), RemoteStatus::Failing);
+        let summary = failing.get_summary();
+        assert!(summary.warning_jobs.is_empty());
+        assert_eq!(summary.failed_builds.len(), 2);
+        assert!(summary.failed_builds[0].starts_with("BUILD-LIGHT-2K TypeError"));
+    }
+}
@@ -0,0 +1,8 @@
+// One issue from api/0/projects/{organization}/{project}/issues/
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SentryIssue {
+    pub short_id: String,
+    pub title: String,
+    pub permalink: Option<String>,
+}
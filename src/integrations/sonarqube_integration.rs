@@ -0,0 +1,277 @@
+use budget::RequestBudget;
+use credentials::Credentials;
+use failure::Error;
+use headers::{Headers, AUTHORIZATION};
+use integrations::aggregation::{AggregationStrategy, JobOutcome, JobState};
+use integrations::remote_integration::RemoteIntegration;
+use integrations::sonarqube_response::*;
+use network::{self, get_basic_credentials, get_url_response, HttpClient};
+use remote_status::{JobStatus, RemoteStatus, StatusSummary};
+use reqwest::Url;
+use std::time::Duration;
+
+// One project's quality gate.
+struct Gate {
+    project_key: String,
+    state: JobState,
+    warning: bool,
+    // The metrics that failed or warned, e.g. "new_coverage 62.5"
+    conditions: Vec<String>,
+}
+
+// The quality gates of SonarQube projects, for a code quality light next to
+// the build light. Each project counts as a job: OK is passing, WARN passing
+// with a warning and ERROR failing.
+pub struct SonarQubeIntegration {
+    r: u16,
+    g: u16,
+    b: u16,
+    name: String,
+    base_url: String,
+    project_keys: Vec<String>,
+    token: Option<String>,
+    client: HttpClient,
+    last_summary: StatusSummary,
+    aggregation: Box<AggregationStrategy>,
+    // Checked for a reloaded login before every poll, if set
+    credentials: Option<Credentials>,
+}
+
+impl SonarQubeIntegration {
+    pub fn new(
+        r: u16,
+        g: u16,
+        b: u16,
+        name: &str,
+        base_url: &str,
+        project_keys: Vec<String>,
+        token: Option<String>,
+        client: HttpClient,
+        aggregation: Box<AggregationStrategy>,
+    ) -> SonarQubeIntegration {
+        SonarQubeIntegration {
+            r: r,
+            g: g,
+            b: b,
+            name: name.to_string(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            project_keys: project_keys,
+            token: token,
+            client: client,
+            last_summary: StatusSummary::default(),
+            aggregation: aggregation,
+            credentials: None,
+        }
+    }
+
+    // Picks up a token reloaded into credentials, see CredentialReloader.
+    pub fn set_credentials(&mut self, credentials: Credentials) {
+        self.credentials = Some(credentials);
+    }
+
+    // Tokens go in as the username, with no password.
+    fn headers(&self) -> Headers {
+        let mut headers = Headers::new();
+        if let Some(ref token) = self.token {
+            headers.set(AUTHORIZATION, get_basic_credentials(token, None));
+        }
+        headers
+    }
+
+    fn get_gate(&self, project_key: &str) -> Result<Gate, Error> {
+        let mut url = Url::parse(&format!(
+            "{}/api/qualitygates/project_status",
+            self.base_url
+        ))?;
+        url.query_pairs_mut().append_pair("projectKey", project_key);
+        let (response, _): (SonarQubeProjectStatusResponse, Headers) =
+            get_url_response(&self.client, url.as_str(), self.headers())?;
+        let status = response.project_status;
+        let (state, warning) = gate_state(&status.status);
+        Ok(Gate {
+            project_key: project_key.to_string(),
+            state: state,
+            warning: warning,
+            conditions: status
+                .conditions
+                .into_iter()
+                .filter(|condition| condition.status == "ERROR" || condition.status == "WARN")
+                .map(|condition| match condition.actual_value {
+                    Some(value) => format!("{} {}", condition.metric_key, value),
+                    None => condition.metric_key,
+                })
+                .collect(),
+        })
+    }
+}
+
+// Projects without a quality gate, or whose analysis hasn't finished, are
+// neither passing nor failing.
+fn gate_state(status: &str) -> (JobState, bool) {
+    match status {
+        "OK" => (JobState::Passing, false),
+        "WARN" => (JobState::Passing, true),
+        "ERROR" => (JobState::Failing, false),
+        _ => (JobState::Indeterminate, false),
+    }
+}
+
+impl RemoteIntegration for SonarQubeIntegration {
+    fn get_red_id(&self) -> u16 {
+        self.r
+    }
+    fn get_green_id(&self) -> u16 {
+        self.g
+    }
+    fn get_blue_id(&self) -> u16 {
+        self.b
+    }
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+    fn get_summary(&self) -> StatusSummary {
+        self.last_summary.clone()
+    }
+    fn get_rate_limit_delay(&self) -> Option<Duration> {
+        self.client.rate_limited_for()
+    }
+    fn get_request_budget(&self) -> Option<RequestBudget> {
+        self.client.request_budget()
+    }
+
+    fn get_status(&mut self) -> RemoteStatus {
+        if let Some(login) = self.credentials.as_ref().and_then(|c| c.login()) {
+            if login.secret != self.token {
+                info!("--SonarQube--: Switching to the reloaded token.");
+                self.token = login.secret;
+            }
+        }
+        self.client.start_poll();
+        let gates: Result<Vec<Gate>, Error> = self
+            .project_keys
+            .iter()
+            .map(|project_key| self.get_gate(project_key))
+            .collect();
+        let gates = match gates {
+            Ok(gates) => gates,
+            Err(err) => {
+                warn!(
+                    "--SonarQube--: Failed to get the quality gates. Error: {}",
+                    err
+                );
+                self.last_summary = if network::is_unreachable(&err) {
+                    StatusSummary::disconnected(err.to_string())
+                } else {
+                    StatusSummary {
+                        error: Some(err.to_string()),
+                        ..StatusSummary::default()
+                    }
+                };
+                return RemoteStatus::Unknown;
+            }
+        };
+        // Quality gates don't say when the analysis ran
+        let outcomes: Vec<JobOutcome> = gates
+            .iter()
+            .map(|gate| JobOutcome {
+                state: gate.state,
+                started_at: None,
+            })
+            .collect();
+        let status = self.aggregation.aggregate(&outcomes);
+
+        let count = |state: JobState| gates.iter().filter(|gate| gate.state == state).count();
+        let keys = |gates: Vec<&Gate>| -> Vec<String> {
+            gates.iter().map(|gate| gate.project_key.clone()).collect()
+        };
+        let failing: Vec<&Gate> = gates
+            .iter()
+            .filter(|gate| gate.state == JobState::Failing)
+            .collect();
+        let failed_builds: Vec<String> = failing
+            .iter()
+            .map(|gate| format!("{} ({})", gate.project_key, gate.conditions.join(", ")))
+            .collect();
+        for build in &failed_builds {
+            warn!("--SonarQube--: {} failed its quality gate.", build);
+        }
+        self.last_summary = StatusSummary {
+            passing: count(JobState::Passing),
+            failing: failing.len(),
+            in_progress: count(JobState::InProgress),
+            indeterminate: count(JobState::Indeterminate),
+            failing_jobs: keys(failing),
+            warning_jobs: keys(gates.iter().filter(|gate| gate.warning).collect()),
+            failed_builds: failed_builds,
+            jobs: gates
+                .iter()
+                .map(|gate| JobStatus {
+                    name: gate.project_key.clone(),
+                    status: gate.state.status(),
+                })
+                .collect(),
+            ..StatusSummary::default()
+        };
+        info!("--SonarQube--: {}.", self.last_summary.describe());
+        status
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use integrations::aggregation::AggregationKind;
+    use test_support::{fixture, MockServer};
+
+    fn sonarqube(server: &MockServer, project_keys: &[&str]) -> SonarQubeIntegration {
+        SonarQubeIntegration::new(
+            0,
+            0,
+            0,
+            "SonarQube",
+            &server.base_url,
+            project_keys.iter().map(|key| key.to_string()).collect(),
+            Some("sonar-token".to_string()),
+            server.client(),
+            AggregationKind::AnyFailureIsRed.strategy(),
+        )
+    }
+
+    #[test]
+    fn quality_gates_pass_warn_and_fail() {
+        let server = MockServer::start();
+        let path = "/api/qualitygates/project_status?projectKey=";
+        server
+            .on(
+                &format!("{}backend", path),
+                200,
+                &fixture("sonarqube_gate_ok.json"),
+            )
+            .on(
+                &format!("{}frontend", path),
+                200,
+                &fixture("sonarqube_gate_warn.json"),
+            )
+            .on(
+                &format!("{}mobile", path),
+                200,
+                &fixture("sonarqube_gate_error.json"),
+            );
+
+        let mut warning = sonarqube(&server, &["backend", "frontend"]);
+        assert_eq!(warning.get_status(), RemoteStatus::Passing);
+        let summary = warning.get_summary();
+        assert_eq!(summary.passing, 2);
+        assert_eq!(summary.warning_jobs, vec!["frontend".to_string()]);
+        assert!(server.requests()[0].contains("Authorization: Basic"));
+
+        let mut failing = sonarqube(&server, &["backend", "frontend", "mobile"]);
+        assert_eq!(failing.get_status(), RemoteStatus::Failing);
+        let summary = failing.get_summary();
+        assert_eq!(summary.failing_jobs, vec!["mobile".to_string()]);
+        assert_eq!(
+            summary.failed_builds,
+            vec!["mobile (new_coverage 62.5)".to_string()]
+        );
+    }
+}
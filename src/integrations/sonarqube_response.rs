@@ -0,0 +1,23 @@
+// From api/qualitygates/project_status
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SonarQubeProjectStatusResponse {
+    pub project_status: SonarQubeProjectStatus,
+}
+
+// Statuses are left as strings, see gate_state
+#[derive(Deserialize)]
+pub struct SonarQubeProjectStatus {
+    // "OK", "WARN", "ERROR", or "NONE" without a quality gate
+    pub status: String,
+    #[serde(default)]
+    pub conditions: Vec<SonarQubeCondition>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SonarQubeCondition {
+    pub status: String,
+    pub metric_key: String,
+    pub actual_value: Option<String>,
+}
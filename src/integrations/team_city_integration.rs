@@ -0,0 +1,642 @@
+use budget::RequestBudget;
+use chrono::DateTime;
+use credentials::Credentials;
+use failure::Error;
+use headers::{Headers, AUTHORIZATION};
+use integrations::aggregation::{AggregationStrategy, JobOutcome, JobState};
+use integrations::remote_integration::RemoteIntegration;
+use integrations::team_city_response::*;
+use network::{self, get_basic_credentials, get_url_body, get_url_response, HttpClient};
+use remote_status::{JobStatus, RemoteStatus, StatusSummary};
+use reqwest::Url;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::time::Duration;
+
+// Each build configuration's latest build, running or not, and only what's
+// shown or decided on
+const BUILD_TYPE_FIELDS: &str = "buildType(id,name,projectName,builds($locator(running:any,count:1),build(id,number,state,status,statusText,startDate,testOccurrences(failed,muted))))";
+// Just enough to tell when a build has started, finished or changed status,
+// see set_watch
+const WATCH_FIELDS: &str =
+    "buildType(id,builds($locator(running:any,count:1),build(id,state,status)))";
+const INVESTIGATION_FIELDS: &str =
+    "investigation(state,assignee(username,name),scope(buildTypes(buildType(id))),target(anyProblem))";
+
+// One build configuration's latest build.
+#[derive(Clone)]
+struct Configuration {
+    id: String,
+    // As "Project :: Build configuration"
+    name: String,
+    state: JobState,
+    build_id: Option<u64>,
+    number: Option<String>,
+    status_text: Option<String>,
+    // Milliseconds since the epoch
+    started_at: Option<i64>,
+    failed_tests: u32,
+    muted_tests: u32,
+    // Why a failure doesn't need anyone's attention, e.g. "investigated by Jane Doe"
+    acknowledged: Option<String>,
+}
+
+// Team City build configurations, each counting as a job. A failing one
+// that's under investigation, or whose failed tests have all been muted or
+// are being investigated since, counts as claimed, so it shows steady instead
+// of blinking red.
+pub struct TeamCityIntegration {
+    r: u16,
+    g: u16,
+    b: u16,
+    name: String,
+    base_url: String,
+    username: String,
+    password: String,
+    // Only the build configurations in this project and its subprojects, if set
+    project: Option<String>,
+    client: HttpClient,
+    last_summary: StatusSummary,
+    aggregation: Box<AggregationStrategy>,
+    // Checked for a reloaded login before every poll, if set
+    credentials: Option<Credentials>,
+    // Only fetch the builds again when one has changed, see set_watch
+    watch: bool,
+    last_listing: Option<(u64, Vec<Configuration>)>,
+}
+
+impl TeamCityIntegration {
+    pub fn new(
+        r: u16,
+        g: u16,
+        b: u16,
+        name: &str,
+        base_url: &str,
+        username: &str,
+        password: &str,
+        project: Option<String>,
+        client: HttpClient,
+        aggregation: Box<AggregationStrategy>,
+    ) -> TeamCityIntegration {
+        TeamCityIntegration {
+            r: r,
+            g: g,
+            b: b,
+            name: name.to_string(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            username: username.to_string(),
+            password: password.to_string(),
+            project: project,
+            client: client,
+            last_summary: StatusSummary::default(),
+            aggregation: aggregation,
+            credentials: None,
+            watch: false,
+            last_listing: None,
+        }
+    }
+
+    // Picks up a login reloaded into credentials, see CredentialReloader.
+    pub fn set_credentials(&mut self, credentials: Credentials) {
+        self.credentials = Some(credentials);
+    }
+
+    // Asks for only the ID, state and status of each latest build first, and
+    // reuses the builds from the last poll while that hashes the same. Polling
+    // often then mostly costs one small request, plus the lookups that keep
+    // the failing ones' investigations up to date.
+    pub fn set_watch(&mut self, watch: bool) {
+        self.watch = watch;
+        self.last_listing = None;
+    }
+
+    // Without a username, as the guest user
+    fn rest_url(&self, path: &str) -> Result<Url, Error> {
+        let auth = if self.username.is_empty() {
+            "/guestAuth"
+        } else {
+            ""
+        };
+        Ok(Url::parse(&format!(
+            "{}{}/app/rest/{}",
+            self.base_url, auth, path
+        ))?)
+    }
+
+    fn headers(&self) -> Headers {
+        let mut headers = Headers::new();
+        if !self.username.is_empty() {
+            headers.set(
+                AUTHORIZATION,
+                get_basic_credentials(&self.username, Some(self.password.clone())),
+            );
+        }
+        headers.set("Accept", "application/json");
+        headers
+    }
+
+    fn build_types_url(&self, fields: &str) -> Result<Url, Error> {
+        let mut url = self.rest_url("buildTypes")?;
+        if let Some(ref project) = self.project {
+            url.query_pairs_mut()
+                .append_pair("locator", &format!("affectedProject:(id:{})", project));
+        }
+        url.query_pairs_mut().append_pair("fields", fields);
+        Ok(url)
+    }
+
+    fn get_configurations(&mut self) -> Result<Vec<Configuration>, Error> {
+        let listing_hash = if self.watch {
+            let url = self.build_types_url(WATCH_FIELDS)?;
+            let (body, _) = get_url_body(&self.client, url.as_str(), self.headers())?;
+            let mut hasher = DefaultHasher::new();
+            body.hash(&mut hasher);
+            Some(hasher.finish())
+        } else {
+            None
+        };
+        match (listing_hash, &self.last_listing) {
+            (Some(hash), &Some((last_hash, ref configurations))) if hash == last_hash => {
+                debug!("--Team City--: No build has changed, reusing the last builds.");
+                return Ok(configurations.clone());
+            }
+            _ => (),
+        }
+        let url = self.build_types_url(BUILD_TYPE_FIELDS)?;
+        let (response, _): (TeamCityBuildTypes, Headers) =
+            get_url_response(&self.client, url.as_str(), self.headers())?;
+        let configurations: Vec<Configuration> = response
+            .build_types
+            .into_iter()
+            .map(configuration)
+            .collect();
+        // Kept before acknowledge, which runs again on every poll since an
+        // investigation doesn't change any build
+        self.last_listing = listing_hash.map(|hash| (hash, configurations.clone()));
+        Ok(configurations)
+    }
+
+    // Who's investigating each build configuration, as (ID, name).
+    fn get_investigators(&self) -> Result<Vec<(String, String)>, Error> {
+        let mut url = self.rest_url("investigations")?;
+        let locator = match self.project {
+            Some(ref project) => format!("state:taken,affectedProject:(id:{})", project),
+            None => "state:taken".to_string(),
+        };
+        url.query_pairs_mut()
+            .append_pair("locator", &locator)
+            .append_pair("fields", INVESTIGATION_FIELDS);
+        let (response, _): (TeamCityInvestigations, Headers) =
+            get_url_response(&self.client, url.as_str(), self.headers())?;
+        let mut investigators = Vec::new();
+        for investigation in response.investigation {
+            // Investigations of single tests are covered by unhandled_test_failures
+            let whole_configuration = investigation
+                .target
+                .as_ref()
+                .map_or(false, |target| target.any_problem);
+            if investigation.state != "TAKEN" || !whole_configuration {
+                continue;
+            }
+            let investigator = match investigation.assignee {
+                Some(user) => user.name.unwrap_or(user.username),
+                None => continue,
+            };
+            let build_types = investigation
+                .scope
+                .and_then(|scope| scope.build_types)
+                .map_or(Vec::new(), |ids| ids.build_types);
+            for build_type in build_types {
+                investigators.push((build_type.id, investigator.clone()));
+            }
+        }
+        Ok(investigators)
+    }
+
+    // How many of a build's failed tests are neither muted nor being
+    // investigated now.
+    fn unhandled_test_failures(&self, build_id: u64) -> Result<u32, Error> {
+        let mut url = self.rest_url("testOccurrences")?;
+        url.query_pairs_mut()
+            .append_pair(
+                "locator",
+                &format!(
+                    "build:(id:{}),status:FAILURE,currentlyMuted:false,currentlyInvestigated:false",
+                    build_id
+                ),
+            )
+            .append_pair("fields", "count");
+        let (response, _): (TeamCityTestOccurrences, Headers) =
+            get_url_response(&self.client, url.as_str(), self.headers())?;
+        Ok(response.count)
+    }
+
+    // Works out which failures have been acknowledged. When that can't be
+    // told, they're shown as plain failures rather than failing the poll.
+    fn acknowledge(&self, configurations: &mut [Configuration]) {
+        if configurations
+            .iter()
+            .all(|configuration| configuration.state != JobState::Failing)
+        {
+            return;
+        }
+        let investigators = self.get_investigators().unwrap_or_else(|err| {
+            warn!(
+                "--Team City--: Failed to get the investigations. Error: {}",
+                err
+            );
+            Vec::new()
+        });
+        for configuration in configurations
+            .iter_mut()
+            .filter(|configuration| configuration.state == JobState::Failing)
+        {
+            if let Some(&(_, ref investigator)) = investigators
+                .iter()
+                .find(|&&(ref id, _)| *id == configuration.id)
+            {
+                configuration.acknowledged = Some(format!("investigated by {}", investigator));
+                continue;
+            }
+            let build_id = match configuration.build_id {
+                Some(build_id) if configuration.failed_tests > 0 => build_id,
+                _ => continue,
+            };
+            match self.unhandled_test_failures(build_id) {
+                Ok(0) => {
+                    configuration.acknowledged =
+                        Some("failed only tests that are muted or investigated".to_string())
+                }
+                Ok(_) => (),
+                Err(err) => warn!(
+                    "--Team City--: Failed to get the failed tests of {}. Error: {}",
+                    configuration.name, err
+                ),
+            }
+        }
+    }
+}
+
+fn configuration(build_type: TeamCityBuildType) -> Configuration {
+    let name = format!("{} :: {}", build_type.project_name, build_type.name);
+    let build = build_type
+        .builds
+        .and_then(|builds| builds.build.into_iter().next());
+    let build = match build {
+        Some(build) => build,
+        None => {
+            return Configuration {
+                id: build_type.id,
+                name: name,
+                state: JobState::Indeterminate,
+                build_id: None,
+                number: None,
+                status_text: None,
+                started_at: None,
+                failed_tests: 0,
+                muted_tests: 0,
+                acknowledged: None,
+            }
+        }
+    };
+    let tests = build.test_occurrences.as_ref();
+    Configuration {
+        id: build_type.id,
+        name: name,
+        state: build_state(
+            &build.state,
+            build.status.as_ref().map(|status| status.as_str()),
+        ),
+        build_id: Some(build.id),
+        number: build.number.clone(),
+        status_text: build.status_text.clone(),
+        started_at: build
+            .start_date
+            .as_ref()
+            .and_then(|date| DateTime::parse_from_str(date, "%Y%m%dT%H%M%S%z").ok())
+            .map(|date| date.timestamp() * 1000),
+        failed_tests: tests.map_or(0, |tests| tests.failed),
+        muted_tests: tests.map_or(0, |tests| tests.muted),
+        acknowledged: None,
+    }
+}
+
+// Running builds are in progress whatever their status says so far.
+fn build_state(state: &str, status: Option<&str>) -> JobState {
+    match (state, status) {
+        ("finished", Some("SUCCESS")) => JobState::Passing,
+        ("finished", Some("FAILURE")) | ("finished", Some("ERROR")) => JobState::Failing,
+        ("finished", _) => JobState::Indeterminate,
+        _ => JobState::InProgress,
+    }
+}
+
+impl RemoteIntegration for TeamCityIntegration {
+    fn get_red_id(&self) -> u16 {
+        self.r
+    }
+    fn get_green_id(&self) -> u16 {
+        self.g
+    }
+    fn get_blue_id(&self) -> u16 {
+        self.b
+    }
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+    fn get_summary(&self) -> StatusSummary {
+        self.last_summary.clone()
+    }
+    fn get_rate_limit_delay(&self) -> Option<Duration> {
+        self.client.rate_limited_for()
+    }
+    fn get_request_budget(&self) -> Option<RequestBudget> {
+        self.client.request_budget()
+    }
+
+    fn get_status(&mut self) -> RemoteStatus {
+        if let Some(login) = self.credentials.as_ref().and_then(|c| c.login()) {
+            let password = login.secret.unwrap_or_default();
+            if login.username != self.username || password != self.password {
+                info!("--Team City--: Switching to the reloaded credentials.");
+                self.username = login.username;
+                self.password = password;
+            }
+        }
+        self.client.start_poll();
+        let mut configurations = match self.get_configurations() {
+            Ok(configurations) => configurations,
+            Err(err) => {
+                warn!(
+                    "--Team City--: Failed to get the build configurations. Error: {}",
+                    err
+                );
+                self.last_summary = if network::is_unreachable(&err) {
+                    StatusSummary::disconnected(err.to_string())
+                } else {
+                    StatusSummary {
+                        error: Some(err.to_string()),
+                        ..StatusSummary::default()
+                    }
+                };
+                return RemoteStatus::Unknown;
+            }
+        };
+        self.acknowledge(&mut configurations);
+        let outcomes: Vec<JobOutcome> = configurations
+            .iter()
+            .map(|configuration| JobOutcome {
+                state: configuration.state,
+                started_at: configuration.started_at,
+            })
+            .collect();
+        let status = self.aggregation.aggregate(&outcomes);
+
+        let count = |state: JobState| {
+            configurations
+                .iter()
+                .filter(|configuration| configuration.state == state)
+                .count()
+        };
+        let names = |configurations: Vec<&Configuration>| -> Vec<String> {
+            configurations
+                .iter()
+                .map(|configuration| configuration.name.clone())
+                .collect()
+        };
+        let failing: Vec<&Configuration> = configurations
+            .iter()
+            .filter(|configuration| configuration.state == JobState::Failing)
+            .collect();
+        let failed_builds: Vec<String> = failing
+            .iter()
+            .map(|configuration| {
+                let build = match configuration.number {
+                    Some(ref number) => format!("{} #{}", configuration.name, number),
+                    None => configuration.name.clone(),
+                };
+                match configuration.status_text {
+                    Some(ref text) => format!("{} ({})", build, text),
+                    None => build,
+                }
+            })
+            .collect();
+        for build in &failed_builds {
+            warn!("--Team City--: Failed: {}", build);
+        }
+        for configuration in &configurations {
+            match configuration.acknowledged {
+                Some(ref acknowledged) => info!(
+                    "--Team City--: {} is failing, but {}.",
+                    configuration.name, acknowledged
+                ),
+                None if configuration.muted_tests > 0 => info!(
+                    "--Team City--: {} has {} muted test failures.",
+                    configuration.name, configuration.muted_tests
+                ),
+                None => (),
+            }
+        }
+        self.last_summary = StatusSummary {
+            passing: count(JobState::Passing),
+            failing: failing.len(),
+            in_progress: count(JobState::InProgress),
+            indeterminate: count(JobState::Indeterminate),
+            claimed_jobs: names(
+                failing
+                    .iter()
+                    .cloned()
+                    .filter(|configuration| configuration.acknowledged.is_some())
+                    .collect(),
+            ),
+            failing_jobs: names(failing),
+            failed_builds: failed_builds,
+            jobs: configurations
+                .iter()
+                .map(|configuration| JobStatus {
+                    name: configuration.name.clone(),
+                    status: configuration.state.status(),
+                })
+                .collect(),
+            ..StatusSummary::default()
+        };
+        info!("--Team City--: {}.", self.last_summary.describe());
+        status
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use integrations::aggregation::AggregationKind;
+    use test_support::{fixture, MockServer};
+
+    #[test]
+    fn investigated_and_muted_failures_count_as_claimed() {
+        let server = MockServer::start();
+        server
+            .on(
+                "/app/rest/buildTypes",
+                200,
+                &fixture("team_city_build_types.json"),
+            )
+            .on(
+                "/app/rest/investigations",
+                200,
+                &fixture("team_city_investigations.json"),
+            )
+            .on("/app/rest/testOccurrences", 200, "{\"count\":0}");
+        let mut team_city = TeamCityIntegration::new(
+            0,
+            0,
+            0,
+            "Team City",
+            &server.base_url,
+            "build-light",
+            "password",
+            Some("Backend".to_string()),
+            server.client(),
+            AggregationKind::AnyFailureIsRed.strategy(),
+        );
+
+        assert_eq!(team_city.get_status(), RemoteStatus::Failing);
+        let summary = team_city.get_summary();
+        assert_eq!(
+            (
+                summary.passing,
+                summary.failing,
+                summary.in_progress,
+                summary.indeterminate
+            ),
+            (1, 3, 1, 1)
+        );
+        assert_eq!(
+            summary.claimed_jobs,
+            vec![
+                "Backend :: Tests".to_string(),
+                "Backend :: Package".to_string(),
+            ]
+        );
+        assert_eq!(
+            summary.failed_builds[2],
+            "Backend :: Lint #17 (Exit code 1)"
+        );
+        let requests = server.requests();
+        assert!(requests[0].contains("locator=affectedProject%3A%28id%3ABackend%29"));
+        // Only the build with failed tests has them looked up
+        assert_eq!(
+            requests
+                .iter()
+                .filter(|request| request.contains("/testOccurrences"))
+                .count(),
+            1
+        );
+        assert!(requests
+            .iter()
+            .any(|request| request.contains("build%3A%28id%3A102%29")));
+    }
+
+    #[test]
+    fn watching_reuses_the_builds_until_one_changes() {
+        let server = MockServer::start();
+        let mut team_city = TeamCityIntegration::new(
+            0,
+            0,
+            0,
+            "Team City",
+            &server.base_url,
+            "build-light",
+            "password",
+            None,
+            server.client(),
+            AggregationKind::AnyFailureIsRed.strategy(),
+        );
+        team_city.set_watch(true);
+        let watch_url = team_city.build_types_url(WATCH_FIELDS).unwrap();
+        let watch_target = format!("{}?{}", watch_url.path(), watch_url.query().unwrap());
+        server
+            .on(
+                &watch_target,
+                200,
+                "{\"buildType\":[{\"id\":\"Backend_Tests\"}]}",
+            )
+            .on(
+                "/app/rest/buildTypes",
+                200,
+                &fixture("team_city_build_types.json"),
+            )
+            .on(
+                "/app/rest/investigations",
+                200,
+                &fixture("team_city_investigations.json"),
+            )
+            .on("/app/rest/testOccurrences", 200, "{\"count\":0}");
+        let builds_fetched = |server: &MockServer| {
+            server
+                .requests()
+                .iter()
+                .filter(|request| request.contains("statusText"))
+                .count()
+        };
+
+        assert_eq!(team_city.get_status(), RemoteStatus::Failing);
+        assert_eq!(team_city.get_status(), RemoteStatus::Failing);
+        assert_eq!(builds_fetched(&server), 1);
+        assert_eq!(team_city.get_summary().claimed_jobs.len(), 2);
+        // Investigations are looked up on every poll all the same
+        assert_eq!(
+            server
+                .requests()
+                .iter()
+                .filter(|request| request.contains("/investigations"))
+                .count(),
+            2
+        );
+
+        let changed = MockServer::start();
+        changed.on(&watch_target, 200, "{\"buildType\":[]}").on(
+            "/app/rest/buildTypes",
+            200,
+            "{\"buildType\":[]}",
+        );
+        team_city.base_url = changed.base_url.clone();
+        assert_eq!(team_city.get_status(), RemoteStatus::Unknown);
+        assert_eq!(builds_fetched(&changed), 1);
+    }
+
+    #[test]
+    fn unhandled_test_failures_stay_failing() {
+        let server = MockServer::start();
+        server
+            .on(
+                "/guestAuth/app/rest/buildTypes",
+                200,
+                &fixture("team_city_build_types.json"),
+            )
+            .on(
+                "/guestAuth/app/rest/investigations",
+                200,
+                "{\"investigation\":[]}",
+            )
+            .on("/guestAuth/app/rest/testOccurrences", 200, "{\"count\":1}");
+        let mut team_city = TeamCityIntegration::new(
+            0,
+            0,
+            0,
+            "Team City",
+            &server.base_url,
+            "",
+            "",
+            None,
+            server.client(),
+            AggregationKind::AnyFailureIsRed.strategy(),
+        );
+
+        assert_eq!(team_city.get_status(), RemoteStatus::Failing);
+        assert!(team_city.get_summary().claimed_jobs.is_empty());
+        assert!(server
+            .requests()
+            .iter()
+            .all(|request| !request.contains("Authorization")));
+    }
+}
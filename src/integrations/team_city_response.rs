@@ -0,0 +1,104 @@
+// From app/rest/buildTypes, with the latest build of each
+#[derive(Deserialize)]
+pub struct TeamCityBuildTypes {
+    #[serde(rename = "buildType", default)]
+    pub build_types: Vec<TeamCityBuildType>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TeamCityBuildType {
+    pub id: String,
+    pub name: String,
+    pub project_name: String,
+    // Left out when the build configuration has never been built
+    pub builds: Option<TeamCityBuilds>,
+}
+
+#[derive(Deserialize)]
+pub struct TeamCityBuilds {
+    #[serde(default)]
+    pub build: Vec<TeamCityBuild>,
+}
+
+// Statuses are left as strings, see build_state
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TeamCityBuild {
+    pub id: u64,
+    pub number: Option<String>,
+    // "queued", "running" or "finished"
+    pub state: String,
+    // "SUCCESS", "FAILURE" or "ERROR", for running builds too
+    pub status: Option<String>,
+    // e.g. "Tests failed: 2 (1 new), passed: 120"
+    pub status_text: Option<String>,
+    // e.g. "20180301T120000+0200"
+    pub start_date: Option<String>,
+    pub test_occurrences: Option<TeamCityTestCounts>,
+}
+
+#[derive(Deserialize)]
+pub struct TeamCityTestCounts {
+    #[serde(default)]
+    pub failed: u32,
+    #[serde(default)]
+    pub muted: u32,
+}
+
+// From app/rest/investigations
+#[derive(Deserialize)]
+pub struct TeamCityInvestigations {
+    #[serde(default)]
+    pub investigation: Vec<TeamCityInvestigation>,
+}
+
+#[derive(Deserialize)]
+pub struct TeamCityInvestigation {
+    // "TAKEN", "FIXED" or "GIVEN_UP"
+    pub state: String,
+    pub assignee: Option<TeamCityUser>,
+    pub scope: Option<TeamCityInvestigationScope>,
+    pub target: Option<TeamCityInvestigationTarget>,
+}
+
+#[derive(Deserialize)]
+pub struct TeamCityUser {
+    pub username: String,
+    pub name: Option<String>,
+}
+
+// Where it's being investigated. Test investigations can cover a whole
+// project instead of build configurations.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TeamCityInvestigationScope {
+    pub build_types: Option<TeamCityBuildTypeIds>,
+}
+
+#[derive(Deserialize)]
+pub struct TeamCityBuildTypeIds {
+    #[serde(rename = "buildType", default)]
+    pub build_types: Vec<TeamCityBuildTypeId>,
+}
+
+#[derive(Deserialize)]
+pub struct TeamCityBuildTypeId {
+    pub id: String,
+}
+
+// What's being investigated: the whole build configuration, or some of its
+// tests or build problems
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TeamCityInvestigationTarget {
+    #[serde(default)]
+    pub any_problem: bool,
+}
+
+// From app/rest/testOccurrences, only counted
+#[derive(Deserialize)]
+pub struct TeamCityTestOccurrences {
+    #[serde(default)]
+    pub count: u32,
+}
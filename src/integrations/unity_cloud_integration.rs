@@ -1,14 +1,19 @@
-use errors::UnityRetrievalError;
+use budget::RequestBudget;
+use chrono::DateTime;
+use credentials::Credentials;
+use errors::{HttpStatusError, UnityRetrievalError};
 use failure::Error;
+use headers::{Headers, AUTHORIZATION};
+use integrations::aggregation::{AggregationStrategy, JobOutcome, JobState};
+use integrations::remote_integration::RemoteIntegration;
 use integrations::unity_cloud_response::*;
-use network::{get_basic_credentials, get_url_response};
-use remote_status::RemoteStatus;
-use reqwest::header::{Authorization, ContentType, Headers};
+use network::{get_basic_credentials, get_url_response, HttpClient};
+use remote_status::{JobStatus, RemoteStatus, StatusSummary};
+use reqwest::StatusCode;
 use std::time::Duration;
-use std::time::Instant;
-use RemoteIntegration;
 
-const UNITY_SLEEP_DURATION: u64 = 1000 * 60;
+const UNITY_BUILD_TARGETS: [&str; 2] = ["ios-development", "android-development"];
+const UNITY_SHARE_URL: &str = "https://developer.cloud.unity3d.com/share";
 
 pub struct UnityCloudIntegration {
     r: u16,
@@ -16,55 +21,88 @@ pub struct UnityCloudIntegration {
     b: u16,
     api_token: String,
     base_url: String,
-    last_tick: Instant,
-    last_status: RemoteStatus,
+    client: HttpClient,
+    last_summary: StatusSummary,
+    aggregation: Box<AggregationStrategy>,
+    // Checked for a new API token before every poll, if set
+    credentials: Option<Credentials>,
+    // For orgs or projects other than the main one, see unity_instances
+    name: Option<String>,
 }
 
 impl UnityCloudIntegration {
-    pub fn new(r: u16, g: u16, b: u16, api_token: &str, base_url: &str) -> UnityCloudIntegration {
+    pub fn new(
+        r: u16,
+        g: u16,
+        b: u16,
+        api_token: &str,
+        base_url: &str,
+        client: HttpClient,
+        aggregation: Box<AggregationStrategy>,
+    ) -> UnityCloudIntegration {
         UnityCloudIntegration {
             r: r,
             g: g,
             b: b,
             api_token: api_token.to_string(),
             base_url: base_url.to_string(),
-            last_tick: Instant::now() - Duration::from_millis(UNITY_SLEEP_DURATION),
-            last_status: RemoteStatus::Unknown,
+            client: client,
+            last_summary: StatusSummary::default(),
+            aggregation: aggregation,
+            credentials: None,
+            name: None,
         }
     }
 
-    fn get_status_internal(&self) -> Vec<Result<(UnityBuildStatus, Headers), UnityRetrievalError>> {
+    pub fn set_name(&mut self, name: &str) {
+        self.name = Some(name.to_string());
+    }
+
+    // Picks up an API token reloaded into credentials, see CredentialReloader.
+    pub fn set_credentials(&mut self, credentials: Credentials) {
+        self.credentials = Some(credentials);
+    }
+
+    fn headers(&self) -> Headers {
         let mut headers = Headers::new();
         let auth_header = get_basic_credentials(&self.api_token, None);
-        headers.set(Authorization(auth_header));
-        headers.set(ContentType::json());
+        headers.set(AUTHORIZATION, auth_header);
+        headers.set("Content-Type", "application/json");
+        headers
+    }
 
-        let ios_url = format!(
-            "{base}/ios-development/builds?per_page=1",
-            base = self.base_url
-        );
-        let ios_build_response =
-            UnityCloudIntegration::get_platform_status(&headers, ios_url.as_str());
+    fn get_status_internal(
+        &self,
+    ) -> Vec<(String, Result<(UnityBuild, Headers), UnityRetrievalError>)> {
+        let headers = self.headers();
 
-        let android_url = format!(
-            "{base}/android-development/builds?per_page=1",
-            base = self.base_url
-        );
-        let android_build_response =
-            UnityCloudIntegration::get_platform_status(&headers, android_url.as_str());
-        vec![ios_build_response, android_build_response]
+        UNITY_BUILD_TARGETS
+            .iter()
+            .map(|target| {
+                let url = format!(
+                    "{base}/{target}/builds?per_page=1",
+                    base = self.base_url,
+                    target = target
+                );
+                (
+                    target.to_string(),
+                    UnityCloudIntegration::get_platform_status(&self.client, &headers, url.as_str()),
+                )
+            })
+            .collect()
     }
 
     fn get_platform_status(
+        client: &HttpClient,
         headers: &Headers,
         url: &str,
-    ) -> Result<(UnityBuildStatus, Headers), UnityRetrievalError> {
+    ) -> Result<(UnityBuild, Headers), UnityRetrievalError> {
         let unity_build_response: Result<(Vec<UnityBuild>, Headers), Error> =
-            get_url_response(&url, headers.clone());
+            get_url_response(client, &url, headers.clone());
         match unity_build_response {
             Ok((mut unity_http_result, response_headers)) => {
                 if unity_http_result.len() != 0 {
-                    Ok((unity_http_result.remove(0).build_status, response_headers))
+                    Ok((unity_http_result.remove(0), response_headers))
                 } else {
                     warn!(
                         "--Unity--: No builds retrieved from Unity Cloud for URL {}. Aborting...",
@@ -84,6 +122,62 @@ impl UnityCloudIntegration {
             }
         }
     }
+
+    // The link a build was shared with, if it was. Builds are only shared when
+    // someone asks for it on the dashboard, so most of them aren't.
+    fn get_share_link(&self, target: &str, number: u32) -> Result<Option<String>, Error> {
+        let url = format!(
+            "{base}/{target}/builds/{number}/share",
+            base = self.base_url,
+            target = target,
+            number = number
+        );
+        let share_response: Result<(UnityShare, Headers), Error> =
+            get_url_response(&self.client, &url, self.headers());
+        match share_response {
+            Ok((share, _)) => Ok(Some(format!("{}/{}/", UNITY_SHARE_URL, share.shareid))),
+            Err(ref err)
+                if err
+                    .downcast_ref::<HttpStatusError>()
+                    .map_or(false, |err| err.status == StatusCode::NotFound) =>
+            {
+                Ok(None)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    // e.g. "Android Development #57 (https://developer.cloud.unity3d.com/share/...)"
+    fn describe_failed_build(&self, target: &str, build: &UnityBuild) -> String {
+        let name = build.target_name.as_ref().map_or(target, |name| name.as_str());
+        let number = match build.number {
+            Some(number) => number,
+            None => return name.to_string(),
+        };
+        match self.get_share_link(target, number) {
+            Ok(Some(link)) => format!("{} #{} ({})", name, number, link),
+            Ok(None) => format!("{} #{}", name, number),
+            Err(e) => {
+                warn!(
+                    "--Unity--: Failed to get the share link of {} #{}. Error: {}",
+                    name, number, e
+                );
+                format!("{} #{}", name, number)
+            }
+        }
+    }
+}
+
+fn job_state(status: UnityBuildStatus) -> JobState {
+    match status {
+        UnityBuildStatus::Success => JobState::Passing,
+        UnityBuildStatus::Failure => JobState::Failing,
+        UnityBuildStatus::Queued
+        | UnityBuildStatus::SentToBuilder
+        | UnityBuildStatus::Started
+        | UnityBuildStatus::Restarted => JobState::InProgress,
+        UnityBuildStatus::Canceled | UnityBuildStatus::Unknown => JobState::Indeterminate,
+    }
 }
 
 impl RemoteIntegration for UnityCloudIntegration {
@@ -96,102 +190,257 @@ impl RemoteIntegration for UnityCloudIntegration {
     fn get_blue_id(&self) -> u16 {
         self.b
     }
+    fn get_name(&self) -> &str {
+        self.name.as_ref().map_or("Unity Cloud", |name| name.as_str())
+    }
+    fn get_summary(&self) -> StatusSummary {
+        self.last_summary.clone()
+    }
+    fn get_rate_limit_delay(&self) -> Option<Duration> {
+        self.client.rate_limited_for()
+    }
+    fn get_request_budget(&self) -> Option<RequestBudget> {
+        self.client.request_budget()
+    }
 
     fn get_status(&mut self) -> RemoteStatus {
-        // Poll this as frequently as the rest, but only actually do any work
-        // once every UNITY_SLEEP_DURATION, so we don't hit the API's
-        // rate limit. It claims we can inspet the rate limit header we get
-        // back to avoid that, but it doesn't work correctly.
-        if Instant::now() - self.last_tick < Duration::from_millis(UNITY_SLEEP_DURATION) {
-            let till_next = Duration::from_millis(UNITY_SLEEP_DURATION) - (Instant::now() - self.last_tick);
-            info!("--Unity-- Sleeping for another {} seconds.", till_next.as_secs());
-            return self.last_status;
+        if let Some(token) = self.credentials.as_ref().and_then(|c| c.unity_api_token()) {
+            if token != self.api_token {
+                info!("--Unity--: Switching to the reloaded API token.");
+                self.api_token = token;
+            }
         }
-
+        self.client.start_poll();
         let unity_results = self.get_status_internal();
         let (retrieved, not_retrieved): (
-            Vec<Result<(UnityBuildStatus, Headers), UnityRetrievalError>>,
-            Vec<Result<(UnityBuildStatus, Headers), UnityRetrievalError>>,
-        ) = unity_results.into_iter().partition(|x| x.is_ok());
+            Vec<(String, Result<(UnityBuild, Headers), UnityRetrievalError>)>,
+            Vec<(String, Result<(UnityBuild, Headers), UnityRetrievalError>)>,
+        ) = unity_results.into_iter().partition(|x| x.1.is_ok());
 
-        let retrieved_results: Vec<(UnityBuildStatus, Headers)> =
-            retrieved.into_iter().map(|x| x.unwrap()).collect();
+        let (target_names, retrieved_results): (Vec<String>, Vec<(UnityBuild, Headers)>) =
+            retrieved
+                .into_iter()
+                .map(|(name, result)| (name, result.unwrap()))
+                .unzip();
         let not_retrieved_results: Vec<UnityRetrievalError> =
-            not_retrieved.into_iter().map(|x| x.unwrap_err()).collect();
+            not_retrieved.into_iter().map(|x| x.1.unwrap_err()).collect();
 
         let return_status: RemoteStatus;
 
         if not_retrieved_results.len() > 0 {
             info!("--Unity--: At least one result not retrieved.");
+            let error = not_retrieved_results[0].to_string();
+            self.last_summary = if retrieved_results.is_empty() {
+                StatusSummary::disconnected(error)
+            } else {
+                StatusSummary {
+                    error: Some(error),
+                    ..StatusSummary::default()
+                }
+            };
             return_status = RemoteStatus::Unknown;
         } else {
             let passing_builds = *(&retrieved_results
                 .iter()
-                .filter(|x| x.0 == UnityBuildStatus::Success)
+                .filter(|x| x.0.build_status == UnityBuildStatus::Success)
                 .count());
             let failing_builds = *(&retrieved_results
                 .iter()
-                .filter(|x| x.0 == UnityBuildStatus::Failure)
+                .filter(|x| x.0.build_status == UnityBuildStatus::Failure)
                 .count());
             let in_progress_builds = *(&retrieved_results
                 .iter()
                 .filter(|x| {
-                    x.0 == UnityBuildStatus::Queued
-                        || x.0 == UnityBuildStatus::SentToBuilder
-                        || x.0 == UnityBuildStatus::Started
-                        || x.0 == UnityBuildStatus::Restarted
+                    x.0.build_status == UnityBuildStatus::Queued
+                        || x.0.build_status == UnityBuildStatus::SentToBuilder
+                        || x.0.build_status == UnityBuildStatus::Started
+                        || x.0.build_status == UnityBuildStatus::Restarted
                 })
                 .count());
             let other_status_builds = *(&retrieved_results
                 .iter()
                 .filter(|x| {
-                    x.0 != UnityBuildStatus::Success
-                        && x.0 != UnityBuildStatus::Failure
-                        && x.0 != UnityBuildStatus::Queued
-                        && x.0 != UnityBuildStatus::SentToBuilder
-                        && x.0 != UnityBuildStatus::Started
-                        && x.0 != UnityBuildStatus::Restarted
+                    x.0.build_status != UnityBuildStatus::Success
+                        && x.0.build_status != UnityBuildStatus::Failure
+                        && x.0.build_status != UnityBuildStatus::Queued
+                        && x.0.build_status != UnityBuildStatus::SentToBuilder
+                        && x.0.build_status != UnityBuildStatus::Started
+                        && x.0.build_status != UnityBuildStatus::Restarted
                 })
                 .count());
 
-            // More misc statuses than knowns
-            if other_status_builds > passing_builds + failing_builds + in_progress_builds {
-                info!("--Unity--: More otherstatuses than passing AND failing.");
-                return_status = RemoteStatus::Unknown;
-            }
-            // No failures and at least one building
-            else if failing_builds == 0 && in_progress_builds > 0 {
-                info!("--Unity--: No failures and at least one building");
-                return_status = RemoteStatus::InProgress;
-            }
-            // All passing or misc
-            else if passing_builds > 0 && failing_builds == 0 {
-                info!("--Unity--: All passing or misc.");
-                return_status = RemoteStatus::Passing;
-            }
-            // All failing or misc
-            else if passing_builds == 0 && failing_builds > 0 {
-                info!("--Unity--: All failing or misc.");
-                return_status = RemoteStatus::Failing;
-            }
-            // Both failing and passing
-            else if passing_builds > 0 && failing_builds > 0 {
-                info!("--Unity--: At least one failing AND passing.");
-                return_status = RemoteStatus::Failing;
-            }
-            // ?????
-            else {
-                info!("--Unity--: Unknown state.");
-                return_status = RemoteStatus::Unknown;
+            let outcomes: Vec<JobOutcome> = retrieved_results
+                .iter()
+                .map(|&(ref build, _)| JobOutcome {
+                    state: job_state(build.build_status),
+                    started_at: build
+                        .created
+                        .as_ref()
+                        .and_then(|created| DateTime::parse_from_rfc3339(created).ok())
+                        .map(|created| created.timestamp() * 1000),
+                })
+                .collect();
+            return_status = self.aggregation.aggregate(&outcomes);
+
+            let failed_builds: Vec<String> = target_names
+                .iter()
+                .zip(retrieved_results.iter())
+                .filter(|&(_, x)| x.0.build_status == UnityBuildStatus::Failure)
+                .map(|(target, &(ref build, _))| self.describe_failed_build(target, build))
+                .collect();
+            for build in &failed_builds {
+                warn!("--Unity--: {} failed.", build);
             }
 
+            self.last_summary = StatusSummary {
+                passing: passing_builds,
+                failing: failing_builds,
+                in_progress: in_progress_builds,
+                aborted: 0,
+                not_built: 0,
+                indeterminate: other_status_builds,
+                failing_jobs: target_names
+                    .iter()
+                    .zip(retrieved_results.iter())
+                    .filter(|&(_, x)| x.0.build_status == UnityBuildStatus::Failure)
+                    .map(|(name, _)| name.clone())
+                    .collect(),
+                flaky_jobs: Vec::new(),
+                overdue_jobs: Vec::new(),
+                culprits: Vec::new(),
+                claimed_jobs: Vec::new(),
+                warning_jobs: Vec::new(),
+                running_builds: Vec::new(),
+                jobs: target_names
+                    .iter()
+                    .zip(retrieved_results.iter())
+                    .map(|(name, &(ref build, _))| JobStatus {
+                        name: name.clone(),
+                        status: job_state(build.build_status).status(),
+                    })
+                    .collect(),
+                pull_request_jobs: Vec::new(),
+                failed_stages: Vec::new(),
+                failed_builds: failed_builds,
+                non_critical_failures_only: false,
+                non_mainline_failures_only: false,
+                queued: None,
+                queue_backed_up: false,
+                disconnected: false,
+                error: None,
+            };
+
             info!(
                 "--Unity--: {} passing builds, {} failing builds, {} builds in progress, {} builds with misc statuses.",
                 passing_builds, failing_builds, in_progress_builds, other_status_builds
             );
         }
-        self.last_tick = Instant::now();
-        self.last_status = return_status;
         return return_status;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use integrations::aggregation::AggregationKind;
+    use test_support::{fixture, MockServer};
+
+    fn integration(server: &MockServer) -> UnityCloudIntegration {
+        UnityCloudIntegration::new(
+            0,
+            0,
+            0,
+            "token",
+            &server.base_url,
+            server.client(),
+            AggregationKind::AnyFailureIsRed.strategy(),
+        )
+    }
+
+    #[test]
+    fn all_targets_passing_is_passing() {
+        let server = MockServer::start();
+        server
+            .on("/ios-development/builds", 200, &fixture("unity_builds_success.json"))
+            .on("/android-development/builds", 200, &fixture("unity_builds_success.json"));
+        let mut unity = integration(&server);
+
+        assert_eq!(unity.get_status(), RemoteStatus::Passing);
+        assert_eq!(unity.get_summary().passing, 2);
+    }
+
+    #[test]
+    fn failing_target_is_failing() {
+        let server = MockServer::start();
+        server
+            .on("/ios-development/builds", 200, &fixture("unity_builds_success.json"))
+            .on("/android-development/builds", 200, &fixture("unity_builds_failure.json"));
+        let mut unity = integration(&server);
+
+        assert_eq!(unity.get_status(), RemoteStatus::Failing);
+        assert_eq!(
+            unity.get_summary().failing_jobs,
+            vec!["android-development".to_string()]
+        );
+        assert_eq!(
+            unity.get_summary().failed_builds,
+            vec!["Android Development #57".to_string()]
+        );
+    }
+
+    #[test]
+    fn shared_failing_build_links_to_its_share() {
+        let server = MockServer::start();
+        server
+            .on("/ios-development/builds", 200, &fixture("unity_builds_success.json"))
+            .on("/android-development/builds", 200, &fixture("unity_builds_failure.json"))
+            .on("/android-development/builds/57/share", 200, &fixture("unity_share.json"));
+        let mut unity = integration(&server);
+
+        assert_eq!(unity.get_status(), RemoteStatus::Failing);
+        assert_eq!(
+            unity.get_summary().failed_builds,
+            vec![
+                "Android Development #57 (https://developer.cloud.unity3d.com/share/-kLx4g2bOM/)"
+                    .to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn started_build_is_in_progress() {
+        let server = MockServer::start();
+        server
+            .on("/ios-development/builds", 200, &fixture("unity_builds_success.json"))
+            .on("/android-development/builds", 200, &fixture("unity_builds_started.json"));
+        let mut unity = integration(&server);
+
+        assert_eq!(unity.get_status(), RemoteStatus::InProgress);
+    }
+
+    #[test]
+    fn target_without_builds_is_unknown() {
+        let server = MockServer::start();
+        server
+            .on("/ios-development/builds", 200, &fixture("unity_builds_success.json"))
+            .on("/android-development/builds", 200, "[]");
+        let mut unity = integration(&server);
+
+        assert_eq!(unity.get_status(), RemoteStatus::Unknown);
+        let summary = unity.get_summary();
+        assert!(!summary.disconnected);
+        assert!(summary.error.is_some());
+    }
+
+    #[test]
+    fn malformed_responses_are_disconnected() {
+        let server = MockServer::start();
+        server
+            .on("/ios-development/builds", 200, &fixture("malformed.json"))
+            .on("/android-development/builds", 200, &fixture("malformed.json"));
+        let mut unity = integration(&server);
+
+        assert_eq!(unity.get_status(), RemoteStatus::Unknown);
+        assert!(unity.get_summary().disconnected);
+    }
+}
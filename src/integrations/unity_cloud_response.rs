@@ -1,10 +1,22 @@
-#[derive(Deserialize)]
+#[derive(Deserialize, Debug)]
 pub struct UnityBuild {
+    #[serde(rename = "build")]
+    pub number: Option<u32>,
+    #[serde(rename = "buildTargetName")]
+    pub target_name: Option<String>,
     #[serde(rename = "buildStatus")]
     pub build_status: UnityBuildStatus,
+    // ISO 8601
+    pub created: Option<String>,
 }
 
-#[derive(Deserialize, Debug, PartialEq)]
+// From a build's share, if anyone shared it
+#[derive(Deserialize, Debug)]
+pub struct UnityShare {
+    pub shareid: String,
+}
+
+#[derive(Deserialize, Debug, PartialEq, Clone, Copy)]
 #[serde(rename_all = "camelCase")]
 pub enum UnityBuildStatus {
     Queued,
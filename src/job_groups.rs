@@ -0,0 +1,362 @@
+use calendar::Freeze;
+use config_file::{JobGroupConfig, PatternScheme};
+use connectivity::Connectivity;
+use controls::Controls;
+use failure::Error;
+use led_controller::LedController;
+use pin::{PatternTimings, RgbLedLight};
+use remote_status::{RemoteStatus, StatusSummary};
+use scheduler::{Tick, TickScheduler};
+use status_board::{IntegrationSnapshot, StatusBoard};
+use std::sync::Arc;
+use std::time::Duration;
+
+const REFRESH_MILLIS: u64 = 1000;
+
+// Drives the LED of one [[jenkins_job_groups]] entry. It has no poll of its
+// own, it shows the jobs matching the group's pattern out of whatever the
+// Jenkins integration last put on the status board.
+pub fn run_job_group(
+    config: JobGroupConfig,
+    board: StatusBoard,
+    patterns: PatternScheme,
+    timings: PatternTimings,
+    controls: Arc<Controls>,
+    connectivity: Connectivity,
+    freeze: Freeze,
+    scheduler: TickScheduler,
+) {
+    let pattern = match JobPattern::parse(&config.jobs) {
+        Ok(pattern) => pattern,
+        Err(e) => {
+            error!(
+                "--{}--: Job pattern \"{}\" isn't usable, leaving its LED off. Error: {}",
+                config.name, config.jobs, e
+            );
+            return;
+        }
+    };
+    let mut led = RgbLedLight::new(config.led_pins[0], config.led_pins[1], config.led_pins[2]);
+    led.set_output_type(config.output_type);
+    let mut led_controller = LedController::new(led, config.colors, patterns, timings, controls);
+    led_controller.set_connectivity(connectivity);
+    led_controller.set_freeze(freeze);
+
+    let mut shown: Option<(RemoteStatus, bool)> = None;
+    let mut ticker = scheduler.ticker();
+    while ticker.is_running() {
+        let snapshots: Vec<IntegrationSnapshot> = board
+            .snapshots()
+            .into_iter()
+            .filter(|snapshot| snapshot.name.starts_with("Jenkins"))
+            .collect();
+        if !snapshots.is_empty() {
+            let (status, summary) = group_status(&snapshots, &pattern);
+            // Only re-show on changes, so running animations aren't restarted
+            let offline = led_controller.is_offline();
+            if shown.map(|(shown_status, _)| shown_status) != Some(status) {
+                info!("--{}--: Status is now {:?}.", config.name, status);
+            }
+            if shown != Some((status, offline)) {
+                led_controller.show_status(status, &summary);
+                shown = Some((status, offline));
+            }
+        }
+        if ticker.wait(Duration::from_millis(REFRESH_MILLIS)) == Tick::Shutdown {
+            break;
+        }
+    }
+    led_controller.shut_down();
+}
+
+// Worst state of the group's jobs, with the summary cut down to just them.
+fn group_status(
+    snapshots: &[IntegrationSnapshot],
+    pattern: &JobPattern,
+) -> (RemoteStatus, StatusSummary) {
+    let in_group = |job: &String| pattern.is_match(job);
+    let mut summary = StatusSummary::default();
+    summary.disconnected = snapshots.iter().all(|s| s.summary.disconnected);
+    for snapshot in snapshots {
+        for job in snapshot
+            .summary
+            .jobs
+            .iter()
+            .filter(|job| in_group(&job.name))
+        {
+            match job.status {
+                RemoteStatus::Passing => summary.passing += 1,
+                RemoteStatus::Failing => summary.failing += 1,
+                RemoteStatus::InProgress => summary.in_progress += 1,
+                RemoteStatus::Unknown => summary.indeterminate += 1,
+            }
+            summary.jobs.push(job.clone());
+        }
+        let jobs = &snapshot.summary;
+        summary.failing_jobs.extend(
+            jobs.failing_jobs
+                .iter()
+                .filter(|job| in_group(job))
+                .cloned(),
+        );
+        summary
+            .flaky_jobs
+            .extend(jobs.flaky_jobs.iter().filter(|job| in_group(job)).cloned());
+        summary.overdue_jobs.extend(
+            jobs.overdue_jobs
+                .iter()
+                .filter(|job| in_group(job))
+                .cloned(),
+        );
+        summary.claimed_jobs.extend(
+            jobs.claimed_jobs
+                .iter()
+                .filter(|job| in_group(job))
+                .cloned(),
+        );
+        summary.warning_jobs.extend(
+            jobs.warning_jobs
+                .iter()
+                .filter(|job| in_group(job))
+                .cloned(),
+        );
+    }
+    (worst_status(&summary), summary)
+}
+
+// The pull request jobs the main Jenkins integration keeps out of its own
+// status, for the PR LED, see jenkins_pr_led_pins.
+pub fn pull_request_status(jenkins: &StatusSummary) -> (RemoteStatus, StatusSummary) {
+    let mut summary = StatusSummary::default();
+    summary.disconnected = jenkins.disconnected;
+    summary.error = jenkins.error.clone();
+    for job in &jenkins.pull_request_jobs {
+        match job.status {
+            RemoteStatus::Passing => summary.passing += 1,
+            RemoteStatus::Failing => {
+                summary.failing += 1;
+                summary.failing_jobs.push(job.name.clone());
+            }
+            RemoteStatus::InProgress => summary.in_progress += 1,
+            RemoteStatus::Unknown => summary.indeterminate += 1,
+        }
+        summary.jobs.push(job.clone());
+    }
+    (worst_status(&summary), summary)
+}
+
+fn worst_status(summary: &StatusSummary) -> RemoteStatus {
+    if summary.failing > 0 {
+        RemoteStatus::Failing
+    } else if summary.in_progress > 0 {
+        RemoteStatus::InProgress
+    } else if summary.passing > 0 {
+        RemoteStatus::Passing
+    } else {
+        RemoteStatus::Unknown
+    }
+}
+
+// The part of regular expressions job names need: literal characters, ".",
+// the "*", "+" and "?" repeats, "^" and "$" anchors, "|" between
+// alternatives, and "\" to escape any of those. Like a regex, it matches
+// anywhere in the name unless anchored. Groups, classes and counted repeats
+// are refused rather than taken literally.
+#[derive(Clone)]
+pub struct JobPattern {
+    branches: Vec<Branch>,
+}
+
+#[derive(Clone)]
+struct Branch {
+    anchored_start: bool,
+    anchored_end: bool,
+    pieces: Vec<Piece>,
+}
+
+#[derive(Clone, Copy)]
+enum Atom {
+    Any,
+    Char(char),
+}
+
+impl Atom {
+    fn matches(&self, c: char) -> bool {
+        match *self {
+            Atom::Any => true,
+            Atom::Char(expected) => expected == c,
+        }
+    }
+}
+
+// The fewest and most times an atom can repeat
+#[derive(Clone, Copy)]
+struct Piece {
+    atom: Atom,
+    min: usize,
+    max: Option<usize>,
+}
+
+impl JobPattern {
+    pub fn parse(pattern: &str) -> Result<JobPattern, Error> {
+        let mut branches = vec![Branch {
+            anchored_start: false,
+            anchored_end: false,
+            pieces: Vec::new(),
+        }];
+        let mut chars = pattern.chars().peekable();
+        while let Some(c) = chars.next() {
+            let branch = branches.last_mut().unwrap();
+            if branch.anchored_end && c != '|' {
+                return Err(format_err!("\"$\" is only allowed at the end"));
+            }
+            let atom = match c {
+                '|' => {
+                    branches.push(Branch {
+                        anchored_start: false,
+                        anchored_end: false,
+                        pieces: Vec::new(),
+                    });
+                    continue;
+                }
+                '^' if branch.pieces.is_empty() && !branch.anchored_start => {
+                    branch.anchored_start = true;
+                    continue;
+                }
+                '$' => {
+                    branch.anchored_end = true;
+                    continue;
+                }
+                '.' => Atom::Any,
+                '\\' => Atom::Char(
+                    chars
+                        .next()
+                        .ok_or_else(|| format_err!("\"\\\" at the end has nothing to escape"))?,
+                ),
+                '*' | '+' | '?' => {
+                    return Err(format_err!("\"{}\" doesn't follow anything to repeat", c))
+                }
+                '(' | ')' | '[' | ']' | '{' | '}' | '^' => {
+                    return Err(format_err!(
+                        "\"{}\" isn't supported, escape it with \"\\\"",
+                        c
+                    ))
+                }
+                c => Atom::Char(c),
+            };
+            let repeat = match chars.peek() {
+                Some(&'*') => Some((0, None)),
+                Some(&'+') => Some((1, None)),
+                Some(&'?') => Some((0, Some(1))),
+                _ => None,
+            };
+            let (min, max) = match repeat {
+                Some(repeat) => {
+                    chars.next();
+                    repeat
+                }
+                None => (1, Some(1)),
+            };
+            branch.pieces.push(Piece {
+                atom: atom,
+                min: min,
+                max: max,
+            });
+        }
+        Ok(JobPattern { branches: branches })
+    }
+
+    pub fn is_match(&self, name: &str) -> bool {
+        let name: Vec<char> = name.chars().collect();
+        self.branches.iter().any(|branch| {
+            let last_start = if branch.anchored_start { 0 } else { name.len() };
+            (0..last_start + 1).any(|start| branch.matches_at(&branch.pieces, &name[start..]))
+        })
+    }
+}
+
+impl Branch {
+    fn matches_at(&self, pieces: &[Piece], name: &[char]) -> bool {
+        let (piece, rest) = match pieces.split_first() {
+            Some(split) => split,
+            None => return !self.anchored_end || name.is_empty(),
+        };
+        let available = name.iter().take_while(|&&c| piece.atom.matches(c)).count();
+        let most = piece.max.map_or(available, |max| max.min(available));
+        if most < piece.min {
+            return false;
+        }
+        // Greedy, backing off one at a time
+        (piece.min..most + 1)
+            .rev()
+            .any(|count| self.matches_at(rest, &name[count..]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Local;
+    use remote_status::JobStatus;
+
+    #[test]
+    fn patterns_match_like_regexes() {
+        let pattern = JobPattern::parse("^backend-.+|api$").unwrap();
+        assert!(pattern.is_match("backend-tests"));
+        assert!(!pattern.is_match("backend-"));
+        assert!(!pattern.is_match("old-backend-tests"));
+        assert!(pattern.is_match("public-api"));
+        assert!(!pattern.is_match("api-docs"));
+
+        let pattern = JobPattern::parse("front.*end\\.js?").unwrap();
+        assert!(pattern.is_match("my-frontend.j"));
+        assert!(pattern.is_match("front-and-backend.js"));
+        assert!(!pattern.is_match("frontendxjs"));
+
+        assert!(JobPattern::parse("").unwrap().is_match("anything"));
+        assert!(JobPattern::parse("(api|app)").is_err());
+        assert!(JobPattern::parse("*api").is_err());
+        assert!(JobPattern::parse("api$-docs").is_err());
+    }
+
+    #[test]
+    fn group_shows_only_its_own_jobs() {
+        let job = |name: &str, status: RemoteStatus| JobStatus {
+            name: name.to_string(),
+            status: status,
+        };
+        let mut summary = StatusSummary::default();
+        summary.jobs = vec![
+            job("backend-api", RemoteStatus::Passing),
+            job("backend-worker", RemoteStatus::InProgress),
+            job("frontend-web", RemoteStatus::Failing),
+        ];
+        summary.failing_jobs = vec!["frontend-web".to_string()];
+        let snapshots = vec![IntegrationSnapshot {
+            name: "Jenkins".to_string(),
+            status: RemoteStatus::Failing,
+            summary: summary,
+            last_poll: Local::now(),
+        }];
+
+        let backend = JobPattern::parse("^backend-").unwrap();
+        let (status, summary) = group_status(&snapshots, &backend);
+        assert_eq!(status, RemoteStatus::InProgress);
+        assert_eq!((summary.passing, summary.in_progress), (1, 1));
+        assert!(summary.failing_jobs.is_empty());
+
+        let frontend = JobPattern::parse("^frontend-").unwrap();
+        let (status, summary) = group_status(&snapshots, &frontend);
+        assert_eq!(status, RemoteStatus::Failing);
+        assert_eq!(summary.failing_jobs, vec!["frontend-web".to_string()]);
+
+        let nothing = JobPattern::parse("^mobile-").unwrap();
+        assert_eq!(group_status(&snapshots, &nothing).0, RemoteStatus::Unknown);
+
+        let mut jenkins = StatusSummary::default();
+        jenkins.pull_request_jobs = vec![job("api-pr-42", RemoteStatus::Failing)];
+        let (status, summary) = pull_request_status(&jenkins);
+        assert_eq!(status, RemoteStatus::Failing);
+        assert_eq!(summary.failing_jobs, vec!["api-pr-42".to_string()]);
+    }
+}
@@ -0,0 +1,682 @@
+use calendar::Freeze;
+use config_file::{ColorScheme, EscalationConfig, PatternScheme, PowerOnTestConfig};
+use connectivity::Connectivity;
+use controls::Controls;
+use escalation;
+use pin::{OutputType, Pattern, PatternTimings, RgbLedLight};
+use preflight::Preflight;
+use remote_status::{RemoteStatus, StatusSummary};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// How bright an acknowledged failure's steady light is, relative to its normal color.
+const ACKNOWLEDGED_BRIGHTNESS_PERCENT: i32 = 30;
+const SHUTDOWN_GLOW_MILLIS: u64 = 1000;
+// The white pulse shown while the build queue is backed up: how long it takes,
+// and how many brightness steps it fades up and back down in.
+const QUEUE_PULSE_MILLIS: u64 = 2000;
+const PULSE_STEPS: u64 = 10;
+// The slower pulse shown during a release freeze.
+const FREEZE_PULSE_MILLIS: u64 = 4000;
+// How many of the power-on test's steps it waits with the LED off first, so
+// the sequence is seen from its start.
+const POWER_ON_PAUSE_STEPS: u64 = 4;
+// How long each blink of the startup check's code stays on, and off.
+const PREFLIGHT_BLINK_MILLIS: u64 = 400;
+
+// Owns an integration's LED, remembers what it last showed, and plays a short
+// transition animation whenever the status changes, so changes are noticeable
+// from across the room before the LED settles into its steady pattern.
+pub struct LedController {
+    led: RgbLedLight,
+    colors: ColorScheme,
+    patterns: PatternScheme,
+    timings: PatternTimings,
+    controls: Arc<Controls>,
+    previous_status: Option<RemoteStatus>,
+    failing_since: Option<Instant>,
+    brightness_percent: i32,
+    escalation: Option<EscalationConfig>,
+    connectivity: Option<Connectivity>,
+    freeze: Option<Freeze>,
+    // What the LED is doing, in words, for the status dump
+    shown: String,
+}
+
+impl LedController {
+    pub fn new(
+        led: RgbLedLight,
+        colors: ColorScheme,
+        patterns: PatternScheme,
+        timings: PatternTimings,
+        controls: Arc<Controls>,
+    ) -> LedController {
+        LedController {
+            led: led,
+            colors: colors,
+            patterns: patterns,
+            timings: timings,
+            controls: controls,
+            previous_status: None,
+            failing_since: None,
+            brightness_percent: 100,
+            escalation: None,
+            connectivity: None,
+            freeze: None,
+            shown: "off".to_string(),
+        }
+    }
+
+    pub fn led(&mut self) -> &mut RgbLedLight {
+        &mut self.led
+    }
+
+    pub fn show_status(&mut self, status: RemoteStatus, summary: &StatusSummary) {
+        if status == RemoteStatus::Failing {
+            self.failing_since = self.failing_since.or_else(|| Some(Instant::now()));
+        } else {
+            self.failing_since = None;
+        }
+
+        let (color, pattern) = self.color_and_pattern_for(status, summary);
+        let color = match self.led.output_type() {
+            OutputType::Rgb => color,
+            OutputType::TrafficLight => lamps_for(status, summary),
+        };
+        let color = self.scale_to_brightness(color);
+
+        if self.controls.is_muted() {
+            self.previous_status = Some(status);
+            self.led.turn_led_off();
+            self.shown = "off, muted".to_string();
+            return;
+        }
+        if self.is_offline() {
+            self.previous_status = Some(status);
+            self.show_offline();
+            return;
+        }
+        if let Some(previous_status) = self.previous_status {
+            if previous_status != status {
+                info!(
+                    "LED transitioning from {:?} to {:?}.",
+                    previous_status, status
+                );
+                self.play_transition(previous_status, status, color);
+            }
+        }
+        self.previous_status = Some(status);
+
+        let acknowledged = self
+            .failing_since
+            .map_or(false, |since| self.controls.is_acknowledged(since));
+        if acknowledged {
+            let (r, g, b) = color;
+            let percent = ACKNOWLEDGED_BRIGHTNESS_PERCENT;
+            let dimmed = (r * percent / 100, g * percent / 100, b * percent / 100);
+            self.led.set_led_rgb_values(dimmed);
+            self.shown = format!("{:?} steady, failure acknowledged", dimmed);
+        } else if let Some(event) = self.freeze.as_ref().and_then(|freeze| freeze.current()) {
+            let freeze_color = match self.led.output_type() {
+                OutputType::Rgb => self.colors.freeze,
+                OutputType::TrafficLight => RgbLedLight::YELLOW_LAMP,
+            };
+            let freeze_color = self.scale_to_brightness(freeze_color);
+            let period_millis = self.timings.freeze_pulse_period_millis;
+            let frames = self.pulse_frames(color, freeze_color, FREEZE_PULSE_MILLIS, period_millis);
+            self.led.animate(frames);
+            self.shown = format!("{:?} with a purple pulse, {}", color, event);
+        } else if summary.queue_backed_up {
+            let frames = self.queue_pulse_frames(color);
+            self.led.animate(frames);
+            self.shown = format!("{:?} with a white pulse, queue backed up", color);
+        } else {
+            self.led.play_pattern(color, pattern, &self.timings);
+            self.shown = format!("{:?} {:?}", color, pattern);
+        }
+    }
+
+    // Shown instead of the status while polling is backing off after repeated failures.
+    pub fn show_degraded(&mut self) {
+        if self.controls.is_muted() {
+            self.led.turn_led_off();
+            self.shown = "off, muted".to_string();
+            return;
+        }
+        if self.is_offline() {
+            self.show_offline();
+            return;
+        }
+        let color = match self.led.output_type() {
+            OutputType::Rgb => self.colors.degraded,
+            OutputType::TrafficLight => RgbLedLight::YELLOW_LAMP,
+        };
+        let color = self.scale_to_brightness(color);
+        let pattern = self.patterns.degraded;
+        self.led.play_pattern(color, pattern, &self.timings);
+        self.shown = format!("{:?} {:?}, backing off", color, pattern);
+    }
+
+    // Cycles through the power-on test's colors, then glows purple until the
+    // first poll is shown. Does nothing if the test is turned off.
+    pub fn run_power_on_test(&mut self, config: &PowerOnTestConfig) {
+        if !config.enabled {
+            return;
+        }
+        let step = Duration::from_millis(config.step_millis);
+        self.led.turn_led_off();
+        thread::sleep(step * POWER_ON_PAUSE_STEPS as u32);
+        for &color in &config.sequence {
+            self.led.set_led_rgb_values(color);
+            thread::sleep(step);
+        }
+        self.led.turn_led_off();
+        self.led.glow_led(RgbLedLight::PURPLE);
+        self.shown = "glowing purple, waiting for the first poll".to_string();
+    }
+
+    // One blink of the passing color if the first poll worked, three of the
+    // failing color if the credentials were turned down, or three of the
+    // disconnected color if the server couldn't be polled at all.
+    pub fn show_preflight(&mut self, preflight: &Preflight) {
+        if self.controls.is_muted() {
+            return;
+        }
+        let traffic_light = self.led.output_type() == OutputType::TrafficLight;
+        let (color, times) = match *preflight {
+            Preflight::Passed if traffic_light => (RgbLedLight::GREEN_LAMP, 1),
+            Preflight::Passed => (self.colors.all_passing, 1),
+            Preflight::CredentialsRejected(_) if traffic_light => (RgbLedLight::RED_LAMP, 3),
+            Preflight::CredentialsRejected(_) => (self.colors.all_failing, 3),
+            Preflight::Failed(_) if traffic_light => (RgbLedLight::YELLOW_LAMP, 3),
+            Preflight::Failed(_) => (self.colors.disconnected, 3),
+        };
+        let color = self.scale_to_brightness(color);
+        self.flash_alternating(color, (0, 0, 0), times, PREFLIGHT_BLINK_MILLIS);
+    }
+
+    // A single white glow, then off, to show the build light is stopping.
+    pub fn shut_down(&mut self) {
+        let white = self.white();
+        self.led.glow_led_period(white, SHUTDOWN_GLOW_MILLIS);
+        thread::sleep(Duration::from_millis(SHUTDOWN_GLOW_MILLIS));
+        self.led.turn_led_off();
+        self.shown = "off".to_string();
+    }
+
+    // Makes the next status shown not count as a transition from the current one.
+    pub fn forget_previous_status(&mut self) {
+        self.previous_status = None;
+        self.failing_since = None;
+    }
+
+    pub fn set_brightness(&mut self, percent: i32) {
+        self.brightness_percent = percent.max(0).min(100);
+    }
+
+    // Failures blink slower or faster depending on how long they've lasted.
+    pub fn set_escalation(&mut self, escalation: Option<EscalationConfig>) {
+        self.escalation = escalation;
+    }
+
+    // While the network itself is down, every LED slowly blinks white instead.
+    pub fn set_connectivity(&mut self, connectivity: Connectivity) {
+        self.connectivity = Some(connectivity);
+    }
+
+    // During a release freeze every LED pulses the freeze color over its status.
+    pub fn set_freeze(&mut self, freeze: Freeze) {
+        self.freeze = Some(freeze);
+    }
+
+    pub fn is_offline(&self) -> bool {
+        self.connectivity
+            .as_ref()
+            .map_or(false, |connectivity| connectivity.is_offline())
+    }
+
+    pub fn turn_off(&mut self) {
+        self.led.turn_led_off();
+        self.shown = "off".to_string();
+    }
+
+    pub fn shown(&self) -> &str {
+        &self.shown
+    }
+
+    // A traffic light blinks yellow instead, like one that's out of service.
+    fn show_offline(&mut self) {
+        let color = match self.led.output_type() {
+            OutputType::Rgb => RgbLedLight::WHITE,
+            OutputType::TrafficLight => RgbLedLight::YELLOW_LAMP,
+        };
+        let color = self.scale_to_brightness(color);
+        self.led
+            .play_pattern(color, Pattern::SlowBlink, &self.timings);
+        self.shown = format!("{:?} {:?}, offline", color, Pattern::SlowBlink);
+    }
+
+    // Every die of an RGB LED, or every lamp of a traffic light.
+    fn white(&self) -> (i32, i32, i32) {
+        match self.led.output_type() {
+            OutputType::Rgb => RgbLedLight::WHITE,
+            OutputType::TrafficLight => RgbLedLight::ALL_LAMPS,
+        }
+    }
+
+    fn scale_to_brightness(&self, color: (i32, i32, i32)) -> (i32, i32, i32) {
+        let (r, g, b) = color;
+        let percent = self.brightness_percent;
+        (r * percent / 100, g * percent / 100, b * percent / 100)
+    }
+
+    fn color_and_pattern_for(
+        &self,
+        status: RemoteStatus,
+        summary: &StatusSummary,
+    ) -> ((i32, i32, i32), Pattern) {
+        let (colors, patterns) = (&self.colors, &self.patterns);
+        match status {
+            RemoteStatus::Failing if only_flaky_failures(summary) => (colors.flaky, patterns.flaky),
+            // Someone's on it, so unclaimed failures elsewhere stand out
+            RemoteStatus::Failing if only_claimed_failures(summary) => {
+                (colors.claimed, patterns.claimed)
+            }
+            RemoteStatus::Failing if summary.non_critical_failures_only => {
+                (colors.non_critical_failing, patterns.non_critical_failing)
+            }
+            RemoteStatus::Failing if summary.non_mainline_failures_only => {
+                (colors.non_mainline_failing, patterns.non_mainline_failing)
+            }
+            RemoteStatus::Passing if !summary.warning_jobs.is_empty() => {
+                (colors.warning, patterns.warning)
+            }
+            RemoteStatus::Passing => (colors.all_passing, patterns.all_passing),
+            RemoteStatus::Failing if summary.passing > 0 => {
+                (colors.some_failing, self.escalated(patterns.some_failing))
+            }
+            RemoteStatus::Failing => (colors.all_failing, self.escalated(patterns.all_failing)),
+            RemoteStatus::InProgress if !summary.overdue_jobs.is_empty() => {
+                (colors.overdue, patterns.overdue)
+            }
+            RemoteStatus::InProgress => (colors.building, patterns.building),
+            RemoteStatus::Unknown if summary.disconnected => {
+                (colors.disconnected, patterns.disconnected)
+            }
+            RemoteStatus::Unknown => (colors.indeterminate, patterns.indeterminate),
+        }
+    }
+
+    fn escalated(&self, pattern: Pattern) -> Pattern {
+        match (self.escalation.as_ref(), self.failing_since) {
+            (Some(escalation), Some(since)) => {
+                escalation::escalated_pattern(escalation, since.elapsed(), pattern)
+            }
+            _ => pattern,
+        }
+    }
+
+    // The status color held steady, with a slow fade through white once a
+    // period. Patterns don't mix with the pulse, so they're left out.
+    fn queue_pulse_frames(&self, color: (i32, i32, i32)) -> Vec<((i32, i32, i32), u64)> {
+        let white = self.scale_to_brightness(self.white());
+        let period_millis = self.timings.queue_pulse_period_millis;
+        self.pulse_frames(color, white, QUEUE_PULSE_MILLIS, period_millis)
+    }
+
+    // Holds color for the rest of period_millis, then fades to pulse and back
+    // over pulse_millis.
+    fn pulse_frames(
+        &self,
+        color: (i32, i32, i32),
+        pulse: (i32, i32, i32),
+        pulse_millis: u64,
+        period_millis: u64,
+    ) -> Vec<((i32, i32, i32), u64)> {
+        let step_millis = pulse_millis / (PULSE_STEPS * 2);
+        let hold_millis = period_millis.saturating_sub(pulse_millis);
+        let blend = |step: u64| {
+            let mix = |from: i32, to: i32| from + (to - from) * step as i32 / PULSE_STEPS as i32;
+            (
+                mix(color.0, pulse.0),
+                mix(color.1, pulse.1),
+                mix(color.2, pulse.2),
+            )
+        };
+        let mut frames = vec![(color, hold_millis)];
+        frames.extend((1..PULSE_STEPS + 1).map(|step| (blend(step), step_millis)));
+        frames.extend(
+            (0..PULSE_STEPS)
+                .rev()
+                .map(|step| (blend(step), step_millis)),
+        );
+        frames
+    }
+
+    fn play_transition(&mut self, from: RemoteStatus, to: RemoteStatus, color: (i32, i32, i32)) {
+        match (from, to) {
+            // Something broke: siren
+            (_, RemoteStatus::Failing) => {
+                let siren = match self.led.output_type() {
+                    OutputType::Rgb => RgbLedLight::BLUE,
+                    OutputType::TrafficLight => RgbLedLight::YELLOW_LAMP,
+                };
+                self.flash_alternating(color, siren, 8, 150)
+            }
+            // Fixed: rapid flash
+            (RemoteStatus::Failing, RemoteStatus::Passing) => {
+                self.flash_alternating(color, (0, 0, 0), 6, 100)
+            }
+            // Anything else just gets a single blip of the new state's color
+            _ => self.flash_alternating(color, (0, 0, 0), 1, 150),
+        }
+    }
+
+    fn flash_alternating(
+        &mut self,
+        first: (i32, i32, i32),
+        second: (i32, i32, i32),
+        times: u32,
+        step_millis: u64,
+    ) {
+        for _ in 0..times {
+            self.led.set_led_rgb_values(first);
+            thread::sleep(Duration::from_millis(step_millis));
+            self.led.set_led_rgb_values(second);
+            thread::sleep(Duration::from_millis(step_millis));
+        }
+    }
+}
+
+fn only_flaky_failures(summary: &StatusSummary) -> bool {
+    !summary.flaky_jobs.is_empty()
+        && summary
+            .failing_jobs
+            .iter()
+            .all(|job| summary.flaky_jobs.contains(job))
+}
+
+fn only_claimed_failures(summary: &StatusSummary) -> bool {
+    !summary.claimed_jobs.is_empty()
+        && summary
+            .failing_jobs
+            .iter()
+            .all(|job| summary.claimed_jobs.contains(job))
+}
+
+// Red for failures that need fixing, green for passing, and yellow for
+// everything in between. The pattern still comes from led_patterns.
+fn lamps_for(status: RemoteStatus, summary: &StatusSummary) -> (i32, i32, i32) {
+    match status {
+        RemoteStatus::Failing
+            if only_flaky_failures(summary)
+                || only_claimed_failures(summary)
+                || summary.non_critical_failures_only =>
+        {
+            RgbLedLight::YELLOW_LAMP
+        }
+        RemoteStatus::Failing => RgbLedLight::RED_LAMP,
+        RemoteStatus::Passing if !summary.warning_jobs.is_empty() => RgbLedLight::YELLOW_LAMP,
+        RemoteStatus::Passing => RgbLedLight::GREEN_LAMP,
+        RemoteStatus::InProgress | RemoteStatus::Unknown => RgbLedLight::YELLOW_LAMP,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gpio::FakeGpio;
+
+    fn controller(gpio: &Arc<FakeGpio>, controls: &Arc<Controls>) -> LedController {
+        LedController::new(
+            RgbLedLight::with_gpio(gpio.clone(), 1, 2, 3),
+            ColorScheme::default(),
+            // Solid everywhere, so the pins hold still for the assertions
+            PatternScheme {
+                all_passing: Pattern::Solid,
+                some_failing: Pattern::Solid,
+                all_failing: Pattern::Solid,
+                building: Pattern::Solid,
+                indeterminate: Pattern::Solid,
+                disconnected: Pattern::Solid,
+                degraded: Pattern::Solid,
+                flaky: Pattern::Solid,
+                non_critical_failing: Pattern::Solid,
+                non_mainline_failing: Pattern::Solid,
+                overdue: Pattern::Solid,
+                claimed: Pattern::Solid,
+                warning: Pattern::Solid,
+            },
+            PatternTimings::default(),
+            Arc::clone(controls),
+        )
+    }
+
+    fn failing_summary() -> StatusSummary {
+        StatusSummary {
+            failing: 1,
+            failing_jobs: vec!["api".to_string()],
+            ..StatusSummary::default()
+        }
+    }
+
+    #[test]
+    fn shows_the_status_color() {
+        let gpio = FakeGpio::new();
+        let mut led = controller(&gpio, &Arc::new(Controls::new()));
+        led.show_status(RemoteStatus::Passing, &StatusSummary::default());
+        assert_eq!(
+            gpio.pwm_values(&[1, 2, 3]),
+            vec![Some(0), Some(100), Some(0)]
+        );
+    }
+
+    #[test]
+    fn passing_board_with_a_flaky_job_stays_green() {
+        let gpio = FakeGpio::new();
+        let mut led = controller(&gpio, &Arc::new(Controls::new()));
+        let summary = StatusSummary {
+            passing: 1,
+            flaky_jobs: vec!["api".to_string()],
+            ..StatusSummary::default()
+        };
+        led.show_status(RemoteStatus::Passing, &summary);
+        assert_eq!(
+            gpio.pwm_values(&[1, 2, 3]),
+            vec![Some(0), Some(100), Some(0)]
+        );
+    }
+
+    #[test]
+    fn power_on_test_can_be_turned_off_or_replaced() {
+        let gpio = FakeGpio::new();
+        let mut led = controller(&gpio, &Arc::new(Controls::new()));
+        led.run_power_on_test(&PowerOnTestConfig {
+            enabled: false,
+            ..PowerOnTestConfig::default()
+        });
+        assert!(gpio.pwm_writes.lock().unwrap().is_empty());
+
+        led.run_power_on_test(&PowerOnTestConfig {
+            enabled: true,
+            step_millis: 1,
+            sequence: vec![(0, 0, 100), (0, 70, 0)],
+        });
+        let writes = gpio.pwm_writes.lock().unwrap().clone();
+        let blue = writes.iter().position(|&write| write == (3, 100));
+        let green = writes.iter().position(|&write| write == (2, 70));
+        assert!(blue.is_some() && green.is_some() && blue < green);
+        assert!(!writes.contains(&(1, 100)));
+    }
+
+    #[test]
+    fn new_failure_plays_the_siren() {
+        let gpio = FakeGpio::new();
+        let mut led = controller(&gpio, &Arc::new(Controls::new()));
+        led.show_status(RemoteStatus::Passing, &StatusSummary::default());
+        gpio.pwm_writes.lock().unwrap().clear();
+        led.show_status(RemoteStatus::Failing, &failing_summary());
+
+        let blue_flashes = gpio
+            .pwm_writes
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|&&write| write == (3, 100))
+            .count();
+        assert_eq!(blue_flashes, 8);
+        assert_eq!(
+            gpio.pwm_values(&[1, 2, 3]),
+            vec![Some(100), Some(0), Some(0)]
+        );
+    }
+
+    #[test]
+    fn acknowledged_failure_is_dimmed() {
+        let gpio = FakeGpio::new();
+        let controls = Arc::new(Controls::new());
+        let mut led = controller(&gpio, &controls);
+        led.show_status(RemoteStatus::Failing, &failing_summary());
+        controls.acknowledge();
+        led.show_status(RemoteStatus::Failing, &failing_summary());
+        assert_eq!(
+            gpio.pwm_values(&[1, 2, 3]),
+            vec![Some(30), Some(0), Some(0)]
+        );
+    }
+
+    #[test]
+    fn muted_led_is_off() {
+        let gpio = FakeGpio::new();
+        let controls = Arc::new(Controls::new());
+        controls.toggle_mute(Duration::from_secs(60));
+        let mut led = controller(&gpio, &controls);
+        led.show_status(RemoteStatus::Failing, &failing_summary());
+        assert_eq!(gpio.pwm_values(&[1, 2, 3]), vec![Some(0), Some(0), Some(0)]);
+    }
+
+    #[test]
+    fn non_critical_failures_are_yellow() {
+        let gpio = FakeGpio::new();
+        let mut led = controller(&gpio, &Arc::new(Controls::new()));
+        let summary = StatusSummary {
+            non_critical_failures_only: true,
+            ..failing_summary()
+        };
+        led.show_status(RemoteStatus::Failing, &summary);
+        let (r, g, b) = RgbLedLight::YELLOW;
+        assert_eq!(gpio.pwm_values(&[1, 2, 3]), vec![Some(r), Some(g), Some(b)]);
+    }
+
+    #[test]
+    fn claimed_failures_are_amber() {
+        let gpio = FakeGpio::new();
+        let mut led = controller(&gpio, &Arc::new(Controls::new()));
+        let summary = StatusSummary {
+            claimed_jobs: vec!["api".to_string()],
+            ..failing_summary()
+        };
+        led.show_status(RemoteStatus::Failing, &summary);
+        let (r, g, b) = RgbLedLight::AMBER;
+        assert_eq!(gpio.pwm_values(&[1, 2, 3]), vec![Some(r), Some(g), Some(b)]);
+
+        // Until another job fails without being claimed
+        let summary = StatusSummary {
+            failing: 2,
+            failing_jobs: vec!["api".to_string(), "app".to_string()],
+            ..summary
+        };
+        led.show_status(RemoteStatus::Failing, &summary);
+        let (r, g, b) = RgbLedLight::RED;
+        assert_eq!(gpio.pwm_values(&[1, 2, 3]), vec![Some(r), Some(g), Some(b)]);
+    }
+
+    #[test]
+    fn warnings_are_yellow() {
+        let gpio = FakeGpio::new();
+        let mut led = controller(&gpio, &Arc::new(Controls::new()));
+        let summary = StatusSummary {
+            passing: 1,
+            warning_jobs: vec!["backend".to_string()],
+            ..StatusSummary::default()
+        };
+        led.show_status(RemoteStatus::Passing, &summary);
+        let (r, g, b) = RgbLedLight::YELLOW;
+        assert_eq!(gpio.pwm_values(&[1, 2, 3]), vec![Some(r), Some(g), Some(b)]);
+    }
+
+    #[test]
+    fn offline_blinks_white_rather_than_yellow() {
+        let gpio = FakeGpio::new();
+        let mut led = controller(&gpio, &Arc::new(Controls::new()));
+        led.show_offline();
+        assert_eq!(led.shown(), "(100, 100, 100) SlowBlink, offline");
+        led.turn_off();
+    }
+
+    #[test]
+    fn backed_up_queue_pulses_white() {
+        let gpio = FakeGpio::new();
+        let led = controller(&gpio, &Arc::new(Controls::new()));
+        let frames = led.queue_pulse_frames(RgbLedLight::GREEN);
+        assert_eq!(
+            frames.first().map(|frame| frame.0),
+            Some(RgbLedLight::GREEN)
+        );
+        assert!(frames.iter().any(|frame| frame.0 == (100, 100, 100)));
+        assert_eq!(frames.last().map(|frame| frame.0), Some(RgbLedLight::GREEN));
+        let total_millis: u64 = frames.iter().map(|frame| frame.1).sum();
+        assert_eq!(
+            total_millis,
+            PatternTimings::default().queue_pulse_period_millis
+        );
+    }
+
+    #[test]
+    fn overdue_build_has_its_own_color() {
+        let gpio = FakeGpio::new();
+        let mut led = controller(&gpio, &Arc::new(Controls::new()));
+        let summary = StatusSummary {
+            in_progress: 1,
+            overdue_jobs: vec!["api".to_string()],
+            ..StatusSummary::default()
+        };
+        led.show_status(RemoteStatus::InProgress, &summary);
+        let (r, g, b) = RgbLedLight::TEAL;
+        assert_eq!(gpio.pwm_values(&[1, 2, 3]), vec![Some(r), Some(g), Some(b)]);
+    }
+
+    #[test]
+    fn traffic_light_lights_the_lamp_for_the_status() {
+        let gpio = FakeGpio::new();
+        let mut light = RgbLedLight::with_gpio(gpio.clone(), 1, 2, 3);
+        light.set_output_type(OutputType::TrafficLight);
+        let mut led = controller(&gpio, &Arc::new(Controls::new()));
+        led.led = light;
+        let lamps = |gpio: &FakeGpio| {
+            let writes = gpio.digital_writes.lock().unwrap();
+            let (_, last_three) = writes.split_at(writes.len() - 3);
+            last_three.to_vec()
+        };
+
+        led.show_status(RemoteStatus::Failing, &failing_summary());
+        assert_eq!(lamps(&gpio), vec![(1, true), (2, false), (3, false)]);
+        led.show_status(RemoteStatus::InProgress, &StatusSummary::default());
+        assert_eq!(lamps(&gpio), vec![(1, false), (2, true), (3, false)]);
+        led.show_status(RemoteStatus::Passing, &StatusSummary::default());
+        assert_eq!(lamps(&gpio), vec![(1, false), (2, false), (3, true)]);
+    }
+
+    #[test]
+    fn brightness_scales_the_color() {
+        let gpio = FakeGpio::new();
+        let mut led = controller(&gpio, &Arc::new(Controls::new()));
+        led.set_brightness(50);
+        led.show_status(RemoteStatus::Passing, &StatusSummary::default());
+        assert_eq!(
+            gpio.pwm_values(&[1, 2, 3]),
+            vec![Some(0), Some(50), Some(0)]
+        );
+    }
+}
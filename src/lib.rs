@@ -0,0 +1,96 @@
+// The build light as a library: the integrations that poll build servers,
+// the status board they publish to, and the LEDs, displays and notifiers that
+// show it. The binary in main.rs is one front-end; app::run wires everything
+// up from config.toml the way it does.
+
+#[macro_use]
+extern crate serde_derive;
+
+#[macro_use]
+extern crate lazy_static;
+
+#[macro_use]
+extern crate failure;
+
+#[macro_use]
+extern crate log;
+extern crate log4rs;
+extern crate log_mdc;
+
+extern crate base64;
+extern crate chrono;
+extern crate ctrlc;
+extern crate libc;
+extern crate native_tls;
+extern crate openssl;
+extern crate rand;
+extern crate reqwest;
+#[macro_use]
+extern crate rusqlite;
+extern crate serde;
+#[macro_use]
+extern crate serde_json;
+extern crate toml;
+extern crate wiringpi;
+
+pub mod aggregate;
+pub mod app;
+mod auth;
+pub mod backoff;
+pub mod beacon;
+pub mod budget;
+pub mod calendar;
+pub mod clock;
+pub mod config_file;
+pub mod connectivity;
+pub mod controls;
+pub mod credentials;
+pub mod displays;
+pub mod durations;
+pub mod errors;
+pub mod escalation;
+pub mod events;
+pub mod export;
+pub mod failures;
+pub mod faults;
+pub mod gpio;
+pub mod headers;
+pub mod health;
+pub mod history;
+pub mod integrations;
+pub mod job_groups;
+pub mod led_controller;
+pub mod metrics;
+pub mod netrc;
+pub mod network;
+pub mod notifiers;
+pub mod pagination;
+pub mod pin;
+pub mod preflight;
+mod rate_limit;
+pub mod recording;
+pub mod redact;
+pub mod relay;
+pub mod remote_status;
+pub mod replay;
+pub mod report;
+pub mod schedule;
+pub mod scheduler;
+pub mod secrets;
+pub mod selector;
+pub mod self_test;
+pub mod setup;
+pub mod signals;
+pub mod status_board;
+pub mod supervisor;
+pub mod systemd;
+pub mod unity_webhook;
+pub mod vault;
+pub mod websocket;
+
+#[cfg(test)]
+mod test_support;
+
+lazy_static! {
+    static ref HTTP_CLIENT: reqwest::Client = network::build_default_client();
+}
@@ -0,0 +1,296 @@
+use chrono::{DateTime, Duration as ChronoDuration, Local, Utc};
+use config_file::MetricsConfig;
+use failure::Error;
+use remote_status::{JobStatus, RemoteStatus};
+use rusqlite::{Connection, NO_PARAMS};
+use status_board::StatusBoard;
+use std::collections::HashMap;
+use std::sync::mpsc;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const PRUNE_EVERY_SECONDS: u64 = 60 * 60;
+// Sorts the same as text as it does as a time, which the queries rely on.
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.3fZ";
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS polls (
+        id INTEGER PRIMARY KEY,
+        polled_at TEXT NOT NULL,
+        integration TEXT NOT NULL,
+        status TEXT NOT NULL,
+        passing INTEGER NOT NULL,
+        failing INTEGER NOT NULL,
+        in_progress INTEGER NOT NULL,
+        indeterminate INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS polls_by_time ON polls (polled_at);
+    CREATE TABLE IF NOT EXISTS job_results (
+        poll_id INTEGER NOT NULL REFERENCES polls (id),
+        job TEXT NOT NULL,
+        status TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS job_results_by_poll ON job_results (poll_id);
+";
+
+// One poll of one integration, as it's stored.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PollRecord {
+    pub polled_at: DateTime<Utc>,
+    pub integration: String,
+    pub status: RemoteStatus,
+    pub passing: usize,
+    pub failing: usize,
+    pub in_progress: usize,
+    pub indeterminate: usize,
+    pub jobs: Vec<JobStatus>,
+}
+
+// Every poll result, kept in an SQLite database for retention_days.
+pub struct MetricsStore {
+    connection: Connection,
+    retention_days: u32,
+}
+
+impl MetricsStore {
+    pub fn open(path: &str, retention_days: u32) -> Result<MetricsStore, Error> {
+        let connection = Connection::open(path)?;
+        connection.execute_batch(SCHEMA)?;
+        Ok(MetricsStore {
+            connection: connection,
+            retention_days: retention_days,
+        })
+    }
+
+    pub fn record(&mut self, poll: &PollRecord) -> Result<(), Error> {
+        let transaction = self.connection.transaction()?;
+        transaction.execute(
+            "INSERT INTO polls (polled_at, integration, status, passing, failing, in_progress, indeterminate)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                poll.polled_at.format(TIMESTAMP_FORMAT).to_string(),
+                poll.integration,
+                format!("{:?}", poll.status),
+                poll.passing as i64,
+                poll.failing as i64,
+                poll.in_progress as i64,
+                poll.indeterminate as i64
+            ],
+        )?;
+        let poll_id = transaction.last_insert_rowid();
+        for job in &poll.jobs {
+            transaction.execute(
+                "INSERT INTO job_results (poll_id, job, status) VALUES (?1, ?2, ?3)",
+                params![poll_id, job.name, format!("{:?}", job.status)],
+            )?;
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    // Polls from `from` up to, but not including, `to`, oldest first.
+    pub fn polls_between(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<PollRecord>, Error> {
+        let mut polls_statement = self.connection.prepare(
+            "SELECT id, polled_at, integration, status, passing, failing, in_progress, indeterminate
+             FROM polls WHERE polled_at >= ?1 AND polled_at < ?2 ORDER BY polled_at, id",
+        )?;
+        let mut jobs_statement = self
+            .connection
+            .prepare("SELECT job, status FROM job_results WHERE poll_id = ?1 ORDER BY rowid")?;
+        let rows = polls_statement.query_map(
+            params![
+                from.format(TIMESTAMP_FORMAT).to_string(),
+                to.format(TIMESTAMP_FORMAT).to_string()
+            ],
+            |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    PollRecord {
+                        polled_at: Utc::now(),
+                        integration: row.get(2)?,
+                        status: parse_status(&row.get::<_, String>(3)?),
+                        passing: row.get::<_, i64>(4)? as usize,
+                        failing: row.get::<_, i64>(5)? as usize,
+                        in_progress: row.get::<_, i64>(6)? as usize,
+                        indeterminate: row.get::<_, i64>(7)? as usize,
+                        jobs: Vec::new(),
+                    },
+                ))
+            },
+        )?;
+
+        let mut polls = Vec::new();
+        for row in rows {
+            let (id, polled_at, mut poll) = row?;
+            poll.polled_at = DateTime::parse_from_rfc3339(&polled_at)?.with_timezone(&Utc);
+            let jobs = jobs_statement.query_map(params![id], |row| {
+                Ok(JobStatus {
+                    name: row.get(0)?,
+                    status: parse_status(&row.get::<_, String>(1)?),
+                })
+            })?;
+            for job in jobs {
+                poll.jobs.push(job?);
+            }
+            polls.push(poll);
+        }
+        Ok(polls)
+    }
+
+    // Deletes the polls older than retention_days, returning how many there were.
+    pub fn prune(&self, now: DateTime<Utc>) -> Result<usize, Error> {
+        let cutoff = (now - ChronoDuration::days(i64::from(self.retention_days)))
+            .format(TIMESTAMP_FORMAT)
+            .to_string();
+        self.connection.execute(
+            "DELETE FROM job_results WHERE poll_id IN (SELECT id FROM polls WHERE polled_at < ?1)",
+            params![cutoff],
+        )?;
+        let deleted = self
+            .connection
+            .execute("DELETE FROM polls WHERE polled_at < ?1", params![cutoff])?;
+        Ok(deleted)
+    }
+
+    pub fn poll_count(&self) -> Result<usize, Error> {
+        let count: i64 =
+            self.connection
+                .query_row("SELECT COUNT(*) FROM polls", NO_PARAMS, |row| row.get(0))?;
+        Ok(count as usize)
+    }
+}
+
+fn parse_status(status: &str) -> RemoteStatus {
+    match status {
+        "Passing" => RemoteStatus::Passing,
+        "Failing" => RemoteStatus::Failing,
+        "InProgress" => RemoteStatus::InProgress,
+        _ => RemoteStatus::Unknown,
+    }
+}
+
+// Stores every poll of every integration in config.database. Writes happen on
+// their own thread, so a slow SD card doesn't hold up the LEDs.
+pub fn start_metrics(config: &MetricsConfig, board: &StatusBoard) {
+    let mut store = match MetricsStore::open(&config.database, config.retention_days) {
+        Ok(store) => store,
+        Err(e) => {
+            error!(
+                "--Metrics--: Failed to open {}. Build history won't be kept. Error: {}",
+                config.database, e
+            );
+            return;
+        }
+    };
+    info!(
+        "--Metrics--: Keeping {} days of build history in {}",
+        config.retention_days, config.database
+    );
+
+    let (sender, receiver) = mpsc::channel::<PollRecord>();
+    thread::spawn(move || {
+        let mut last_pruned: Option<Instant> = None;
+        for poll in receiver {
+            if let Err(e) = store.record(&poll) {
+                warn!("--Metrics--: Failed to store a poll. Error: {}", e);
+            }
+            if last_pruned.map_or(true, |pruned| {
+                pruned.elapsed() >= Duration::from_secs(PRUNE_EVERY_SECONDS)
+            }) {
+                last_pruned = Some(Instant::now());
+                match store.prune(Utc::now()) {
+                    Ok(0) => (),
+                    Ok(deleted) => info!("--Metrics--: Deleted {} old polls.", deleted),
+                    Err(e) => warn!("--Metrics--: Failed to delete old polls. Error: {}", e),
+                }
+            }
+        }
+    });
+
+    // Subscribers see every integration on every update, so only the one
+    // that was just polled gets stored.
+    let last_polls: Mutex<HashMap<String, DateTime<Local>>> = Mutex::new(HashMap::new());
+    board.subscribe(Box::new(move |snapshots| {
+        if let Ok(mut last_polls) = last_polls.lock() {
+            for snapshot in snapshots {
+                if last_polls.insert(snapshot.name.clone(), snapshot.last_poll)
+                    == Some(snapshot.last_poll)
+                {
+                    continue;
+                }
+                let summary = &snapshot.summary;
+                let _ = sender.send(PollRecord {
+                    polled_at: snapshot.last_poll.with_timezone(&Utc),
+                    integration: snapshot.name.clone(),
+                    status: snapshot.status,
+                    passing: summary.passing,
+                    failing: summary.failing,
+                    in_progress: summary.in_progress,
+                    indeterminate: summary.indeterminate,
+                    jobs: summary.jobs.clone(),
+                });
+            }
+        }
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn poll(day: u32, status: RemoteStatus) -> PollRecord {
+        PollRecord {
+            polled_at: Utc.ymd(2018, 3, day).and_hms(12, 0, 0),
+            integration: "Jenkins".to_string(),
+            status: status,
+            passing: 1,
+            failing: 1,
+            in_progress: 0,
+            indeterminate: 0,
+            jobs: vec![
+                JobStatus {
+                    name: "api".to_string(),
+                    status: RemoteStatus::Failing,
+                },
+                JobStatus {
+                    name: "app".to_string(),
+                    status: RemoteStatus::Passing,
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn stored_polls_read_back_by_time() {
+        let mut store = MetricsStore::open(":memory:", 30).unwrap();
+        store.record(&poll(1, RemoteStatus::Failing)).unwrap();
+        store.record(&poll(2, RemoteStatus::Passing)).unwrap();
+        store.record(&poll(3, RemoteStatus::Failing)).unwrap();
+
+        let polls = store
+            .polls_between(
+                Utc.ymd(2018, 3, 2).and_hms(0, 0, 0),
+                Utc.ymd(2018, 3, 3).and_hms(12, 0, 0),
+            )
+            .unwrap();
+        assert_eq!(polls, vec![poll(2, RemoteStatus::Passing)]);
+    }
+
+    #[test]
+    fn polls_older_than_the_retention_are_pruned() {
+        let mut store = MetricsStore::open(":memory:", 30).unwrap();
+        store.record(&poll(1, RemoteStatus::Failing)).unwrap();
+        store.record(&poll(20, RemoteStatus::Passing)).unwrap();
+
+        let deleted = store.prune(Utc.ymd(2018, 4, 5).and_hms(0, 0, 0)).unwrap();
+        assert_eq!(deleted, 1);
+        assert_eq!(store.poll_count().unwrap(), 1);
+    }
+}
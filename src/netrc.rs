@@ -0,0 +1,170 @@
+use failure::Error;
+use reqwest::Url;
+use std::env;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+// One machine's entry, or the default one.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NetrcEntry {
+    pub login: Option<String>,
+    pub password: Option<String>,
+}
+
+// The parts of a .netrc file the build light uses: login and password by
+// machine, and the default entry. Macros and accounts are skipped.
+#[derive(Debug, Default)]
+pub struct Netrc {
+    machines: Vec<(String, NetrcEntry)>,
+    default: Option<NetrcEntry>,
+}
+
+impl Netrc {
+    pub fn read(path: &Path) -> Result<Netrc, Error> {
+        let mut text = String::new();
+        File::open(path)
+            .and_then(|mut file| file.read_to_string(&mut text))
+            .map_err(|err| format_err!("Couldn't read {}: {}", path.display(), err))?;
+        Netrc::parse(&text).map_err(|err| format_err!("{} is invalid: {}", path.display(), err))
+    }
+
+    pub fn parse(text: &str) -> Result<Netrc, Error> {
+        let mut netrc = Netrc::default();
+        // Where login and password go, None before the first machine
+        let mut current: Option<NetrcEntry> = None;
+        let mut current_machine: Option<String> = None;
+        let mut lines = text.lines();
+        while let Some(line) = lines.next() {
+            if line.trim_start().starts_with('#') {
+                continue;
+            }
+            let mut tokens = line.split_whitespace();
+            while let Some(token) = tokens.next() {
+                let mut value = || {
+                    tokens
+                        .next()
+                        .map(|value| value.to_string())
+                        .ok_or_else(|| format_err!("\"{}\" without a value", token))
+                };
+                match token {
+                    "machine" | "default" => {
+                        let machine = if token == "machine" {
+                            Some(value()?)
+                        } else {
+                            None
+                        };
+                        netrc.finish(current_machine.take(), current.take());
+                        current_machine = machine;
+                        current = Some(NetrcEntry {
+                            login: None,
+                            password: None,
+                        });
+                    }
+                    "login" | "password" => {
+                        let value = value()?;
+                        let entry = current
+                            .as_mut()
+                            .ok_or_else(|| format_err!("\"{}\" before any machine", token))?;
+                        if token == "login" {
+                            entry.login = Some(value);
+                        } else {
+                            entry.password = Some(value);
+                        }
+                    }
+                    "account" => {
+                        value()?;
+                    }
+                    // A macro runs until the next blank line
+                    "macdef" => {
+                        while lines.next().map_or(false, |line| !line.trim().is_empty()) {}
+                        break;
+                    }
+                    _ => return Err(format_err!("Unknown token \"{}\"", token)),
+                }
+            }
+        }
+        netrc.finish(current_machine, current);
+        Ok(netrc)
+    }
+
+    fn finish(&mut self, machine: Option<String>, entry: Option<NetrcEntry>) {
+        match (machine, entry) {
+            (Some(machine), Some(entry)) => self.machines.push((machine, entry)),
+            (None, Some(entry)) => self.default = Some(entry),
+            _ => (),
+        }
+    }
+
+    // The first entry for host, else the default entry, like curl does.
+    pub fn entry(&self, host: &str) -> Option<&NetrcEntry> {
+        self.machines
+            .iter()
+            .find(|&&(ref machine, _)| machine.eq_ignore_ascii_case(host))
+            .map(|&(_, ref entry)| entry)
+            .or(self.default.as_ref())
+    }
+
+    // The entry for the host in url, which has to have a password.
+    pub fn entry_for_url(&self, url: &str) -> Result<&NetrcEntry, Error> {
+        let host = Url::parse(url)
+            .ok()
+            .and_then(|url| url.host_str().map(|host| host.to_string()))
+            .ok_or_else(|| format_err!("{} has no host to look up", url))?;
+        match self.entry(&host) {
+            Some(entry) if entry.password.is_some() => Ok(entry),
+            Some(_) => Err(format_err!("The entry for {} has no password", host)),
+            None => Err(format_err!("There's no entry for {}", host)),
+        }
+    }
+}
+
+// $NETRC if it's set, otherwise ~/.netrc.
+pub fn default_path() -> Option<PathBuf> {
+    match env::var_os("NETRC") {
+        Some(path) => Some(PathBuf::from(path)),
+        None => env::var_os("HOME").map(|home| Path::new(&home).join(".netrc")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_are_found_by_host() {
+        let netrc = Netrc::parse(
+            "# Provisioned by the office tooling
+             machine ci.example.com login light password secret
+             machine build-api.cloud.unity3d.com
+                 password unity-token
+
+             macdef init
+             cd /pub
+
+             default login anonymous password guest",
+        )
+        .unwrap();
+
+        let jenkins = netrc
+            .entry_for_url("https://ci.example.com/jenkins")
+            .unwrap();
+        assert_eq!(jenkins.login, Some("light".to_string()));
+        assert_eq!(jenkins.password, Some("secret".to_string()));
+        let unity = netrc
+            .entry_for_url("https://BUILD-API.cloud.unity3d.com/api/v1")
+            .unwrap();
+        assert_eq!(unity.password, Some("unity-token".to_string()));
+        assert_eq!(
+            netrc.entry("elsewhere.example.com").unwrap().login,
+            Some("anonymous".to_string())
+        );
+
+        assert!(Netrc::parse("machine").is_err());
+        assert!(Netrc::parse("login light").is_err());
+        assert!(Netrc::parse("machine ci.example.com login light")
+            .unwrap()
+            .entry_for_url("https://ci.example.com")
+            .is_err());
+    }
+}
@@ -1,39 +1,691 @@
 extern crate serde;
 extern crate serde_json;
 
+use auth::BearerAuth;
+use base64;
+use budget::RequestBudget;
+use clock::ClockCheck;
+use config_file::HttpConfig;
+use errors::HttpStatusError;
 use failure::Error;
-use reqwest::header::{Basic, Headers};
-use reqwest::{StatusCode, Url};
+use faults::{self, Fault, FaultInjector, InjectedTimeout};
+use headers::{self, Headers, AUTHORIZATION};
+use rate_limit::RateLimit;
+use recording::{RecordedResponse, Recorder, Replay};
+use redact::{self, redact};
+use reqwest::header::Headers as ClientHeaders;
+use reqwest::{self, Certificate, Client, Identity, Proxy, Response, StatusCode, Url};
+use std::collections::HashMap;
+use std::env;
+use std::fs::File;
+use std::io::Read;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 use HTTP_CLIENT;
 
-pub fn get_basic_credentials(username: &str, password: Option<String>) -> Basic {
-    Basic {
-        username: username.to_string(),
-        password: password,
+// A response we can reuse when the server says it hasn't changed. The ETag
+// and Last-Modified values are sent back as they came.
+struct CachedResponse {
+    etag: Option<String>,
+    last_modified: Option<String>,
+    body: String,
+    headers: Headers,
+}
+
+// An integration's HTTP client, along with what we've learned about its
+// server's rate limits and clock, the last response from every URL that had
+// an ETag or Last-Modified header, and the bearer token to send, if any.
+// Responses can also be recorded, or served from a recording instead of
+// being requested at all, and requests made to fail for soak tests.
+#[derive(Clone)]
+pub struct HttpClient {
+    client: Client,
+    timeout: Duration,
+    max_response_bytes: u64,
+    rate_limit: RateLimit,
+    clock: ClockCheck,
+    // Shared with clones, so a reload reaches every one of them
+    bearer_auth: Arc<Mutex<Option<BearerAuth>>>,
+    cache: Arc<Mutex<HashMap<String, CachedResponse>>>,
+    recorder: Option<Recorder>,
+    replay: Option<Replay>,
+    faults: Option<FaultInjector>,
+    // See trace_http
+    trace: bool,
+}
+
+impl HttpClient {
+    // A client that never sends anything, and answers from replay instead.
+    pub fn replaying(replay: Replay) -> HttpClient {
+        HttpClient {
+            client: Client::new(),
+            timeout: Duration::from_secs(0),
+            max_response_bytes: ::std::u64::MAX,
+            rate_limit: RateLimit::new(),
+            clock: ClockCheck::new(),
+            bearer_auth: Arc::new(Mutex::new(None)),
+            cache: Arc::new(Mutex::new(HashMap::new())),
+            recorder: None,
+            replay: Some(replay),
+            faults: None,
+            trace: false,
+        }
+    }
+
+    // Switches to the bearer_token or oauth2 settings in config, e.g. after
+    // the token was rotated. The old ones are kept if the new ones are invalid.
+    pub fn reload_bearer_auth(&self, config: &HttpConfig) -> Result<(), Error> {
+        let auth = BearerAuth::from_config(config)?;
+        let mut current = self
+            .bearer_auth
+            .lock()
+            .map_err(|_| format_err!("Failed to acquire a lock on the bearer token."))?;
+        *current = auth;
+        Ok(())
+    }
+
+    fn bearer_auth(&self) -> Option<BearerAuth> {
+        self.bearer_auth.lock().ok().and_then(|auth| auth.clone())
+    }
+
+    pub fn inject_faults(&mut self, faults: FaultInjector) {
+        self.faults = Some(faults);
+    }
+
+    // Records every response from here on under directory, see [recording].
+    pub fn record_to(&mut self, directory: PathBuf, max_polls: usize) {
+        self.recorder = Some(Recorder::new(directory, max_polls));
+    }
+
+    // Called by integrations as they start polling, so the responses of
+    // each poll are recorded, or replayed, together.
+    pub fn start_poll(&self) {
+        if let Some(ref recorder) = self.recorder {
+            recorder.start_poll();
+        }
+        if let Some(ref replay) = self.replay {
+            replay.start_poll();
+        }
+    }
+
+    fn record(&self, url_string: &str, status: StatusCode, headers: &Headers, body: &str) {
+        if let Some(ref recorder) = self.recorder {
+            recorder.record(&RecordedResponse::new(url_string, status, headers, body));
+        }
+    }
+
+    // How long the server wants us to stay away, if it's rate limiting us.
+    pub fn rate_limited_for(&self) -> Option<Duration> {
+        self.rate_limit.remaining()
+    }
+
+    pub fn request_budget(&self) -> Option<RequestBudget> {
+        self.rate_limit.budget()
+    }
+}
+
+// An integration's server, and mirrors of it to switch to when the one in
+// use can't be reached. Whichever one last worked is kept on using.
+#[derive(Clone, Debug)]
+pub struct BaseUrls {
+    urls: Vec<String>,
+    current: usize,
+}
+
+impl BaseUrls {
+    pub fn new(primary: &str, fallbacks: &[String]) -> BaseUrls {
+        let mut urls = vec![primary.to_string()];
+        urls.extend(fallbacks.iter().cloned());
+        BaseUrls {
+            urls: urls,
+            current: 0,
+        }
+    }
+
+    pub fn current(&self) -> &str {
+        &self.urls[self.current]
+    }
+
+    pub fn count(&self) -> usize {
+        self.urls.len()
+    }
+
+    // Moves on to the next one, back to the primary after the last fallback.
+    pub fn fall_back(&mut self) {
+        self.current = (self.current + 1) % self.urls.len();
+    }
+}
+
+// Whether a request never got an answer: DNS, connection and TLS failures and
+// timeouts, as opposed to an error status or a response we couldn't read.
+pub fn is_unreachable(err: &Error) -> bool {
+    err.downcast_ref::<InjectedTimeout>().is_some()
+        || err.downcast_ref::<reqwest::Error>().map_or(false, |err| {
+            err.status().is_none() && !err.is_serialization() && !err.is_redirect()
+        })
+}
+
+// Builds the HTTP client an integration polls its server at base_url with.
+pub fn build_client(config: &HttpConfig, base_url: &str) -> Result<HttpClient, Error> {
+    let mut builder = Client::builder();
+    builder.timeout(Duration::from_secs(config.timeout_seconds));
+    let proxies = ProxySettings::new(config.proxy.as_ref().map(|proxy| proxy.as_str()))?;
+    if let Some(proxy_url) = Url::parse(base_url)
+        .ok()
+        .and_then(|server_url| proxies.proxy_for(&server_url))
+    {
+        if !proxy_url.username().is_empty() {
+            // The client can only send credentials along with plain HTTP requests,
+            // not when opening a tunnel for HTTPS.
+            if !base_url.starts_with("http://") {
+                return Err(format_err!(
+                    "Proxy credentials are only supported for http:// servers, not {}",
+                    base_url
+                ));
+            }
+            let mut headers = Headers::new();
+            headers.set(
+                "Proxy-Authorization",
+                get_basic_credentials(
+                    proxy_url.username(),
+                    proxy_url.password().map(|password| password.to_string()),
+                ),
+            );
+            builder.default_headers(client_headers(&headers));
+        }
+    }
+    builder.proxy(proxies.into_proxy());
+    if let Some(ref path) = config.root_certificate {
+        let der = read_der(path)?;
+        builder.add_root_certificate(Certificate::from_der(&der)?);
+    }
+    if let Some(ref path) = config.client_identity {
+        let mut pkcs12 = Vec::new();
+        File::open(path)?.read_to_end(&mut pkcs12)?;
+        let password = config
+            .client_identity_password
+            .as_ref()
+            .map_or("", |p| p.as_str());
+        builder.identity(Identity::from_pkcs12_der(&pkcs12, password)?);
+    }
+    if config.danger_accept_invalid_hostnames {
+        builder.danger_disable_hostname_verification();
+    }
+    Ok(HttpClient {
+        client: builder.build()?,
+        timeout: Duration::from_secs(config.timeout_seconds),
+        max_response_bytes: config.max_response_kilobytes * 1024,
+        rate_limit: RateLimit::new(),
+        clock: ClockCheck::new(),
+        bearer_auth: Arc::new(Mutex::new(BearerAuth::from_config(config)?)),
+        cache: Arc::new(Mutex::new(HashMap::new())),
+        recorder: None,
+        replay: None,
+        faults: None,
+        trace: config.trace_http,
+    })
+}
+
+// Client for everything other than polling the integrations, e.g. notifications.
+pub fn build_default_client() -> Client {
+    build_proxied_client(None)
+}
+
+// Like the default client, but giving up on requests after timeout.
+pub fn build_client_with_timeout(timeout: Duration) -> Client {
+    build_proxied_client(Some(timeout))
+}
+
+fn build_proxied_client(timeout: Option<Duration>) -> Client {
+    ProxySettings::new(None)
+        .and_then(|proxies| {
+            let mut builder = Client::builder();
+            builder.proxy(proxies.into_proxy());
+            if let Some(timeout) = timeout {
+                builder.timeout(timeout);
+            }
+            Ok(builder.build()?)
+        })
+        .unwrap_or_else(|err| {
+            error!("Invalid proxy settings, not using a proxy. Error: {}", err);
+            Client::new()
+        })
+}
+
+// Which proxy each request goes through: the configured one if there is one,
+// otherwise http_proxy or https_proxy from the environment, depending on the
+// request's scheme. Hosts in no_proxy are always reached directly.
+struct ProxySettings {
+    explicit: Option<Url>,
+    http: Option<Url>,
+    https: Option<Url>,
+    no_proxy: Vec<String>,
+}
+
+impl ProxySettings {
+    fn new(explicit: Option<&str>) -> Result<ProxySettings, Error> {
+        let parse = |url: Option<String>| -> Result<Option<Url>, Error> {
+            match url {
+                Some(ref url) if !url.is_empty() => Ok(Some(Url::parse(url)?)),
+                _ => Ok(None),
+            }
+        };
+        let no_proxy = env_var("no_proxy")
+            .map(|hosts| {
+                hosts
+                    .split(',')
+                    .map(|host| host.trim().trim_matches('.').to_lowercase())
+                    .filter(|host| !host.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default();
+        Ok(ProxySettings {
+            explicit: parse(explicit.map(|url| url.to_string()))?,
+            http: parse(env_var("http_proxy"))?,
+            https: parse(env_var("https_proxy"))?,
+            no_proxy: no_proxy,
+        })
+    }
+
+    fn proxy_for(&self, url: &Url) -> Option<Url> {
+        let host = url.host_str().unwrap_or("").to_lowercase();
+        let bypassed = self.no_proxy.iter().any(|no_proxy| {
+            no_proxy == "*" || host == *no_proxy || host.ends_with(&format!(".{}", no_proxy))
+        });
+        if bypassed {
+            return None;
+        }
+        self.explicit.clone().or_else(|| match url.scheme() {
+            "https" => self.https.clone(),
+            _ => self.http.clone(),
+        })
+    }
+
+    fn into_proxy(self) -> Proxy {
+        Proxy::custom(move |url| self.proxy_for(url))
+    }
+}
+
+// Proxy variables are conventionally lowercase, but uppercase is common too.
+fn env_var(name: &str) -> Option<String> {
+    env::var(name)
+        .or_else(|_| env::var(name.to_uppercase()))
+        .ok()
+}
+
+// Reads a certificate file, either DER or PEM, as DER.
+fn read_der(path: &str) -> Result<Vec<u8>, Error> {
+    let mut contents = Vec::new();
+    File::open(path)?.read_to_end(&mut contents)?;
+    if !contents.starts_with(b"-----BEGIN") {
+        return Ok(contents);
+    }
+    let pem = String::from_utf8(contents)?;
+    let body: String = pem
+        .lines()
+        .skip_while(|line| !line.starts_with("-----BEGIN"))
+        .skip(1)
+        .take_while(|line| !line.starts_with("-----END"))
+        .collect();
+    Ok(base64::decode(&body)?)
+}
+
+pub fn get_basic_credentials(username: &str, password: Option<String>) -> String {
+    headers::basic_auth(
+        username,
+        password.as_ref().map(|password| password.as_str()),
+    )
+}
+
+// Headers as the HTTP client takes them. Nothing outside this file uses its
+// typed headers, so they don't stand in the way of upgrading it.
+fn client_headers(headers: &Headers) -> ClientHeaders {
+    let mut converted = ClientHeaders::new();
+    for &(ref name, ref value) in headers.iter() {
+        converted.append_raw(name.clone(), value.as_bytes().to_vec());
     }
+    converted
 }
 
-pub fn get_url_response<T>(url_string: &str, headers: Headers) -> Result<(T, Headers), Error>
+fn response_headers(response: &Response) -> Headers {
+    let mut headers = Headers::new();
+    for header in response.headers().iter() {
+        for line in header.raw().iter() {
+            headers.append(header.name(), String::from_utf8_lossy(line).into_owned());
+        }
+    }
+    headers
+}
+
+// A GET with a plain reqwest client, for checks that don't poll an
+// integration, e.g. during setup.
+pub fn send_with_headers(
+    client: &Client,
+    url: &str,
+    headers: &Headers,
+) -> Result<Response, Error> {
+    Ok(client.get(url).headers(client_headers(headers)).send()?)
+}
+
+pub fn get_url_response<T>(
+    client: &HttpClient,
+    url_string: &str,
+    headers: Headers,
+) -> Result<(T, Headers), Error>
 where
     T: serde::de::DeserializeOwned,
 {
+    let (body, headers) = get_url_body(client, url_string, headers)?;
+    Ok((serde_json::from_str::<T>(body.as_str())?, headers))
+}
+
+// The body of a JSON response before it's parsed, e.g. to tell whether it has
+// changed at all. Cached, recorded and faulted like get_url_response.
+pub fn get_url_body(
+    client: &HttpClient,
+    url_string: &str,
+    mut headers: Headers,
+) -> Result<(String, Headers), Error> {
     if let Ok(url) = Url::parse(&url_string) {
-        let mut response = HTTP_CLIENT.get(url).headers(headers).send()?;
+        if let Some(ref replay) = client.replay {
+            let recorded = replay.take(url_string)?;
+            return Ok((recorded.ok_body()?.to_string(), recorded.headers()));
+        }
+        if let Some(wait) = client.rate_limit.remaining() {
+            return Err(format_err!(
+                "Not calling {}, still rate limited for {} seconds.",
+                &url_string,
+                wait.as_secs()
+            ));
+        }
+        if let Ok(cache) = client.cache.lock() {
+            if let Some(cached) = cache.get(url_string) {
+                if let Some(ref etag) = cached.etag {
+                    headers.set("If-None-Match", etag.as_str());
+                }
+                if let Some(ref last_modified) = cached.last_modified {
+                    headers.set("If-Modified-Since", last_modified.as_str());
+                }
+            }
+        }
+        if let Some(fault) = client
+            .faults
+            .as_ref()
+            .and_then(|faults| faults.next_fault())
+        {
+            return inject_fault(client, url_string, fault);
+        }
+        let mut response = send_get(client, &url, &headers)?;
+        if response.status() == StatusCode::Unauthorized {
+            // The token may have been revoked before it expired; try once with a new one
+            if client
+                .bearer_auth()
+                .map_or(false, |auth| auth.invalidate())
+            {
+                response = send_get(client, &url, &headers)?;
+            }
+        }
+        let headers = response_headers(&response);
+        client
+            .rate_limit
+            .observe(url_string, response.status(), &headers);
+        client.clock.observe(url_string, &headers);
 
         match response.status() {
             StatusCode::Ok => {
-                let body_string = response.text()?;
-                let deser = serde_json::from_str::<T>(body_string.as_str())?;
-                //todo: Do we have to clone this?
-                Ok((deser, response.headers().clone()))
+                let body_string = read_body(
+                    &mut response,
+                    &headers,
+                    client.max_response_bytes,
+                    url_string,
+                )?;
+                // Recorded before parsing, it's the responses that don't parse that are wanted
+                client.record(url_string, StatusCode::Ok, &headers, &body_string);
+                let etag = headers.get("ETag").map(|etag| etag.to_string());
+                let last_modified = headers.get("Last-Modified").map(|date| date.to_string());
+                if etag.is_some() || last_modified.is_some() {
+                    if let Ok(mut cache) = client.cache.lock() {
+                        cache.insert(
+                            url_string.to_string(),
+                            CachedResponse {
+                                etag: etag,
+                                last_modified: last_modified,
+                                body: body_string.clone(),
+                                headers: headers.clone(),
+                            },
+                        );
+                    }
+                }
+                Ok((body_string, headers))
+            }
+            // Nothing changed since the cached response
+            StatusCode::NotModified => match client.cache.lock() {
+                Ok(cache) => match cache.get(url_string) {
+                    // Recorded as if it had been sent again, so a replay doesn't need the cache
+                    Some(cached) => {
+                        client.record(url_string, StatusCode::Ok, &cached.headers, &cached.body);
+                        Ok((cached.body.clone(), cached.headers.clone()))
+                    }
+                    None => Err(format_err!(
+                        "HTTP call to {} returned 304 Not Modified, but nothing was cached.",
+                        &url_string
+                    )),
+                },
+                Err(_) => Err(format_err!(
+                    "Failed to acquire a lock on the response cache."
+                )),
+            },
+            other_code => {
+                client.record(url_string, other_code, &headers, "");
+                Err(HttpStatusError {
+                    url: url_string.to_string(),
+                    status: other_code,
+                }
+                .into())
             }
-            other_code => Err(format_err!(
-                "HTTP call to {} failed with code: {}",
-                &url_string,
-                other_code
-            )),
         }
     } else {
-        Err(format_err!("Unable to parse url: {}", url_string))
+        Err(format_err!("Unable to parse url: {}", redact(url_string)))
+    }
+}
+
+// A plain text GET, for feeds that aren't JSON. Not cached, recorded or
+// faulted like get_url_response.
+pub fn get_url_text(
+    client: &HttpClient,
+    url_string: &str,
+    headers: Headers,
+) -> Result<String, Error> {
+    let url =
+        Url::parse(url_string).map_err(|_| format_err!("Unable to parse url: {}", redact(url_string)))?;
+    let mut response = send_get(client, &url, &headers)?;
+    let headers = response_headers(&response);
+    match response.status() {
+        StatusCode::Ok => {
+            read_body(&mut response, &headers, client.max_response_bytes, url_string)
+        }
+        other_code => Err(HttpStatusError {
+            url: url_string.to_string(),
+            status: other_code,
+        }
+        .into()),
+    }
+}
+
+fn inject_fault(
+    client: &HttpClient,
+    url_string: &str,
+    fault: Fault,
+) -> Result<(String, Headers), Error> {
+    debug!("Injecting {:?} into the call to {}.", fault, url_string);
+    match fault {
+        Fault::Timeout => {
+            thread::sleep(client.timeout);
+            Err(InjectedTimeout {
+                url: url_string.to_string(),
+            }
+            .into())
+        }
+        Fault::ServerError => Err(HttpStatusError {
+            url: url_string.to_string(),
+            status: StatusCode::InternalServerError,
+        }
+        .into()),
+        Fault::MalformedJson => Ok((faults::TRUNCATED_JSON.to_string(), Headers::new())),
+    }
+}
+
+// Refuses bodies over max_bytes, without reading them at all when the server
+// says up front how big they are. A misconfigured URL pointing at a huge
+// listing would otherwise run a Pi Zero out of memory.
+fn read_body(
+    response: &mut Response,
+    headers: &Headers,
+    max_bytes: u64,
+    url_string: &str,
+) -> Result<String, Error> {
+    let too_large = || {
+        format_err!(
+            "The response from {} is larger than {} KB, not reading it. Check the URL, or raise max_response_kilobytes.",
+            url_string,
+            max_bytes / 1024
+        )
+    };
+    if let Some(length) = headers.get_parsed::<u64>("Content-Length") {
+        if length > max_bytes {
+            return Err(too_large());
+        }
+    }
+    let mut body = String::new();
+    response.take(max_bytes + 1).read_to_string(&mut body)?;
+    if body.len() as u64 > max_bytes {
+        return Err(too_large());
+    }
+    Ok(body)
+}
+
+// Bearer tokens take the place of whatever Authorization the integration set.
+fn send_get(client: &HttpClient, url: &Url, headers: &Headers) -> Result<Response, Error> {
+    let mut headers = headers.clone();
+    if let Some(auth) = client.bearer_auth() {
+        headers.set(AUTHORIZATION, headers::bearer_auth(&auth.token(&client.client)?));
+    }
+    if !client.trace {
+        return Ok(client
+            .client
+            .get(url.clone())
+            .headers(client_headers(&headers))
+            .send()?);
+    }
+    // Logged under the http_trace target, which log4rs.yml lets through at debug level
+    let traced_url = redact(url.as_str());
+    debug!(target: "http_trace", "GET {} {}", traced_url, traced_headers(&headers));
+    let sent = Instant::now();
+    let response = client
+        .client
+        .get(url.clone())
+        .headers(client_headers(&headers))
+        .send();
+    let elapsed = sent.elapsed();
+    let millis = elapsed.as_secs() * 1000 + u64::from(elapsed.subsec_nanos() / 1_000_000);
+    match response {
+        Ok(ref response) => debug!(
+            target: "http_trace",
+            "{} from {} in {} ms {}",
+            response.status(),
+            traced_url,
+            millis,
+            traced_headers(&response_headers(response))
+        ),
+        Err(ref err) => debug!(
+            target: "http_trace",
+            "No response from {} after {} ms: {}",
+            traced_url,
+            millis,
+            redact(&err.to_string())
+        ),
+    }
+    Ok(response?)
+}
+
+// Headers as "{Name: value, ...}", with the values of any that could carry
+// credentials left out.
+fn traced_headers(headers: &Headers) -> String {
+    let fields: Vec<String> = headers
+        .iter()
+        .map(|&(ref name, ref value)| {
+            if redact::is_sensitive(name) {
+                format!("{}: {}", name, redact::REDACTED)
+            } else {
+                format!("{}: {}", name, redact(value))
+            }
+        })
+        .collect();
+    format!("{{{}}}", fields.join(", "))
+}
+
+pub fn post_json(url_string: &str, body: &serde_json::Value) -> Result<(), Error> {
+    if let Ok(url) = Url::parse(&url_string) {
+        let response = HTTP_CLIENT.post(url).json(body).send().map_err(|e| {
+            format_err!(
+                "HTTP POST to {} failed: {}",
+                redact(url_string),
+                redact(&e.to_string())
+            )
+        })?;
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(format_err!(
+                "HTTP POST to {} failed with code: {}",
+                redact(url_string),
+                response.status()
+            ))
+        }
+    } else {
+        Err(format_err!("Unable to parse url: {}", redact(url_string)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use redact::register_secrets;
+    use test_support::MockServer;
+
+    #[test]
+    fn traced_headers_leave_credentials_out() {
+        let mut headers = Headers::new();
+        headers.set(
+            AUTHORIZATION,
+            get_basic_credentials("user", Some("hunter2".to_string())),
+        );
+        headers.set("Jenkins-Crumb", "0123abcd");
+        headers.set("PRIVATE-TOKEN", "glpat-secret");
+        headers.set("ETag", "\"v1\"");
+
+        let traced = traced_headers(&headers);
+        assert!(traced.contains("ETag: \"v1\""));
+        assert!(traced.contains("Authorization: [redacted]"));
+        assert!(!traced.contains("hunter2"));
+        assert!(!traced.contains("0123abcd"));
+        assert!(!traced.contains("glpat-secret"));
+    }
+
+    #[test]
+    fn failed_posts_leave_the_webhook_token_out() {
+        let server = MockServer::start();
+        server.on("/services/T000/B000/XXXXwebhooktoken", 500, "");
+        let url = format!("{}/services/T000/B000/XXXXwebhooktoken", server.base_url);
+        let unreachable = "http://127.0.0.1:1/services/T000/B000/YYYYwebhooktoken";
+        register_secrets(&[url.clone(), unreachable.to_string()]);
+
+        let rejected = post_json(&url, &json!({ "text": "Build failed" })).unwrap_err();
+        assert!(rejected.to_string().contains("500"));
+        assert!(!rejected.to_string().contains("XXXXwebhooktoken"));
+        let refused = post_json(unreachable, &json!({ "text": "Build failed" })).unwrap_err();
+        assert!(!refused.to_string().contains("YYYYwebhooktoken"));
     }
 }
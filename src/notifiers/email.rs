@@ -0,0 +1,216 @@
+use base64;
+use chrono::Local;
+use config_file::EmailConfig;
+use failure::Error;
+use native_tls::TlsConnector;
+use remote_status::RemoteStatus;
+use status_board::StatusBoard;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::sync::mpsc;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const SMTP_TIMEOUT_SECONDS: u64 = 30;
+const HELO_NAME: &str = "rusty-build-light";
+
+struct Email {
+    subject: String,
+    body: String,
+}
+
+struct FailingIntegration {
+    since: Instant,
+    emailed: bool,
+}
+
+// Emails the configured addresses once an integration has been failing for
+// longer than red_for_minutes. Only one email is sent per failure; the next
+// one can only come after the integration has passed again.
+pub fn start_email_notifier(config: EmailConfig, board: &StatusBoard) {
+    let red_for = Duration::from_secs(config.red_for_minutes * 60);
+    let (sender, receiver) = mpsc::channel::<Email>();
+    thread::spawn(move || {
+        for email in receiver {
+            info!("--Email--: Sending \"{}\"", email.subject);
+            if let Err(e) = send_email(&config, &email) {
+                warn!("--Email--: Failed to send email. Error: {}", e);
+            }
+        }
+    });
+
+    let failing: Mutex<HashMap<String, FailingIntegration>> = Mutex::new(HashMap::new());
+    board.subscribe(Box::new(move |snapshots| {
+        if let Ok(mut failing) = failing.lock() {
+            for snapshot in snapshots {
+                match snapshot.status {
+                    RemoteStatus::Passing => {
+                        failing.remove(&snapshot.name);
+                    }
+                    RemoteStatus::Failing => {
+                        let integration = failing
+                            .entry(snapshot.name.clone())
+                            .or_insert_with(|| FailingIntegration {
+                                since: Instant::now(),
+                                emailed: false,
+                            });
+                        let failing_for = integration.since.elapsed();
+                        if !integration.emailed && failing_for >= red_for {
+                            integration.emailed = true;
+                            let mut body = format!(
+                                "{} has been failing for {} minutes.\r\n\r\n{} failing, {} passing.\r\n",
+                                snapshot.name,
+                                failing_for.as_secs() / 60,
+                                snapshot.summary.failing,
+                                snapshot.summary.passing
+                            );
+                            if !snapshot.summary.failing_jobs.is_empty() {
+                                body.push_str("\r\nFailing jobs:\r\n");
+                                for job in &snapshot.summary.failing_jobs {
+                                    body.push_str(&format!("  - {}\r\n", job));
+                                }
+                            }
+                            if !snapshot.summary.failed_builds.is_empty() {
+                                body.push_str("\r\nFailed builds:\r\n");
+                                for build in &snapshot.summary.failed_builds {
+                                    body.push_str(&format!("  - {}\r\n", build));
+                                }
+                            }
+                            if !snapshot.summary.failed_stages.is_empty() {
+                                body.push_str("\r\nFailed stages:\r\n");
+                                for stage in &snapshot.summary.failed_stages {
+                                    body.push_str(&format!("  - {}\r\n", stage));
+                                }
+                            }
+                            if !snapshot.summary.culprits.is_empty() {
+                                body.push_str(&format!(
+                                    "\r\nChanges by: {}\r\n",
+                                    snapshot.summary.culprits.join(", ")
+                                ));
+                            }
+                            let _ = sender.send(Email {
+                                subject: format!("[Build light] {} is failing", snapshot.name),
+                                body: body,
+                            });
+                        }
+                    }
+                    // Building or unknown doesn't end a failure
+                    _ => (),
+                }
+            }
+        }
+    }));
+}
+
+fn send_email(config: &EmailConfig, email: &Email) -> Result<(), Error> {
+    let stream = TcpStream::connect((config.smtp_host.as_str(), config.smtp_port))?;
+    stream.set_read_timeout(Some(Duration::from_secs(SMTP_TIMEOUT_SECONDS)))?;
+    stream.set_write_timeout(Some(Duration::from_secs(SMTP_TIMEOUT_SECONDS)))?;
+
+    let mut connection = SmtpConnection::new(stream);
+    connection.expect_reply(220)?;
+    connection.command(&format!("EHLO {}", HELO_NAME), 250)?;
+    if config.starttls {
+        connection.command("STARTTLS", 220)?;
+        let connector = TlsConnector::builder()?.build()?;
+        let tls_stream = connector
+            .connect(&config.smtp_host, connection.into_inner())
+            .map_err(|e| format_err!("TLS handshake with {} failed: {}", config.smtp_host, e))?;
+        let mut connection = SmtpConnection::new(tls_stream);
+        connection.command(&format!("EHLO {}", HELO_NAME), 250)?;
+        deliver(&mut connection, config, email)
+    } else {
+        deliver(&mut connection, config, email)
+    }
+}
+
+fn deliver<S: Read + Write>(
+    connection: &mut SmtpConnection<S>,
+    config: &EmailConfig,
+    email: &Email,
+) -> Result<(), Error> {
+    if let Some(ref username) = config.username {
+        let password = config.password.as_ref().map_or("", |p| p.as_str());
+        connection.command("AUTH LOGIN", 334)?;
+        connection.command(&base64::encode(username.as_bytes()), 334)?;
+        connection.command(&base64::encode(password.as_bytes()), 235)?;
+    }
+    connection.command(&format!("MAIL FROM:<{}>", config.from), 250)?;
+    for recipient in &config.to {
+        connection.command(&format!("RCPT TO:<{}>", recipient), 250)?;
+    }
+    connection.command("DATA", 354)?;
+
+    let mut message = format!(
+        "From: {}\r\nTo: {}\r\nSubject: {}\r\nDate: {}\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n",
+        config.from,
+        config.to.join(", "),
+        email.subject,
+        Local::now().to_rfc2822()
+    );
+    // Lines starting with a dot need another one, or they could end the message early
+    for line in email.body.split("\r\n") {
+        if line.starts_with('.') {
+            message.push('.');
+        }
+        message.push_str(line);
+        message.push_str("\r\n");
+    }
+    message.push('.');
+    connection.command(&message, 250)?;
+    connection.command("QUIT", 221)?;
+    Ok(())
+}
+
+// Just enough SMTP to send one plain text email.
+struct SmtpConnection<S: Read + Write> {
+    stream: BufReader<S>,
+}
+
+impl<S: Read + Write> SmtpConnection<S> {
+    fn new(stream: S) -> SmtpConnection<S> {
+        SmtpConnection {
+            stream: BufReader::new(stream),
+        }
+    }
+
+    fn into_inner(self) -> S {
+        self.stream.into_inner()
+    }
+
+    fn command(&mut self, command: &str, expected_code: u16) -> Result<(), Error> {
+        {
+            let stream = self.stream.get_mut();
+            stream.write_all(command.as_bytes())?;
+            stream.write_all(b"\r\n")?;
+            stream.flush()?;
+        }
+        self.expect_reply(expected_code)
+    }
+
+    // Replies can span several lines, "250-..." up to the last one, "250 ...".
+    fn expect_reply(&mut self, expected_code: u16) -> Result<(), Error> {
+        loop {
+            let mut line = String::new();
+            if self.stream.read_line(&mut line)? == 0 {
+                return Err(format_err!("SMTP server closed the connection."));
+            }
+            let code: u16 = line
+                .get(0..3)
+                .and_then(|code| code.parse().ok())
+                .ok_or_else(|| format_err!("Unexpected SMTP reply: {}", line.trim()))?;
+            if code != expected_code {
+                return Err(format_err!(
+                    "Expected SMTP reply {}, got: {}",
+                    expected_code,
+                    line.trim()
+                ));
+            }
+            if line.get(3..4) != Some("-") {
+                return Ok(());
+            }
+        }
+    }
+}
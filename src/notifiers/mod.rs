@@ -0,0 +1,213 @@
+pub mod email;
+pub mod slack;
+pub mod speech;
+pub mod teams;
+pub mod webhook;
+
+use failure::Error;
+use remote_status::{RemoteStatus, StatusSummary};
+use status_board::StatusBoard;
+use std::collections::HashMap;
+use std::sync::mpsc;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// An integration going from passing to failing, or back. Reminders about a
+// failure that's still going on come as a failing to failing transition.
+pub struct Transition {
+    pub integration: String,
+    pub from: RemoteStatus,
+    pub to: RemoteStatus,
+    pub summary: StatusSummary,
+    // How long the failure has lasted, for reminders
+    pub reminder_after_minutes: Option<u64>,
+}
+
+impl Transition {
+    pub fn is_fixed(&self) -> bool {
+        self.to == RemoteStatus::Passing
+    }
+
+    // One-line, human-readable description for chat messages.
+    pub fn describe(&self) -> String {
+        let summary = &self.summary;
+        let description = if self.is_fixed() {
+            format!(
+                "{} is passing again: {} passing.",
+                self.integration, summary.passing
+            )
+        } else {
+            let failing = match self.reminder_after_minutes {
+                Some(minutes) => format!("is still failing after {} minutes", minutes),
+                None => "is failing".to_string(),
+            };
+            if summary.failing_jobs.is_empty() {
+                format!(
+                    "{} {}: {} failing, {} passing.",
+                    self.integration, failing, summary.failing, summary.passing
+                )
+            } else {
+                format!(
+                    "{} {}: {} failing, {} passing ({}).",
+                    self.integration,
+                    failing,
+                    summary.failing,
+                    summary.passing,
+                    summary.failing_jobs.join(", ")
+                )
+            }
+        };
+        let description = match self.failed_builds() {
+            Some(builds) => format!("{} Failed builds: {}.", description, builds),
+            None => description,
+        };
+        let description = match self.failed_stages() {
+            Some(stages) => format!("{} Failed in {}.", description, stages),
+            None => description,
+        };
+        match self.culprits() {
+            Some(culprits) => format!("{} Changes by {}.", description, culprits),
+            None => description,
+        }
+    }
+
+    // Which builds failed, if this is a failure and the integration says.
+    pub fn failed_builds(&self) -> Option<String> {
+        if self.is_fixed() || self.summary.failed_builds.is_empty() {
+            None
+        } else {
+            Some(self.summary.failed_builds.join(", "))
+        }
+    }
+
+    // Which pipeline stage each failing job failed in, if this is a failure
+    // and any of them are pipelines.
+    pub fn failed_stages(&self) -> Option<String> {
+        if self.is_fixed() || self.summary.failed_stages.is_empty() {
+            None
+        } else {
+            Some(self.summary.failed_stages.join(", "))
+        }
+    }
+
+    // Who committed to the failing builds, if this is a failure and anyone did.
+    pub fn culprits(&self) -> Option<String> {
+        if self.is_fixed() || self.summary.culprits.is_empty() {
+            None
+        } else {
+            Some(self.summary.culprits.join(", "))
+        }
+    }
+}
+
+pub trait Notifier: Send {
+    fn name(&self) -> &str;
+    fn notify(&self, transition: &Transition) -> Result<(), Error>;
+
+    // Lets a notifier only hear about some of the integrations.
+    fn handles(&self, _integration: &str) -> bool {
+        true
+    }
+}
+
+// Whether an integration is in a notifier's configured list. No list means all of them.
+pub fn is_selected(integrations: &Option<Vec<String>>, integration: &str) -> bool {
+    integrations
+        .as_ref()
+        .map_or(true, |names| names.iter().any(|name| name == integration))
+}
+
+// Watches the status board for integrations flipping between passing and
+// failing, and hands each flip to every notifier. Notifications are sent from
+// their own thread, so a slow webhook doesn't hold up the LEDs. A failure
+// that lasts each of the remind_after durations gets notified about again.
+pub fn start_notifiers(
+    notifiers: Vec<Box<Notifier>>,
+    board: &StatusBoard,
+    mut remind_after: Vec<Duration>,
+) {
+    remind_after.sort();
+    let (sender, receiver) = mpsc::channel::<Transition>();
+    thread::spawn(move || {
+        for transition in receiver {
+            match transition.reminder_after_minutes {
+                Some(minutes) => info!(
+                    "{} still failing after {} minutes, reminding.",
+                    transition.integration, minutes
+                ),
+                None => info!(
+                    "{} went from {:?} to {:?}, notifying.",
+                    transition.integration, transition.from, transition.to
+                ),
+            }
+            for notifier in &notifiers {
+                if !notifier.handles(&transition.integration) {
+                    continue;
+                }
+                if let Err(e) = notifier.notify(&transition) {
+                    warn!(
+                        "--{}--: Failed to send notification. Error: {}",
+                        notifier.name(),
+                        e
+                    );
+                }
+            }
+        }
+    });
+
+    // Building and unknown states in between don't count, so passing ->
+    // building -> failing is still a single transition.
+    let last_conclusive: Mutex<HashMap<String, RemoteStatus>> = Mutex::new(HashMap::new());
+    // When each failure started, and how many reminders it has had
+    let failing: Mutex<HashMap<String, (Instant, usize)>> = Mutex::new(HashMap::new());
+    board.subscribe(Box::new(move |snapshots| {
+        if let (Ok(mut last_conclusive), Ok(mut failing)) = (last_conclusive.lock(), failing.lock())
+        {
+            for snapshot in snapshots {
+                if snapshot.status != RemoteStatus::Passing
+                    && snapshot.status != RemoteStatus::Failing
+                {
+                    continue;
+                }
+                let previous = last_conclusive.insert(snapshot.name.clone(), snapshot.status);
+                match previous {
+                    Some(from) if from != snapshot.status => {
+                        let _ = sender.send(Transition {
+                            integration: snapshot.name.clone(),
+                            from: from,
+                            to: snapshot.status,
+                            summary: snapshot.summary.clone(),
+                            reminder_after_minutes: None,
+                        });
+                    }
+                    _ => (),
+                }
+
+                if snapshot.status == RemoteStatus::Passing {
+                    failing.remove(&snapshot.name);
+                    continue;
+                }
+                let failure = failing
+                    .entry(snapshot.name.clone())
+                    .or_insert_with(|| (Instant::now(), 0));
+                let failing_for = failure.0.elapsed();
+                let due = remind_after
+                    .iter()
+                    .filter(|&&after| failing_for >= after)
+                    .count();
+                // Several reminders coming due at once only get sent once
+                if due > failure.1 {
+                    failure.1 = due;
+                    let _ = sender.send(Transition {
+                        integration: snapshot.name.clone(),
+                        from: RemoteStatus::Failing,
+                        to: RemoteStatus::Failing,
+                        summary: snapshot.summary.clone(),
+                        reminder_after_minutes: Some(failing_for.as_secs() / 60),
+                    });
+                }
+            }
+        }
+    }));
+}
@@ -0,0 +1,40 @@
+use config_file::SlackConfig;
+use failure::Error;
+use network::post_json;
+use notifiers::{Notifier, Transition};
+
+// Posts transitions to a Slack incoming webhook.
+pub struct SlackNotifier {
+    config: SlackConfig,
+}
+
+impl SlackNotifier {
+    pub fn new(config: SlackConfig) -> SlackNotifier {
+        SlackNotifier { config: config }
+    }
+}
+
+impl Notifier for SlackNotifier {
+    fn name(&self) -> &str {
+        "Slack"
+    }
+
+    fn notify(&self, transition: &Transition) -> Result<(), Error> {
+        let emoji = if transition.is_fixed() {
+            ":large_green_circle:"
+        } else {
+            ":red_circle:"
+        };
+        let mut message = json!({
+            "text": format!("{} {}", emoji, transition.describe()),
+        });
+        if let Some(ref channel) = self.config.channel {
+            message["channel"] = json!(channel);
+        }
+        if let Some(ref username) = self.config.username {
+            message["username"] = json!(username);
+        }
+        info!("--Slack--: Posting \"{}\"", transition.describe());
+        post_json(&self.config.webhook_url, &message)
+    }
+}
@@ -0,0 +1,86 @@
+use config_file::SpeechConfig;
+use failure::Error;
+use notifiers::{is_selected, Notifier, Transition};
+use schedule::HourRange;
+use std::process::Command;
+
+// Announces new failures out loud through espeak, e.g. "Jenkins build failed: nightly".
+pub struct SpeechNotifier {
+    config: SpeechConfig,
+    quiet_hours: Option<HourRange>,
+}
+
+impl SpeechNotifier {
+    pub fn new(config: SpeechConfig) -> Result<SpeechNotifier, Error> {
+        let quiet_hours = match config.quiet_hours {
+            Some(ref hours) => Some(HourRange::parse(hours)?),
+            None => None,
+        };
+        Ok(SpeechNotifier {
+            config: config,
+            quiet_hours: quiet_hours,
+        })
+    }
+}
+
+impl Notifier for SpeechNotifier {
+    fn name(&self) -> &str {
+        "Speech"
+    }
+
+    fn notify(&self, transition: &Transition) -> Result<(), Error> {
+        if transition.is_fixed() {
+            return Ok(());
+        }
+        if self
+            .quiet_hours
+            .as_ref()
+            .map_or(false, |q| q.contains_now())
+        {
+            info!("--Speech--: Quiet hours, not announcing.");
+            return Ok(());
+        }
+
+        let failing_jobs = &transition.summary.failing_jobs;
+        let failed = match transition.reminder_after_minutes {
+            Some(minutes) => format!("still failing after {} minutes", minutes),
+            None => "failed".to_string(),
+        };
+        let mut announcement = if failing_jobs.is_empty() {
+            format!("{} build {}", transition.integration, failed)
+        } else {
+            format!(
+                "{} build {}: {}",
+                transition.integration,
+                failed,
+                failing_jobs.join(", ")
+            )
+        };
+        if let Some(culprits) = transition.culprits() {
+            announcement.push_str(&format!(". Changes by {}", culprits));
+        }
+        announcement.push('.');
+        info!("--Speech--: Announcing \"{}\"", announcement);
+        // espeak's amplitude goes from 0 to 200, with 100 as its default
+        let amplitude = self.config.volume_percent.min(100) * 2;
+        let mut command = Command::new(&self.config.command);
+        command.arg("-a").arg(amplitude.to_string());
+        if let Some(ref voice) = self.config.voice {
+            command.arg("-v").arg(voice);
+        }
+        let status = command.arg(&announcement).status()?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(format_err!(
+                "{} exited with {}",
+                self.config.command,
+                status
+            ))
+        }
+    }
+
+    fn handles(&self, integration: &str) -> bool {
+        is_selected(&self.config.integrations, integration)
+    }
+}
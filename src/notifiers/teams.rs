@@ -0,0 +1,60 @@
+use config_file::WebhookConfig;
+use failure::Error;
+use network::post_json;
+use notifiers::{is_selected, Notifier, Transition};
+
+// Posts transitions to a Microsoft Teams incoming webhook, as a message card.
+pub struct TeamsNotifier {
+    config: WebhookConfig,
+}
+
+impl TeamsNotifier {
+    pub fn new(config: WebhookConfig) -> TeamsNotifier {
+        TeamsNotifier { config: config }
+    }
+}
+
+impl Notifier for TeamsNotifier {
+    fn name(&self) -> &str {
+        "Teams"
+    }
+
+    fn notify(&self, transition: &Transition) -> Result<(), Error> {
+        let summary = &transition.summary;
+        let theme_color = if transition.is_fixed() {
+            "2EB886"
+        } else {
+            "D00000"
+        };
+        let mut facts = vec![
+            json!({"name": "Passing", "value": summary.passing.to_string()}),
+            json!({"name": "Failing", "value": summary.failing.to_string()}),
+        ];
+        if !summary.failing_jobs.is_empty() {
+            facts.push(json!({"name": "Failing jobs", "value": summary.failing_jobs.join(", ")}));
+        }
+        if let Some(builds) = transition.failed_builds() {
+            facts.push(json!({"name": "Failed builds", "value": builds}));
+        }
+        if let Some(stages) = transition.failed_stages() {
+            facts.push(json!({"name": "Failed stages", "value": stages}));
+        }
+        if let Some(culprits) = transition.culprits() {
+            facts.push(json!({"name": "Changes by", "value": culprits}));
+        }
+        let card = json!({
+            "@type": "MessageCard",
+            "@context": "https://schema.org/extensions",
+            "themeColor": theme_color,
+            "summary": transition.describe(),
+            "title": transition.describe(),
+            "sections": [{ "facts": facts }],
+        });
+        info!("--Teams--: Posting \"{}\"", transition.describe());
+        post_json(&self.config.url, &card)
+    }
+
+    fn handles(&self, integration: &str) -> bool {
+        is_selected(&self.config.integrations, integration)
+    }
+}
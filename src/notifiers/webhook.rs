@@ -0,0 +1,84 @@
+use config_file::WebhookConfig;
+use failure::Error;
+use network::post_json;
+use notifiers::{is_selected, Notifier, Transition};
+use reqwest::Url;
+use serde_json::{self, Value};
+
+const DEFAULT_BODY_TEMPLATE: &str = r#"{"text": "{{message}}"}"#;
+
+// Posts transitions to any URL, with a JSON body built from a template.
+// Placeholders are only substituted inside JSON strings, e.g. "{{failing}}".
+pub struct WebhookNotifier {
+    config: WebhookConfig,
+}
+
+impl WebhookNotifier {
+    pub fn new(config: WebhookConfig) -> WebhookNotifier {
+        WebhookNotifier { config: config }
+    }
+
+    fn render_body(&self, transition: &Transition) -> Result<Value, Error> {
+        let template = self
+            .config
+            .body_template
+            .as_ref()
+            .map_or(DEFAULT_BODY_TEMPLATE, |template| template.as_str());
+        let summary = &transition.summary;
+        let placeholders = [
+            ("{{integration}}", transition.integration.clone()),
+            ("{{status}}", format!("{:?}", transition.to)),
+            ("{{previous_status}}", format!("{:?}", transition.from)),
+            ("{{passing}}", summary.passing.to_string()),
+            ("{{failing}}", summary.failing.to_string()),
+            ("{{failing_jobs}}", summary.failing_jobs.join(", ")),
+            ("{{culprits}}", transition.culprits().unwrap_or_default()),
+            (
+                "{{failed_builds}}",
+                transition.failed_builds().unwrap_or_default(),
+            ),
+            (
+                "{{failed_stages}}",
+                transition.failed_stages().unwrap_or_default(),
+            ),
+            ("{{message}}", transition.describe()),
+        ];
+        let mut body = template.to_string();
+        for &(placeholder, ref value) in placeholders.iter() {
+            body = body.replace(placeholder, &escape_json(value));
+        }
+        serde_json::from_str(&body).map_err(|e| {
+            format_err!(
+                "The webhook body template isn't valid JSON once filled in. Error: {}",
+                e
+            )
+        })
+    }
+}
+
+impl Notifier for WebhookNotifier {
+    fn name(&self) -> &str {
+        "Webhook"
+    }
+
+    fn notify(&self, transition: &Transition) -> Result<(), Error> {
+        let body = self.render_body(transition)?;
+        // The rest of the URL may be a token
+        let host = Url::parse(&self.config.url)
+            .ok()
+            .and_then(|url| url.host_str().map(|host| host.to_string()))
+            .unwrap_or_else(|| "an invalid URL".to_string());
+        info!("--Webhook--: Posting to {}", host);
+        post_json(&self.config.url, &body)
+    }
+
+    fn handles(&self, integration: &str) -> bool {
+        is_selected(&self.config.integrations, integration)
+    }
+}
+
+// Escapes a value for use inside a JSON string, without the surrounding quotes.
+fn escape_json(value: &str) -> String {
+    let quoted = Value::String(value.to_string()).to_string();
+    quoted[1..quoted.len() - 1].to_string()
+}
@@ -0,0 +1,206 @@
+use failure::Error;
+use headers::Headers;
+use network::{get_url_response, HttpClient};
+use reqwest::Url;
+use serde::de::DeserializeOwned;
+
+// One page of a listing.
+pub trait Page: DeserializeOwned {
+    type Item;
+
+    fn into_items(self) -> Vec<Self::Item>;
+
+    // For APIs that put the next page's URL in the body, e.g. as nextPageUrl.
+    fn next_page_url(&self) -> Option<String> {
+        None
+    }
+}
+
+// Plain JSON arrays, paged with Link headers or page numbers.
+impl<T: DeserializeOwned> Page for Vec<T> {
+    type Item = T;
+
+    fn into_items(self) -> Vec<T> {
+        self
+    }
+}
+
+// Fetches up to max_pages pages of a listing starting at url_string, and
+// returns everything on them. The next page is the first of: the Link
+// header's rel="next", the page's own next_page_url, or the next page number
+// if url_string has page and per_page parameters and the page came back full.
+pub fn get_all_pages<P: Page>(
+    client: &HttpClient,
+    url_string: &str,
+    headers: Headers,
+    max_pages: usize,
+) -> Result<Vec<P::Item>, Error> {
+    let mut items = Vec::new();
+    let mut next = Some(url_string.to_string());
+    let mut pages = 0;
+    while let Some(url) = next.take() {
+        if pages == max_pages {
+            warn!(
+                "Stopped after {} pages of {}, the rest are left out.",
+                max_pages, url_string
+            );
+            break;
+        }
+        let (page, response_headers): (P, Headers) =
+            get_url_response(client, &url, headers.clone())?;
+        pages += 1;
+        let linked = next_link(&response_headers, &url);
+        let in_body = page.next_page_url();
+        let page_items = page.into_items();
+        next = linked
+            .or(in_body)
+            .or_else(|| next_page_number(&url, page_items.len()));
+        items.extend(page_items);
+    }
+    Ok(items)
+}
+
+// The rel="next" URL of a Link header, relative to the page it came with.
+fn next_link(headers: &Headers, page_url: &str) -> Option<String> {
+    let next = link_values(headers.get("Link")?)
+        .into_iter()
+        .find(|&(_, ref rels)| {
+            rels.split_whitespace()
+                .any(|rel| rel.eq_ignore_ascii_case("next"))
+        })?;
+    Url::parse(page_url)
+        .and_then(|page_url| page_url.join(&next.0))
+        .ok()
+        .map(|url| url.to_string())
+}
+
+// The targets of a Link header, e.g. `</page/2>; rel="next", </page/9>;
+// rel=last`, with their rel parameters. Commas inside <> are part of the URL.
+fn link_values(link: &str) -> Vec<(String, String)> {
+    let mut values = Vec::new();
+    let mut rest = link;
+    while let Some(start) = rest.find('<') {
+        let end = match rest[start..].find('>') {
+            Some(end) => start + end,
+            None => break,
+        };
+        let target = rest[start + 1..end].trim().to_string();
+        rest = &rest[end + 1..];
+        let params_end = rest.find(',').unwrap_or(rest.len());
+        let rel = rest[..params_end]
+            .split(';')
+            .filter_map(|param| {
+                let mut parts = param.splitn(2, '=');
+                match (parts.next(), parts.next()) {
+                    (Some(name), Some(value)) if name.trim().eq_ignore_ascii_case("rel") => {
+                        Some(value.trim().trim_matches('"').to_string())
+                    }
+                    _ => None,
+                }
+            })
+            .next()
+            .unwrap_or_default();
+        values.push((target, rel));
+        rest = &rest[params_end..];
+    }
+    values
+}
+
+// The same URL with page one higher, if this page was full.
+fn next_page_number(page_url: &str, items_on_page: usize) -> Option<String> {
+    let mut url = Url::parse(page_url).ok()?;
+    let param = |name: &str| {
+        url.query_pairs()
+            .find(|(key, _)| key == name)
+            .and_then(|(_, value)| value.parse::<usize>().ok())
+    };
+    let (page, per_page) = (param("page")?, param("per_page")?);
+    if per_page == 0 || items_on_page < per_page {
+        return None;
+    }
+    let pairs: Vec<(String, String)> = url
+        .query_pairs()
+        .map(|(key, value)| {
+            if key == "page" {
+                (key.into_owned(), (page + 1).to_string())
+            } else {
+                (key.into_owned(), value.into_owned())
+            }
+        })
+        .collect();
+    url.query_pairs_mut().clear().extend_pairs(pairs);
+    Some(url.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_support::MockServer;
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct NamedPage {
+        names: Vec<String>,
+        next_page_url: Option<String>,
+    }
+
+    impl Page for NamedPage {
+        type Item = String;
+
+        fn into_items(self) -> Vec<String> {
+            self.names
+        }
+
+        fn next_page_url(&self) -> Option<String> {
+            self.next_page_url.clone()
+        }
+    }
+
+    #[test]
+    fn full_pages_are_followed_by_the_next_number() {
+        let server = MockServer::start();
+        server
+            .on("/builds?page=1&per_page=2", 200, "[1, 2]")
+            .on("/builds?page=2&per_page=2", 200, "[3, 4]")
+            .on("/builds?page=3&per_page=2", 200, "[5]");
+        let url = format!("{}/builds?page=1&per_page=2", server.base_url);
+
+        let builds: Vec<u32> =
+            get_all_pages::<Vec<u32>>(&server.client(), &url, Headers::new(), 10).unwrap();
+        assert_eq!(builds, vec![1, 2, 3, 4, 5]);
+        let pages: Vec<u32> =
+            get_all_pages::<Vec<u32>>(&server.client(), &url, Headers::new(), 2).unwrap();
+        assert_eq!(pages, vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn next_page_urls_in_the_body_are_followed() {
+        let server = MockServer::start();
+        let second = format!("{}/jobs/2", server.base_url);
+        server
+            .on(
+                "/jobs",
+                200,
+                &format!("{{\"names\": [\"app\"], \"nextPageUrl\": \"{}\"}}", second),
+            )
+            .on("/jobs/2", 200, "{\"names\": [\"api\"]}");
+        let url = format!("{}/jobs", server.base_url);
+
+        let names = get_all_pages::<NamedPage>(&server.client(), &url, Headers::new(), 10).unwrap();
+        assert_eq!(names, vec!["app".to_string(), "api".to_string()]);
+    }
+
+    #[test]
+    fn link_header_next_is_relative_to_the_page() {
+        let mut headers = Headers::new();
+        headers.set(
+            "Link",
+            "</builds?page=1>; rel=\"first\", </builds?page=3&ids=1,2>; rel=\"next last\"",
+        );
+        assert_eq!(
+            next_link(&headers, "https://example.com/api/builds?page=2"),
+            Some("https://example.com/builds?page=3&ids=1,2".to_string())
+        );
+        assert_eq!(next_link(&Headers::new(), "https://example.com/"), None);
+    }
+}
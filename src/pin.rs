@@ -1,41 +1,125 @@
-use std::sync::mpsc::{Receiver, Sender};
+use gpio::{self, GpioBackend};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
 use std::sync::{mpsc, Arc, Mutex};
 use std::thread;
-use std::time::Duration;
-use wiringpi;
-use wiringpi::*;
+use std::time::{Duration, Instant};
 
-lazy_static! {
-    static ref PI: WiringPi<pin::Gpio> = wiringpi::setup_gpio();
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Pattern {
+    Solid,
+    Blink,
+    SlowBlink,
+    FastBlink,
+    DoubleBlink,
+    Heartbeat,
+    Glow,
+    FastGlow,
+    Chase,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct PatternTimings {
+    pub blink_period_millis: u64,
+    // Fraction of each blink period the LED is lit, from 0.0 to 1.0
+    pub blink_duty_cycle: f32,
+    pub slow_blink_period_millis: u64,
+    pub fast_blink_period_millis: u64,
+    pub glow_period_millis: u64,
+    pub fast_glow_period_millis: u64,
+    pub heartbeat_period_millis: u64,
+    pub queue_pulse_period_millis: u64,
+    pub freeze_pulse_period_millis: u64,
+}
+
+impl Default for PatternTimings {
+    fn default() -> PatternTimings {
+        PatternTimings {
+            blink_period_millis: 1500,
+            blink_duty_cycle: 0.5,
+            slow_blink_period_millis: 3000,
+            fast_blink_period_millis: 400,
+            glow_period_millis: 1400,
+            fast_glow_period_millis: 700,
+            heartbeat_period_millis: 1200,
+            queue_pulse_period_millis: 6000,
+            freeze_pulse_period_millis: 8000,
+        }
+    }
+}
+
+// What a light's three pins are wired to.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputType {
+    // One RGB LED, dimmed with software PWM. Pins are R, G, B.
+    Rgb,
+    // Three single-color lamps, or relays driving them, switched fully on or
+    // off. Pins are red, yellow, green, and colors are given as which of
+    // them to light, e.g. RgbLedLight::YELLOW_LAMP.
+    TrafficLight,
+}
+
+impl Default for OutputType {
+    fn default() -> OutputType {
+        OutputType::Rgb
+    }
 }
 
 pub struct RgbLedLight {
-    red_pin: wiringpi::pin::SoftPwmPin<wiringpi::pin::Gpio>,
-    green_pin: wiringpi::pin::SoftPwmPin<wiringpi::pin::Gpio>,
-    blue_pin: wiringpi::pin::SoftPwmPin<wiringpi::pin::Gpio>,
+    gpio: Arc<GpioBackend>,
+    red_pin: u16,
+    green_pin: u16,
+    blue_pin: u16,
+    output_type: OutputType,
     is_blinking: Arc<Mutex<bool>>,
     stop_blinking_transmitter: Option<Sender<bool>>,
 }
 
 impl RgbLedLight {
     pub const RED: (i32, i32, i32) = (100, 0, 0);
+    pub const DIM_RED: (i32, i32, i32) = (40, 0, 0);
     pub const GREEN: (i32, i32, i32) = (0, 100, 0);
     pub const BLUE: (i32, i32, i32) = (0, 0, 100);
     pub const TEAL: (i32, i32, i32) = (0, 100, 100);
     pub const YELLOW: (i32, i32, i32) = (100, 75, 0);
-    pub const WHITE: (i32, i32, i32) = (100, 100, 00);
+    pub const WHITE: (i32, i32, i32) = (100, 100, 100);
     pub const PURPLE: (i32, i32, i32) = (100, 0, 100);
+    pub const ORANGE: (i32, i32, i32) = (100, 30, 0);
+    pub const AMBER: (i32, i32, i32) = (100, 50, 0);
+    pub const OFF: (i32, i32, i32) = (0, 0, 0);
+
+    // The lamps of a traffic light
+    pub const RED_LAMP: (i32, i32, i32) = (100, 0, 0);
+    pub const YELLOW_LAMP: (i32, i32, i32) = (0, 100, 0);
+    pub const GREEN_LAMP: (i32, i32, i32) = (0, 0, 100);
+    pub const ALL_LAMPS: (i32, i32, i32) = (100, 100, 100);
 
     pub fn new(red: u16, green: u16, blue: u16) -> RgbLedLight {
+        RgbLedLight::with_gpio(gpio::wiring_pi(), red, green, blue)
+    }
+
+    pub fn with_gpio(gpio: Arc<GpioBackend>, red: u16, green: u16, blue: u16) -> RgbLedLight {
         RgbLedLight {
-            red_pin: PI.soft_pwm_pin(red),
-            green_pin: PI.soft_pwm_pin(green),
-            blue_pin: PI.soft_pwm_pin(blue),
+            gpio: gpio,
+            red_pin: red,
+            green_pin: green,
+            blue_pin: blue,
+            output_type: OutputType::Rgb,
             is_blinking: Arc::new(Mutex::new(false)),
             stop_blinking_transmitter: None,
         }
     }
 
+    pub fn set_output_type(&mut self, output_type: OutputType) {
+        self.output_type = output_type;
+    }
+
+    pub fn output_type(&self) -> OutputType {
+        self.output_type
+    }
+
     pub fn turn_led_on(&mut self) {
         self.stop_blinking();
         self.turn_led_on_internal();
@@ -53,39 +137,85 @@ impl RgbLedLight {
     }
 
     pub fn blink_led(&mut self, rgb: (i32, i32, i32)) {
+        self.animate(vec![(rgb, 750), (RgbLedLight::OFF, 750)]);
+    }
+
+    pub fn play_pattern(&mut self, rgb: (i32, i32, i32), pattern: Pattern, timings: &PatternTimings) {
+        let off = RgbLedLight::OFF;
+        match pattern {
+            Pattern::Solid => self.set_led_rgb_values(rgb),
+            Pattern::Glow => self.glow_led_period(rgb, timings.glow_period_millis),
+            Pattern::FastGlow => self.glow_led_period(rgb, timings.fast_glow_period_millis),
+            Pattern::Blink => self.blink_led_period(rgb, timings.blink_period_millis, timings),
+            Pattern::SlowBlink => {
+                self.blink_led_period(rgb, timings.slow_blink_period_millis, timings)
+            }
+            Pattern::FastBlink => {
+                self.blink_led_period(rgb, timings.fast_blink_period_millis, timings)
+            }
+            Pattern::DoubleBlink => {
+                let flash_millis = 120;
+                let pause_millis = timings.blink_period_millis.saturating_sub(flash_millis * 3);
+                self.animate(vec![
+                    (rgb, flash_millis),
+                    (off, flash_millis),
+                    (rgb, flash_millis),
+                    (off, pause_millis),
+                ]);
+            }
+            Pattern::Heartbeat => {
+                // A strong beat, a weaker echo, then rest.
+                let (r, g, b) = rgb;
+                let echo = (r / 3, g / 3, b / 3);
+                let rest_millis = timings.heartbeat_period_millis.saturating_sub(400);
+                self.animate(vec![
+                    (rgb, 100),
+                    (off, 150),
+                    (echo, 150),
+                    (off, rest_millis),
+                ]);
+            }
+            Pattern::Chase => {
+                // On a single RGB LED, "chasing" steps through its three dies in turn.
+                let (r, g, b) = rgb;
+                let step_millis = timings.blink_period_millis / 3;
+                self.animate(vec![
+                    ((r, 0, 0), step_millis),
+                    ((0, g, 0), step_millis),
+                    ((0, 0, b), step_millis),
+                ]);
+            }
+        }
+    }
+
+    fn blink_led_period(&mut self, rgb: (i32, i32, i32), period: u64, timings: &PatternTimings) {
+        let duty_cycle = timings.blink_duty_cycle.max(0.0).min(1.0);
+        let on_millis = (period as f32 * duty_cycle) as u64;
+        self.animate(vec![(rgb, on_millis), (RgbLedLight::OFF, period - on_millis)]);
+    }
+
+    // Loops through the given (color, duration in ms) frames on a background
+    // thread until the LED is told to do something else.
+    pub fn animate(&mut self, frames: Vec<((i32, i32, i32), u64)>) {
         if self.is_blinking() {
             self.stop_blinking();
         }
 
-        let mut led_clone = RgbLedLight {
-            red_pin: PI.soft_pwm_pin(self.red_pin.number() as u16),
-            green_pin: PI.soft_pwm_pin(self.green_pin.number() as u16),
-            blue_pin: PI.soft_pwm_pin(self.blue_pin.number() as u16),
-            is_blinking: Arc::new(Mutex::new(false)),
-            stop_blinking_transmitter: None,
-        };
-
-        let (r, g, b) = rgb; //destructure the tuple, so we can refer to individual values
+        let mut led_clone = self.clone_pins();
 
         self.start_blinking();
         let (tx, rx): (Sender<bool>, Receiver<bool>) = mpsc::channel();
         self.stop_blinking_transmitter = Some(tx);
-        // reference to self.is_blinking, so the thread can safely watch it for value changes
-        let is_blinking = self.is_blinking.clone();
         thread::spawn(move || loop {
-            if rx.try_recv().is_ok() {
-                return;
-            }
-            led_clone.set_led_rgb_values_internal(r, g, b);
-            thread::sleep(Duration::from_millis(750));
-
-            if rx.try_recv().is_ok() {
-                return;
+            for &((r, g, b), millis) in frames.iter() {
+                led_clone.set_led_rgb_values_internal(r, g, b);
+                match rx.recv_timeout(Duration::from_millis(millis)) {
+                    Err(RecvTimeoutError::Timeout) => {}
+                    _ => return,
+                }
             }
-            led_clone.turn_led_off_internal();
-            thread::sleep(Duration::from_millis(750));
         });
-    }        
+    }
 
     pub fn glow_led(&mut self, rgb: (i32, i32, i32)) {
         self.glow_led_period(rgb, 1400);
@@ -99,13 +229,7 @@ impl RgbLedLight {
         let period = if period >= 200 { period } else { 200 };
         let sleep_per_tick = period / 200;
 
-        let mut led_clone = RgbLedLight {
-            red_pin: PI.soft_pwm_pin(self.red_pin.number() as u16),
-            green_pin: PI.soft_pwm_pin(self.green_pin.number() as u16),
-            blue_pin: PI.soft_pwm_pin(self.blue_pin.number() as u16),
-            is_blinking: Arc::new(Mutex::new(false)),
-            stop_blinking_transmitter: None,
-        };
+        let mut led_clone = self.clone_pins();
 
         let (r, g, b) = rgb; //destructure the tuple, so we can refer to individual values
 
@@ -141,22 +265,42 @@ impl RgbLedLight {
         });        
     }    
 
+    // A second handle to the same pins, for animation threads to drive.
+    fn clone_pins(&self) -> RgbLedLight {
+        RgbLedLight {
+            gpio: Arc::clone(&self.gpio),
+            red_pin: self.red_pin,
+            green_pin: self.green_pin,
+            blue_pin: self.blue_pin,
+            output_type: self.output_type,
+            is_blinking: Arc::new(Mutex::new(false)),
+            stop_blinking_transmitter: None,
+        }
+    }
+
     fn turn_led_on_internal(&mut self) {
-        self.red_pin.pwm_write(100);
-        self.green_pin.pwm_write(100);
-        self.blue_pin.pwm_write(100);
+        self.set_led_rgb_values_internal(100, 100, 100);
     }
 
     fn turn_led_off_internal(&mut self) {
-        self.red_pin.pwm_write(0);
-        self.green_pin.pwm_write(0);
-        self.blue_pin.pwm_write(0);
+        self.set_led_rgb_values_internal(0, 0, 0);
     }
 
     fn set_led_rgb_values_internal(&mut self, r: i32, g: i32, b: i32) {
-        self.red_pin.pwm_write(r);
-        self.green_pin.pwm_write(g);
-        self.blue_pin.pwm_write(b);
+        match self.output_type {
+            OutputType::Rgb => {
+                self.gpio.pwm_write(self.red_pin, r);
+                self.gpio.pwm_write(self.green_pin, g);
+                self.gpio.pwm_write(self.blue_pin, b);
+            }
+            // Lamps can't be dimmed, and relays shouldn't chatter, so any
+            // brightness at all is on. Fades become blinks.
+            OutputType::TrafficLight => {
+                self.gpio.digital_write(self.red_pin, r > 0);
+                self.gpio.digital_write(self.green_pin, g > 0);
+                self.gpio.digital_write(self.blue_pin, b > 0);
+            }
+        }
     }
 
     fn start_blinking(&mut self) {
@@ -177,3 +321,200 @@ impl RgbLedLight {
         return *is_blinking;
     }
 }
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ButtonPress {
+    Short,
+    Long,
+}
+
+// A momentary push button wired between a GPIO pin and ground, using the Pi's
+// internal pull-up. Reads are debounced by requiring the pin to hold steady
+// for DEBOUNCE_MILLIS before a change counts.
+pub struct Button {
+    gpio: Arc<GpioBackend>,
+    pin: u16,
+    long_press: Duration,
+}
+
+impl Button {
+    const DEBOUNCE_MILLIS: u64 = 30;
+    const POLL_MILLIS: u64 = 5;
+
+    pub fn new(pin: u16, long_press_millis: u64) -> Button {
+        Button {
+            gpio: gpio::wiring_pi(),
+            pin: pin,
+            long_press: Duration::from_millis(long_press_millis),
+        }
+    }
+
+    // Blocks until a press has been released, or returns None if `timeout`
+    // passes with the button untouched.
+    pub fn wait_for_press(&mut self, timeout: Duration) -> Option<ButtonPress> {
+        let waiting_since = Instant::now();
+        while !self.is_pressed() {
+            if waiting_since.elapsed() >= timeout {
+                return None;
+            }
+        }
+
+        let pressed_at = Instant::now();
+        while self.is_pressed() {}
+        if pressed_at.elapsed() >= self.long_press {
+            Some(ButtonPress::Long)
+        } else {
+            Some(ButtonPress::Short)
+        }
+    }
+
+    // Debounced read. Takes at least DEBOUNCE_MILLIS to return.
+    fn is_pressed(&self) -> bool {
+        let mut reading = self.gpio.digital_read(self.pin);
+        let mut steady_since = Instant::now();
+        while steady_since.elapsed() < Duration::from_millis(Button::DEBOUNCE_MILLIS) {
+            thread::sleep(Duration::from_millis(Button::POLL_MILLIS));
+            let new_reading = self.gpio.digital_read(self.pin);
+            if new_reading != reading {
+                reading = new_reading;
+                steady_since = Instant::now();
+            }
+        }
+        // Pulled up, so pressed reads as low
+        !reading
+    }
+}
+
+// A mechanical quadrature rotary encoder with its A and B pins wired to
+// ground through the switch, using the Pi's internal pull-ups.
+pub struct RotaryEncoder {
+    gpio: Arc<GpioBackend>,
+    a_pin: u16,
+    b_pin: u16,
+    last_a: bool,
+}
+
+impl RotaryEncoder {
+    pub fn new(a: u16, b: u16) -> RotaryEncoder {
+        let gpio = gpio::wiring_pi();
+        gpio.digital_read(b);
+        let last_a = gpio.digital_read(a);
+        RotaryEncoder {
+            gpio: gpio,
+            a_pin: a,
+            b_pin: b,
+            last_a: last_a,
+        }
+    }
+
+    // Blocks until the encoder clicks one detent, returning 1 for clockwise
+    // and -1 for counter-clockwise, or None if `timeout` passes first.
+    pub fn wait_for_turn(&mut self, timeout: Duration) -> Option<i32> {
+        let waiting_since = Instant::now();
+        while waiting_since.elapsed() < timeout {
+            let a = self.gpio.digital_read(self.a_pin);
+            if a != self.last_a {
+                self.last_a = a;
+                // Count on A's falling edge only, so each detent counts once.
+                if !a {
+                    return if self.gpio.digital_read(self.b_pin) {
+                        Some(1)
+                    } else {
+                        Some(-1)
+                    };
+                }
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gpio::FakeGpio;
+
+    fn red_writes(gpio: &FakeGpio) -> Vec<i32> {
+        gpio.pwm_writes
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|&&(pin, _)| pin == 1)
+            .map(|&(_, value)| value)
+            .collect()
+    }
+
+    #[test]
+    fn solid_sets_every_pin() {
+        let gpio = FakeGpio::new();
+        let mut led = RgbLedLight::with_gpio(gpio.clone(), 1, 2, 3);
+        led.play_pattern((100, 0, 50), Pattern::Solid, &PatternTimings::default());
+        assert_eq!(gpio.pwm_values(&[1, 2, 3]), vec![Some(100), Some(0), Some(50)]);
+    }
+
+    #[test]
+    fn traffic_light_switches_lamps_on_and_off() {
+        let gpio = FakeGpio::new();
+        let mut led = RgbLedLight::with_gpio(gpio.clone(), 1, 2, 3);
+        led.set_output_type(OutputType::TrafficLight);
+        led.set_led_rgb_values((30, 0, 100));
+        assert_eq!(
+            *gpio.digital_writes.lock().unwrap(),
+            vec![(1, true), (2, false), (3, true)]
+        );
+        assert!(gpio.pwm_writes.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn blink_alternates_between_color_and_off() {
+        let gpio = FakeGpio::new();
+        let mut led = RgbLedLight::with_gpio(gpio.clone(), 1, 2, 3);
+        let timings = PatternTimings {
+            blink_period_millis: 40,
+            ..PatternTimings::default()
+        };
+        led.play_pattern(RgbLedLight::RED, Pattern::Blink, &timings);
+        thread::sleep(Duration::from_millis(150));
+        led.turn_led_off();
+
+        let writes = red_writes(&gpio);
+        // Everything but the final turn_led_off comes from the blink thread
+        let blinks = &writes[..writes.len() - 1];
+        assert!(blinks.len() >= 4, "Only {} writes", blinks.len());
+        for (i, value) in blinks.iter().enumerate() {
+            assert_eq!(*value, if i % 2 == 0 { 100 } else { 0 });
+        }
+    }
+
+    #[test]
+    fn glow_ramps_up_from_off() {
+        let gpio = FakeGpio::new();
+        let mut led = RgbLedLight::with_gpio(gpio.clone(), 1, 2, 3);
+        led.glow_led_period(RgbLedLight::RED, 200);
+        thread::sleep(Duration::from_millis(50));
+        led.turn_led_off();
+
+        let writes = red_writes(&gpio);
+        assert_eq!(writes[0], 0);
+        let ramp = &writes[..writes.len() - 1];
+        assert!(ramp.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert!(*ramp.last().unwrap() > 0);
+    }
+
+    #[test]
+    fn turning_off_stops_the_animation() {
+        let gpio = FakeGpio::new();
+        let mut led = RgbLedLight::with_gpio(gpio.clone(), 1, 2, 3);
+        led.animate(vec![(RgbLedLight::RED, 10), (RgbLedLight::OFF, 10)]);
+        thread::sleep(Duration::from_millis(50));
+        led.turn_led_off();
+        // Let a write that was already underway land
+        thread::sleep(Duration::from_millis(20));
+
+        let writes_after_stop = gpio.pwm_writes.lock().unwrap().len();
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(gpio.pwm_writes.lock().unwrap().len(), writes_after_stop);
+        assert_eq!(gpio.pwm_values(&[1, 2, 3]), vec![Some(0), Some(0), Some(0)]);
+    }
+}
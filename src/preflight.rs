@@ -0,0 +1,75 @@
+use remote_status::StatusSummary;
+
+// How an integration's first poll after startup went. Logged and blinked on
+// its LED, so wrong credentials or a wrong URL show up while whoever set the
+// light up is still looking at it, rather than as a blue LED hours later.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Preflight {
+    Passed,
+    // The server answered 401 or 403
+    CredentialsRejected(String),
+    Failed(String),
+}
+
+impl Preflight {
+    pub fn from_summary(summary: &StatusSummary) -> Preflight {
+        match summary.error {
+            Some(ref error) if is_auth_error(error) => {
+                Preflight::CredentialsRejected(error.clone())
+            }
+            Some(ref error) if summary.disconnected => Preflight::Failed(error.clone()),
+            None if summary.disconnected => Preflight::Failed("No response".to_string()),
+            _ => Preflight::Passed,
+        }
+    }
+
+    pub fn log(&self, name: &str) {
+        match *self {
+            Preflight::Passed => info!("--Preflight--: {} answered its first poll.", name),
+            Preflight::CredentialsRejected(ref error) => error!(
+                "--Preflight--: {} turned down the credentials. Check its username, password or token in config.toml. Error: {}",
+                name, error
+            ),
+            Preflight::Failed(ref error) => error!(
+                "--Preflight--: {} couldn't be polled. Check its URL, and that the Pi can reach it. Error: {}",
+                name, error
+            ),
+        }
+    }
+}
+
+// HttpStatusError's message, from a poll or one of the requests in it.
+fn is_auth_error(error: &str) -> bool {
+    error.contains("failed with code: 401") || error.contains("failed with code: 403")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_polls_are_told_apart_by_what_went_wrong() {
+        assert_eq!(
+            Preflight::from_summary(&StatusSummary::default()),
+            Preflight::Passed
+        );
+        let rejected = StatusSummary::disconnected(
+            "HTTP call to https://jenkins/api/json failed with code: 401 Unauthorized".to_string(),
+        );
+        match Preflight::from_summary(&rejected) {
+            Preflight::CredentialsRejected(ref error) => assert!(error.contains("401")),
+            other => panic!("Expected the credentials to be rejected, not {:?}", other),
+        }
+        let unreachable = StatusSummary::disconnected("connection refused".to_string());
+        assert_eq!(
+            Preflight::from_summary(&unreachable),
+            Preflight::Failed("connection refused".to_string())
+        );
+        // One job that couldn't be fetched doesn't fail the whole integration
+        let partial = StatusSummary {
+            error: Some("HTTP call to https://jenkins/job/app failed with code: 500".to_string()),
+            ..StatusSummary::default()
+        };
+        assert_eq!(Preflight::from_summary(&partial), Preflight::Passed);
+    }
+}
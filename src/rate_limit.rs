@@ -0,0 +1,174 @@
+use budget::RequestBudget;
+use clock;
+use headers::{self, Headers, X_RATE_LIMIT_REMAINING, X_RATE_LIMIT_RESET};
+use reqwest::StatusCode;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// When a server says "too many requests" without saying for how long.
+const DEFAULT_RETRY_AFTER_SECONDS: u64 = 60;
+// Don't let a bogus header keep us quiet forever.
+const MAX_RETRY_AFTER_SECONDS: u64 = 60 * 60;
+
+// What a server has told us about its rate limits, through Retry-After or
+// X-RateLimit-* headers. While limited, requests to it aren't sent at all.
+// Otherwise, the requests it has left are there for polls to budget with.
+#[derive(Clone)]
+pub struct RateLimit {
+    limited_until: Arc<Mutex<Option<Instant>>>,
+    // Requests left, and when the window resets
+    window: Arc<Mutex<Option<(u32, Instant)>>>,
+    requests_sent: Arc<AtomicUsize>,
+}
+
+impl RateLimit {
+    pub fn new() -> RateLimit {
+        RateLimit {
+            limited_until: Arc::new(Mutex::new(None)),
+            window: Arc::new(Mutex::new(None)),
+            requests_sent: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    // None until the server has said how many requests it allows, or once
+    // the window it did has passed.
+    pub fn budget(&self) -> Option<RequestBudget> {
+        let window = match self.window.lock() {
+            Ok(window) => *window,
+            Err(_) => None,
+        };
+        let now = Instant::now();
+        window.and_then(|(remaining, resets_at)| {
+            if resets_at > now {
+                Some(RequestBudget {
+                    remaining: remaining,
+                    resets_in: resets_at - now,
+                    requests_sent: self.requests_sent.load(Ordering::SeqCst),
+                })
+            } else {
+                None
+            }
+        })
+    }
+
+    // How much longer we have to wait, if at all.
+    pub fn remaining(&self) -> Option<Duration> {
+        let limited_until = match self.limited_until.lock() {
+            Ok(limited_until) => *limited_until,
+            Err(_) => None,
+        };
+        limited_until.and_then(|until| {
+            let now = Instant::now();
+            if until > now {
+                Some(until - now)
+            } else {
+                None
+            }
+        })
+    }
+
+    // Times in the headers are compared with the server's own clock, from its
+    // Date header, since the Pi's can be far off after a power cut.
+    pub fn observe(&self, url: &str, status: StatusCode, headers: &Headers) {
+        self.requests_sent.fetch_add(1, Ordering::SeqCst);
+        let now = clock::server_time(headers).unwrap_or_else(SystemTime::now);
+        let window = (
+            headers.get_parsed::<u32>(X_RATE_LIMIT_REMAINING),
+            headers.get_parsed::<u64>(X_RATE_LIMIT_RESET),
+        );
+        if let (Some(remaining), Some(reset)) = window {
+            let resets_in = (UNIX_EPOCH + Duration::from_secs(reset))
+                .duration_since(now)
+                .unwrap_or_else(|_| Duration::from_secs(0));
+            if let Ok(mut window) = self.window.lock() {
+                *window = Some((remaining, Instant::now() + resets_in));
+            }
+        }
+        let retry_after = headers
+            .get("Retry-After")
+            .and_then(|retry_after| parse_retry_after(retry_after, now));
+        let limited_for = match status {
+            StatusCode::TooManyRequests => Some(
+                retry_after.unwrap_or_else(|| Duration::from_secs(DEFAULT_RETRY_AFTER_SECONDS)),
+            ),
+            // Without Retry-After, a 503 is just the server being down
+            StatusCode::ServiceUnavailable => retry_after,
+            _ => match window {
+                (Some(0), Some(reset)) => {
+                    let reset = UNIX_EPOCH + Duration::from_secs(reset);
+                    reset.duration_since(now).ok()
+                }
+                _ => None,
+            },
+        };
+
+        if let Some(limited_for) = limited_for {
+            let limited_for = limited_for.min(Duration::from_secs(MAX_RETRY_AFTER_SECONDS));
+            warn!(
+                "Rate limited by {}, not calling it again for {} seconds.",
+                url,
+                limited_for.as_secs()
+            );
+            if let Ok(mut limited_until) = self.limited_until.lock() {
+                *limited_until = Some(Instant::now() + limited_for);
+            }
+        }
+    }
+}
+
+// Retry-After is either a number of seconds, or a date.
+fn parse_retry_after(value: &str, now: SystemTime) -> Option<Duration> {
+    match value.trim().parse::<u64>() {
+        Ok(seconds) => Some(Duration::from_secs(seconds)),
+        Err(_) => headers::parse_http_date(value).map(|date| {
+            date.duration_since(now)
+                .unwrap_or_else(|_| Duration::from_secs(0))
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reset_is_relative_to_the_servers_clock() {
+        // The server is a day behind us, so our own clock would say the reset already passed
+        let server_now = SystemTime::now() - Duration::from_secs(24 * 60 * 60);
+        let since_epoch = server_now.duration_since(UNIX_EPOCH).unwrap();
+        let mut headers = Headers::new();
+        headers.set("Date", headers::format_http_date(server_now));
+        headers.set(X_RATE_LIMIT_REMAINING, "0");
+        headers.set(
+            X_RATE_LIMIT_RESET,
+            (since_epoch.as_secs() + 120).to_string(),
+        );
+
+        let rate_limit = RateLimit::new();
+        rate_limit.observe("https://example.com", StatusCode::Ok, &headers);
+        let remaining = rate_limit.remaining().unwrap().as_secs();
+        assert!(remaining > 100 && remaining <= 120);
+    }
+
+    #[test]
+    fn budget_counts_the_requests_sent() {
+        let since_epoch = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        let mut headers = Headers::new();
+        headers.set(X_RATE_LIMIT_REMAINING, "99");
+        headers.set(
+            X_RATE_LIMIT_RESET,
+            (since_epoch.as_secs() + 600).to_string(),
+        );
+
+        let rate_limit = RateLimit::new();
+        assert_eq!(rate_limit.budget(), None);
+        rate_limit.observe("https://example.com", StatusCode::Ok, &headers);
+        rate_limit.observe("https://example.com", StatusCode::Ok, &headers);
+        let budget = rate_limit.budget().unwrap();
+        assert_eq!(budget.remaining, 99);
+        assert_eq!(budget.requests_sent, 2);
+        assert!(budget.resets_in <= Duration::from_secs(600));
+        assert!(rate_limit.remaining().is_none());
+    }
+}
@@ -0,0 +1,323 @@
+use chrono::Local;
+use errors::HttpStatusError;
+use failure::Error;
+use headers::Headers;
+use reqwest::{StatusCode, Url};
+use serde_json;
+use std::collections::VecDeque;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+// A response as it came from the server, before any of it was parsed.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RecordedResponse {
+    pub url: String,
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl RecordedResponse {
+    // Cookies are left out, they could be someone's session.
+    pub fn new(url: &str, status: StatusCode, headers: &Headers, body: &str) -> RecordedResponse {
+        RecordedResponse {
+            url: url.to_string(),
+            status: status.as_u16(),
+            headers: headers
+                .iter()
+                .filter(|&&(ref name, _)| !name.eq_ignore_ascii_case("Set-Cookie"))
+                .cloned()
+                .collect(),
+            body: body.to_string(),
+        }
+    }
+
+    // The body, or the error the original request got if it wasn't a 200.
+    pub fn ok_body(&self) -> Result<&str, Error> {
+        match StatusCode::try_from(self.status) {
+            Ok(StatusCode::Ok) => Ok(&self.body),
+            Ok(status) => Err(HttpStatusError {
+                url: self.url.clone(),
+                status: status,
+            }
+            .into()),
+            Err(_) => Err(format_err!(
+                "The recorded response from {} has an invalid status {}",
+                self.url,
+                self.status
+            )),
+        }
+    }
+
+    pub fn headers(&self) -> Headers {
+        let mut headers = Headers::new();
+        for &(ref name, ref value) in &self.headers {
+            headers.append(name.as_str(), value.as_str());
+        }
+        headers
+    }
+}
+
+// Writes every response an integration gets to disk, one file per poll, so
+// a response that trips up the parsing can be replayed off the device. Only
+// the newest max_polls files are kept.
+#[derive(Clone)]
+pub struct Recorder {
+    directory: PathBuf,
+    max_polls: usize,
+    poll_file: Arc<Mutex<Option<PathBuf>>>,
+}
+
+impl Recorder {
+    pub fn new(directory: PathBuf, max_polls: usize) -> Recorder {
+        Recorder {
+            directory: directory,
+            max_polls: max_polls,
+            poll_file: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    // Responses from here on go to a new file, named after when the poll started.
+    pub fn start_poll(&self) {
+        let name = format!("{}.jsonl", Local::now().format("%Y%m%d-%H%M%S%.3f"));
+        if let Ok(mut poll_file) = self.poll_file.lock() {
+            *poll_file = Some(self.directory.join(name));
+        }
+        if let Err(err) = self.prune() {
+            warn!(
+                "Couldn't remove old recordings from {}. Error: {}",
+                self.directory.display(),
+                err
+            );
+        }
+    }
+
+    // Failing to record is only logged, it mustn't get in the way of the poll.
+    pub fn record(&self, response: &RecordedResponse) {
+        if let Err(err) = self.append(response) {
+            warn!(
+                "Couldn't record the response from {} in {}. Error: {}",
+                response.url,
+                self.directory.display(),
+                err
+            );
+        }
+    }
+
+    fn append(&self, response: &RecordedResponse) -> Result<(), Error> {
+        let needs_poll = self
+            .poll_file
+            .lock()
+            .map(|file| file.is_none())
+            .unwrap_or(false);
+        if needs_poll {
+            self.start_poll();
+        }
+        let poll_file = self
+            .poll_file
+            .lock()
+            .map_err(|_| format_err!("Failed to acquire a lock on the recording."))?;
+        let path = poll_file
+            .as_ref()
+            .ok_or_else(|| format_err!("No poll to record the response in"))?;
+        fs::create_dir_all(&self.directory)?;
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        writeln!(file, "{}", serde_json::to_string(response)?)?;
+        Ok(())
+    }
+
+    fn prune(&self) -> Result<(), Error> {
+        let polls = poll_files(&self.directory)?;
+        if polls.len() > self.max_polls {
+            for old in &polls[..polls.len() - self.max_polls] {
+                fs::remove_file(old)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// The poll files in a recording directory, oldest first. Nothing recorded
+// yet is the same as an empty directory.
+fn poll_files(directory: &Path) -> Result<Vec<PathBuf>, Error> {
+    if !directory.exists() {
+        return Ok(Vec::new());
+    }
+    let mut files = Vec::new();
+    for entry in fs::read_dir(directory)? {
+        let path = entry?.path();
+        if path
+            .extension()
+            .map_or(false, |extension| extension == "jsonl")
+        {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+// Everything one poll got, by the file it was recorded in.
+#[derive(Clone, Debug)]
+pub struct RecordedPoll {
+    pub name: String,
+    pub responses: Vec<RecordedResponse>,
+}
+
+pub fn read_polls(directory: &Path) -> Result<Vec<RecordedPoll>, Error> {
+    let mut polls = Vec::new();
+    for path in poll_files(directory)? {
+        let mut responses = Vec::new();
+        for line in BufReader::new(File::open(&path)?).lines() {
+            let line = line?;
+            if !line.trim().is_empty() {
+                responses.push(serde_json::from_str(&line).map_err(|err| {
+                    format_err!("{} isn't a recorded poll: {}", path.display(), err)
+                })?);
+            }
+        }
+        polls.push(RecordedPoll {
+            name: path
+                .file_name()
+                .map_or(String::new(), |name| name.to_string_lossy().into_owned()),
+            responses: responses,
+        });
+    }
+    Ok(polls)
+}
+
+struct ReplayState {
+    remaining: VecDeque<RecordedPoll>,
+    current: Option<RecordedPoll>,
+}
+
+// Serves recorded polls back in place of the server, one poll at a time.
+#[derive(Clone)]
+pub struct Replay {
+    state: Arc<Mutex<ReplayState>>,
+}
+
+impl Replay {
+    pub fn new(polls: Vec<RecordedPoll>) -> Replay {
+        Replay {
+            state: Arc::new(Mutex::new(ReplayState {
+                remaining: polls.into_iter().collect(),
+                current: None,
+            })),
+        }
+    }
+
+    pub fn start_poll(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            state.current = state.remaining.pop_front();
+        }
+    }
+
+    // The name of the poll being replayed, None once they've all been.
+    pub fn current_poll(&self) -> Option<String> {
+        let state = self.state.lock().ok()?;
+        state.current.as_ref().map(|poll| poll.name.clone())
+    }
+
+    pub fn polls_left(&self) -> usize {
+        self.state.lock().map_or(0, |state| state.remaining.len())
+    }
+
+    // The current poll's response from url. If the server was reached at
+    // another address than the one configured now, a response with the same
+    // path and query does too.
+    pub fn take(&self, url_string: &str) -> Result<RecordedResponse, Error> {
+        let mut state = self
+            .state
+            .lock()
+            .map_err(|_| format_err!("Failed to acquire a lock on the replay."))?;
+        let poll = state
+            .current
+            .as_mut()
+            .ok_or_else(|| format_err!("No recorded poll left to replay {}", url_string))?;
+        let same_path = |recorded: &str| match (Url::parse(recorded), Url::parse(url_string)) {
+            (Ok(recorded), Ok(wanted)) => {
+                recorded.path() == wanted.path() && recorded.query() == wanted.query()
+            }
+            _ => false,
+        };
+        let index = poll
+            .responses
+            .iter()
+            .position(|response| response.url == url_string)
+            .or_else(|| {
+                poll.responses
+                    .iter()
+                    .position(|response| same_path(&response.url))
+            })
+            .ok_or_else(|| {
+                format_err!("{} has no recorded response from {}", poll.name, url_string)
+            })?;
+        Ok(poll.responses.remove(index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn recorded_polls_are_replayed_in_order() {
+        let directory =
+            env::temp_dir().join(format!("rusty-build-light-recording-{}", process::id()));
+        let recorder = Recorder::new(directory.clone(), 2);
+        let mut headers = Headers::new();
+        headers.set("Link", "</jobs?page=2>; rel=\"next\"");
+        headers.set("Set-Cookie", "JSESSIONID=secret");
+        let response = |body: &str| {
+            RecordedResponse::new(
+                "https://ci.example.com/api/json",
+                StatusCode::Ok,
+                &headers,
+                body,
+            )
+        };
+        for body in &["{\"jobs\": 1}", "{\"jobs\": 2}", "{\"jobs\": 3}"] {
+            recorder.start_poll();
+            recorder.record(&response(body));
+            // Poll files are named to the millisecond
+            thread::sleep(Duration::from_millis(2));
+        }
+        recorder.start_poll();
+
+        // Only the newest two are kept
+        let polls = read_polls(&directory).unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(polls.len(), 2);
+        assert_eq!(polls[0].responses, vec![response("{\"jobs\": 2}")]);
+        assert_eq!(polls[0].responses[0].headers.len(), 1);
+
+        let poll_names: Vec<String> = polls.iter().map(|poll| poll.name.clone()).collect();
+        let replay = Replay::new(polls);
+        assert!(replay.take("https://ci.example.com/api/json").is_err());
+        replay.start_poll();
+        // A mirror with the same paths gets the same responses
+        let replayed = replay.take("https://mirror.example.com/api/json").unwrap();
+        assert_eq!(replayed.ok_body().unwrap(), "{\"jobs\": 2}");
+        assert!(replayed.headers().get("Link").is_some());
+        assert!(replay.take("https://ci.example.com/api/json").is_err());
+        replay.start_poll();
+        assert_eq!(replay.polls_left(), 0);
+        assert_eq!(replay.current_poll(), Some(poll_names[1].clone()));
+        assert!(replay.take("https://ci.example.com/api/json").is_ok());
+
+        let failed = RecordedResponse::new(
+            "https://ci.example.com/",
+            StatusCode::BadGateway,
+            &Headers::new(),
+            "",
+        );
+        assert!(failed.ok_body().is_err());
+    }
+}
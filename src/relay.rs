@@ -0,0 +1,294 @@
+use backoff::Backoff;
+use config_file::{RelayClientConfig, RelayServerConfig};
+use failure::Error;
+use remote_status::{RemoteStatus, StatusSummary};
+use scheduler::{Tick, TickScheduler, Ticker};
+use serde_json;
+use status_board::{IntegrationSnapshot, StatusBoard};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// The server sends an empty line this often, so clients can tell a quiet
+// server from a dead connection.
+const HEARTBEAT_SECONDS: u64 = 30;
+const MISSED_HEARTBEATS: u64 = 3;
+// A client that can't keep up is dropped instead of holding up the poll threads.
+const WRITE_TIMEOUT_SECONDS: u64 = 5;
+const READ_TIMEOUT_MILLIS: u64 = 1000;
+const RECONNECT_BASE_SECONDS: u64 = 1;
+const RECONNECT_MAX_SECONDS: u64 = 60;
+
+// One integration's latest poll, sent as a line of JSON.
+#[derive(Serialize, Deserialize)]
+struct RelayedStatus {
+    name: String,
+    status: RemoteStatus,
+    summary: StatusSummary,
+}
+
+// Sends every integration's status to the display-only build lights connected
+// to it after every poll, so only this one has to poll the servers.
+pub fn start_relay_server(config: &RelayServerConfig, board: &StatusBoard) {
+    match TcpListener::bind(config.listen.as_str()) {
+        Ok(listener) => {
+            info!("--Relay--: Relaying statuses on {}.", config.listen);
+            serve(listener, board);
+        }
+        Err(e) => error!(
+            "--Relay--: Failed to listen on {}. Relay server disabled. Error: {}",
+            config.listen, e
+        ),
+    }
+}
+
+fn serve(listener: TcpListener, board: &StatusBoard) {
+    let clients: Arc<Mutex<Vec<TcpStream>>> = Arc::new(Mutex::new(Vec::new()));
+
+    let (accept_clients, accept_board) = (Arc::clone(&clients), board.clone());
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    warn!("--Relay--: Failed to accept a client. Error: {}", e);
+                    continue;
+                }
+            };
+            let peer = stream
+                .peer_addr()
+                .map(|addr| addr.to_string())
+                .unwrap_or_default();
+            // Catch the client up right away, instead of after the next poll
+            let welcome = encode(&accept_board.snapshots());
+            let sent = stream
+                .set_write_timeout(Some(Duration::from_secs(WRITE_TIMEOUT_SECONDS)))
+                .and_then(|_| (&stream).write_all(welcome.as_bytes()));
+            match sent {
+                Ok(_) => {
+                    info!("--Relay--: {} connected.", peer);
+                    if let Ok(mut clients) = accept_clients.lock() {
+                        clients.push(stream);
+                    }
+                }
+                Err(e) => warn!("--Relay--: Failed to send to {}. Error: {}", peer, e),
+            }
+        }
+    });
+
+    let heartbeat_clients = Arc::clone(&clients);
+    thread::spawn(move || loop {
+        thread::sleep(Duration::from_secs(HEARTBEAT_SECONDS));
+        broadcast(&heartbeat_clients, "\n");
+    });
+
+    board.subscribe(Box::new(move |snapshots| {
+        broadcast(&clients, &encode(snapshots))
+    }));
+}
+
+fn encode(snapshots: &[IntegrationSnapshot]) -> String {
+    let mut message = String::new();
+    for snapshot in snapshots {
+        let relayed = RelayedStatus {
+            name: snapshot.name.clone(),
+            status: snapshot.status,
+            summary: snapshot.summary.clone(),
+        };
+        match serde_json::to_string(&relayed) {
+            Ok(line) => {
+                message.push_str(&line);
+                message.push('\n');
+            }
+            Err(e) => warn!(
+                "--Relay--: Failed to encode {}. Error: {}",
+                snapshot.name, e
+            ),
+        }
+    }
+    message
+}
+
+// Sends the message to every client, dropping the ones it can't be sent to.
+fn broadcast(clients: &Mutex<Vec<TcpStream>>, message: &str) {
+    if let Ok(mut clients) = clients.lock() {
+        clients.retain(|client| match (&*client).write_all(message.as_bytes()) {
+            Ok(_) => true,
+            Err(e) => {
+                info!("--Relay--: Dropping a client. Error: {}", e);
+                false
+            }
+        });
+    }
+}
+
+// What the relay server last sent, shared between the thread receiving it and
+// the integrations showing it.
+#[derive(Clone)]
+pub struct RelayFeed {
+    statuses: Arc<Mutex<HashMap<String, (RemoteStatus, StatusSummary)>>>,
+    // Why there's no connection to the server, while there isn't one
+    connection_error: Arc<Mutex<Option<String>>>,
+}
+
+impl RelayFeed {
+    fn new() -> RelayFeed {
+        RelayFeed {
+            statuses: Arc::new(Mutex::new(HashMap::new())),
+            connection_error: Arc::new(Mutex::new(Some(
+                "Not connected to the relay server yet.".to_string(),
+            ))),
+        }
+    }
+
+    // The integration's latest relayed status, if anything was relayed about
+    // it yet, or why the server can't be heard from.
+    pub fn latest(&self, name: &str) -> Result<Option<(RemoteStatus, StatusSummary)>, String> {
+        if let Ok(connection_error) = self.connection_error.lock() {
+            if let Some(ref error) = *connection_error {
+                return Err(error.clone());
+            }
+        }
+        match self.statuses.lock() {
+            Ok(statuses) => Ok(statuses.get(name).cloned()),
+            Err(_) => Err("Failed to acquire a lock on the relayed statuses.".to_string()),
+        }
+    }
+
+    fn set_connection_error(&self, error: Option<String>) {
+        if let Ok(mut connection_error) = self.connection_error.lock() {
+            *connection_error = error;
+        }
+    }
+
+    // Takes in a line from the server. Empty lines are heartbeats.
+    fn receive(&self, line: &[u8]) -> Result<(), Error> {
+        if line.iter().all(|byte| (*byte as char).is_whitespace()) {
+            return Ok(());
+        }
+        let relayed: RelayedStatus = serde_json::from_slice(line)?;
+        if let Ok(mut statuses) = self.statuses.lock() {
+            statuses.insert(relayed.name, (relayed.status, relayed.summary));
+        }
+        Ok(())
+    }
+}
+
+// Keeps a connection to the relay server at config.server open until shutdown,
+// reconnecting whenever it's lost.
+pub fn start_relay_client(config: RelayClientConfig, scheduler: TickScheduler) -> RelayFeed {
+    let feed = RelayFeed::new();
+    let client_feed = feed.clone();
+    thread::spawn(move || {
+        let mut ticker = scheduler.ticker();
+        let mut backoff = Backoff::new(
+            Duration::from_secs(RECONNECT_BASE_SECONDS),
+            Duration::from_secs(RECONNECT_MAX_SECONDS),
+        );
+        while ticker.is_running() {
+            if let Err(e) = receive(&config.server, &client_feed, &mut ticker, &mut backoff) {
+                let error = format!("Lost the relay server at {}: {}", config.server, e);
+                let delay = backoff.next_delay();
+                warn!(
+                    "--Relay--: {} Reconnecting in {} seconds.",
+                    error,
+                    delay.as_secs()
+                );
+                client_feed.set_connection_error(Some(error));
+                if ticker.wait(delay) == Tick::Shutdown {
+                    break;
+                }
+            }
+        }
+    });
+    feed
+}
+
+// Reads statuses from the server until the connection is lost, or shutdown.
+fn receive(
+    server: &str,
+    feed: &RelayFeed,
+    ticker: &mut Ticker,
+    backoff: &mut Backoff,
+) -> Result<(), Error> {
+    let stream = TcpStream::connect(server)?;
+    stream.set_read_timeout(Some(Duration::from_millis(READ_TIMEOUT_MILLIS)))?;
+    info!("--Relay--: Connected to {}.", server);
+    feed.set_connection_error(None);
+    backoff.reset();
+
+    let mut reader = BufReader::new(stream);
+    let mut line = Vec::new();
+    let mut last_heard = Instant::now();
+    while ticker.is_running() {
+        match reader.read_until(b'\n', &mut line) {
+            Ok(0) => return Err(format_err!("The server closed the connection.")),
+            // Without a newline, the rest of the line is still on its way
+            Ok(_) if !line.ends_with(b"\n") => last_heard = Instant::now(),
+            Ok(_) => {
+                last_heard = Instant::now();
+                if let Err(e) = feed.receive(&line) {
+                    warn!("--Relay--: Ignoring an unreadable status. Error: {}", e);
+                }
+                line.clear();
+            }
+            Err(ref e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => {
+                let silent_for = last_heard.elapsed();
+                if silent_for > Duration::from_secs(HEARTBEAT_SECONDS * MISSED_HEARTBEATS) {
+                    return Err(format_err!(
+                        "Nothing heard from the server in {} seconds.",
+                        silent_for.as_secs()
+                    ));
+                }
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The summary relayed along with status, once the feed has it.
+    fn wait_for(feed: &RelayFeed, name: &str, status: RemoteStatus) -> StatusSummary {
+        let waited_since = Instant::now();
+        loop {
+            if let Ok(Some((latest, summary))) = feed.latest(name) {
+                if latest == status {
+                    return summary;
+                }
+            }
+            assert!(waited_since.elapsed() < Duration::from_secs(5));
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn statuses_reach_the_client() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let board = StatusBoard::new();
+        board.update("Jenkins", RemoteStatus::Passing, StatusSummary::default());
+        serve(listener, &board);
+
+        let scheduler = TickScheduler::new();
+        let feed = start_relay_client(RelayClientConfig { server: address }, scheduler.clone());
+        // Whatever was on the board before the client connected, then each update
+        wait_for(&feed, "Jenkins", RemoteStatus::Passing);
+        let summary = StatusSummary {
+            failing: 1,
+            failing_jobs: vec!["api".to_string()],
+            ..StatusSummary::default()
+        };
+        board.update("Jenkins", RemoteStatus::Failing, summary);
+        let relayed = wait_for(&feed, "Jenkins", RemoteStatus::Failing);
+        assert_eq!(relayed.failing_jobs, vec!["api".to_string()]);
+        assert!(feed.latest("Unity Cloud").unwrap().is_none());
+        scheduler.shutdown();
+    }
+}
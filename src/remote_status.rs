@@ -1,7 +1,129 @@
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum RemoteStatus {
     Unknown,    // Glowing Purple
     InProgress, // Rapid glowing green
     Passing,    // Green
     Failing,    // Blinking red
 }
+
+impl RemoteStatus {
+    // Short, fixed-width-friendly label for character displays and logs.
+    pub fn label(&self) -> &'static str {
+        match *self {
+            RemoteStatus::Unknown => "????",
+            RemoteStatus::InProgress => "BLD",
+            RemoteStatus::Passing => "PASS",
+            RemoteStatus::Failing => "FAIL",
+        }
+    }
+}
+
+// The latest build of one job, under the name the integration gives it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct JobStatus {
+    pub name: String,
+    pub status: RemoteStatus,
+}
+
+// A build that's still running, for showing how far along it is.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BuildProgress {
+    pub job: String,
+    // Milliseconds since the epoch, by the server's clock
+    pub started_at: i64,
+    // How long the whole build is expected to take, if anything says
+    pub estimated_millis: Option<u64>,
+}
+
+impl BuildProgress {
+    // From 0.0 up, past 1.0 once it's taking longer than expected. None
+    // without an estimate.
+    pub fn fraction(&self, now_millis: i64) -> Option<f64> {
+        self.estimated_millis
+            .filter(|&estimate| estimate > 0)
+            .map(|estimate| (now_millis - self.started_at).max(0) as f64 / estimate as f64)
+    }
+}
+
+// Counts and failing job names from the most recent poll of an integration.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StatusSummary {
+    pub passing: usize,
+    pub failing: usize,
+    pub in_progress: usize,
+    // Jobs whose latest build was aborted, unless that counts as failing
+    pub aborted: usize,
+    // Jobs that haven't been built yet
+    pub not_built: usize,
+    // Jobs in no state above, e.g. ones that couldn't be retrieved
+    pub indeterminate: usize,
+    pub failing_jobs: Vec<String>,
+    // Jobs that have been flipping between passing and failing lately
+    pub flaky_jobs: Vec<String>,
+    // Jobs whose running build has taken much longer than their usual builds
+    pub overdue_jobs: Vec<String>,
+    // Whoever committed to the failing jobs' latest builds, when the server says
+    pub culprits: Vec<String>,
+    // The stage each failing pipeline job failed in, as "job: stage"
+    pub failed_stages: Vec<String>,
+    // Enough about each failing build to find it, e.g. its number and a link
+    pub failed_builds: Vec<String>,
+    // Failing jobs someone has claimed, i.e. said they're fixing
+    pub claimed_jobs: Vec<String>,
+    // Jobs that pass, but with warnings, e.g. a quality gate at WARN
+    pub warning_jobs: Vec<String>,
+    // Every job polled, passing or not
+    pub jobs: Vec<JobStatus>,
+    // Pull request jobs, which are left out of everything else here for the
+    // PR LED to show, see jenkins_pr_led_pins
+    pub pull_request_jobs: Vec<JobStatus>,
+    // Running builds, if the integration can tell when they started
+    pub running_builds: Vec<BuildProgress>,
+    // Some jobs are marked critical, and none of them are failing
+    pub non_critical_failures_only: bool,
+    // A mainline pattern is set, and none of the failing jobs match it
+    pub non_mainline_failures_only: bool,
+    // Builds waiting for an executor, if the integration checks its queue
+    pub queued: Option<usize>,
+    // More builds are queued than the configured threshold
+    pub queue_backed_up: bool,
+    // True when the server couldn't be reached at all, as opposed to
+    // being reachable but reporting nothing conclusive.
+    pub disconnected: bool,
+    // What went wrong, if anything did while polling
+    pub error: Option<String>,
+}
+
+impl StatusSummary {
+    pub fn disconnected(error: String) -> StatusSummary {
+        StatusSummary {
+            disconnected: true,
+            error: Some(error),
+            ..StatusSummary::default()
+        }
+    }
+
+    // e.g. "3 passing, 1 failing, 0 in progress, 0 unknown (api-tests)", with
+    // any aborted or not built jobs after the counts and the error on the end
+    // if there was one.
+    pub fn describe(&self) -> String {
+        let mut description = format!(
+            "{} passing, {} failing, {} in progress, {} unknown",
+            self.passing, self.failing, self.in_progress, self.indeterminate
+        );
+        if self.aborted > 0 {
+            description.push_str(&format!(", {} aborted", self.aborted));
+        }
+        if self.not_built > 0 {
+            description.push_str(&format!(", {} not built", self.not_built));
+        }
+        if !self.failing_jobs.is_empty() {
+            description.push_str(&format!(" ({})", self.failing_jobs.join(", ")));
+        }
+        if let Some(ref error) = self.error {
+            description.push_str(&format!(". Error: {}", error));
+        }
+        description
+    }
+}
@@ -0,0 +1,170 @@
+use app;
+use config_file::Config;
+use errors::RunError;
+use failure::Error;
+use integrations::jenkins_integration::{JenkinsIntegration, JobSelection};
+use integrations::remote_integration::RemoteIntegration;
+use integrations::unity_cloud_integration::UnityCloudIntegration;
+use network::{BaseUrls, HttpClient};
+use recording::{self, Replay};
+use std::io::{self, Write};
+use std::path::Path;
+
+pub const USAGE: &str = "Usage: rusty_build_light replay DIRECTORY
+
+Feeds responses recorded with [recording] back through the integrations, one
+poll at a time, and writes what each poll came out as to standard output.
+DIRECTORY is the [recording] directory. config.toml is read as usual, so use
+the one from the build light the recording came from.";
+
+// The integrations responses are recorded for, by their directory's name.
+const RECORDED_INTEGRATIONS: [&str; 2] = ["Jenkins", "Unity Cloud"];
+
+pub fn run_replay(args: &[String]) -> Result<(), RunError> {
+    let directory = match args {
+        [directory] => Path::new(directory),
+        _ => {
+            return Err(RunError::InvalidArguments {
+                reason: USAGE.to_string(),
+            })
+        }
+    };
+    let config = app::read_config(&app::exe_dir()?)?;
+    let failed = |err: Error| RunError::StartupFailed {
+        reason: format!("Replay failed: {}", err),
+    };
+
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let mut replayed_any = false;
+    for name in RECORDED_INTEGRATIONS.iter() {
+        let polls = recording::read_polls(&directory.join(name)).map_err(&failed)?;
+        if polls.is_empty() {
+            continue;
+        }
+        replayed_any = true;
+        let replay = Replay::new(polls);
+        let mut integration =
+            replayed_integration(name, &config, HttpClient::replaying(replay.clone()));
+        while replay.polls_left() > 0 {
+            let status = integration.get_status();
+            let poll = replay.current_poll().unwrap_or_default();
+            writeln!(
+                out,
+                "{} {}: {:?}, {}",
+                name,
+                poll,
+                status,
+                integration.get_summary().describe()
+            )
+            .map_err(|err| failed(err.into()))?;
+        }
+    }
+    if replayed_any {
+        Ok(())
+    } else {
+        Err(failed(format_err!(
+            "Nothing recorded in {}",
+            directory.display()
+        )))
+    }
+}
+
+// Set up the way app::run would, except for anything that keeps state on
+// disk, like flakiness and build durations.
+fn replayed_integration(name: &str, config: &Config, client: HttpClient) -> Box<RemoteIntegration> {
+    let jenkins = |jobs: JobSelection| {
+        let mut integration = JenkinsIntegration::new(
+            0,
+            0,
+            0,
+            &config.jenkins_username,
+            config
+                .jenkins_api_token
+                .as_ref()
+                .unwrap_or(&config.jenkins_password),
+            config.jenkins_api_token.is_some(),
+            BaseUrls::new(&config.jenkins_base_url, &config.jenkins_fallback_urls),
+            client.clone(),
+            None,
+            config.jenkins_critical_jobs(),
+            config.jenkins_aggregation.strategy(),
+            config.jenkins_last_completed_only,
+            config.jenkins_queue_threshold,
+            None,
+            jobs,
+        );
+        integration.set_aborted_is_failure(config.jenkins_aborted_is_failure);
+        Box::new(integration)
+    };
+    let pr_jobs = config.jenkins_pr_jobs();
+    match name {
+        "Jenkins" if pr_jobs.is_empty() => jenkins(JobSelection::All),
+        "Jenkins" if config.jenkins_pr_led_pins.is_some() => {
+            jenkins(JobSelection::MainlineAndPullRequests(pr_jobs))
+        }
+        "Jenkins" => jenkins(JobSelection::Mainline(pr_jobs)),
+        _ => Box::new(UnityCloudIntegration::new(
+            0,
+            0,
+            0,
+            &config.unity_cloud_api_token,
+            &config.unity_base_url,
+            client,
+            config.unity_aggregation.strategy(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use headers::Headers;
+    use integrations::aggregation::AggregationKind;
+    use recording::{RecordedPoll, RecordedResponse};
+    use remote_status::RemoteStatus;
+    use reqwest::StatusCode;
+    use test_support::fixture;
+
+    const BASE_URL: &str =
+        "https://build-api.cloud.unity3d.com/api/v1/orgs/org/projects/game/buildtargets";
+
+    #[test]
+    fn recorded_polls_are_parsed_again() {
+        let response = |target: &str, body: &str| {
+            RecordedResponse::new(
+                &format!("{}/{}/builds?per_page=1", BASE_URL, target),
+                StatusCode::Ok,
+                &Headers::new(),
+                body,
+            )
+        };
+        let poll = |name: &str, android_body: &str| RecordedPoll {
+            name: name.to_string(),
+            responses: vec![
+                response("ios-development", &fixture("unity_builds_success.json")),
+                response("android-development", android_body),
+            ],
+        };
+        let replay = Replay::new(vec![
+            poll("1.jsonl", &fixture("unity_builds_success.json")),
+            // A response the parsing chokes on, as reported from the field
+            poll("2.jsonl", &fixture("malformed.json")),
+        ]);
+        let mut unity = UnityCloudIntegration::new(
+            0,
+            0,
+            0,
+            "token",
+            BASE_URL,
+            HttpClient::replaying(replay.clone()),
+            AggregationKind::AnyFailureIsRed.strategy(),
+        );
+
+        assert_eq!(unity.get_status(), RemoteStatus::Passing);
+        assert_eq!(replay.current_poll(), Some("1.jsonl".to_string()));
+        assert_eq!(unity.get_status(), RemoteStatus::Unknown);
+        assert!(unity.get_summary().describe().contains("Error: "));
+        assert_eq!(replay.polls_left(), 0);
+    }
+}
@@ -0,0 +1,277 @@
+use chrono::{Local, NaiveDateTime, NaiveTime};
+use config_file::{DailyReportConfig, SlackConfig};
+use failure::Error;
+use network::post_json;
+use remote_status::RemoteStatus;
+use scheduler::{Tick, TickScheduler};
+use status_board::{IntegrationSnapshot, StatusBoard};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const FLAKIEST_JOBS: usize = 5;
+
+#[derive(Default)]
+struct IntegrationDay {
+    failures: u32,
+    fixes: u32,
+    red_for: Duration,
+}
+
+// What happened to the builds since the last report.
+#[derive(Default)]
+struct DayTally {
+    integrations: BTreeMap<String, IntegrationDay>,
+    // Latest passing or failing status, and since when it's been failing
+    last_conclusive: HashMap<String, RemoteStatus>,
+    red_since: HashMap<String, Instant>,
+    // Jobs failing in each integration's latest poll, to count their flips
+    failing_jobs: HashMap<String, BTreeSet<String>>,
+    flips: BTreeMap<String, u32>,
+}
+
+impl DayTally {
+    fn record(&mut self, snapshot: &IntegrationSnapshot, now: Instant) {
+        let failing_jobs: BTreeSet<String> =
+            snapshot.summary.failing_jobs.iter().cloned().collect();
+        if let Some(previous) = self
+            .failing_jobs
+            .insert(snapshot.name.clone(), failing_jobs.clone())
+        {
+            for job in previous.symmetric_difference(&failing_jobs) {
+                *self
+                    .flips
+                    .entry(format!("{}: {}", snapshot.name, job))
+                    .or_insert(0) += 1;
+            }
+        }
+
+        // Building and unknown states in between don't count, like for notifications
+        if snapshot.status != RemoteStatus::Passing && snapshot.status != RemoteStatus::Failing {
+            return;
+        }
+        let previous = self
+            .last_conclusive
+            .insert(snapshot.name.clone(), snapshot.status);
+        let day = self.integrations.entry(snapshot.name.clone()).or_default();
+        match (previous, snapshot.status) {
+            (Some(RemoteStatus::Passing), RemoteStatus::Failing) => day.failures += 1,
+            (Some(RemoteStatus::Failing), RemoteStatus::Passing) => day.fixes += 1,
+            _ => (),
+        }
+        if snapshot.status == RemoteStatus::Failing {
+            self.red_since.entry(snapshot.name.clone()).or_insert(now);
+        } else if let Some(since) = self.red_since.remove(&snapshot.name) {
+            day.red_for += now.duration_since(since);
+        }
+    }
+
+    // Renders the report and starts counting the next one from now.
+    fn report(&mut self, date: &str, now: Instant) -> String {
+        for (name, since) in &mut self.red_since {
+            if let Some(day) = self.integrations.get_mut(name) {
+                day.red_for += now.duration_since(*since);
+            }
+            *since = now;
+        }
+
+        let mut report = format!("Build light report for {}\n\n", date);
+        if self.integrations.is_empty() {
+            report.push_str("No builds were polled.\n");
+        }
+        for (name, day) in &self.integrations {
+            report.push_str(&format!(
+                "{}: {} failures, {} fixes, red for {}\n",
+                name,
+                day.failures,
+                day.fixes,
+                format_duration(day.red_for)
+            ));
+        }
+
+        let mut flakiest: Vec<(&String, &u32)> =
+            self.flips.iter().filter(|&(_, &flips)| flips > 1).collect();
+        flakiest.sort_by(|a, b| b.1.cmp(a.1));
+        if !flakiest.is_empty() {
+            report.push_str("\nFlakiest jobs:\n");
+            for &(job, flips) in flakiest.iter().take(FLAKIEST_JOBS) {
+                report.push_str(&format!("  - {} ({} flips)\n", job, flips));
+            }
+        }
+
+        for day in self.integrations.values_mut() {
+            *day = IntegrationDay::default();
+        }
+        self.flips.clear();
+        report
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    let minutes = duration.as_secs() / 60;
+    if minutes < 60 {
+        format!("{} min", minutes)
+    } else {
+        format!("{} h {} min", minutes / 60, minutes % 60)
+    }
+}
+
+// How long from now until the next time it's `at` o'clock.
+fn until_next(at: NaiveTime, now: NaiveDateTime) -> Duration {
+    let today = now.date().and_time(at);
+    let next = if today > now {
+        today
+    } else {
+        today + ::chrono::Duration::days(1)
+    };
+    next.signed_duration_since(now)
+        .to_std()
+        .unwrap_or_else(|_| Duration::from_secs(0))
+}
+
+// Once a day at config.at, writes a summary of the day's builds to
+// config.directory and/or posts it to Slack.
+pub fn run_daily_report(
+    config: DailyReportConfig,
+    slack: Option<SlackConfig>,
+    board: StatusBoard,
+    scheduler: TickScheduler,
+) {
+    let at = match NaiveTime::parse_from_str(&config.at, "%H:%M") {
+        Ok(at) => at,
+        Err(e) => {
+            error!(
+                "--Report--: at should look like \"18:00\", but was \"{}\". Daily reports disabled. Error: {}",
+                config.at, e
+            );
+            return;
+        }
+    };
+    if config.post_to_slack && slack.is_none() {
+        warn!("--Report--: post_to_slack needs a [slack] section. Only writing reports.");
+    }
+
+    let tally = Arc::new(Mutex::new(DayTally::default()));
+    let subscriber_tally = Arc::clone(&tally);
+    board.subscribe(Box::new(move |snapshots| {
+        if let Ok(mut tally) = subscriber_tally.lock() {
+            for snapshot in snapshots {
+                tally.record(snapshot, Instant::now());
+            }
+        }
+    }));
+
+    let mut last_report_date = None;
+    let mut ticker = scheduler.ticker();
+    loop {
+        match ticker.wait(until_next(at, Local::now().naive_local())) {
+            Tick::Shutdown => break,
+            Tick::Reload => continue,
+            Tick::Elapsed => (),
+        }
+        let date = Local::now().format("%Y-%m-%d").to_string();
+        // Waking up a moment early would otherwise report the same day twice
+        if last_report_date.as_ref() == Some(&date) {
+            continue;
+        }
+        let report = match tally.lock() {
+            Ok(mut tally) => tally.report(&date, Instant::now()),
+            Err(_) => break,
+        };
+        last_report_date = Some(date.clone());
+        info!("--Report--: Daily report for {} ready.", date);
+
+        if let Some(ref directory) = config.directory {
+            if let Err(e) = write_report(directory, &date, &report) {
+                warn!("--Report--: Failed to write the daily report. Error: {}", e);
+            }
+        }
+        if let (true, Some(slack)) = (config.post_to_slack, slack.as_ref()) {
+            if let Err(e) = post_report(slack, &report) {
+                warn!(
+                    "--Report--: Failed to post the daily report to Slack. Error: {}",
+                    e
+                );
+            }
+        }
+    }
+}
+
+fn write_report(directory: &str, date: &str, report: &str) -> Result<(), Error> {
+    fs::create_dir_all(directory)?;
+    let path: PathBuf = [directory, &format!("build-report-{}.txt", date)]
+        .iter()
+        .collect();
+    File::create(&path)?.write_all(report.as_bytes())?;
+    Ok(())
+}
+
+fn post_report(slack: &SlackConfig, report: &str) -> Result<(), Error> {
+    let mut message = json!({ "text": format!("```{}```", report) });
+    if let Some(ref channel) = slack.channel {
+        message["channel"] = json!(channel);
+    }
+    if let Some(ref username) = slack.username {
+        message["username"] = json!(username);
+    }
+    post_json(&slack.webhook_url, &message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use remote_status::StatusSummary;
+
+    fn snapshot(status: RemoteStatus, failing_jobs: &[&str]) -> IntegrationSnapshot {
+        IntegrationSnapshot {
+            name: "Jenkins".to_string(),
+            status: status,
+            summary: StatusSummary {
+                failing_jobs: failing_jobs.iter().map(|job| job.to_string()).collect(),
+                ..StatusSummary::default()
+            },
+            last_poll: Local::now(),
+        }
+    }
+
+    #[test]
+    fn report_counts_transitions_red_time_and_flips() {
+        let start = Instant::now();
+        let minutes = |minutes: u64| start + Duration::from_secs(minutes * 60);
+        let mut tally = DayTally::default();
+        tally.record(&snapshot(RemoteStatus::Passing, &[]), minutes(0));
+        tally.record(&snapshot(RemoteStatus::Failing, &["api"]), minutes(10));
+        tally.record(&snapshot(RemoteStatus::InProgress, &["api"]), minutes(20));
+        tally.record(&snapshot(RemoteStatus::Passing, &[]), minutes(40));
+        tally.record(&snapshot(RemoteStatus::Failing, &["api"]), minutes(50));
+
+        let report = tally.report("2018-03-01", minutes(120));
+        assert_eq!(
+            report,
+            "Build light report for 2018-03-01\n\n\
+             Jenkins: 2 failures, 1 fixes, red for 1 h 40 min\n\n\
+             Flakiest jobs:\n  - Jenkins: api (3 flips)\n"
+        );
+
+        // Still failing, so the next day starts out red
+        let report = tally.report("2018-03-02", minutes(180));
+        assert!(report.contains("Jenkins: 0 failures, 0 fixes, red for 1 h 0 min\n"));
+        assert!(!report.contains("Flakiest"));
+    }
+
+    #[test]
+    fn next_report_is_today_or_tomorrow() {
+        let at = NaiveTime::from_hms(18, 0, 0);
+        let morning = NaiveDate::from_ymd(2018, 3, 1).and_hms(9, 30, 0);
+        assert_eq!(
+            until_next(at, morning),
+            Duration::from_secs(8 * 3600 + 30 * 60)
+        );
+        let evening = NaiveDate::from_ymd(2018, 3, 1).and_hms(18, 0, 0);
+        assert_eq!(until_next(at, evening), Duration::from_secs(24 * 3600));
+    }
+}
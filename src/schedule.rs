@@ -0,0 +1,93 @@
+use chrono::{Datelike, FixedOffset, Local, NaiveDateTime, NaiveTime, Utc, Weekday};
+use config_file::{OutsideHoursMode, ScheduleConfig};
+use failure::Error;
+
+// When the lights should run normally. Outside these hours the LEDs are
+// turned off or dimmed, and polling optionally paused.
+#[derive(Clone)]
+pub struct Schedule {
+    hours: HourRange,
+    weekdays_only: bool,
+    utc_offset: Option<FixedOffset>,
+    pub outside_hours: OutsideHoursMode,
+    pub dim_percent: i32,
+    pub poll_outside_active_hours: bool,
+}
+
+impl Schedule {
+    pub fn from_config(config: &ScheduleConfig) -> Result<Schedule, Error> {
+        let hours = HourRange::parse(&config.active_hours)?;
+        let utc_offset =
+            match config.utc_offset_minutes {
+                Some(minutes) => Some(FixedOffset::east_opt(minutes * 60).ok_or_else(|| {
+                    format_err!("utc_offset_minutes is out of range: {}", minutes)
+                })?),
+                None => None,
+            };
+        Ok(Schedule {
+            hours: hours,
+            weekdays_only: config.weekdays_only,
+            utc_offset: utc_offset,
+            outside_hours: config.outside_hours,
+            dim_percent: config.dim_percent,
+            poll_outside_active_hours: config.poll_outside_active_hours,
+        })
+    }
+
+    pub fn is_active_now(&self) -> bool {
+        let now = match self.utc_offset {
+            Some(offset) => Utc::now().with_timezone(&offset).naive_local(),
+            None => Local::now().naive_local(),
+        };
+        self.is_active_at(now)
+    }
+
+    fn is_active_at(&self, now: NaiveDateTime) -> bool {
+        if self.weekdays_only {
+            match now.weekday() {
+                Weekday::Sat | Weekday::Sun => return false,
+                _ => {}
+            }
+        }
+        self.hours.contains(now.time())
+    }
+}
+
+// A daily span of time like "08:00-19:00", which may wrap past midnight.
+#[derive(Clone)]
+pub struct HourRange {
+    start: NaiveTime,
+    end: NaiveTime,
+}
+
+impl HourRange {
+    // Parses "HH:MM-HH:MM".
+    pub fn parse(hours: &str) -> Result<HourRange, Error> {
+        let parts: Vec<&str> = hours.split('-').map(|part| part.trim()).collect();
+        if parts.len() != 2 {
+            return Err(format_err!(
+                "Hours should look like \"08:00-19:00\", but were \"{}\"",
+                hours
+            ));
+        }
+        let start = NaiveTime::parse_from_str(parts[0], "%H:%M")?;
+        let end = NaiveTime::parse_from_str(parts[1], "%H:%M")?;
+        Ok(HourRange {
+            start: start,
+            end: end,
+        })
+    }
+
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            time >= self.start && time < self.end
+        } else {
+            // Wraps past midnight, e.g. "22:00-06:00"
+            time >= self.start || time < self.end
+        }
+    }
+
+    pub fn contains_now(&self) -> bool {
+        self.contains(Local::now().time())
+    }
+}
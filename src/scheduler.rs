@@ -0,0 +1,143 @@
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// What woke a ticker up.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Tick {
+    Elapsed,
+    Reload,
+    Shutdown,
+}
+
+#[derive(Copy, Clone, Debug)]
+enum Message {
+    Reload,
+    Shutdown,
+}
+
+struct SchedulerState {
+    // Named after the integration polling with it, if any, see wake
+    tickers: Vec<(Option<String>, Sender<Message>)>,
+    is_shut_down: bool,
+}
+
+// Hands out a ticker to every worker thread, and wakes them all up at once on
+// shutdown or config reload. Workers block on their ticker instead of calling
+// thread::sleep, so neither has to wait for the current sleep to run out.
+#[derive(Clone)]
+pub struct TickScheduler {
+    state: Arc<Mutex<SchedulerState>>,
+}
+
+impl TickScheduler {
+    pub fn new() -> TickScheduler {
+        TickScheduler {
+            state: Arc::new(Mutex::new(SchedulerState {
+                tickers: Vec::new(),
+                is_shut_down: false,
+            })),
+        }
+    }
+
+    pub fn ticker(&self) -> Ticker {
+        self.add_ticker(None)
+    }
+
+    // A ticker that wake can reach on its own, for an integration's poll thread.
+    pub fn named_ticker(&self, name: &str) -> Ticker {
+        self.add_ticker(Some(name.to_string()))
+    }
+
+    fn add_ticker(&self, name: Option<String>) -> Ticker {
+        let (sender, receiver) = mpsc::channel();
+        match self.state.lock() {
+            Ok(mut state) => {
+                if state.is_shut_down {
+                    let _ = sender.send(Message::Shutdown);
+                }
+                state.tickers.push((name, sender));
+            }
+            Err(_) => {
+                let _ = sender.send(Message::Shutdown);
+            }
+        }
+        Ticker {
+            receiver,
+            pending_reload: false,
+            is_shut_down: false,
+        }
+    }
+
+    pub fn shutdown(&self) {
+        self.broadcast(Message::Shutdown);
+    }
+
+    // Wakes every worker up early, e.g. to poll right away once the network is back.
+    pub fn reload(&self) {
+        self.broadcast(Message::Reload);
+    }
+
+    // Wakes only the named integrations up, to poll right away, e.g. when a
+    // webhook says one of their builds changed.
+    pub fn wake(&self, names: &[String]) {
+        if let Ok(mut state) = self.state.lock() {
+            state.tickers.retain(|&(ref name, ref sender)| match *name {
+                Some(ref name) if names.contains(name) => sender.send(Message::Reload).is_ok(),
+                _ => true,
+            });
+        }
+    }
+
+    fn broadcast(&self, message: Message) {
+        if let Ok(mut state) = self.state.lock() {
+            if let Message::Shutdown = message {
+                state.is_shut_down = true;
+            }
+            // Tickers whose thread has ended just get dropped
+            state
+                .tickers
+                .retain(|&(_, ref sender)| sender.send(message).is_ok());
+        }
+    }
+}
+
+pub struct Ticker {
+    receiver: Receiver<Message>,
+    pending_reload: bool,
+    is_shut_down: bool,
+}
+
+impl Ticker {
+    // Blocks for up to `duration`, returning early on shutdown or reload.
+    pub fn wait(&mut self, duration: Duration) -> Tick {
+        if self.is_shut_down {
+            return Tick::Shutdown;
+        }
+        if self.pending_reload {
+            self.pending_reload = false;
+            return Tick::Reload;
+        }
+        match self.receiver.recv_timeout(duration) {
+            Ok(Message::Reload) => Tick::Reload,
+            Err(RecvTimeoutError::Timeout) => Tick::Elapsed,
+            Ok(Message::Shutdown) | Err(RecvTimeoutError::Disconnected) => {
+                self.is_shut_down = true;
+                Tick::Shutdown
+            }
+        }
+    }
+
+    // Checks for shutdown without blocking. Reloads seen along the way are
+    // kept for the next wait().
+    pub fn is_running(&mut self) -> bool {
+        while !self.is_shut_down {
+            match self.receiver.try_recv() {
+                Ok(Message::Reload) => self.pending_reload = true,
+                Err(TryRecvError::Empty) => break,
+                Ok(Message::Shutdown) | Err(TryRecvError::Disconnected) => self.is_shut_down = true,
+            }
+        }
+        !self.is_shut_down
+    }
+}
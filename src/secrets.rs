@@ -0,0 +1,143 @@
+use base64;
+use errors::RunError;
+use failure::Error;
+use openssl::rand::rand_bytes;
+use openssl::symm::{decrypt_aead, encrypt_aead, Cipher};
+use std::env;
+use std::io::{self, Read};
+
+// Base64 of the 32 byte key, e.g. from "openssl rand -base64 32".
+pub const KEY_ENV_VAR: &str = "BUILD_LIGHT_CONFIG_KEY";
+
+// Marks a config value as encrypted. The rest is base64 of the nonce, the
+// ciphertext and the tag, in that order.
+const PREFIX: &str = "enc:v1:";
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+
+pub const USAGE: &str = "Usage: rusty_build_light encrypt
+
+Reads a password or token from standard input, and writes it out encrypted with
+the key in BUILD_LIGHT_CONFIG_KEY, ready to paste into config.toml. Create a key
+with \"openssl rand -base64 32\".";
+
+// The key from the environment, None if it isn't set.
+pub fn key_from_env() -> Result<Option<Vec<u8>>, Error> {
+    let encoded = match env::var(KEY_ENV_VAR) {
+        Ok(encoded) => encoded,
+        Err(_) => return Ok(None),
+    };
+    let key = base64::decode(encoded.trim())
+        .map_err(|err| format_err!("{} isn't base64: {}", KEY_ENV_VAR, err))?;
+    if key.len() != 32 {
+        return Err(format_err!(
+            "{} is {} bytes, it has to be 32",
+            KEY_ENV_VAR,
+            key.len()
+        ));
+    }
+    Ok(Some(key))
+}
+
+pub fn is_encrypted(value: &str) -> bool {
+    value.starts_with(PREFIX)
+}
+
+// AES-256-GCM with a random nonce, so the same value never encrypts the same.
+pub fn encrypt(key: &[u8], plaintext: &str) -> Result<String, Error> {
+    let mut nonce = [0; NONCE_LEN];
+    rand_bytes(&mut nonce).map_err(|err| format_err!("Couldn't generate a nonce: {}", err))?;
+    let mut tag = [0; TAG_LEN];
+    let ciphertext = encrypt_aead(
+        Cipher::aes_256_gcm(),
+        key,
+        Some(&nonce),
+        &[],
+        plaintext.as_bytes(),
+        &mut tag,
+    )?;
+    let mut sealed = nonce.to_vec();
+    sealed.extend(ciphertext);
+    sealed.extend(tag.iter());
+    Ok(format!("{}{}", PREFIX, base64::encode(&sealed)))
+}
+
+pub fn decrypt(key: &[u8], value: &str) -> Result<String, Error> {
+    let sealed = base64::decode(value.trim_start_matches(PREFIX))
+        .map_err(|err| format_err!("Not base64: {}", err))?;
+    if sealed.len() < NONCE_LEN + TAG_LEN {
+        return Err(format_err!("Too short to be encrypted"));
+    }
+    let (nonce, rest) = sealed.split_at(NONCE_LEN);
+    let (ciphertext, tag) = rest.split_at(rest.len() - TAG_LEN);
+    // A wrong key fails the tag check, rather than decrypting to garbage
+    let plaintext = decrypt_aead(
+        Cipher::aes_256_gcm(),
+        key,
+        Some(nonce),
+        &[],
+        ciphertext,
+        tag,
+    )
+    .map_err(|_| format_err!("Wrong key, or the value has been changed"))?;
+    String::from_utf8(plaintext).map_err(|_| format_err!("Didn't decrypt to text"))
+}
+
+// Decrypts one config value in place, if it's encrypted.
+pub fn decrypt_value(key: Option<&[u8]>, name: &str, value: &mut String) -> Result<(), Error> {
+    if !is_encrypted(value) {
+        return Ok(());
+    }
+    let key =
+        key.ok_or_else(|| format_err!("{} is encrypted, but {} isn't set", name, KEY_ENV_VAR))?;
+    *value =
+        decrypt(key, value).map_err(|err| format_err!("Couldn't decrypt {}: {}", name, err))?;
+    Ok(())
+}
+
+pub fn run_encrypt(args: &[String]) -> Result<(), RunError> {
+    let invalid = |reason: String| RunError::InvalidArguments { reason: reason };
+    if !args.is_empty() {
+        return Err(invalid(USAGE.to_string()));
+    }
+    let key = key_from_env()
+        .map_err(|err| invalid(err.to_string()))?
+        .ok_or_else(|| invalid(format!("{} isn't set.\n\n{}", KEY_ENV_VAR, USAGE)))?;
+    let mut plaintext = String::new();
+    io::stdin()
+        .read_to_string(&mut plaintext)
+        .map_err(|err| invalid(format!("Couldn't read standard input: {}", err)))?;
+    // The newline echo or a here-string adds isn't part of the secret
+    let encrypted =
+        encrypt(&key, plaintext.trim_end_matches(|c| c == '\n' || c == '\r')).map_err(|err| {
+            RunError::StartupFailed {
+                reason: format!("Encrypting failed: {}", err),
+            }
+        })?;
+    println!("{}", encrypted);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_decrypt_with_the_right_key_only() {
+        let key = [7; 32];
+        let encrypted = encrypt(&key, "jenkins-admin-password").unwrap();
+        assert!(is_encrypted(&encrypted));
+        assert!(!encrypted.contains("jenkins-admin-password"));
+        assert_ne!(encrypted, encrypt(&key, "jenkins-admin-password").unwrap());
+
+        let mut value = encrypted.clone();
+        decrypt_value(Some(&key), "jenkins_password", &mut value).unwrap();
+        assert_eq!(value, "jenkins-admin-password");
+        assert!(decrypt(&[8; 32], &encrypted).is_err());
+        assert!(decrypt_value(None, "jenkins_password", &mut encrypted.clone()).is_err());
+
+        let mut plain = "not-encrypted".to_string();
+        decrypt_value(None, "unity_cloud_api_token", &mut plain).unwrap();
+        assert_eq!(plain, "not-encrypted");
+    }
+}
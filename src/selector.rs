@@ -0,0 +1,120 @@
+use calendar::Freeze;
+use config_file::{ColorScheme, PatternScheme, SelectorConfig};
+use connectivity::Connectivity;
+use controls::Controls;
+use led_controller::LedController;
+use pin::{Button, PatternTimings, RgbLedLight, RotaryEncoder};
+use remote_status::RemoteStatus;
+use scheduler::TickScheduler;
+use status_board::StatusBoard;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const INPUT_TIMEOUT_MILLIS: u64 = 500;
+const IDENTITY_FLASH_MILLIS: u64 = 200;
+
+enum SelectorInput {
+    Button(Button),
+    Encoder(RotaryEncoder),
+}
+
+impl SelectorInput {
+    // How far to move the selection, if the user did anything.
+    fn wait_for_step(&mut self) -> Option<i32> {
+        let timeout = Duration::from_millis(INPUT_TIMEOUT_MILLIS);
+        match *self {
+            SelectorInput::Button(ref mut button) => button.wait_for_press(timeout).map(|_| 1),
+            SelectorInput::Encoder(ref mut encoder) => encoder.wait_for_turn(timeout),
+        }
+    }
+}
+
+// Drives a single RGB LED that shows one integration at a time. The user
+// steps through the integrations with a button or rotary encoder, or the
+// selector cycles through them every cycle_seconds, and each switch is
+// announced by flashing the LED white once per position (1 flash for the
+// first integration, 2 for the second, and so on).
+pub fn run_selector(
+    config: SelectorConfig,
+    board: StatusBoard,
+    patterns: PatternScheme,
+    timings: PatternTimings,
+    controls: Arc<Controls>,
+    connectivity: Connectivity,
+    freeze: Freeze,
+    scheduler: TickScheduler,
+) {
+    let mut input = match (config.button_pin, config.encoder_pins) {
+        (_, Some(ref pins)) if pins.len() == 2 => {
+            Some(SelectorInput::Encoder(RotaryEncoder::new(pins[0], pins[1])))
+        }
+        (Some(pin), None) => Some(SelectorInput::Button(Button::new(pin, 1000))),
+        (None, None) if config.cycle_seconds.is_some() => None,
+        _ => {
+            error!("--Selector--: Needs either a button_pin, exactly two encoder_pins, or cycle_seconds. Selector disabled.");
+            return;
+        }
+    };
+    let cycle = config
+        .cycle_seconds
+        .map(|seconds| Duration::from_secs(seconds.max(1)));
+    let mut led = RgbLedLight::new(config.led_pins[0], config.led_pins[1], config.led_pins[2]);
+    led.set_output_type(config.output_type);
+    let mut led_controller =
+        LedController::new(led, ColorScheme::default(), patterns, timings, controls);
+    led_controller.set_connectivity(connectivity);
+    led_controller.set_freeze(freeze);
+
+    let mut selected: usize = 0;
+    let mut shown: Option<(usize, RemoteStatus, bool)> = None;
+    let mut switched_at = Instant::now();
+    let mut ticker = scheduler.ticker();
+    while ticker.is_running() {
+        let snapshots = board.snapshots();
+        let mut step = match input {
+            Some(ref mut input) => input.wait_for_step(),
+            None => {
+                thread::sleep(Duration::from_millis(INPUT_TIMEOUT_MILLIS));
+                None
+            }
+        };
+        // With a single integration there's nothing to cycle to
+        if cycle.map_or(false, |cycle| switched_at.elapsed() >= cycle) && snapshots.len() > 1 {
+            step = step.or(Some(1));
+        }
+        if let Some(step) = step {
+            if !snapshots.is_empty() {
+                let count = snapshots.len() as i32;
+                selected = (((selected as i32 + step) % count + count) % count) as usize;
+                info!("--Selector--: Now showing {}.", snapshots[selected].name);
+                flash_identity(led_controller.led(), selected);
+                led_controller.forget_previous_status();
+                shown = None;
+                switched_at = Instant::now();
+            }
+        }
+
+        if let Some(snapshot) = snapshots.get(selected) {
+            // Only re-show on changes, so running animations aren't restarted
+            let offline = led_controller.is_offline();
+            if shown != Some((selected, snapshot.status, offline)) {
+                led_controller.show_status(snapshot.status, &snapshot.summary);
+                shown = Some((selected, snapshot.status, offline));
+            }
+        }
+    }
+    led_controller.shut_down();
+}
+
+fn flash_identity(led: &mut RgbLedLight, position: usize) {
+    led.turn_led_off();
+    thread::sleep(Duration::from_millis(IDENTITY_FLASH_MILLIS * 2));
+    for _ in 0..(position + 1) {
+        led.set_led_rgb_values(RgbLedLight::WHITE);
+        thread::sleep(Duration::from_millis(IDENTITY_FLASH_MILLIS));
+        led.turn_led_off();
+        thread::sleep(Duration::from_millis(IDENTITY_FLASH_MILLIS));
+    }
+    thread::sleep(Duration::from_millis(IDENTITY_FLASH_MILLIS * 2));
+}
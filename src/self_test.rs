@@ -0,0 +1,250 @@
+use app;
+use config_file::Config;
+use errors::RunError;
+use gpio::{self, GpioBackend};
+use pin::{OutputType, RgbLedLight};
+use std::io::{self, Write};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+const STEP_MILLIS: u64 = 1000;
+
+// An LED from config.toml, by the setting its pins are under.
+struct TestedLed {
+    name: String,
+    pins: Vec<u16>,
+    output_type: OutputType,
+}
+
+// Lights every configured LED in turn, one color at a time, then sounds the
+// buzzer and switches the beacon, printing which pin is driven at each step.
+// For checking the wiring of a new build light without any builds to show.
+pub fn run_self_test() -> Result<(), RunError> {
+    let mut config = app::read_config(&app::exe_dir()?)?;
+    config
+        .resolve_pins()
+        .map_err(|err| RunError::ConfigInvalid {
+            reason: format!("Invalid pins: {}", err),
+        })?;
+    gpio::check_access().map_err(|err| RunError::GpioInitFailed {
+        reason: err.to_string(),
+    })?;
+
+    let gpio = gpio::wiring_pi();
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let step = Duration::from_millis(STEP_MILLIS);
+    let failed = |err: io::Error| RunError::StartupFailed {
+        reason: format!("Self-test failed: {}", err),
+    };
+    for led in configured_leds(&config) {
+        test_led(&gpio, &led, step, &mut out).map_err(&failed)?;
+    }
+    if let Some(pin) = config.escalation.as_ref().and_then(|e| e.buzzer_pin) {
+        writeln!(out, "[escalation] buzzer_pin: beeping GPIO {}", pin).map_err(&failed)?;
+        pulse(&gpio, pin, true, step);
+    }
+    if let Some(ref beacon) = config.beacon {
+        writeln!(out, "[beacon] pin: switching GPIO {} on", beacon.pin).map_err(&failed)?;
+        pulse(&gpio, beacon.pin, !beacon.active_low, step * 3);
+    }
+    writeln!(out, "Self-test done.").map_err(&failed)
+}
+
+fn configured_leds(config: &Config) -> Vec<TestedLed> {
+    let led = |name: &str, pins: &[u16], output_type: OutputType| TestedLed {
+        name: name.to_string(),
+        pins: pins.to_vec(),
+        output_type: output_type,
+    };
+    let mut leds = vec![led(
+        "jenkins_led_pins",
+        &config.jenkins_led_pins,
+        config.jenkins_output_type,
+    )];
+    if let Some(ref pins) = config.jenkins_pr_led_pins {
+        leds.push(led("jenkins_pr_led_pins", pins, config.jenkins_output_type));
+    }
+    for instance in &config.jenkins_instances {
+        leds.push(led(
+            &format!("[[jenkins_instances]] \"{}\"", instance.name),
+            &instance.led_pins,
+            instance.output_type,
+        ));
+    }
+    for group in &config.jenkins_job_groups {
+        leds.push(led(
+            &format!("[[jenkins_job_groups]] \"{}\"", group.name),
+            &group.led_pins,
+            group.output_type,
+        ));
+    }
+    leds.push(led(
+        "unity_led_pins",
+        &config.unity_led_pins,
+        config.unity_output_type,
+    ));
+    for instance in &config.unity_instances {
+        leds.push(led(
+            &format!("[[unity_instances]] \"{}\"", instance.name),
+            &instance.led_pins,
+            instance.output_type,
+        ));
+    }
+    if config.team_city_enabled() {
+        leds.push(led(
+            "team_city_led_pins",
+            &config.team_city_led_pins,
+            config.team_city_output_type,
+        ));
+    }
+    for github in &config.github {
+        leds.push(led(
+            &format!("[[github]] \"{}\"", github.repository),
+            &github.led_pins,
+            github.output_type,
+        ));
+    }
+    for bitbucket in &config.bitbucket {
+        leds.push(led(
+            &format!("[[bitbucket]] \"{}\"", bitbucket.repository),
+            &bitbucket.led_pins,
+            bitbucket.output_type,
+        ));
+    }
+    for sonarqube in &config.sonarqube {
+        leds.push(led(
+            &format!("[[sonarqube]] \"{}\"", sonarqube.base_url),
+            &sonarqube.led_pins,
+            sonarqube.output_type,
+        ));
+    }
+    for grafana in &config.grafana {
+        leds.push(led(
+            &format!("[[grafana]] \"{}\"", grafana.base_url),
+            &grafana.led_pins,
+            grafana.output_type,
+        ));
+    }
+    for icinga in &config.icinga {
+        leds.push(led(
+            &format!("[[icinga]] \"{}\"", icinga.base_url),
+            &icinga.led_pins,
+            icinga.output_type,
+        ));
+    }
+    for kubernetes in &config.kubernetes {
+        leds.push(led(
+            &format!("[[kubernetes]] \"{}\"", kubernetes.api_url),
+            &kubernetes.led_pins,
+            kubernetes.output_type,
+        ));
+    }
+    for sentry in &config.sentry {
+        leds.push(led(
+            &format!("[[sentry]] \"{}\"", sentry.project),
+            &sentry.led_pins,
+            sentry.output_type,
+        ));
+    }
+    for jira in &config.jira {
+        leds.push(led(
+            &format!("[[jira]] \"{}\"", jira.base_url),
+            &jira.led_pins,
+            jira.output_type,
+        ));
+    }
+    for plugin in &config.plugins {
+        leds.push(led(
+            &format!("[[plugins]] \"{}\"", plugin.name),
+            &plugin.led_pins,
+            plugin.output_type,
+        ));
+    }
+    for exec in &config.exec {
+        leds.push(led(
+            &format!("[[exec]] \"{}\"", exec.name),
+            &exec.led_pins,
+            exec.output_type,
+        ));
+    }
+    if let Some(ref aggregate) = config.aggregate {
+        leds.push(led(
+            "[aggregate]",
+            &aggregate.led_pins,
+            aggregate.output_type,
+        ));
+    }
+    if let Some(ref selector) = config.selector {
+        leds.push(led("[selector]", &selector.led_pins, selector.output_type));
+    }
+    leds
+}
+
+// One step per die of an RGB LED or lamp of a traffic light, then all at once.
+fn test_led<W: Write>(
+    gpio: &Arc<GpioBackend>,
+    led: &TestedLed,
+    step: Duration,
+    out: &mut W,
+) -> Result<(), io::Error> {
+    let names = match led.output_type {
+        OutputType::Rgb => ["red", "green", "blue", "white"],
+        OutputType::TrafficLight => ["red lamp", "yellow lamp", "green lamp", "all lamps"],
+    };
+    let mut light = RgbLedLight::with_gpio(Arc::clone(gpio), led.pins[0], led.pins[1], led.pins[2]);
+    light.set_output_type(led.output_type);
+    let colors = [(100, 0, 0), (0, 100, 0), (0, 0, 100), (100, 100, 100)];
+    for (i, (name, color)) in names.iter().zip(colors.iter()).enumerate() {
+        match led.pins.get(i) {
+            Some(pin) => writeln!(out, "{}: {} on GPIO {}", led.name, name, pin)?,
+            None => writeln!(out, "{}: {}", led.name, name)?,
+        }
+        light.set_led_rgb_values(*color);
+        thread::sleep(step);
+    }
+    light.turn_led_off();
+    Ok(())
+}
+
+fn pulse(gpio: &Arc<GpioBackend>, pin: u16, on: bool, duration: Duration) {
+    gpio.digital_write(pin, on);
+    thread::sleep(duration);
+    gpio.digital_write(pin, !on);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gpio::FakeGpio;
+
+    #[test]
+    fn each_pin_is_named_as_it_lights() {
+        let fake = FakeGpio::new();
+        let gpio: Arc<GpioBackend> = fake.clone();
+        let led = TestedLed {
+            name: "jenkins_led_pins".to_string(),
+            pins: vec![17, 27, 22],
+            output_type: OutputType::Rgb,
+        };
+        let mut out = Vec::new();
+        test_led(&gpio, &led, Duration::from_millis(0), &mut out).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "jenkins_led_pins: red on GPIO 17\n\
+             jenkins_led_pins: green on GPIO 27\n\
+             jenkins_led_pins: blue on GPIO 22\n\
+             jenkins_led_pins: white\n"
+        );
+        assert_eq!(
+            &fake.pwm_writes.lock().unwrap()[..3],
+            &[(17, 100), (27, 0), (22, 0)]
+        );
+        assert_eq!(
+            fake.pwm_values(&[17, 27, 22]),
+            vec![Some(0), Some(0), Some(0)]
+        );
+    }
+}
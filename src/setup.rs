@@ -0,0 +1,466 @@
+use app;
+use errors::RunError;
+use failure::Error;
+use gpio::{self, GpioBackend, PinNumbering};
+use headers::{Headers, AUTHORIZATION};
+use network;
+use pin::RgbLedLight;
+use reqwest::{Client, StatusCode};
+use secrets;
+use std::fs::File;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use toml::Value;
+
+pub const USAGE: &str = "Usage: rusty_build_light setup
+
+Asks for the Jenkins and Unity Cloud Build servers, their credentials and the
+pins the LEDs are wired to, checking each as it goes, and writes config.toml
+next to the executable. Everything else in the example config.toml can be
+added to it afterwards.";
+
+// How long each color is lit before asking whether it lit up.
+const FLASH_MILLIS: u64 = 1500;
+
+// What the wizard asked for, enough for a config.toml the build light starts with.
+#[derive(Debug, Default, PartialEq)]
+pub struct SetupAnswers {
+    pub jenkins_base_url: String,
+    pub jenkins_username: String,
+    pub jenkins_password: String,
+    pub jenkins_password_is_api_token: bool,
+    pub jenkins_led_pins: Vec<u16>,
+    pub unity_base_url: String,
+    pub unity_cloud_api_token: String,
+    pub unity_led_pins: Vec<u16>,
+}
+
+// Questions on out, answers from input, one per line.
+struct Prompter<R: BufRead, W: Write> {
+    input: R,
+    out: W,
+}
+
+impl<R: BufRead, W: Write> Prompter<R, W> {
+    fn say(&mut self, text: &str) -> io::Result<()> {
+        writeln!(self.out, "{}", text)
+    }
+
+    // An empty answer takes the default, if there is one.
+    fn ask(&mut self, question: &str, default: Option<&str>) -> io::Result<String> {
+        match default {
+            Some(default) if !default.is_empty() => {
+                write!(self.out, "{} [{}]: ", question, default)?
+            }
+            _ => write!(self.out, "{}: ", question)?,
+        }
+        self.out.flush()?;
+        let mut line = String::new();
+        if self.input.read_line(&mut line)? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "Setup was cut short",
+            ));
+        }
+        let answer = line.trim();
+        Ok(match default {
+            Some(default) if answer.is_empty() => default.to_string(),
+            _ => answer.to_string(),
+        })
+    }
+
+    fn confirm(&mut self, question: &str, default: bool) -> io::Result<bool> {
+        let hint = if default { "Y/n" } else { "y/N" };
+        loop {
+            let answer = self.ask(&format!("{} ({})", question, hint), None)?;
+            match answer.to_lowercase().as_str() {
+                "" => return Ok(default),
+                "y" | "yes" => return Ok(true),
+                "n" | "no" => return Ok(false),
+                _ => self.say("Please answer y or n.")?,
+            }
+        }
+    }
+}
+
+pub fn run_setup(args: &[String]) -> Result<(), RunError> {
+    if !args.is_empty() {
+        return Err(RunError::InvalidArguments {
+            reason: USAGE.to_string(),
+        });
+    }
+    let config_path = app::exe_dir()?.join("config.toml");
+    // Flashing the LEDs is skipped rather than failing the whole setup
+    let gpio = match gpio::check_access() {
+        Ok(()) => Some(gpio::wiring_pi()),
+        Err(err) => {
+            println!(
+                "Can't access the GPIO pins ({}), so the LEDs won't be flashed to check the wiring.",
+                err
+            );
+            None
+        }
+    };
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let mut prompter = Prompter {
+        input: stdin.lock(),
+        out: stdout.lock(),
+    };
+    let failed = |err: io::Error| RunError::StartupFailed {
+        reason: format!("Setup failed: {}", err),
+    };
+    let client = network::build_default_client();
+    let answers = ask_answers(&mut prompter, &client, gpio.as_ref()).map_err(&failed)?;
+    write_config(&mut prompter, &config_path, &answers).map_err(&failed)
+}
+
+fn ask_answers<R: BufRead, W: Write>(
+    prompter: &mut Prompter<R, W>,
+    client: &Client,
+    gpio: Option<&Arc<GpioBackend>>,
+) -> io::Result<SetupAnswers> {
+    let mut answers = SetupAnswers::default();
+    let flash = Duration::from_millis(FLASH_MILLIS);
+    prompter.say("--- Jenkins ---")?;
+    answers.jenkins_base_url = ask_url(prompter, "Jenkins URL, e.g. https://ci.example.com")?;
+    if !answers.jenkins_base_url.is_empty() {
+        loop {
+            answers.jenkins_username = prompter.ask(
+                "Username (leave empty if anyone can read this Jenkins)",
+                None,
+            )?;
+            if !answers.jenkins_username.is_empty() {
+                answers.jenkins_password_is_api_token =
+                    prompter.confirm("Use an API token instead of the password?", true)?;
+                let secret = if answers.jenkins_password_is_api_token {
+                    "API token (from your Configure page in Jenkins)"
+                } else {
+                    "Password"
+                };
+                answers.jenkins_password = prompter.ask(secret, None)?;
+            } else {
+                answers.jenkins_password_is_api_token = false;
+                answers.jenkins_password = String::new();
+            }
+            prompter.say("Checking...")?;
+            let checked = check_jenkins(
+                client,
+                &answers.jenkins_base_url,
+                &answers.jenkins_username,
+                &answers.jenkins_password,
+            );
+            if !retry_check(prompter, checked)? {
+                break;
+            }
+        }
+    }
+    answers.jenkins_led_pins = ask_pins(prompter, gpio, "Jenkins", &[17, 27, 22], flash)?;
+
+    prompter.say("--- Unity Cloud Build ---")?;
+    answers.unity_base_url = ask_url(
+        prompter,
+        "Build targets URL, e.g. https://build-api.cloud.unity3d.com/api/v1/orgs/ORG/projects/PROJECT/buildtargets",
+    )?;
+    if !answers.unity_base_url.is_empty() {
+        loop {
+            answers.unity_cloud_api_token = prompter.ask("API key", None)?;
+            prompter.say("Checking...")?;
+            let checked = check_unity(
+                client,
+                &answers.unity_base_url,
+                &answers.unity_cloud_api_token,
+            );
+            if !retry_check(prompter, checked)? {
+                break;
+            }
+        }
+    }
+    answers.unity_led_pins = ask_pins(prompter, gpio, "Unity", &[5, 6, 13], flash)?;
+    Ok(answers)
+}
+
+// Without a trailing slash, as the config wants it. Empty skips the server.
+fn ask_url<R: BufRead, W: Write>(
+    prompter: &mut Prompter<R, W>,
+    question: &str,
+) -> io::Result<String> {
+    let question = format!("{} (empty if you don't use it)", question);
+    Ok(prompter
+        .ask(&question, None)?
+        .trim_end_matches('/')
+        .to_string())
+}
+
+// Whether to ask for the credentials again after a check. They can be kept
+// even when the check failed, e.g. when the server is down right now.
+fn retry_check<R: BufRead, W: Write>(
+    prompter: &mut Prompter<R, W>,
+    checked: Result<(), Error>,
+) -> io::Result<bool> {
+    match checked {
+        Ok(()) => {
+            prompter.say("OK, the server accepted them.")?;
+            Ok(false)
+        }
+        Err(err) => {
+            prompter.say(&format!("That didn't work: {}", err))?;
+            prompter.confirm("Enter them again?", true)
+        }
+    }
+}
+
+fn check_jenkins(
+    client: &Client,
+    base_url: &str,
+    username: &str,
+    password: &str,
+) -> Result<(), Error> {
+    let mut headers = Headers::new();
+    if !username.is_empty() {
+        headers.set(
+            AUTHORIZATION,
+            network::get_basic_credentials(username, Some(password.to_string())),
+        );
+    }
+    check_response(client, &format!("{}/api/json", base_url), headers)
+}
+
+fn check_unity(client: &Client, base_url: &str, api_token: &str) -> Result<(), Error> {
+    let mut headers = Headers::new();
+    headers.set(
+        AUTHORIZATION,
+        network::get_basic_credentials(api_token, None),
+    );
+    check_response(client, base_url, headers)
+}
+
+fn check_response(client: &Client, url: &str, headers: Headers) -> Result<(), Error> {
+    let response = network::send_with_headers(client, url, &headers)?;
+    match response.status() {
+        StatusCode::Ok => Ok(()),
+        StatusCode::Unauthorized | StatusCode::Forbidden => Err(format_err!(
+            "{} turned down the credentials ({})",
+            url,
+            response.status()
+        )),
+        status => Err(format_err!("{} answered with code: {}", url, status)),
+    }
+}
+
+// Asks for an LED's R, G, B pins, and if the pins can be driven, lights each
+// color in turn until the answers say they're wired up right.
+fn ask_pins<R: BufRead, W: Write>(
+    prompter: &mut Prompter<R, W>,
+    gpio: Option<&Arc<GpioBackend>>,
+    name: &str,
+    defaults: &[u16],
+    flash: Duration,
+) -> io::Result<Vec<u16>> {
+    let defaults = defaults
+        .iter()
+        .map(|pin| pin.to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    loop {
+        let answer = prompter.ask(
+            &format!("GPIO (BCM) pins of the {} LED, as R, G, B", name),
+            Some(&defaults),
+        )?;
+        let pins = match parse_pins(&answer) {
+            Ok(pins) => pins,
+            Err(err) => {
+                prompter.say(&err.to_string())?;
+                continue;
+            }
+        };
+        let gpio = match gpio {
+            Some(gpio) => gpio,
+            None => return Ok(pins),
+        };
+        if flash_led(prompter, gpio, name, &pins, flash)? {
+            return Ok(pins);
+        }
+        prompter.say("Check the wiring, or give the pins it's actually on.")?;
+    }
+}
+
+fn parse_pins(answer: &str) -> Result<Vec<u16>, Error> {
+    let pins = answer
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|pin| !pin.is_empty())
+        .map(|pin| {
+            pin.parse::<u16>()
+                .ok()
+                .and_then(|pin| PinNumbering::Bcm.to_bcm(pin))
+                .ok_or_else(|| format_err!("\"{}\" isn't a GPIO pin number (0 to 27)", pin))
+        })
+        .collect::<Result<Vec<u16>, Error>>()?;
+    if pins.len() != 3 {
+        return Err(format_err!("Give 3 pins, not {}", pins.len()));
+    }
+    Ok(pins)
+}
+
+// Whether every color lit up as asked.
+fn flash_led<R: BufRead, W: Write>(
+    prompter: &mut Prompter<R, W>,
+    gpio: &Arc<GpioBackend>,
+    name: &str,
+    pins: &[u16],
+    flash: Duration,
+) -> io::Result<bool> {
+    let mut light = RgbLedLight::with_gpio(Arc::clone(gpio), pins[0], pins[1], pins[2]);
+    let colors = [
+        ("red", (100, 0, 0)),
+        ("green", (0, 100, 0)),
+        ("blue", (0, 0, 100)),
+    ];
+    for (&(color, rgb), pin) in colors.iter().zip(pins) {
+        prompter.say(&format!("Lighting GPIO {}...", pin))?;
+        light.set_led_rgb_values(rgb);
+        thread::sleep(flash);
+        let lit = prompter.confirm(&format!("Did the {} LED light up {}?", name, color), true);
+        light.turn_led_off();
+        if !lit? {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+// Secrets are written encrypted when there's a key to encrypt them with.
+pub fn render_config(answers: &SetupAnswers, key: Option<&[u8]>) -> Result<String, Error> {
+    let secret = |value: &str| -> Result<String, Error> {
+        let value = match key {
+            Some(key) if !value.is_empty() => secrets::encrypt(key, value)?,
+            _ => value.to_string(),
+        };
+        Ok(Value::String(value).to_string())
+    };
+    let string = |value: &str| Value::String(value.to_string()).to_string();
+    let pins = |pins: &[u16]| {
+        let pins: Vec<String> = pins.iter().map(|pin| pin.to_string()).collect();
+        format!("[{}]", pins.join(", "))
+    };
+    // An empty jenkins_api_token would still be used instead of the password
+    let jenkins_password = if answers.jenkins_password_is_api_token {
+        format!(
+            "jenkins_password = \"\"\njenkins_api_token = {}",
+            secret(&answers.jenkins_password)?
+        )
+    } else {
+        format!("jenkins_password = {}", secret(&answers.jenkins_password)?)
+    };
+    Ok(format!(
+        "# Written by \"rusty_build_light setup\". See the example config.toml for everything else
+# that can be set.
+
+# --- JENKINS ---
+
+jenkins_username = {}
+{}
+jenkins_base_url = {}
+# Pin numbers are given in order as R, G, B
+jenkins_led_pins = {}
+
+# --- UNITY ---
+
+unity_cloud_api_token = {}
+unity_base_url = {}
+# Pin numbers are given in order as R, G, B
+unity_led_pins = {}
+",
+        secret(&answers.jenkins_username)?,
+        jenkins_password,
+        string(&answers.jenkins_base_url),
+        pins(&answers.jenkins_led_pins),
+        secret(&answers.unity_cloud_api_token)?,
+        string(&answers.unity_base_url),
+        pins(&answers.unity_led_pins),
+    ))
+}
+
+fn write_config<R: BufRead, W: Write>(
+    prompter: &mut Prompter<R, W>,
+    path: &Path,
+    answers: &SetupAnswers,
+) -> io::Result<()> {
+    let to_io = |err: Error| io::Error::new(io::ErrorKind::Other, err.to_string());
+    let key = secrets::key_from_env().map_err(&to_io)?;
+    if key.is_some() {
+        prompter.say(&format!(
+            "{} is set, so the credentials are written encrypted.",
+            secrets::KEY_ENV_VAR
+        ))?;
+    }
+    let text = render_config(answers, key.as_ref().map(|key| key.as_slice())).map_err(&to_io)?;
+    if path.exists()
+        && !prompter.confirm(
+            &format!("{} already exists. Replace it?", path.display()),
+            false,
+        )?
+    {
+        prompter.say("Nothing was written. The config would have been:")?;
+        return prompter.say(&text);
+    }
+    File::create(path).and_then(|mut file| file.write_all(text.as_bytes()))?;
+    prompter.say(&format!(
+        "Wrote {}. Start the build light, or try the wiring again with --self-test.",
+        path.display()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use config_file::Config;
+    use gpio::FakeGpio;
+    use toml;
+
+    #[test]
+    fn pins_are_asked_again_until_the_led_lights_up() {
+        let fake = FakeGpio::new();
+        let gpio: Arc<GpioBackend> = fake.clone();
+        let mut prompter = Prompter {
+            // The defaults, whose green doesn't light, then 17, 27 and 23
+            input: "\ny\nn\n17 27 99\n17,27,23\n\n\n\n".as_bytes(),
+            out: Vec::new(),
+        };
+        let pins = ask_pins(
+            &mut prompter,
+            Some(&gpio),
+            "Jenkins",
+            &[17, 27, 22],
+            Duration::from_millis(0),
+        )
+        .unwrap();
+        assert_eq!(pins, vec![17, 27, 23]);
+        let out = String::from_utf8(prompter.out).unwrap();
+        assert!(out.contains("\"99\" isn't a GPIO pin number"));
+        assert!(out.contains("Did the Jenkins LED light up blue?"));
+        assert_eq!(
+            fake.pwm_values(&[17, 27, 23]),
+            vec![Some(0), Some(0), Some(0)]
+        );
+
+        let answers = SetupAnswers {
+            jenkins_base_url: "https://ci.example.com".to_string(),
+            jenkins_username: "light".to_string(),
+            jenkins_password: "to\"ken".to_string(),
+            jenkins_password_is_api_token: true,
+            jenkins_led_pins: pins,
+            unity_base_url: String::new(),
+            unity_cloud_api_token: String::new(),
+            unity_led_pins: vec![5, 6, 13],
+        };
+        let config: Config = toml::from_str(&render_config(&answers, None).unwrap()).unwrap();
+        assert_eq!(config.jenkins_api_token, Some("to\"ken".to_string()));
+        assert_eq!(config.jenkins_password, "");
+        assert_eq!(config.jenkins_led_pins, vec![17, 27, 23]);
+        assert_eq!(config.unity_led_pins, vec![5, 6, 13]);
+    }
+}
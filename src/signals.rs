@@ -0,0 +1,170 @@
+use credentials::CredentialReloader;
+use health::{Health, ThreadState};
+use libc;
+use scheduler::{Tick, TickScheduler};
+use status_board::{IntegrationSnapshot, StatusBoard};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+const CHECK_MILLIS: u64 = 500;
+
+// Set from the signal handlers, which can't safely do much more than that.
+static STATUS_DUMP_REQUESTED: AtomicBool = AtomicBool::new(false);
+static RELOAD_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn request_status_dump(_: libc::c_int) {
+    STATUS_DUMP_REQUESTED.store(true, Ordering::SeqCst);
+}
+
+extern "C" fn request_reload(_: libc::c_int) {
+    RELOAD_REQUESTED.store(true, Ordering::SeqCst);
+}
+
+// Handles signals sent to the running build light, e.g. with
+// "kill -USR1 $(pidof rusty_build_light)", without having to restart it:
+// SIGUSR1 logs a snapshot of every integration, and SIGHUP reloads the
+// credentials from config.toml.
+pub fn run_signals(
+    board: StatusBoard,
+    health: Health,
+    reloader: CredentialReloader,
+    scheduler: TickScheduler,
+) {
+    unsafe {
+        libc::signal(
+            libc::SIGUSR1,
+            request_status_dump as extern "C" fn(libc::c_int) as libc::sighandler_t,
+        );
+        libc::signal(
+            libc::SIGHUP,
+            request_reload as extern "C" fn(libc::c_int) as libc::sighandler_t,
+        );
+    }
+    let mut ticker = scheduler.ticker();
+    while ticker.is_running() {
+        if STATUS_DUMP_REQUESTED.swap(false, Ordering::SeqCst) {
+            info!(
+                "--Status--: SIGUSR1 received.\n{}",
+                status_dump(&board.snapshots(), &health.thread_states())
+            );
+        }
+        if RELOAD_REQUESTED.swap(false, Ordering::SeqCst) {
+            info!("--Credentials--: SIGHUP received, reloading them.");
+            reloader.reload_logged(&scheduler);
+        }
+        if ticker.wait(Duration::from_millis(CHECK_MILLIS)) == Tick::Shutdown {
+            break;
+        }
+    }
+}
+
+// One paragraph per integration: its last poll and counts, what its LED is
+// showing, how often it was restarted, and its most recent poll errors.
+fn status_dump(snapshots: &[IntegrationSnapshot], threads: &[ThreadState]) -> String {
+    let mut names: Vec<&str> = snapshots
+        .iter()
+        .map(|snapshot| snapshot.name.as_str())
+        .chain(threads.iter().map(|thread| thread.name.as_str()))
+        .collect();
+    names.sort();
+    names.dedup();
+
+    let mut lines = Vec::new();
+    for name in names {
+        match snapshots.iter().find(|snapshot| snapshot.name == name) {
+            Some(snapshot) => lines.push(format!(
+                "{}: {:?} as of {}, {}",
+                name,
+                snapshot.status,
+                snapshot.last_poll.to_rfc3339(),
+                snapshot.summary.describe()
+            )),
+            None => lines.push(format!("{}: not polled yet", name)),
+        }
+        let thread = threads.iter().find(|thread| thread.name == name);
+        if let Some(led) = thread.and_then(|thread| thread.led.as_ref()) {
+            lines.push(format!("  LED: {}", led));
+        }
+        if let Some(success) = thread.and_then(|thread| thread.last_successful_poll) {
+            lines.push(format!("  Last successful poll: {}", success.to_rfc3339()));
+        }
+        if let Some(thread) = thread.filter(|thread| thread.restarts > 0) {
+            lines.push(format!(
+                "  Restarted {} times, last at {}",
+                thread.restarts,
+                thread
+                    .last_restart
+                    .map_or_else(|| "?".to_string(), |time| time.to_rfc3339())
+            ));
+        }
+        let errors = thread.map_or(&[][..], |thread| &thread.recent_errors[..]);
+        if errors.is_empty() {
+            lines.push("  No recent errors".to_string());
+        } else {
+            lines.push(format!("  Last {} errors:", errors.len()));
+            for &(ref time, ref error) in errors {
+                lines.push(format!("    {} {}", time.to_rfc3339(), error));
+            }
+        }
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Local, TimeZone};
+    use remote_status::{RemoteStatus, StatusSummary};
+
+    #[test]
+    fn dump_covers_every_integration() {
+        let time = Local.ymd(2018, 3, 1).and_hms(9, 30, 0);
+        let mut summary = StatusSummary::default();
+        summary.passing = 2;
+        summary.failing = 1;
+        summary.failing_jobs = vec!["api-tests".to_string()];
+        let snapshots = vec![IntegrationSnapshot {
+            name: "Jenkins".to_string(),
+            status: RemoteStatus::Failing,
+            summary: summary,
+            last_poll: time,
+        }];
+        let threads = vec![
+            ThreadState {
+                name: "Jenkins".to_string(),
+                last_successful_poll: Some(time),
+                led: Some("(100, 0, 0) Blink".to_string()),
+                recent_errors: Vec::new(),
+                restarts: 0,
+                last_restart: None,
+            },
+            ThreadState {
+                name: "Unity Cloud".to_string(),
+                last_successful_poll: None,
+                led: None,
+                recent_errors: vec![(time, "HTTP call failed with code: 503".to_string())],
+                restarts: 2,
+                last_restart: Some(time),
+            },
+        ];
+
+        let dump = status_dump(&snapshots, &threads);
+        let lines: Vec<&str> = dump.lines().collect();
+        assert_eq!(
+            lines[0],
+            format!(
+                "Jenkins: Failing as of {}, 2 passing, 1 failing, 0 in progress, 0 unknown (api-tests)",
+                time.to_rfc3339()
+            )
+        );
+        assert_eq!(lines[1], "  LED: (100, 0, 0) Blink");
+        assert_eq!(lines[3], "  No recent errors");
+        assert_eq!(lines[4], "Unity Cloud: not polled yet");
+        assert_eq!(
+            lines[5],
+            format!("  Restarted 2 times, last at {}", time.to_rfc3339())
+        );
+        assert_eq!(lines[6], "  Last 1 errors:");
+        assert!(lines[7].ends_with(" HTTP call failed with code: 503"));
+    }
+}
@@ -0,0 +1,76 @@
+use chrono::{DateTime, Local};
+use remote_status::{RemoteStatus, StatusSummary};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+#[derive(Clone)]
+pub struct IntegrationSnapshot {
+    pub name: String,
+    pub status: RemoteStatus,
+    pub summary: StatusSummary,
+    pub last_poll: DateTime<Local>,
+}
+
+pub type BoardSubscriber = Box<Fn(&[IntegrationSnapshot]) + Send>;
+
+// Latest poll result of every integration, shared between the worker threads
+// that write to it and the displays that read from it. Subscribers are called
+// on the updating worker thread right after each poll.
+#[derive(Clone)]
+pub struct StatusBoard {
+    snapshots: Arc<Mutex<BTreeMap<String, IntegrationSnapshot>>>,
+    subscribers: Arc<Mutex<Vec<BoardSubscriber>>>,
+}
+
+impl StatusBoard {
+    pub fn new() -> StatusBoard {
+        StatusBoard {
+            snapshots: Arc::new(Mutex::new(BTreeMap::new())),
+            subscribers: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    pub fn subscribe(&self, subscriber: BoardSubscriber) {
+        if let Ok(mut subscribers) = self.subscribers.lock() {
+            subscribers.push(subscriber);
+        } else {
+            error!("Failed to acquire a lock on the status board to add a subscriber.");
+        }
+    }
+
+    pub fn update(&self, name: &str, status: RemoteStatus, summary: StatusSummary) {
+        if let Ok(mut snapshots) = self.snapshots.lock() {
+            snapshots.insert(
+                name.to_string(),
+                IntegrationSnapshot {
+                    name: name.to_string(),
+                    status: status,
+                    summary: summary,
+                    last_poll: Local::now(),
+                },
+            );
+        } else {
+            error!(
+                "Failed to acquire a lock on the status board to update {}.",
+                name
+            );
+        }
+
+        let snapshots = self.snapshots();
+        if let Ok(subscribers) = self.subscribers.lock() {
+            for subscriber in subscribers.iter() {
+                subscriber(&snapshots);
+            }
+        }
+    }
+
+    pub fn snapshots(&self) -> Vec<IntegrationSnapshot> {
+        match self.snapshots.lock() {
+            Ok(snapshots) => snapshots.values().cloned().collect(),
+            Err(_) => {
+                error!("Failed to acquire a lock on the status board to read it.");
+                Vec::new()
+            }
+        }
+    }
+}
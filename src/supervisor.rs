@@ -0,0 +1,256 @@
+use backoff::Backoff;
+use config_file::RestartLimitConfig;
+use errors::RunError;
+use events;
+use failures::{Exceeded, FailureLimits};
+use health::Health;
+use redact;
+use scheduler::{Tick, TickScheduler};
+use std::any::Any;
+use std::cmp;
+use std::collections::VecDeque;
+use std::panic;
+use std::thread;
+use std::time::{Duration, Instant};
+use systemd;
+
+const RESTART_BACKOFF_MILLIS: u64 = 1000;
+const MAX_RESTART_BACKOFF_MILLIS: u64 = 1000 * 60 * 5;
+
+// Starts the integration threads and restarts them whenever they panic, within
+// the failure limits and [restart_limit]. Restarts are counted in Health, so
+// they show up on /healthz and in the status dump.
+pub struct Supervisor {
+    failures: FailureLimits,
+    restart_limit: Option<RestartLimitConfig>,
+    health: Health,
+    scheduler: TickScheduler,
+    // In the order they were started
+    workers: Vec<(String, thread::JoinHandle<Result<(), String>>)>,
+}
+
+impl Supervisor {
+    pub fn new(
+        failures: FailureLimits,
+        restart_limit: Option<RestartLimitConfig>,
+        health: Health,
+        scheduler: TickScheduler,
+    ) -> Supervisor {
+        Supervisor {
+            failures: failures,
+            restart_limit: restart_limit,
+            health: health,
+            scheduler: scheduler,
+            workers: Vec::new(),
+        }
+    }
+
+    // Runs func on a thread of its own, restarting it whenever it panics,
+    // until it returns or the scheduler shuts down.
+    pub fn spawn<F>(&mut self, name: &str, func: F)
+    where
+        F: Fn() + panic::UnwindSafe + panic::RefUnwindSafe + Send + 'static,
+    {
+        let worker = Worker {
+            name: name.to_string(),
+            failures: self.failures.clone(),
+            rate: RestartRate::new(self.restart_limit.clone()),
+            health: self.health.clone(),
+            scheduler: self.scheduler.clone(),
+        };
+        let handle = thread::spawn(move || worker.run(func));
+        self.workers.push((name.to_string(), handle));
+    }
+
+    // Waits for every worker to stop, the last one started first, turning
+    // the ones that gave up or panicked for good into errors.
+    pub fn join_all(self) -> Vec<Result<(), RunError>> {
+        self.workers
+            .into_iter()
+            .rev()
+            .map(|(name, handle)| {
+                let result = handle
+                    .join()
+                    .unwrap_or_else(|payload| Err(panic_message(&payload)));
+                debug!("--Supervisor--: {} stopped.", name);
+                result.map_err(|reason| RunError::ThreadFailed {
+                    thread: name,
+                    reason: reason,
+                })
+            })
+            .collect()
+    }
+}
+
+struct Worker {
+    name: String,
+    failures: FailureLimits,
+    rate: RestartRate,
+    health: Health,
+    scheduler: TickScheduler,
+}
+
+impl Worker {
+    fn run<F: Fn() + panic::UnwindSafe + panic::RefUnwindSafe>(
+        mut self,
+        func: F,
+    ) -> Result<(), String> {
+        let thread_name = self.name.clone();
+        let mut restart_ticker = self.scheduler.ticker();
+        let mut restart_backoff = Backoff::new(
+            Duration::from_millis(RESTART_BACKOFF_MILLIS),
+            Duration::from_millis(MAX_RESTART_BACKOFF_MILLIS),
+        );
+        loop {
+            let exceeded = self.failures.exceeded(&thread_name);
+            if let (Some(exceeded), Some(cooldown)) = (exceeded, self.failures.cooldown()) {
+                warn!(
+                    "Thread {} went over its failure limit ({:?}). Trying again in {} minutes...",
+                    thread_name,
+                    exceeded,
+                    cooldown.as_secs() / 60
+                );
+                // A reload cuts the wait short, in case the config was fixed
+                if restart_ticker.wait(cooldown) == Tick::Shutdown {
+                    return Err(format!(
+                        "Thread {} stopped while cooling down.",
+                        thread_name
+                    ));
+                }
+                info!("Thread {} cooled down, restarting it.", thread_name);
+                self.failures.reset(&thread_name, exceeded);
+                restart_backoff.reset();
+                continue;
+            }
+            match exceeded {
+                Some(Exceeded::Total(limit)) => {
+                    systemd::notify("STOPPING=1");
+                    self.scheduler.shutdown(); // Force a global stop
+                    return Err(format!(
+                        "The integrations crashed more than {} times between them, the last one {}, forcing stop.",
+                        limit, thread_name
+                    ));
+                }
+                // The other integrations keep going
+                Some(Exceeded::Thread(limit)) => {
+                    error!(
+                        "Thread {} crashed more than {} times, giving up on it.",
+                        thread_name, limit
+                    );
+                    return Err(format!(
+                        "Failure count for {} exceeded, stopped it.",
+                        thread_name
+                    ));
+                }
+                None => (),
+            }
+            match panic::catch_unwind(|| func()) {
+                Ok(()) => {
+                    info!("Thread {} terminated gracefully. Ending...", thread_name);
+                    return Ok(());
+                }
+                Err(payload) => {
+                    let held_off = self.rate.delay(Instant::now());
+                    if held_off > Duration::from_secs(0) {
+                        warn!(
+                            "--Supervisor--: {} restarted too often, holding off for {} seconds.",
+                            thread_name,
+                            held_off.as_secs()
+                        );
+                    }
+                    let restart_delay = cmp::max(restart_backoff.next_delay(), held_off);
+                    let panic_message = panic_message(&payload);
+                    events::log_restart(&thread_name, &panic_message, restart_delay);
+                    self.health.restarted(&thread_name);
+                    self.failures.record_failure(&thread_name);
+                    if restart_ticker.wait(restart_delay) == Tick::Shutdown {
+                        return Err(format!(
+                            "Thread {} stopped while waiting to restart.",
+                            thread_name
+                        ));
+                    }
+                    self.rate.record(Instant::now());
+                }
+            }
+        }
+    }
+}
+
+// A worker's restarts within the last [restart_limit] window.
+struct RestartRate {
+    limit: Option<RestartLimitConfig>,
+    recent: VecDeque<Instant>,
+}
+
+impl RestartRate {
+    fn new(limit: Option<RestartLimitConfig>) -> RestartRate {
+        RestartRate {
+            limit: limit,
+            recent: VecDeque::new(),
+        }
+    }
+
+    // How long to hold off a restart at `now`, for the oldest restart in a
+    // full window to drop out of it.
+    fn delay(&mut self, now: Instant) -> Duration {
+        let (max_restarts, window) = match self.limit {
+            Some(ref limit) => (
+                limit.max_restarts,
+                Duration::from_secs(limit.window_minutes * 60),
+            ),
+            None => return Duration::from_secs(0),
+        };
+        while self
+            .recent
+            .front()
+            .map_or(false, |restart| now.duration_since(*restart) >= window)
+        {
+            self.recent.pop_front();
+        }
+        match self.recent.front() {
+            Some(oldest) if self.recent.len() as u32 >= max_restarts => {
+                window - now.duration_since(*oldest)
+            }
+            _ => Duration::from_secs(0),
+        }
+    }
+
+    fn record(&mut self, restart: Instant) {
+        self.recent.push_back(restart);
+    }
+}
+
+pub fn panic_message(payload: &Box<Any + Send>) -> String {
+    let message = payload
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string());
+    redact::redact(&message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restarts_are_held_off_once_the_window_is_full() {
+        let minute = Duration::from_secs(60);
+        let start = Instant::now();
+        let mut rate = RestartRate::new(Some(RestartLimitConfig {
+            max_restarts: 2,
+            window_minutes: 10,
+        }));
+        assert_eq!(rate.delay(start), Duration::from_secs(0));
+        rate.record(start);
+        rate.record(start + minute);
+
+        assert_eq!(rate.delay(start + minute * 2), minute * 8);
+        // The first restart has dropped out of the window
+        assert_eq!(rate.delay(start + minute * 10), Duration::from_secs(0));
+
+        let mut unlimited = RestartRate::new(None);
+        unlimited.record(start);
+        assert_eq!(unlimited.delay(start), Duration::from_secs(0));
+    }
+}
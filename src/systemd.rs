@@ -0,0 +1,81 @@
+use status_board::StatusBoard;
+use std::collections::HashSet;
+use std::env;
+use std::os::unix::net::UnixDatagram;
+use std::sync::{Arc, Mutex};
+
+// Sends a state update to systemd, e.g. "WATCHDOG=1". Does nothing unless
+// we were started by systemd with NOTIFY_SOCKET set (Type=notify services).
+pub fn notify(state: &str) {
+    let socket_path = match env::var("NOTIFY_SOCKET") {
+        Ok(path) => path,
+        Err(_) => return,
+    };
+    if socket_path.starts_with('@') {
+        warn!("--systemd--: Abstract notify sockets aren't supported, not notifying.");
+        return;
+    }
+    let result =
+        UnixDatagram::unbound().and_then(|socket| socket.send_to(state.as_bytes(), &socket_path));
+    if let Err(e) = result {
+        warn!(
+            "--systemd--: Failed to notify systemd of {}. Error: {}",
+            state, e
+        );
+    }
+}
+
+// How often systemd wants to hear from us, if the service has WatchdogSec set.
+pub fn watchdog_interval_micros() -> Option<u64> {
+    env::var("WATCHDOG_USEC")
+        .ok()
+        .and_then(|usec| usec.parse().ok())
+}
+
+// Tells systemd we're up once every named integration thread has finished
+// its power-on test. Threads restarting later don't count again.
+#[derive(Clone)]
+pub struct Readiness {
+    pending: Arc<Mutex<HashSet<String>>>,
+}
+
+impl Readiness {
+    pub fn new(thread_names: &[&str]) -> Readiness {
+        Readiness {
+            pending: Arc::new(Mutex::new(
+                thread_names.iter().map(|name| name.to_string()).collect(),
+            )),
+        }
+    }
+
+    pub fn mark_ready(&self, thread_name: &str) {
+        if let Ok(mut pending) = self.pending.lock() {
+            if pending.remove(thread_name) && pending.is_empty() {
+                info!("--systemd--: All threads started, notifying systemd.");
+                notify("READY=1");
+            }
+        }
+    }
+}
+
+// Keeps `systemctl status` showing a one-line summary of the latest polls.
+pub fn start_status_updates(board: &StatusBoard) {
+    if env::var("NOTIFY_SOCKET").is_err() {
+        return;
+    }
+    board.subscribe(Box::new(|snapshots| {
+        let status: Vec<String> = snapshots
+            .iter()
+            .map(|snapshot| {
+                format!(
+                    "{}: {} ({} passing, {} failing)",
+                    snapshot.name,
+                    snapshot.status.label(),
+                    snapshot.summary.passing,
+                    snapshot.summary.failing
+                )
+            })
+            .collect();
+        notify(&format!("STATUS={}", status.join(" | ")));
+    }));
+}
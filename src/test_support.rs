@@ -0,0 +1,116 @@
+use config_file::HttpConfig;
+use network::{build_client, HttpClient};
+use std::env;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, Once, ONCE_INIT};
+use std::thread;
+
+static NO_PROXY: Once = ONCE_INIT;
+
+// Canned responses, by request path. Paths with a query string only match
+// requests with the same one; others match any. Unknown paths get a 404.
+pub struct MockServer {
+    pub base_url: String,
+    responses: Arc<Mutex<Vec<(String, u16, String)>>>,
+    requests: Arc<Mutex<Vec<String>>>,
+}
+
+impl MockServer {
+    pub fn start() -> MockServer {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Couldn't bind the mock server.");
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let responses = Arc::new(Mutex::new(Vec::new()));
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let (thread_responses, thread_requests) = (Arc::clone(&responses), Arc::clone(&requests));
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                if let Ok(stream) = stream {
+                    respond(stream, &thread_responses, &thread_requests);
+                }
+            }
+        });
+        MockServer {
+            base_url: base_url,
+            responses: responses,
+            requests: requests,
+        }
+    }
+
+    // Serves body with the given status code for path.
+    pub fn on(&self, path: &str, status: u16, body: &str) -> &MockServer {
+        self.responses
+            .lock()
+            .unwrap()
+            .push((path.to_string(), status, body.to_string()));
+        self
+    }
+
+    // Request lines and headers of everything received so far, oldest first.
+    pub fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    pub fn client(&self) -> HttpClient {
+        self.client_with(&HttpConfig::default())
+    }
+
+    pub fn client_with(&self, config: &HttpConfig) -> HttpClient {
+        // A proxy from the environment would never reach us
+        NO_PROXY.call_once(|| env::set_var("no_proxy", "127.0.0.1"));
+        build_client(config, &self.base_url).unwrap()
+    }
+}
+
+fn respond(
+    stream: TcpStream,
+    responses: &Mutex<Vec<(String, u16, String)>>,
+    requests: &Mutex<Vec<String>>,
+) {
+    let mut reader = BufReader::new(stream);
+    let mut request = String::new();
+    loop {
+        let mut line = String::new();
+        match reader.read_line(&mut line) {
+            Ok(0) | Err(_) => return,
+            Ok(_) if line == "\r\n" => break,
+            Ok(_) => request.push_str(&line),
+        }
+    }
+    let target = request.split_whitespace().nth(1).unwrap_or("").to_string();
+    let path = target.split('?').next().unwrap_or("").to_string();
+    requests.lock().unwrap().push(request);
+
+    let responses = responses.lock().unwrap();
+    let (status, body) = responses
+        .iter()
+        .find(|&&(ref response_path, _, _)| *response_path == target)
+        .or_else(|| {
+            responses
+                .iter()
+                .find(|&&(ref response_path, _, _)| *response_path == path)
+        })
+        .map(|&(_, status, ref body)| (status, body.clone()))
+        .unwrap_or((404, String::new()));
+    let response = format!(
+        "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    let _ = reader.get_mut().write_all(response.as_bytes());
+}
+
+// Contents of a file in tests/fixtures.
+pub fn fixture(name: &str) -> String {
+    let path: PathBuf = [env!("CARGO_MANIFEST_DIR"), "tests", "fixtures", name]
+        .iter()
+        .collect();
+    let mut contents = String::new();
+    File::open(&path)
+        .and_then(|mut file| file.read_to_string(&mut contents))
+        .unwrap_or_else(|err| panic!("Couldn't read fixture {:?}: {}", path, err));
+    contents
+}
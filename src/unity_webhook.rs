@@ -0,0 +1,231 @@
+use config_file::UnityWebhookConfig;
+use scheduler::{Tick, TickScheduler};
+use serde_json;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::Duration;
+
+const ACCEPT_POLL_MILLIS: u64 = 200;
+// Build events are a few KB; anything much bigger isn't one
+const MAX_REQUEST_BYTES: usize = 64 * 1024;
+
+// The parts of a Unity Cloud Build webhook's payload that get logged.
+#[derive(Deserialize, Debug, PartialEq)]
+struct BuildEvent {
+    #[serde(rename = "projectName")]
+    project_name: Option<String>,
+    #[serde(rename = "buildTargetName")]
+    build_target_name: Option<String>,
+    #[serde(rename = "buildNumber")]
+    build_number: Option<u64>,
+    // e.g. "queued", "started", "success", "failure" or "canceled"
+    #[serde(rename = "buildStatus")]
+    build_status: Option<String>,
+}
+
+// Receives Unity Cloud Build's webhooks on config.listen until shutdown, and
+// has the Unity integrations poll right away on every build event, so their
+// LEDs change within seconds instead of at the next poll.
+pub fn run_unity_webhook(
+    config: UnityWebhookConfig,
+    integrations: Vec<String>,
+    scheduler: TickScheduler,
+) {
+    let listener = match TcpListener::bind(config.listen.as_str())
+        .and_then(|listener| listener.set_nonblocking(true).map(|_| listener))
+    {
+        Ok(listener) => {
+            info!(
+                "--Unity Webhook--: Receiving build events on {}.",
+                config.listen
+            );
+            listener
+        }
+        Err(e) => {
+            error!(
+                "--Unity Webhook--: Failed to listen on {}. Webhook disabled. Error: {}",
+                config.listen, e
+            );
+            return;
+        }
+    };
+
+    let mut ticker = scheduler.ticker();
+    loop {
+        loop {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    if respond(stream, config.secret.as_ref().map(|s| s.as_str())) {
+                        scheduler.wake(&integrations);
+                    }
+                }
+                Err(ref e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    warn!(
+                        "--Unity Webhook--: Failed to accept a connection. Error: {}",
+                        e
+                    );
+                    break;
+                }
+            }
+        }
+        if ticker.wait(Duration::from_millis(ACCEPT_POLL_MILLIS)) == Tick::Shutdown {
+            break;
+        }
+    }
+}
+
+// Answers one request, and says whether it was a build event.
+fn respond(mut stream: TcpStream, secret: Option<&str>) -> bool {
+    let _ = stream.set_nonblocking(false);
+    let _ = stream.set_read_timeout(Some(Duration::from_secs(2)));
+    let request = read_request(&mut stream);
+    let (status_line, event) = handle(&request, secret);
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        status_line
+    );
+    if let Err(e) = stream.write_all(response.as_bytes()) {
+        warn!(
+            "--Unity Webhook--: Failed to respond to a request. Error: {}",
+            e
+        );
+    }
+    match event {
+        Some(event) => {
+            info!(
+                "--Unity Webhook--: {} {} #{} is {}.",
+                event.project_name.unwrap_or_default(),
+                event.build_target_name.unwrap_or_default(),
+                event.build_number.unwrap_or(0),
+                event.build_status.unwrap_or_default()
+            );
+            true
+        }
+        None => false,
+    }
+}
+
+// The headers, and as much of the body as Content-Length says there is.
+fn read_request(stream: &mut TcpStream) -> Vec<u8> {
+    let mut request = Vec::new();
+    let mut buffer = [0u8; 4096];
+    while request.len() < MAX_REQUEST_BYTES {
+        let read = match stream.read(&mut buffer) {
+            Ok(0) | Err(_) => break,
+            Ok(read) => read,
+        };
+        request.extend_from_slice(&buffer[..read]);
+        if let Some(body_start) = body_start(&request) {
+            if request.len() >= body_start + content_length(&request[..body_start]) {
+                break;
+            }
+        }
+    }
+    request
+}
+
+fn body_start(request: &[u8]) -> Option<usize> {
+    request
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .map(|end| end + 4)
+}
+
+fn content_length(headers: &[u8]) -> usize {
+    String::from_utf8_lossy(headers)
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(2, ':');
+            match (parts.next(), parts.next()) {
+                (Some(name), Some(value)) if name.eq_ignore_ascii_case("content-length") => {
+                    value.trim().parse().ok()
+                }
+                _ => None,
+            }
+        })
+        .next()
+        .unwrap_or(0)
+}
+
+// Build events are POSTed as JSON. With a secret, the webhook's URL has to
+// end in ?secret=<secret>.
+fn handle(request: &[u8], secret: Option<&str>) -> (&'static str, Option<BuildEvent>) {
+    let body_start = match body_start(request) {
+        Some(body_start) => body_start,
+        None => return ("400 Bad Request", None),
+    };
+    let head = String::from_utf8_lossy(&request[..body_start]);
+    let mut request_line = head.split_whitespace();
+    let method = request_line.next().unwrap_or("");
+    let target = request_line.next().unwrap_or("");
+    if let Some(secret) = secret {
+        let given = target
+            .splitn(2, '?')
+            .nth(1)
+            .unwrap_or("")
+            .split('&')
+            .find(|pair| pair.starts_with("secret="))
+            .map(|pair| &pair["secret=".len()..]);
+        if given != Some(secret) {
+            warn!("--Unity Webhook--: Refused a request without the right secret.");
+            return ("403 Forbidden", None);
+        }
+    }
+    if method != "POST" {
+        return ("405 Method Not Allowed", None);
+    }
+    match serde_json::from_slice::<BuildEvent>(&request[body_start..]) {
+        Ok(event) => ("200 OK", Some(event)),
+        Err(e) => {
+            warn!(
+                "--Unity Webhook--: Couldn't read a build event. Error: {}",
+                e
+            );
+            ("400 Bad Request", None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_events_need_the_secret() {
+        let body = r#"{"projectName": "Game", "buildTargetName": "Android", "buildNumber": 42, "buildStatus": "success", "platform": "android"}"#;
+        let request = |target: &str| {
+            format!(
+                "POST {} HTTP/1.1\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+                target,
+                body.len(),
+                body
+            )
+        };
+
+        let (status, event) = handle(request("/unity?secret=s3cret").as_bytes(), Some("s3cret"));
+        assert_eq!(status, "200 OK");
+        assert_eq!(
+            event,
+            Some(BuildEvent {
+                project_name: Some("Game".to_string()),
+                build_target_name: Some("Android".to_string()),
+                build_number: Some(42),
+                build_status: Some("success".to_string()),
+            })
+        );
+        assert_eq!(
+            handle(request("/unity?secret=guess").as_bytes(), Some("s3cret")).0,
+            "403 Forbidden"
+        );
+        assert_eq!(handle(request("/unity").as_bytes(), None).0, "200 OK");
+        assert_eq!(
+            handle(b"GET /unity HTTP/1.1\r\n\r\n", None).0,
+            "405 Method Not Allowed"
+        );
+        assert_eq!(
+            content_length(request("/").split("\r\n\r\n").next().unwrap().as_bytes()),
+            body.len()
+        );
+    }
+}
@@ -0,0 +1,166 @@
+use config_file::VaultConfig;
+use credentials::{CredentialProvider, ProvidedCredentials};
+use failure::Error;
+use headers::Headers;
+use network;
+use reqwest::{Client, StatusCode};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+#[derive(Deserialize)]
+struct LoginResponse {
+    auth: LoginAuth,
+}
+
+#[derive(Deserialize)]
+struct LoginAuth {
+    client_token: String,
+}
+
+#[derive(Deserialize)]
+struct SecretResponse {
+    data: SecretData,
+}
+
+// KV version 2 nests the secret's own key-value pairs one level down.
+#[derive(Deserialize)]
+struct SecretData {
+    data: BTreeMap<String, Value>,
+}
+
+// Reads the credentials from a secret in HashiCorp Vault's KV version 2
+// secrets engine, logging in with a token or an AppRole. An AppRole logs in
+// again on every fetch, so its short-lived tokens never need renewing.
+pub struct VaultProvider {
+    config: VaultConfig,
+    client: Client,
+}
+
+impl VaultProvider {
+    pub fn new(config: VaultConfig) -> Result<VaultProvider, Error> {
+        match (&config.token, &config.role_id, &config.secret_id) {
+            (&Some(_), &None, &None) | (&None, &Some(_), &Some(_)) => (),
+            _ => {
+                return Err(format_err!(
+                    "Give either token, or both role_id and secret_id."
+                ))
+            }
+        }
+        Ok(VaultProvider {
+            config: config,
+            client: network::build_default_client(),
+        })
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/v1/{}", self.config.address.trim_end_matches('/'), path)
+    }
+
+    fn token(&self) -> Result<String, Error> {
+        if let Some(ref token) = self.config.token {
+            return Ok(token.clone());
+        }
+        let url = self.url("auth/approle/login");
+        let mut response = self
+            .client
+            .post(url.as_str())
+            .json(&json!({
+                "role_id": self.config.role_id,
+                "secret_id": self.config.secret_id,
+            }))
+            .send()?;
+        if response.status() != StatusCode::Ok {
+            return Err(format_err!(
+                "AppRole login at {} failed with code: {}",
+                url,
+                response.status()
+            ));
+        }
+        let login: LoginResponse = response.json()?;
+        Ok(login.auth.client_token)
+    }
+}
+
+impl CredentialProvider for VaultProvider {
+    fn name(&self) -> &str {
+        "Vault"
+    }
+
+    fn fetch(&self) -> Result<ProvidedCredentials, Error> {
+        let url = self.url(&format!(
+            "{}/data/{}",
+            self.config.mount.trim_matches('/'),
+            self.config.path.trim_matches('/')
+        ));
+        let mut headers = Headers::new();
+        headers.set("X-Vault-Token", self.token()?);
+        let mut response = network::send_with_headers(&self.client, &url, &headers)?;
+        if response.status() != StatusCode::Ok {
+            return Err(format_err!(
+                "Reading {} failed with code: {}",
+                url,
+                response.status()
+            ));
+        }
+        let secret: SecretResponse = response.json()?;
+        let value = |key: &str| {
+            secret
+                .data
+                .data
+                .get(key)
+                .and_then(|value| value.as_str())
+                .map(|value| value.to_string())
+        };
+        Ok(ProvidedCredentials {
+            jenkins_username: value("jenkins_username"),
+            jenkins_password: value("jenkins_password"),
+            jenkins_api_token: value("jenkins_api_token"),
+            unity_cloud_api_token: value("unity_cloud_api_token"),
+        })
+    }
+
+    fn refresh_interval(&self) -> Option<Duration> {
+        match self.config.refresh_minutes {
+            0 => None,
+            minutes => Some(Duration::from_secs(minutes * 60)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_support::MockServer;
+
+    #[test]
+    fn credentials_are_read_from_the_secret() {
+        let server = MockServer::start();
+        // Only for setting no_proxy, so the request isn't sent to a proxy
+        server.client();
+        server.on(
+            "/v1/secret/data/build-light",
+            200,
+            "{\"data\": {\"data\": {\"jenkins_username\": \"light\", \"jenkins_api_token\": \"jenkins-token\"}, \"metadata\": {\"version\": 3}}}",
+        );
+        let provider = VaultProvider::new(VaultConfig {
+            address: format!("{}/", server.base_url),
+            token: Some("vault-token".to_string()),
+            role_id: None,
+            secret_id: None,
+            mount: "secret".to_string(),
+            path: "build-light".to_string(),
+            refresh_minutes: 60,
+        })
+        .unwrap();
+
+        let credentials = provider.fetch().unwrap();
+        assert_eq!(credentials.jenkins_username, Some("light".to_string()));
+        assert_eq!(
+            credentials.jenkins_api_token,
+            Some("jenkins-token".to_string())
+        );
+        assert_eq!(credentials.unity_cloud_api_token, None);
+        assert!(server.requests()[0].contains("X-Vault-Token: vault-token"));
+    }
+}
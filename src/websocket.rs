@@ -0,0 +1,231 @@
+use base64;
+use config_file::WebSocketConfig;
+use failure::Error;
+use status_board::{IntegrationSnapshot, StatusBoard};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+// Appended to the client's key before hashing it, as RFC 6455 says.
+const HANDSHAKE_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+// Pings the clients this often, so ones that went away without closing the
+// connection get noticed and dropped.
+const PING_SECONDS: u64 = 30;
+const TIMEOUT_SECONDS: u64 = 5;
+
+const OPCODE_TEXT: u8 = 0x1;
+const OPCODE_PING: u8 = 0x9;
+
+// Pushes every integration's status to the WebSocket clients connected to
+// config.listen as soon as it's polled, as one JSON text message per
+// integration. New clients get the current statuses right away.
+pub fn start_websocket(config: &WebSocketConfig, board: &StatusBoard) {
+    let listener = match TcpListener::bind(config.listen.as_str()) {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!(
+                "--WebSocket--: Failed to listen on {}. WebSocket updates disabled. Error: {}",
+                config.listen, e
+            );
+            return;
+        }
+    };
+    info!("--WebSocket--: Pushing statuses on ws://{}/", config.listen);
+    let clients: Arc<Mutex<Vec<TcpStream>>> = Arc::new(Mutex::new(Vec::new()));
+
+    let (accept_clients, accept_board) = (Arc::clone(&clients), board.clone());
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    warn!("--WebSocket--: Failed to accept a connection. Error: {}", e);
+                    continue;
+                }
+            };
+            let welcome = accept_board.snapshots().iter().map(encode).fold(
+                Vec::new(),
+                |mut frames, frame| {
+                    frames.extend(frame);
+                    frames
+                },
+            );
+            match handshake(&stream).and_then(|_| Ok((&stream).write_all(&welcome)?)) {
+                Ok(_) => {
+                    if let Ok(mut clients) = accept_clients.lock() {
+                        clients.push(stream);
+                    }
+                }
+                Err(e) => warn!("--WebSocket--: Rejected a connection. Error: {}", e),
+            }
+        }
+    });
+
+    let ping_clients = Arc::clone(&clients);
+    thread::spawn(move || loop {
+        thread::sleep(Duration::from_secs(PING_SECONDS));
+        broadcast(&ping_clients, &frame(OPCODE_PING, &[]));
+    });
+
+    board.subscribe(Box::new(move |snapshots| {
+        for snapshot in snapshots {
+            broadcast(&clients, &encode(snapshot));
+        }
+    }));
+}
+
+// Reads the client's upgrade request and accepts it.
+fn handshake(stream: &TcpStream) -> Result<(), Error> {
+    stream.set_read_timeout(Some(Duration::from_secs(TIMEOUT_SECONDS)))?;
+    stream.set_write_timeout(Some(Duration::from_secs(TIMEOUT_SECONDS)))?;
+    let (mut reader, mut writer) = (BufReader::new(stream), stream);
+    let mut key = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(format_err!("The client closed the connection."));
+        }
+        if line == "\r\n" {
+            break;
+        }
+        let mut header = line.splitn(2, ':');
+        let name = header.next().unwrap_or("").trim().to_lowercase();
+        if name == "sec-websocket-key" {
+            key = header.next().map(|value| value.trim().to_string());
+        }
+    }
+    match key {
+        Some(key) => {
+            let response = format!(
+                "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+                accept_key(&key)
+            );
+            Ok(writer.write_all(response.as_bytes())?)
+        }
+        None => {
+            let _ = writer.write_all(
+                b"HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            );
+            Err(format_err!("Not a WebSocket upgrade request."))
+        }
+    }
+}
+
+fn accept_key(key: &str) -> String {
+    base64::encode(&sha1(format!("{}{}", key, HANDSHAKE_GUID).as_bytes()))
+}
+
+fn encode(snapshot: &IntegrationSnapshot) -> Vec<u8> {
+    let message = json!({
+        "name": snapshot.name,
+        "status": snapshot.status,
+        "summary": snapshot.summary,
+        "last_poll": snapshot.last_poll.to_rfc3339(),
+    });
+    frame(OPCODE_TEXT, message.to_string().as_bytes())
+}
+
+// A single, final, unmasked frame, the way servers send them.
+fn frame(opcode: u8, payload: &[u8]) -> Vec<u8> {
+    let mut frame = vec![0x80 | opcode];
+    let length = payload.len();
+    if length < 126 {
+        frame.push(length as u8);
+    } else if length <= 0xFFFF {
+        frame.push(126);
+        frame.extend(&[(length >> 8) as u8, length as u8]);
+    } else {
+        frame.push(127);
+        frame.extend((0..8).rev().map(|byte| (length as u64 >> (byte * 8)) as u8));
+    }
+    frame.extend(payload);
+    frame
+}
+
+// Sends the frame to every client, dropping the ones it can't be sent to.
+fn broadcast(clients: &Mutex<Vec<TcpStream>>, frame: &[u8]) {
+    if let Ok(mut clients) = clients.lock() {
+        clients.retain(|client| (&*client).write_all(frame).is_ok());
+    }
+}
+
+// Only needed for the handshake, which isn't worth a dependency.
+fn sha1(message: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+    let mut padded = message.to_vec();
+    padded.push(0x80);
+    while padded.len() % 64 != 56 {
+        padded.push(0);
+    }
+    let bit_length = (message.len() as u64) * 8;
+    padded.extend((0..8).rev().map(|byte| (bit_length >> (byte * 8)) as u8));
+
+    for chunk in padded.chunks(64) {
+        let mut w = [0u32; 80];
+        for i in 0..16 {
+            w[i] = (chunk[i * 4] as u32) << 24
+                | (chunk[i * 4 + 1] as u32) << 16
+                | (chunk[i * 4 + 2] as u32) << 8
+                | chunk[i * 4 + 3] as u32;
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+        let (mut a, mut b, mut c, mut d, mut e) = (h[0], h[1], h[2], h[3], h[4]);
+        for (i, word) in w.iter().enumerate() {
+            let (f, k) = match i / 20 {
+                0 => ((b & c) | (!b & d), 0x5A827999),
+                1 => (b ^ c ^ d, 0x6ED9EBA1),
+                2 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        h[0] = h[0].wrapping_add(a);
+        h[1] = h[1].wrapping_add(b);
+        h[2] = h[2].wrapping_add(c);
+        h[3] = h[3].wrapping_add(d);
+        h[4] = h[4].wrapping_add(e);
+    }
+
+    let mut digest = [0u8; 20];
+    for (i, word) in h.iter().enumerate() {
+        for byte in 0..4 {
+            digest[i * 4 + byte] = (word >> (24 - byte * 8)) as u8;
+        }
+    }
+    digest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accept_key_matches_the_rfc_example() {
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+
+    #[test]
+    fn long_payloads_get_an_extended_length() {
+        assert_eq!(frame(OPCODE_TEXT, b"hi"), vec![0x81, 2, b'h', b'i']);
+        let long = frame(OPCODE_TEXT, &[b'x'; 300]);
+        assert_eq!(&long[..4], &[0x81, 126, 1, 44]);
+        assert_eq!(long.len(), 304);
+    }
+}